@@ -4,6 +4,9 @@ use std::hint::black_box;
 use divan::Bencher;
 use valence::nbt::{compound, List};
 use valence::prelude::*;
+#[cfg(feature = "libdeflate")]
+use valence::protocol::compression::Libdeflate;
+use valence::protocol::compression::{CompressionOptions, Flate2};
 use valence::protocol::decode::PacketDecoder;
 use valence::protocol::encode::{PacketEncoder, PacketWriter, WritePacket};
 use valence::protocol::packets::play::{AddEntityS2c, LevelChunkWithLightS2c, TabListS2c};
@@ -297,3 +300,94 @@ fn decode_spawn_data_compressed(bencher: Bencher) {
         black_box(decoder);
     });
 }
+
+/// Compression levels compared by the compression backend benchmarks.
+const COMPRESSION_LEVELS: [u32; 2] = [1, 6];
+
+fn encode_chunk_data_with(bencher: Bencher, options: CompressionOptions) {
+    let (mut encoder, chunk_data_packet, _, _) = setup();
+    encoder.set_compression(256.into());
+    encoder.set_compression_options(options);
+
+    bencher.bench_local(|| {
+        let encoder = black_box(&mut encoder);
+
+        encoder.clear();
+        encoder.append_packet(&chunk_data_packet).unwrap();
+
+        black_box(encoder);
+    });
+}
+
+fn decode_chunk_data_with(bencher: Bencher, options: CompressionOptions) {
+    let (_, chunk_data_packet, _, _) = setup();
+
+    let mut decoder = PacketDecoder::new();
+    let mut packet_buf = vec![];
+
+    decoder.set_compression(256.into());
+    decoder.set_compression_options(options);
+
+    PacketWriter::with_options(&mut packet_buf, 256.into(), options)
+        .write_packet(&chunk_data_packet);
+
+    bencher.bench_local(|| {
+        let decoder = black_box(&mut decoder);
+
+        decoder.queue_slice(&packet_buf);
+        decoder
+            .try_next_packet()
+            .unwrap()
+            .unwrap()
+            .decode::<LevelChunkWithLightS2c>()
+            .unwrap();
+
+        black_box(decoder);
+    });
+}
+
+#[divan::bench(args = COMPRESSION_LEVELS)]
+fn encode_chunk_data_flate2(bencher: Bencher, level: u32) {
+    encode_chunk_data_with(
+        bencher,
+        CompressionOptions {
+            backend: &Flate2,
+            level,
+        },
+    );
+}
+
+#[cfg(feature = "libdeflate")]
+#[divan::bench(args = COMPRESSION_LEVELS)]
+fn encode_chunk_data_libdeflate(bencher: Bencher, level: u32) {
+    encode_chunk_data_with(
+        bencher,
+        CompressionOptions {
+            backend: &Libdeflate,
+            level,
+        },
+    );
+}
+
+#[divan::bench(args = COMPRESSION_LEVELS)]
+fn decode_chunk_data_flate2(bencher: Bencher, level: u32) {
+    decode_chunk_data_with(
+        bencher,
+        CompressionOptions {
+            backend: &Flate2,
+            level,
+        },
+    );
+}
+
+#[cfg(feature = "libdeflate")]
+#[divan::bench(args = COMPRESSION_LEVELS)]
+fn decode_chunk_data_libdeflate(bencher: Bencher, level: u32) {
+    decode_chunk_data_with(
+        bencher,
+        CompressionOptions {
+            backend: &Libdeflate,
+            level,
+        },
+    );
+}
@@ -8,6 +8,7 @@ use bevy_ecs::prelude::*;
 use flume::{Receiver, Sender};
 use valence_server::client::{Client, OldView, View};
 use valence_server::entity::{EntityLayerId, OldEntityLayerId};
+use valence_server::layer::chunk::UnloadedChunk;
 use valence_server::layer::UpdateLayersPreClientSet;
 use valence_server::protocol::anyhow;
 use valence_server::registry::BiomeRegistry;
@@ -17,6 +18,44 @@ use crate::parsing::{DimensionFolder, ParsedChunk};
 
 type WorkerResult = anyhow::Result<Option<ParsedChunk>>;
 
+/// A request sent to the anvil worker thread.
+enum WorkerRequest {
+    /// Load the chunk at the position.
+    Load(ChunkPos),
+    /// Save the chunk at the position.
+    Save {
+        pos: ChunkPos,
+        chunk: UnloadedChunk,
+        min_y: i32,
+        /// Whether the chunk was removed from the layer. It is sent back if it
+        /// couldn't be saved.
+        unloaded: bool,
+    },
+    /// Respond with [`WorkerResponse::Flushed`] once all previous requests are
+    /// handled.
+    Flush,
+}
+
+/// A response sent by the anvil worker thread.
+#[derive(Debug)]
+enum WorkerResponse {
+    Loaded(ChunkPos, WorkerResult),
+    Saved {
+        pos: ChunkPos,
+        result: anyhow::Result<()>,
+        /// The unloaded chunk, if it couldn't be saved.
+        unsaved: Option<UnsavedChunk>,
+    },
+    Flushed,
+}
+
+/// An unloaded chunk whose changes couldn't be saved yet.
+#[derive(Debug)]
+struct UnsavedChunk {
+    chunk: UnloadedChunk,
+    min_y: i32,
+}
+
 /// The order in which chunks should be processed by the anvil worker. Smaller
 /// values are sent first.
 type Priority = u64;
@@ -30,13 +69,32 @@ pub struct AnvilLevel {
     ///
     /// This set is empty by default, but you can modify it at any time.
     pub ignored_chunks: HashSet<ChunkPos>,
+    /// Determines if and when modified chunks are written back to the region
+    /// files of this level.
+    ///
+    /// [`SaveMode::Disabled`] by default, but you can modify it at any time.
+    pub save_mode: SaveMode,
     /// Chunks that need to be loaded. Chunks with `None` priority have already
     /// been sent to the anvil thread.
     pending: HashMap<ChunkPos, Option<Priority>>,
+    /// Ticks elapsed since modified chunks were last saved periodically.
+    ticks_since_save: u32,
+    /// Whether all modified chunks should be saved this tick.
+    save_requested: bool,
+    /// Chunks whose last save failed. They are saved again with the next
+    /// modified chunks, even if they weren't modified since.
+    failed_saves: HashSet<ChunkPos>,
+    /// Unloaded chunks whose last save failed. They are kept until they are
+    /// saved, and are put back into the layer instead of the chunk in the
+    /// region file if they are loaded again.
+    unsaved: HashMap<ChunkPos, UnsavedChunk>,
+    /// Responses received by [`AnvilLevel::flush`] that haven't been handled
+    /// yet.
+    received: Vec<WorkerResponse>,
     /// Sender for the chunk worker thread.
-    sender: Sender<ChunkPos>,
+    sender: Sender<WorkerRequest>,
     /// Receiver for the chunk worker thread.
-    receiver: Receiver<(ChunkPos, WorkerResult)>,
+    receiver: Receiver<WorkerResponse>,
 }
 
 impl AnvilLevel {
@@ -51,7 +109,13 @@ impl AnvilLevel {
                 receiver: pending_receiver,
             }),
             ignored_chunks: HashSet::new(),
+            save_mode: SaveMode::default(),
             pending: HashMap::new(),
+            ticks_since_save: 0,
+            save_requested: false,
+            failed_saves: HashSet::new(),
+            unsaved: HashMap::new(),
+            received: vec![],
             sender: pending_sender,
             receiver: finished_receiver,
         }
@@ -77,17 +141,115 @@ impl AnvilLevel {
             }
         }
     }
+
+    /// Saves all modified chunks in this world at the end of the tick,
+    /// regardless of the [`AnvilLevel::save_mode`]. Chunks in
+    /// [`AnvilLevel::ignored_chunks`] are not saved.
+    ///
+    /// The chunks are written on another thread. To save the world before the
+    /// server shuts down, call this and then [`AnvilLevel::flush`] after
+    /// `PostUpdate`, for example in `Last`.
+    pub fn save_all(&mut self) {
+        self.save_requested = true;
+    }
+
+    /// Blocks until every chunk sent to be saved or loaded so far has been
+    /// handled, after sending the unloaded chunks that couldn't be saved
+    /// before to be saved again. The results are still reported with
+    /// [`ChunkSaveEvent`] and [`ChunkLoadEvent`] on the next update.
+    ///
+    /// Returns the positions of the chunks that failed to save. Unloaded chunks
+    /// among them are kept and saved again with the next save or flush.
+    pub fn flush(&mut self) -> Vec<ChunkPos> {
+        // Saves can be queued before the worker was started.
+        if let Some(state) = self.worker_state.take() {
+            thread::spawn(move || anvil_worker(state));
+        }
+
+        self.retry_unsaved();
+
+        let mut failed = vec![];
+
+        if self.sender.send(WorkerRequest::Flush).is_err() {
+            return failed;
+        }
+
+        // Keep receiving so the worker is never blocked on a full channel.
+        while let Ok(mut res) = self.receiver.recv() {
+            match &mut res {
+                WorkerResponse::Flushed => break,
+                WorkerResponse::Saved {
+                    pos,
+                    result,
+                    unsaved,
+                } => {
+                    if result.is_err() {
+                        failed.push(*pos);
+                    }
+
+                    // Kept right away, so flushing again retries the save.
+                    if let Some(unsaved) = unsaved.take() {
+                        self.unsaved.insert(*pos, unsaved);
+                    }
+                }
+                WorkerResponse::Loaded(..) => {}
+            }
+
+            self.received.push(res);
+        }
+
+        failed
+    }
+
+    /// Sends the unloaded chunks whose last save failed to be saved again.
+    /// Chunks that are being loaded again are put back into the layer instead.
+    fn retry_unsaved(&mut self) {
+        let pending = &self.pending;
+        let sender = &self.sender;
+
+        self.unsaved.retain(|&pos, unsaved| {
+            if pending.contains_key(&pos) {
+                return true;
+            }
+
+            let _ = sender.send(WorkerRequest::Save {
+                pos,
+                chunk: std::mem::take(&mut unsaved.chunk),
+                min_y: unsaved.min_y,
+                unloaded: true,
+            });
+
+            false
+        });
+    }
+}
+
+/// Determines when an [`AnvilLevel`] writes modified chunks back to its region
+/// files. Chunks are saved on a separate thread.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum SaveMode {
+    /// Chunks are never saved. The region files are only read from.
+    #[default]
+    Disabled,
+    /// Modified chunks are saved when they are unloaded.
+    OnUnload,
+    /// Modified chunks are saved when they are unloaded and every `interval`
+    /// ticks.
+    Periodic {
+        /// The number of ticks between saves.
+        interval: u32,
+    },
 }
 
 #[derive(Debug)]
 struct ChunkWorkerState {
     /// The world folder containing the region folder where chunks are loaded
-    /// from.
+    /// from and saved to.
     dimension_folder: DimensionFolder,
     /// Sender of finished chunks.
-    sender: Sender<(ChunkPos, WorkerResult)>,
+    sender: Sender<WorkerResponse>,
     /// Receiver of pending chunks.
-    receiver: Receiver<ChunkPos>,
+    receiver: Receiver<WorkerRequest>,
 }
 
 pub struct AnvilPlugin;
@@ -96,10 +258,16 @@ impl Plugin for AnvilPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ChunkLoadEvent>()
             .add_event::<ChunkUnloadEvent>()
+            .add_event::<ChunkSaveEvent>()
             .add_systems(PreUpdate, remove_unviewed_chunks)
             .add_systems(
                 PostUpdate,
-                (init_anvil, update_client_views, send_recv_chunks)
+                (
+                    init_anvil,
+                    update_client_views,
+                    save_modified_chunks,
+                    send_recv_chunks,
+                )
                     .chain()
                     .before(UpdateLayersPreClientSet),
             );
@@ -119,14 +287,28 @@ fn init_anvil(mut query: Query<&mut AnvilLevel, (Added<AnvilLevel>, With<ChunkLa
 /// This needs to run in `PreUpdate` where the chunk viewer counts have been
 /// updated from the previous tick.
 fn remove_unviewed_chunks(
-    mut chunk_layers: Query<(Entity, &mut ChunkLayer, &AnvilLevel)>,
+    mut chunk_layers: Query<(Entity, &mut ChunkLayer, &mut AnvilLevel)>,
     mut unload_events: EventWriter<ChunkUnloadEvent>,
 ) {
     for (entity, mut layer, anvil) in &mut chunk_layers {
+        let anvil = anvil.into_inner();
+        let min_y = layer.min_y();
+
         layer.retain_chunks(|pos, chunk| {
             if chunk.viewer_count_mut() > 0 || anvil.ignored_chunks.contains(&pos) {
                 true
             } else {
+                let failed = anvil.failed_saves.remove(&pos);
+
+                if anvil.save_mode != SaveMode::Disabled && (chunk.is_modified() || failed) {
+                    let _ = anvil.sender.send(WorkerRequest::Save {
+                        pos,
+                        chunk: chunk.to_unloaded(),
+                        min_y,
+                        unloaded: true,
+                    });
+                }
+
                 unload_events.send(ChunkUnloadEvent {
                     chunk_layer: entity,
                     pos,
@@ -178,22 +360,111 @@ fn update_client_views(
     }
 }
 
+/// Sends modified chunks to be saved when a periodic save is due or a save
+/// was requested with [`AnvilLevel::save_all`].
+fn save_modified_chunks(mut layers: Query<(&mut ChunkLayer, &mut AnvilLevel)>) {
+    for (mut layer, anvil) in &mut layers {
+        let anvil = anvil.into_inner();
+
+        let periodic_save = match anvil.save_mode {
+            SaveMode::Disabled | SaveMode::OnUnload => false,
+            SaveMode::Periodic { interval } => {
+                anvil.ticks_since_save += 1;
+                anvil.ticks_since_save >= interval
+            }
+        };
+
+        if !periodic_save && !anvil.save_requested {
+            continue;
+        }
+
+        anvil.ticks_since_save = 0;
+        anvil.save_requested = false;
+
+        let min_y = layer.min_y();
+
+        for (pos, chunk) in layer.chunks_mut() {
+            let failed = anvil.failed_saves.contains(&pos);
+
+            if (chunk.is_modified() || failed) && !anvil.ignored_chunks.contains(&pos) {
+                // Cleared now so changes made while saving are saved next time. If the
+                // save fails, the chunk is saved again anyway.
+                chunk.clear_modified();
+                anvil.failed_saves.remove(&pos);
+
+                let _ = anvil.sender.send(WorkerRequest::Save {
+                    pos,
+                    chunk: chunk.to_unloaded(),
+                    min_y,
+                    unloaded: false,
+                });
+            }
+        }
+
+        anvil.retry_unsaved();
+    }
+}
+
 fn send_recv_chunks(
     mut layers: Query<(Entity, &mut ChunkLayer, &mut AnvilLevel)>,
     mut to_send: Local<Vec<(Priority, ChunkPos)>>,
     mut load_events: EventWriter<ChunkLoadEvent>,
+    mut save_events: EventWriter<ChunkSaveEvent>,
 ) {
     for (entity, mut layer, anvil) in &mut layers {
         let anvil = anvil.into_inner();
 
         // Insert the chunks that are finished loading into the chunk layer and send
-        // load events.
-        for (pos, res) in anvil.receiver.drain() {
+        // load and save events.
+        let received = std::mem::take(&mut anvil.received);
+
+        for res in received.into_iter().chain(anvil.receiver.drain()) {
+            let (pos, res) = match res {
+                WorkerResponse::Loaded(pos, res) => (pos, res),
+                WorkerResponse::Flushed => continue,
+                WorkerResponse::Saved {
+                    pos,
+                    result,
+                    unsaved,
+                } => {
+                    if let Some(unsaved) = unsaved {
+                        anvil.unsaved.insert(pos, unsaved);
+                    } else if result.is_err() && layer.chunk(pos).is_some() {
+                        anvil.failed_saves.insert(pos);
+                    }
+
+                    save_events.send(ChunkSaveEvent {
+                        chunk_layer: entity,
+                        pos,
+                        result,
+                    });
+                    continue;
+                }
+            };
+
             anvil.pending.remove(&pos);
 
+            // The region file doesn't have the latest changes to the chunk.
+            if let Some(unsaved) = anvil.unsaved.remove(&pos) {
+                layer.insert_chunk(pos, unsaved.chunk);
+
+                load_events.send(ChunkLoadEvent {
+                    chunk_layer: entity,
+                    pos,
+                    status: ChunkLoadStatus::Unsaved,
+                });
+                continue;
+            }
+
             let status = match res {
                 Ok(Some(ParsedChunk { chunk, timestamp })) => {
                     layer.insert_chunk(pos, chunk);
+
+                    // The chunk is identical to the one on disk, so there's nothing to save.
+                    if let Some(chunk) = layer.chunk_mut(pos) {
+                        chunk.clear_modified();
+                    }
+
                     ChunkLoadStatus::Success { timestamp }
                 }
                 Ok(None) => ChunkLoadStatus::Empty,
@@ -219,19 +490,44 @@ fn send_recv_chunks(
 
         // Send the sorted chunks to be loaded.
         for (_, pos) in to_send.drain(..) {
-            let _ = anvil.sender.try_send(pos);
+            let _ = anvil.sender.try_send(WorkerRequest::Load(pos));
         }
     }
 }
 
 fn anvil_worker(mut state: ChunkWorkerState) {
-    while let Ok(pos) = state.receiver.recv() {
-        let res = state
-            .dimension_folder
-            .get_chunk(pos)
-            .map_err(anyhow::Error::from);
+    // Requests are handled in order, so a chunk that is saved and then loaded
+    // again is always loaded with the saved changes.
+    while let Ok(req) = state.receiver.recv() {
+        let res = match req {
+            WorkerRequest::Load(pos) => WorkerResponse::Loaded(
+                pos,
+                state
+                    .dimension_folder
+                    .get_chunk(pos)
+                    .map_err(anyhow::Error::from),
+            ),
+            WorkerRequest::Save {
+                pos,
+                chunk,
+                min_y,
+                unloaded,
+            } => {
+                let result = state
+                    .dimension_folder
+                    .set_chunk(pos, &chunk, min_y)
+                    .map_err(anyhow::Error::from);
+
+                WorkerResponse::Saved {
+                    pos,
+                    unsaved: (result.is_err() && unloaded).then_some(UnsavedChunk { chunk, min_y }),
+                    result,
+                }
+            }
+            WorkerRequest::Flush => WorkerResponse::Flushed,
+        };
 
-        let _ = state.sender.send((pos, res));
+        let _ = state.sender.send(res);
     }
 }
 
@@ -258,6 +554,9 @@ pub enum ChunkLoadStatus {
     Empty,
     /// An attempt was made to load the chunk, but something went wrong.
     Failed(anyhow::Error),
+    /// The chunk was unloaded before its changes could be saved, so that chunk
+    /// was inserted into the layer again instead of the one in the region file.
+    Unsaved,
 }
 
 /// An event sent by `valence_anvil` when a chunk is unloaded from an layer.
@@ -268,3 +567,53 @@ pub struct ChunkUnloadEvent {
     /// The position of the chunk that was unloaded.
     pub pos: ChunkPos,
 }
+
+/// An event sent by `valence_anvil` after an attempt to save a chunk is made.
+#[derive(Event, Debug)]
+pub struct ChunkSaveEvent {
+    /// The [`ChunkLayer`] where the chunk is located.
+    pub chunk_layer: Entity,
+    /// The position of the chunk in the layer.
+    pub pos: ChunkPos,
+    /// The result of writing the chunk to the region file.
+    pub result: anyhow::Result<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_server::ident;
+    use valence_server::layer::chunk::Chunk;
+    use valence_server::registry::biome::Biome;
+
+    use super::*;
+
+    #[test]
+    fn unloaded_chunks_are_kept_until_saved() {
+        // A file where the world folder should be, so saving always fails.
+        let root =
+            std::env::temp_dir().join(format!("valence_anvil_unsaved_{}", std::process::id()));
+        std::fs::write(&root, []).unwrap();
+
+        let mut biomes = BiomeRegistry::default();
+        biomes.insert(ident!("plains"), Biome::default());
+
+        let mut level = AnvilLevel::new(&root, &biomes);
+        let pos = ChunkPos::new(3, -2);
+
+        let _ = level.sender.send(WorkerRequest::Save {
+            pos,
+            chunk: UnloadedChunk::with_height(32),
+            min_y: 0,
+            unloaded: true,
+        });
+
+        assert_eq!(level.flush(), [pos]);
+        assert!(level.unsaved.contains_key(&pos));
+
+        // Flushing again saves the chunk again.
+        assert_eq!(level.flush(), [pos]);
+        assert_eq!(level.unsaved[&pos].chunk.height(), 32);
+
+        std::fs::remove_file(&root).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 #![doc = include_str!("../README.md")]
 
+use std::collections::BTreeMap;
 use std::fs::{DirEntry, File};
 use std::hash::Hash;
 use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
@@ -20,6 +21,7 @@ use valence_nbt::Compound;
 
 #[cfg(feature = "bevy_plugin")]
 mod bevy;
+mod lz4;
 #[cfg(feature = "parsing")]
 pub mod parsing;
 
@@ -43,6 +45,12 @@ pub enum RegionError {
     InvalidChunkSize,
     #[error("invalid compression scheme number of {0}")]
     InvalidCompressionScheme(u8),
+    #[error("missing name of custom compression algorithm")]
+    MissingCustomCompressionName,
+    #[error("unsupported custom compression algorithm \"{0}\"")]
+    UnsupportedCustomCompression(String),
+    #[error("invalid LZ4 stream: {0}")]
+    InvalidLz4Stream(&'static str),
     #[error("failed to parse NBT: {0}")]
     Nbt(#[from] valence_nbt::Error),
     #[error("not all chunk NBT data was read")]
@@ -59,6 +67,9 @@ pub enum Compression {
     #[default]
     Zlib = 2,
     None = 3,
+    /// LZ4 compression, available since Minecraft 1.20.5 with the
+    /// `region-file-compression=lz4` server property.
+    Lz4 = 4,
 }
 
 impl Compression {
@@ -67,11 +78,22 @@ impl Compression {
             1 => Some(Compression::Gzip),
             2 => Some(Compression::Zlib),
             3 => Some(Compression::None),
+            4 => Some(Compression::Lz4),
             _ => None,
         }
     }
 }
 
+/// The compression scheme number of chunks compressed with a custom algorithm.
+/// The chunk data starts with the namespaced name of the algorithm.
+const CUSTOM_COMPRESSION: u8 = 127;
+
+/// A function that decompresses chunk data compressed with a custom
+/// algorithm. The decompressed data is appended to the vector.
+///
+/// See [`RegionFolder::register_custom_decompressor`].
+pub type CustomDecompressor = fn(&[u8], &mut Vec<u8>) -> std::io::Result<()>;
+
 #[derive(Copy, Clone, Debug, Default)]
 #[non_exhaustive]
 pub struct WriteOptions {
@@ -96,6 +118,9 @@ pub struct RegionFolder {
     region_root: PathBuf,
     /// Scratch buffer for (de)compression.
     compression_buf: Vec<u8>,
+    /// Decompressors for custom compression algorithms, keyed by the name of
+    /// the algorithm.
+    custom_decompressors: BTreeMap<String, CustomDecompressor>,
     /// Options to use for writing the chunk.
     pub write_options: WriteOptions,
 }
@@ -106,10 +131,23 @@ impl RegionFolder {
             regions: LruCache::new(LRU_CACHE_SIZE),
             region_root: region_root.into(),
             compression_buf: Vec::new(),
+            custom_decompressors: BTreeMap::new(),
             write_options: WriteOptions::default(),
         }
     }
 
+    /// Registers a decompressor for chunks compressed with the custom
+    /// algorithm named `name`, such as `"example:zstd"`. Reading chunks that
+    /// use a custom algorithm without a registered decompressor fails with
+    /// [`RegionError::UnsupportedCustomCompression`].
+    pub fn register_custom_decompressor<N: Into<String>>(
+        &mut self,
+        name: N,
+        decompressor: CustomDecompressor,
+    ) {
+        self.custom_decompressors.insert(name.into(), decompressor);
+    }
+
     fn region<'a>(
         regions: &'a mut LruCache<RegionPos, RegionEntry>,
         region_root: &Path,
@@ -170,7 +208,13 @@ impl RegionFolder {
             return Ok(None);
         };
 
-        region.get_chunk(pos_x, pos_z, &mut self.compression_buf, &self.region_root)
+        region.get_chunk(
+            pos_x,
+            pos_z,
+            &mut self.compression_buf,
+            &self.custom_decompressors,
+            &self.region_root,
+        )
     }
 
     /// Deletes the chunk at the given chunk position, returning whether the
@@ -416,6 +460,7 @@ impl Region {
         pos_x: i32,
         pos_z: i32,
         decompress_buf: &mut Vec<u8>,
+        custom_decompressors: &BTreeMap<String, CustomDecompressor>,
         region_root: &Path,
     ) -> Result<Option<RawChunk<S>>, RegionError>
     where
@@ -469,26 +514,50 @@ impl Region {
             data_buf
         };
 
-        let r = data_buf.as_ref();
+        let mut r = data_buf.as_ref();
 
         decompress_buf.clear();
 
         // What compression does the chunk use?
-        let mut nbt_slice = match Compression::from_u8(compression) {
-            Some(Compression::Gzip) => {
-                let mut z = GzDecoder::new(r);
-                z.read_to_end(decompress_buf)?;
-                decompress_buf.as_slice()
-            }
-            Some(Compression::Zlib) => {
-                let mut z = ZlibDecoder::new(r);
-                z.read_to_end(decompress_buf)?;
-                decompress_buf.as_slice()
+        let mut nbt_slice = if compression == CUSTOM_COMPRESSION {
+            // The name of the algorithm is stored as a Java modified UTF-8 string.
+            let Ok(len) = r.read_u16::<BigEndian>() else {
+                return Err(RegionError::MissingCustomCompressionName);
+            };
+
+            let Some((name, data)) = r.split_at_checked(usize::from(len)) else {
+                return Err(RegionError::MissingCustomCompressionName);
+            };
+
+            let name = String::from_utf8_lossy(name);
+
+            let Some(decompressor) = custom_decompressors.get(name.as_ref()) else {
+                return Err(RegionError::UnsupportedCustomCompression(name.into_owned()));
+            };
+
+            decompressor(data, decompress_buf)?;
+            decompress_buf.as_slice()
+        } else {
+            match Compression::from_u8(compression) {
+                Some(Compression::Gzip) => {
+                    let mut z = GzDecoder::new(r);
+                    z.read_to_end(decompress_buf)?;
+                    decompress_buf.as_slice()
+                }
+                Some(Compression::Zlib) => {
+                    let mut z = ZlibDecoder::new(r);
+                    z.read_to_end(decompress_buf)?;
+                    decompress_buf.as_slice()
+                }
+                Some(Compression::Lz4) => {
+                    lz4::decompress(r, decompress_buf)?;
+                    decompress_buf.as_slice()
+                }
+                // Uncompressed
+                Some(Compression::None) => r,
+                // Unknown
+                None => return Err(RegionError::InvalidCompressionScheme(compression)),
             }
-            // Uncompressed
-            Some(Compression::None) => r,
-            // Unknown
-            None => return Err(RegionError::InvalidCompressionScheme(compression)),
         };
 
         let (data, _) = valence_nbt::from_binary(&mut nbt_slice)?;
@@ -565,6 +634,11 @@ impl Region {
                 Some(""),
             )?,
             Compression::None => valence_nbt::to_binary(chunk, &mut compress_cursor, Some(""))?,
+            Compression::Lz4 => {
+                let mut nbt_buf = vec![];
+                valence_nbt::to_binary(chunk, &mut nbt_buf, Some(""))?;
+                lz4::compress(&nbt_buf, compress_cursor.get_mut());
+            }
         }
         let compress_buf = compress_cursor.into_inner();
 
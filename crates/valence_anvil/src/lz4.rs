@@ -0,0 +1,179 @@
+//! The LZ4 block stream format used by region files with the `lz4` compression
+//! scheme. This is the format written by `LZ4BlockOutputStream` from the
+//! lz4-java library, not the standard LZ4 frame format.
+
+use std::hash::Hasher;
+
+use byteorder::{ByteOrder, LittleEndian};
+use twox_hash::XxHash32;
+
+use crate::RegionError;
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+
+/// The size of uncompressed blocks. This is the default of lz4-java, which is
+/// what the vanilla server uses.
+const BLOCK_SIZE: usize = 1 << 16;
+
+/// The largest block size lz4-java supports.
+const MAX_BLOCK_SIZE: usize = 1 << 25;
+
+/// The low bits of the token byte, derived from the block size.
+const COMPRESSION_LEVEL: u8 = BLOCK_SIZE.trailing_zeros() as u8 - 10;
+
+/// The seed of the XXH32 checksum of every block.
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(CHECKSUM_SEED);
+    hasher.write(data);
+    // lz4-java only keeps the low 28 bits of the checksum.
+    hasher.finish() as u32 & 0xfffffff
+}
+
+/// Decompresses an LZ4 block stream and appends the result to `out`.
+pub(crate) fn decompress(mut input: &[u8], out: &mut Vec<u8>) -> Result<(), RegionError> {
+    while !input.is_empty() {
+        if input.len() < HEADER_LEN || &input[..MAGIC.len()] != MAGIC {
+            return Err(RegionError::InvalidLz4Stream("bad block header"));
+        }
+
+        let token = input[MAGIC.len()];
+        let compressed_len = LittleEndian::read_u32(&input[9..13]) as usize;
+        let original_len = LittleEndian::read_u32(&input[13..17]) as usize;
+        let check = LittleEndian::read_u32(&input[17..21]);
+
+        input = &input[HEADER_LEN..];
+
+        // An empty block marks the end of the stream.
+        if original_len == 0 {
+            break;
+        }
+
+        if compressed_len > input.len() || original_len > MAX_BLOCK_SIZE {
+            return Err(RegionError::InvalidLz4Stream("bad block length"));
+        }
+
+        let (data, rest) = input.split_at(compressed_len);
+        input = rest;
+
+        let start = out.len();
+
+        match token & 0xf0 {
+            METHOD_RAW => {
+                if compressed_len != original_len {
+                    return Err(RegionError::InvalidLz4Stream("bad block length"));
+                }
+
+                out.extend_from_slice(data);
+            }
+            METHOD_LZ4 => {
+                out.resize(start + original_len, 0);
+
+                match lz4_flex::block::decompress_into(data, &mut out[start..]) {
+                    Ok(len) if len == original_len => {}
+                    _ => return Err(RegionError::InvalidLz4Stream("corrupt block data")),
+                }
+            }
+            _ => {
+                return Err(RegionError::InvalidLz4Stream(
+                    "unknown block compression method",
+                ))
+            }
+        }
+
+        if checksum(&out[start..]) != check {
+            return Err(RegionError::InvalidLz4Stream("checksum mismatch"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Compresses `input` into an LZ4 block stream and appends the result to
+/// `out`.
+pub(crate) fn compress(input: &[u8], out: &mut Vec<u8>) {
+    for block in input.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+
+        // Blocks that don't shrink are stored uncompressed.
+        let (method, data) = if compressed.len() < block.len() {
+            (METHOD_LZ4, compressed.as_slice())
+        } else {
+            (METHOD_RAW, block)
+        };
+
+        write_header(
+            out,
+            method,
+            data.len() as u32,
+            block.len() as u32,
+            checksum(block),
+        );
+        out.extend_from_slice(data);
+    }
+
+    // End of stream marker.
+    write_header(out, METHOD_RAW, 0, 0, 0);
+}
+
+fn write_header(out: &mut Vec<u8>, method: u8, compressed_len: u32, original_len: u32, check: u32) {
+    let mut header = [0; HEADER_LEN];
+
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[MAGIC.len()] = method | COMPRESSION_LEVEL;
+    LittleEndian::write_u32(&mut header[9..13], compressed_len);
+    LittleEndian::write_u32(&mut header[13..17], original_len);
+    LittleEndian::write_u32(&mut header[17..21], check);
+
+    out.extend_from_slice(&header);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lz4_round_trip() {
+        let input: Vec<u8> = (0..BLOCK_SIZE * 3 + 1234)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect();
+
+        let mut compressed = vec![];
+        compress(&input, &mut compressed);
+        assert!(compressed.len() < input.len());
+
+        let mut decompressed = vec![];
+        decompress(&compressed, &mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn lz4_raw_block() {
+        let data = b"not worth compressing";
+
+        let mut stream = vec![];
+        write_header(
+            &mut stream,
+            METHOD_RAW,
+            data.len() as u32,
+            data.len() as u32,
+            checksum(data),
+        );
+        stream.extend_from_slice(data);
+        write_header(&mut stream, METHOD_RAW, 0, 0, 0);
+
+        let mut out = vec![];
+        decompress(&stream, &mut out).unwrap();
+        assert_eq!(out, data);
+
+        // Corrupt the data.
+        let last = stream.len() - HEADER_LEN - 1;
+        stream[last] ^= 1;
+        assert!(decompress(&stream, &mut vec![]).is_err());
+    }
+}
@@ -1,15 +1,18 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
 use std::path::PathBuf;
 
 use thiserror::Error;
+use valence_nbt::compound;
 use valence_server::block::{PropName, PropValue};
-use valence_server::layer::chunk::{Chunk, UnloadedChunk};
+use valence_server::layer::chunk::{Chunk, Heightmaps, UnloadedChunk};
 use valence_server::nbt::{Compound, List, Value};
 use valence_server::protocol::BlockKind;
 use valence_server::registry::biome::BiomeId;
-use valence_server::registry::BiomeRegistry;
-use valence_server::{ChunkPos, Ident};
+use valence_server::registry::{BiomeRegistry, RegistryIdx};
+use valence_server::{BlockState, ChunkPos, Ident};
 
 use crate::{RegionError, RegionFolder};
 
@@ -18,6 +21,8 @@ pub struct DimensionFolder {
     region: RegionFolder,
     /// Mapping of biome names to their biome ID.
     biome_to_id: BTreeMap<Ident<String>, BiomeId>,
+    /// Mapping of biome IDs to their biome name, indexed by biome ID.
+    id_to_biome: Vec<Ident<String>>,
 }
 
 impl DimensionFolder {
@@ -31,6 +36,10 @@ impl DimensionFolder {
                 .iter()
                 .map(|(id, name, _)| (name.to_string_ident(), id))
                 .collect(),
+            id_to_biome: biomes
+                .iter()
+                .map(|(_, name, _)| name.to_string_ident())
+                .collect(),
         }
     }
 
@@ -50,9 +59,23 @@ impl DimensionFolder {
             timestamp: raw_chunk.timestamp,
         }))
     }
+
+    /// Writes the chunk at the given chunk position in the vanilla format,
+    /// overwriting the old chunk if it exists. `min_y` is the lowest Y
+    /// coordinate of the dimension the chunk belongs to.
+    pub fn set_chunk(
+        &mut self,
+        pos: ChunkPos,
+        chunk: &UnloadedChunk,
+        min_y: i32,
+    ) -> Result<(), RegionError> {
+        let nbt = write_chunk(pos, chunk, min_y, &self.id_to_biome);
+        self.region.set_chunk(pos.x, pos.z, &nbt)
+    }
 }
 
 /// A chunk parsed to show block information, biome information etc.
+#[derive(Debug)]
 pub struct ParsedChunk {
     pub chunk: UnloadedChunk,
     pub timestamp: u32,
@@ -360,9 +383,188 @@ fn parse_chunk(
         }
     }
 
+    // Heightmaps that are missing or malformed are computed when the chunk is
+    // loaded instead.
+    if let Some(Value::Compound(heightmaps)) = nbt.get("Heightmaps") {
+        let heightmaps = Heightmaps::from_nbt(heightmaps, chunk.height());
+        chunk.set_heightmaps(heightmaps);
+    }
+
     Ok(chunk)
 }
 
+/// Converts a chunk into the NBT format used by vanilla region files. This is
+/// the inverse of [`parse_chunk`].
+fn write_chunk(
+    pos: ChunkPos,
+    chunk: &UnloadedChunk,
+    min_y: i32,
+    biome_names: &[Ident<String>],
+) -> Compound {
+    let min_sect_y = min_y.div_euclid(16);
+
+    let mut sections = vec![];
+
+    let mut block_palette = vec![];
+    let mut block_to_idx = HashMap::new();
+    let mut block_idxs = vec![0; BLOCKS_PER_SECTION];
+
+    let mut biome_palette = vec![];
+    let mut biome_to_idx = HashMap::new();
+    let mut biome_idxs = vec![0; BIOMES_PER_SECTION];
+
+    for sect_y in 0..chunk.height() / 16 {
+        block_to_idx.clear();
+
+        for (i, idx) in block_idxs.iter_mut().enumerate() {
+            let i = i as u32;
+
+            let x = i % 16;
+            let z = i / 16 % 16;
+            let y = i / (16 * 16);
+
+            let state = chunk.block_state(x, sect_y * 16 + y, z);
+
+            *idx = match block_to_idx.entry(state) {
+                Entry::Occupied(oe) => *oe.get(),
+                Entry::Vacant(ve) => {
+                    block_palette.push(block_state_to_nbt(state));
+                    *ve.insert(block_palette.len() - 1)
+                }
+            };
+        }
+
+        let mut block_states = Compound::new();
+
+        if block_palette.len() > 1 {
+            let bits_per_idx = bit_width(block_palette.len() - 1).max(4);
+            block_states.insert("data", pack_indices(&block_idxs, bits_per_idx));
+        }
+
+        block_states.insert("palette", List::Compound(mem::take(&mut block_palette)));
+
+        biome_to_idx.clear();
+
+        for (i, idx) in biome_idxs.iter_mut().enumerate() {
+            let i = i as u32;
+
+            let x = i % 4;
+            let z = i / 4 % 4;
+            let y = i / (4 * 4);
+
+            let biome = chunk.biome(x, sect_y * 4 + y, z);
+
+            *idx = match biome_to_idx.entry(biome) {
+                Entry::Occupied(oe) => *oe.get(),
+                Entry::Vacant(ve) => {
+                    let name = biome_names
+                        .get(biome.to_index())
+                        .map_or("minecraft:plains", |name| name.as_str());
+
+                    biome_palette.push(name.to_owned());
+                    *ve.insert(biome_palette.len() - 1)
+                }
+            };
+        }
+
+        let mut biomes = Compound::new();
+
+        if biome_palette.len() > 1 {
+            let bits_per_idx = bit_width(biome_palette.len() - 1);
+            biomes.insert("data", pack_indices(&biome_idxs, bits_per_idx));
+        }
+
+        biomes.insert("palette", List::String(mem::take(&mut biome_palette)));
+
+        sections.push(compound! {
+            "Y" => (min_sect_y + sect_y as i32) as i8,
+            "block_states" => block_states,
+            "biomes" => biomes,
+        });
+    }
+
+    let block_entities: Vec<_> = chunk
+        .block_entities()
+        .filter_map(|((x, y, z), nbt)| {
+            let kind = chunk.block_state(x, y, z).block_entity_kind()?;
+
+            let mut nbt = nbt.clone();
+            nbt.insert("id", kind.ident().to_string());
+            nbt.insert("x", pos.x * 16 + x as i32);
+            nbt.insert("y", min_y + y as i32);
+            nbt.insert("z", pos.z * 16 + z as i32);
+            nbt.insert("keepPacked", false);
+
+            Some(nbt)
+        })
+        .collect();
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => pos.x,
+        "zPos" => pos.z,
+        "yPos" => min_sect_y,
+        "Status" => "minecraft:full",
+        "LastUpdate" => 0_i64,
+        "InhabitedTime" => 0_i64,
+        // Let the vanilla server compute the light on load.
+        "isLightOn" => false,
+        "sections" => List::Compound(sections),
+        "block_entities" => List::Compound(block_entities),
+        "Heightmaps" => write_heightmaps(chunk),
+    }
+}
+
+/// Writes all heightmaps of a chunk, computing them if the chunk doesn't have
+/// them.
+fn write_heightmaps(chunk: &UnloadedChunk) -> Compound {
+    match chunk.heightmaps() {
+        Some(heightmaps) => heightmaps.to_nbt(chunk.height()),
+        None => Heightmaps::compute(chunk).to_nbt(chunk.height()),
+    }
+}
+
+/// Converts a block state into a compound with its name and properties.
+fn block_state_to_nbt(state: BlockState) -> Compound {
+    let kind = state.to_kind();
+
+    let mut nbt = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    if !kind.props().is_empty() {
+        let properties: Compound = kind
+            .props()
+            .iter()
+            .filter_map(|&name| {
+                let value = state.get(name)?;
+                Some((name.to_str().to_owned(), Value::from(value.to_str())))
+            })
+            .collect();
+
+        nbt.insert("Properties", properties);
+    }
+
+    nbt
+}
+
+/// Packs indices of `bits_per_idx` bits into longs. Indices do not span
+/// across longs.
+fn pack_indices(idxs: &[usize], bits_per_idx: usize) -> Value {
+    let idxs_per_long = 64 / bits_per_idx;
+    let mut data = vec![0_i64; idxs.len().div_ceil(idxs_per_long)];
+
+    for (i, &idx) in idxs.iter().enumerate() {
+        data[i / idxs_per_long] |= (idx as i64) << (i % idxs_per_long * bits_per_idx);
+    }
+
+    Value::LongArray(data)
+}
+
+/// The data version of chunks written by Valence. Vanilla uses this to upgrade
+/// chunks from older versions of the game.
+const DATA_VERSION: i32 = 4082;
+
 const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
 const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
 
@@ -378,3 +580,71 @@ fn ident_path(ident: &str) -> &str {
 const fn bit_width(n: usize) -> usize {
     (usize::BITS - n.leading_zeros()) as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_server::protocol::BlockKind;
+
+    use super::*;
+
+    #[test]
+    fn write_then_parse_chunk() {
+        let biome_names = [
+            Ident::new("minecraft:plains").unwrap().to_string_ident(),
+            Ident::new("minecraft:desert").unwrap().to_string_ident(),
+        ];
+        let biome_map = biome_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), BiomeId::from_index(i)))
+            .collect();
+
+        let mut chunk = UnloadedChunk::with_height(64);
+
+        chunk.fill_block_state_section(0, BlockState::STONE);
+        chunk.set_block_state(
+            3,
+            20,
+            7,
+            BlockState::OAK_LOG.set(PropName::Axis, PropValue::X),
+        );
+        chunk.set_block_state(4, 20, 7, BlockState::CHEST);
+        chunk.set_block_entity(4, 20, 7, Some(compound! { "Lock" => "key" }));
+        chunk.set_biome(1, 9, 2, BiomeId::from_index(1));
+
+        let nbt = write_chunk(ChunkPos::new(-3, 5), &chunk, -32, &biome_names);
+
+        let Some(Value::Int(y_pos)) = nbt.get("yPos") else {
+            panic!("missing yPos");
+        };
+        assert_eq!(*y_pos, -2);
+
+        let parsed = parse_chunk(nbt, &biome_map).unwrap();
+
+        assert_eq!(parsed.height(), 64);
+
+        for y in 0..64 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(parsed.block_state(x, y, z), chunk.block_state(x, y, z));
+                }
+            }
+        }
+
+        for y in 0..16 {
+            for z in 0..4 {
+                for x in 0..4 {
+                    assert_eq!(parsed.biome(x, y, z), chunk.biome(x, y, z));
+                }
+            }
+        }
+
+        assert_eq!(parsed.block_state(3, 20, 7).to_kind(), BlockKind::OakLog);
+        assert_eq!(
+            parsed.block_entity(4, 20, 7),
+            Some(&compound! { "Lock" => "key" })
+        );
+        // The heightmaps are stored with the chunk.
+        assert_eq!(parsed.heightmaps(), Some(&Heightmaps::compute(&chunk)));
+    }
+}
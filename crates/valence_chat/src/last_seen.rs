@@ -0,0 +1,230 @@
+//! Bookkeeping of the signed messages a client has received.
+//!
+//! Every signed message includes the signatures of the last messages its
+//! sender had seen, which ties it to the conversation it was sent in. Clients
+//! acknowledge the messages they have seen in a window of the last 20 messages
+//! sent to them, and refer to them by index into a cache of recent signatures
+//! instead of sending the full signature every time. Both sides have to keep
+//! the exact same state for this to work.
+
+use std::collections::VecDeque;
+
+use valence_server::protocol::FixedBitSet;
+
+use crate::{ChatError, MessageSignature};
+
+/// The number of messages a client can acknowledge at a time.
+pub(crate) const LAST_SEEN_COUNT: usize = 20;
+
+/// The number of signatures in the cache of a client.
+const SIGNATURE_CACHE_SIZE: usize = 128;
+
+/// The number of messages that may await acknowledgement before the client is
+/// disconnected.
+const MAX_PENDING: usize = 4096;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct TrackedMessage {
+    signature: MessageSignature,
+    /// Whether the client has neither acknowledged nor ignored the message
+    /// yet.
+    pending: bool,
+}
+
+/// Validates the acknowledgements of a client.
+#[derive(Clone, Debug)]
+pub(crate) struct LastSeenValidator {
+    /// The first [`LAST_SEEN_COUNT`] entries are the window the client
+    /// acknowledges messages in. Messages are `None` once they are ignored.
+    tracked: VecDeque<Option<TrackedMessage>>,
+    last_pending: Option<MessageSignature>,
+}
+
+impl Default for LastSeenValidator {
+    fn default() -> Self {
+        Self {
+            tracked: std::iter::repeat_n(None, LAST_SEEN_COUNT).collect(),
+            last_pending: None,
+        }
+    }
+}
+
+impl LastSeenValidator {
+    /// Tracks a signed message that was sent to the client.
+    pub(crate) fn add_pending(&mut self, signature: &MessageSignature) -> Result<(), ChatError> {
+        if self.last_pending.as_ref() != Some(signature) {
+            self.tracked.push_back(Some(TrackedMessage {
+                signature: *signature,
+                pending: true,
+            }));
+            self.last_pending = Some(*signature);
+        }
+
+        if self.tracked.len() > MAX_PENDING {
+            return Err(ChatError::TooManyPending);
+        }
+
+        Ok(())
+    }
+
+    /// Moves the window forward by `offset` messages.
+    pub(crate) fn apply_offset(&mut self, offset: i32) -> Result<(), ChatError> {
+        let max_offset = self.tracked.len() - LAST_SEEN_COUNT;
+
+        match usize::try_from(offset) {
+            Ok(offset) if offset <= max_offset => {
+                self.tracked.drain(..offset);
+                Ok(())
+            }
+            _ => Err(ChatError::InvalidAcknowledgement),
+        }
+    }
+
+    /// Applies an acknowledgement of the client and returns the signatures of
+    /// the messages it had seen, oldest first.
+    pub(crate) fn apply_update(
+        &mut self,
+        offset: i32,
+        acknowledged: FixedBitSet<LAST_SEEN_COUNT, 3>,
+    ) -> Result<Vec<MessageSignature>, ChatError> {
+        self.apply_offset(offset)?;
+
+        let mut last_seen = Vec::new();
+
+        for (idx, entry) in self.tracked.iter_mut().take(LAST_SEEN_COUNT).enumerate() {
+            if acknowledged.bit(idx) {
+                let Some(message) = entry else {
+                    // Acknowledged a message it never received or ignored before.
+                    return Err(ChatError::InvalidAcknowledgement);
+                };
+
+                message.pending = false;
+                last_seen.push(message.signature);
+            } else {
+                if entry.as_ref().is_some_and(|message| !message.pending) {
+                    // Ignored a message it acknowledged before.
+                    return Err(ChatError::InvalidAcknowledgement);
+                }
+
+                *entry = None;
+            }
+        }
+
+        Ok(last_seen)
+    }
+}
+
+/// Mirrors the cache of recent message signatures kept by a client.
+#[derive(Clone, Debug)]
+pub(crate) struct SignatureCache {
+    entries: Vec<Option<MessageSignature>>,
+}
+
+impl Default for SignatureCache {
+    fn default() -> Self {
+        Self {
+            entries: vec![None; SIGNATURE_CACHE_SIZE],
+        }
+    }
+}
+
+impl SignatureCache {
+    /// Returns the index of `signature` in the cache.
+    pub(crate) fn index_of(&self, signature: &MessageSignature) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.as_ref() == Some(signature))
+    }
+
+    /// Adds the signatures of a message that was sent to the client. Like the
+    /// client, the signature of the message goes first, followed by the
+    /// messages it had seen, newest first. Pushed out entries move towards the
+    /// end unless they were pushed in again.
+    pub(crate) fn push(
+        &mut self,
+        last_seen: &[MessageSignature],
+        signature: Option<&MessageSignature>,
+    ) {
+        let mut queue: VecDeque<MessageSignature> = last_seen.iter().copied().collect();
+        queue.extend(signature.copied());
+
+        let pushed = queue.clone();
+
+        for entry in &mut self.entries {
+            let Some(next) = queue.pop_back() else {
+                break;
+            };
+
+            if let Some(previous) = entry.replace(next) {
+                if !pushed.contains(&previous) {
+                    queue.push_front(previous);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(n: u8) -> MessageSignature {
+        [n; 256]
+    }
+
+    fn acknowledged(bits: &[usize]) -> FixedBitSet<LAST_SEEN_COUNT, 3> {
+        let mut set = FixedBitSet([0; 3]);
+        for &bit in bits {
+            set.set_bit(bit, true);
+        }
+        set
+    }
+
+    #[test]
+    fn acknowledge_messages() {
+        let mut validator = LastSeenValidator::default();
+
+        validator.add_pending(&signature(1)).unwrap();
+        validator.add_pending(&signature(2)).unwrap();
+        // Duplicates of the last message are only tracked once.
+        validator.add_pending(&signature(2)).unwrap();
+
+        // The window starts 20 entries before the newest message.
+        let last_seen = validator.apply_update(2, acknowledged(&[18, 19])).unwrap();
+        assert_eq!(last_seen, [signature(1), signature(2)]);
+
+        // Ignoring a message that was acknowledged before is not allowed.
+        assert!(validator
+            .clone()
+            .apply_update(0, acknowledged(&[18]))
+            .is_err());
+
+        // Acknowledging an unknown message is not allowed.
+        assert!(validator
+            .clone()
+            .apply_update(0, acknowledged(&[0, 18, 19]))
+            .is_err());
+
+        // Moving the window past the newest message is not allowed.
+        assert!(validator.clone().apply_offset(1).is_err());
+        assert!(validator.apply_offset(-1).is_err());
+    }
+
+    #[test]
+    fn signature_cache_order() {
+        let mut cache = SignatureCache::default();
+
+        cache.push(&[], Some(&signature(1)));
+        cache.push(&[signature(1)], Some(&signature(2)));
+
+        assert_eq!(cache.index_of(&signature(2)), Some(0));
+        assert_eq!(cache.index_of(&signature(1)), Some(1));
+
+        cache.push(&[signature(2)], Some(&signature(3)));
+
+        assert_eq!(cache.index_of(&signature(3)), Some(0));
+        assert_eq!(cache.index_of(&signature(2)), Some(1));
+        assert_eq!(cache.index_of(&signature(1)), Some(2));
+        assert_eq!(cache.index_of(&signature(4)), None);
+    }
+}
@@ -0,0 +1,640 @@
+#![doc = include_str!("../README.md")]
+
+mod last_seen;
+mod session;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use last_seen::{LastSeenValidator, SignatureCache, LAST_SEEN_COUNT};
+use rsa::RsaPublicKey;
+pub use session::ChatSession;
+use session::SignedContent;
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+use valence_lang::keys;
+use valence_server::client::{Client, DisconnectClient, Username};
+use valence_server::event_loop::{EventLoopPreUpdate, PacketEvent};
+use valence_server::ident;
+use valence_server::message::SendMessage;
+use valence_server::protocol::packets::play::player_chat_s2c::{
+    MessageFilterType, MessageSignature as PackedSignature,
+};
+use valence_server::protocol::packets::play::{
+    ChatAckC2s, ChatC2s, ChatCommandSignedC2s, ChatSessionUpdateC2s, DisguisedChatS2c,
+    PlayerChatS2c,
+};
+use valence_server::protocol::{Bounded, FixedBitSet, VarInt, WritePacket};
+use valence_server::registry::RegistryCodec;
+use valence_server::text::{Color, IntoText};
+use valence_server::{Text, UniqueId};
+
+/// The RSA signature of a chat message.
+pub type MessageSignature = [u8; 256];
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatSettings>()
+            .add_event::<ChatMessageEvent>()
+            .add_systems(PreUpdate, init_chat_state)
+            .add_systems(
+                EventLoopPreUpdate,
+                (
+                    handle_chat_packets,
+                    broadcast_chat_messages.after(handle_chat_packets),
+                ),
+            );
+    }
+}
+
+/// Configures how chat messages are validated and relayed.
+#[derive(Resource, Clone, Debug)]
+pub struct ChatSettings {
+    pub mode: ChatMode,
+    /// The keys Mojang signs the profile keys of players with, as listed
+    /// under `playerCertificateKeys` at
+    /// `https://api.minecraftservices.com/publickeys`. If this is empty, any
+    /// profile key is accepted. Messages are still checked against the
+    /// profile key in that case, but nothing proves that the key belongs to
+    /// the player.
+    pub certificate_keys: Vec<RsaPublicKey>,
+    /// Whether chat messages are sent to all clients automatically.
+    ///
+    /// [`Default`] value: `false`.
+    pub broadcast: bool,
+    /// The entry of the `minecraft:chat_type` registry that broadcast messages
+    /// are formatted with.
+    pub chat_type: String,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            mode: ChatMode::default(),
+            certificate_keys: vec![],
+            broadcast: false,
+            chat_type: "minecraft:chat".into(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum ChatMode {
+    /// Clients with a chat session must sign their messages, which are relayed
+    /// with the signature. Clients without one, like clients in offline mode,
+    /// send unsigned messages.
+    #[default]
+    Secure,
+    /// Like [`ChatMode::Secure`], but clients without a chat session can't
+    /// chat.
+    Enforced,
+    /// Signatures are ignored and all messages are relayed unsigned. Clients
+    /// don't show a warning for these, but they can't be reported either.
+    Unsigned,
+}
+
+/// Sent when a client sends a chat message that passed validation.
+#[derive(Event, Clone, Debug)]
+pub struct ChatMessageEvent {
+    pub client: Entity,
+    pub message: Box<str>,
+    /// In milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The message as it is relayed to other clients.
+    pub player_message: PlayerMessage,
+}
+
+/// A chat message of a player, including everything other clients need to
+/// verify it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerMessage {
+    pub sender: Uuid,
+    /// The position of the message in the sender's chat session.
+    pub index: i32,
+    /// `None` if the message is unsigned.
+    pub signature: Option<Box<MessageSignature>>,
+    pub content: Box<str>,
+    /// In milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub salt: u64,
+    /// The signatures of the messages the sender had seen, oldest first.
+    pub last_seen: Vec<MessageSignature>,
+    /// Shown instead of the content if present. This doesn't invalidate the
+    /// signature, but clients reporting the message report the content.
+    pub unsigned_content: Option<Text>,
+}
+
+/// How a message is presented, using an entry of the `minecraft:chat_type`
+/// registry.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChatTypeBinding {
+    /// The index of the entry in the registry.
+    pub index: i32,
+    /// The `sender` parameter of the chat type.
+    pub name: Text,
+    /// The `target` parameter of the chat type.
+    pub target_name: Option<Text>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum ChatError {
+    #[error("invalid message acknowledgement")]
+    InvalidAcknowledgement,
+    #[error("too many unacknowledged messages")]
+    TooManyPending,
+    #[error("message sent out of order")]
+    OutOfOrder,
+    #[error("invalid profile public key")]
+    InvalidPublicKey,
+    #[error("expired profile public key")]
+    ExpiredPublicKey,
+    #[error("message is not signed")]
+    MissingProfileKey,
+    #[error("message signed with an expired profile key")]
+    ExpiredProfileKey,
+    #[error("invalid message signature")]
+    InvalidSignature,
+    #[error("message chain is broken")]
+    ChainBroken,
+}
+
+impl ChatError {
+    /// Whether the client is disconnected for this error. Otherwise, only the
+    /// message is rejected.
+    pub fn disconnects(self) -> bool {
+        matches!(
+            self,
+            Self::InvalidAcknowledgement
+                | Self::TooManyPending
+                | Self::OutOfOrder
+                | Self::InvalidPublicKey
+                | Self::ExpiredPublicKey
+        )
+    }
+
+    /// The message shown to the client.
+    pub fn reason(self) -> Text {
+        let key = match self {
+            Self::InvalidAcknowledgement => keys::MULTIPLAYER_DISCONNECT_CHAT_VALIDATION_FAILED,
+            Self::TooManyPending => keys::MULTIPLAYER_DISCONNECT_TOO_MANY_PENDING_CHATS,
+            Self::OutOfOrder => keys::CHAT_DISABLED_OUT_OF_ORDER_CHAT,
+            Self::InvalidPublicKey => keys::MULTIPLAYER_DISCONNECT_INVALID_PUBLIC_KEY_SIGNATURE,
+            Self::ExpiredPublicKey => keys::MULTIPLAYER_DISCONNECT_EXPIRED_PUBLIC_KEY,
+            Self::MissingProfileKey => keys::CHAT_DISABLED_MISSING_PROFILE_KEY,
+            Self::ExpiredProfileKey => keys::CHAT_DISABLED_EXPIRED_PROFILE_KEY,
+            Self::InvalidSignature => keys::CHAT_DISABLED_INVALID_SIGNATURE,
+            Self::ChainBroken => keys::CHAT_DISABLED_CHAIN_BROKEN,
+        };
+
+        Text::translate(key, [])
+    }
+}
+
+/// The chat state of a client.
+#[derive(Component, Default, Debug)]
+pub struct ChatState {
+    session: Option<ChatSession>,
+    /// The index of the next message in the session.
+    next_index: i32,
+    /// Set after an invalid signature. Signed messages are rejected until the
+    /// client starts a new session.
+    chain_broken: bool,
+    last_timestamp: u64,
+    last_seen: LastSeenValidator,
+    signature_cache: SignatureCache,
+}
+
+impl ChatState {
+    pub fn session(&self) -> Option<&ChatSession> {
+        self.session.as_ref()
+    }
+
+    /// Sends a chat message to the client. Unsigned messages are sent as
+    /// disguised messages so that clients don't flag them as unverifiable.
+    ///
+    /// Fails if the client hasn't acknowledged too many messages, in which
+    /// case it should be disconnected.
+    pub fn send_message(
+        &mut self,
+        client: &mut Client,
+        message: &PlayerMessage,
+        chat_type: &ChatTypeBinding,
+    ) -> Result<(), ChatError> {
+        let Some(signature) = &message.signature else {
+            client.write_packet(&DisguisedChatS2c {
+                message: match &message.unsigned_content {
+                    Some(content) => content.into_cow_text(),
+                    None => message.content.to_string().into_cow_text(),
+                },
+                chat_type: VarInt(chat_type.index + 1),
+                sender_name: (&chat_type.name).into_cow_text(),
+                target_name: chat_type.target_name.as_ref().map(IntoText::into_cow_text),
+            });
+            return Ok(());
+        };
+
+        let previous_messages = message
+            .last_seen
+            .iter()
+            .map(|seen| match self.signature_cache.index_of(seen) {
+                Some(idx) => PackedSignature {
+                    message_id: idx as i32,
+                    signature: None,
+                },
+                None => PackedSignature {
+                    message_id: -1,
+                    signature: Some(seen),
+                },
+            })
+            .collect();
+
+        client.write_packet(&PlayerChatS2c {
+            sender: message.sender,
+            index: VarInt(message.index),
+            message_signature: Some(signature),
+            message: Bounded(&message.content[..]),
+            timestamp: message.timestamp,
+            salt: message.salt,
+            previous_messages,
+            unsigned_content: message
+                .unsigned_content
+                .as_ref()
+                .map(IntoText::into_cow_text),
+            filter_type: MessageFilterType::PassThrough,
+            filter_type_bits: None,
+            chat_type: VarInt(chat_type.index + 1),
+            network_name: (&chat_type.name).into_cow_text(),
+            network_target_name: chat_type.target_name.as_ref().map(IntoText::into_cow_text),
+        });
+
+        self.signature_cache
+            .push(&message.last_seen, Some(signature));
+        self.last_seen.add_pending(signature)
+    }
+
+    fn start_session(&mut self, session: ChatSession) {
+        self.session = Some(session);
+        self.next_index = 0;
+        self.chain_broken = false;
+    }
+
+    /// Checks the timestamp and acknowledgements that are part of every chat
+    /// message and signed command.
+    fn accept(
+        &mut self,
+        timestamp: u64,
+        offset: i32,
+        acknowledged: FixedBitSet<LAST_SEEN_COUNT, 3>,
+    ) -> Result<Vec<MessageSignature>, ChatError> {
+        let last_seen = self.last_seen.apply_update(offset, acknowledged)?;
+
+        if timestamp < self.last_timestamp {
+            return Err(ChatError::OutOfOrder);
+        }
+        self.last_timestamp = timestamp;
+
+        Ok(last_seen)
+    }
+
+    /// Checks the signatures of the message arguments of a signed command.
+    /// Every signed argument is a message in the chain of the session.
+    fn read_command(
+        &mut self,
+        sender: Uuid,
+        packet: &ChatCommandSignedC2s,
+    ) -> Result<(), ChatError> {
+        let last_seen = self.accept(
+            packet.timestamp,
+            packet.message_count.0,
+            packet.acknowledgement,
+        )?;
+
+        if packet.argument_signatures.is_empty() {
+            return Ok(());
+        }
+
+        let Some(session) = &self.session else {
+            return Err(ChatError::MissingProfileKey);
+        };
+
+        if session.is_expired(now_millis()) {
+            return Err(ChatError::ExpiredProfileKey);
+        }
+
+        if self.chain_broken {
+            return Err(ChatError::ChainBroken);
+        }
+
+        for argument in &packet.argument_signatures {
+            // Only message arguments are signed. They are greedy, so the signed
+            // text is the rest of the command after one of the spaces.
+            let valid = signable_arguments(packet.command.0).any(|message| {
+                let content = SignedContent {
+                    sender,
+                    index: self.next_index,
+                    salt: packet.salt,
+                    timestamp: packet.timestamp,
+                    message,
+                    last_seen: &last_seen,
+                };
+
+                session.verify(argument.signature, &content).is_ok()
+            });
+
+            if !valid {
+                self.chain_broken = true;
+                return Err(ChatError::InvalidSignature);
+            }
+
+            self.next_index += 1;
+        }
+
+        Ok(())
+    }
+
+    fn read_message(
+        &mut self,
+        sender: Uuid,
+        packet: &ChatC2s,
+        mode: ChatMode,
+    ) -> Result<PlayerMessage, ChatError> {
+        let last_seen = self.accept(
+            packet.timestamp,
+            packet.message_count.0,
+            packet.acknowledgement,
+        )?;
+
+        let mut message = PlayerMessage {
+            sender,
+            index: 0,
+            signature: None,
+            content: packet.message.0.into(),
+            timestamp: packet.timestamp,
+            salt: packet.salt,
+            last_seen: vec![],
+            unsigned_content: None,
+        };
+
+        if mode == ChatMode::Unsigned {
+            return Ok(message);
+        }
+
+        let Some(session) = &self.session else {
+            return match mode {
+                ChatMode::Enforced => Err(ChatError::MissingProfileKey),
+                _ => Ok(message),
+            };
+        };
+
+        let Some(signature) = packet.signature else {
+            return Err(ChatError::MissingProfileKey);
+        };
+
+        if session.is_expired(now_millis()) {
+            return Err(ChatError::ExpiredProfileKey);
+        }
+
+        if self.chain_broken {
+            return Err(ChatError::ChainBroken);
+        }
+
+        let content = SignedContent {
+            sender,
+            index: self.next_index,
+            salt: packet.salt,
+            timestamp: packet.timestamp,
+            message: packet.message.0,
+            last_seen: &last_seen,
+        };
+
+        if let Err(e) = session.verify(signature, &content) {
+            self.chain_broken = true;
+            return Err(e);
+        }
+
+        message.index = self.next_index;
+        message.signature = Some(Box::new(*signature));
+        message.last_seen = last_seen;
+        self.next_index += 1;
+
+        Ok(message)
+    }
+}
+
+/// The possible values of a greedy argument of `command`.
+fn signable_arguments(command: &str) -> impl Iterator<Item = &str> {
+    command
+        .match_indices(' ')
+        .map(|(idx, _)| &command[idx + 1..])
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64)
+}
+
+fn init_chat_state(clients: Query<Entity, Added<Client>>, mut commands: Commands) {
+    for client in &clients {
+        commands.entity(client).insert(ChatState::default());
+    }
+}
+
+fn handle_chat_packets(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&mut Client, &mut ChatState, &UniqueId)>,
+    settings: Res<ChatSettings>,
+    mut events: EventWriter<ChatMessageEvent>,
+    mut commands: Commands,
+) {
+    for packet in packets.read() {
+        let Ok((mut client, mut state, uuid)) = clients.get_mut(packet.client) else {
+            continue;
+        };
+
+        let result = if let Some(pkt) = packet.decode::<ChatSessionUpdateC2s>() {
+            if settings.mode == ChatMode::Unsigned {
+                continue;
+            }
+
+            ChatSession::new(
+                uuid.0,
+                pkt.session_id,
+                pkt.expires_at,
+                pkt.public_key_data.0,
+                pkt.key_signature.0,
+                &settings.certificate_keys,
+            )
+            .and_then(|session| {
+                if session.is_expired(now_millis()) {
+                    return Err(ChatError::ExpiredPublicKey);
+                }
+
+                state.start_session(session);
+                Ok(())
+            })
+        } else if let Some(pkt) = packet.decode::<ChatAckC2s>() {
+            state.last_seen.apply_offset(pkt.message_count.0)
+        } else if let Some(pkt) = packet.decode::<ChatCommandSignedC2s>() {
+            // The command itself is handled by the command plugin.
+            if settings.mode == ChatMode::Unsigned {
+                state
+                    .accept(pkt.timestamp, pkt.message_count.0, pkt.acknowledgement)
+                    .map(drop)
+            } else {
+                state.read_command(uuid.0, &pkt)
+            }
+        } else if let Some(pkt) = packet.decode::<ChatC2s>() {
+            state
+                .read_message(uuid.0, &pkt, settings.mode)
+                .map(|player_message| {
+                    events.send(ChatMessageEvent {
+                        client: packet.client,
+                        message: pkt.message.0.into(),
+                        timestamp: pkt.timestamp,
+                        player_message,
+                    });
+                })
+        } else {
+            continue;
+        };
+
+        if let Err(e) = result {
+            if e.disconnects() {
+                warn!("disconnecting client {:?}: {e}", packet.client);
+                commands.add(DisconnectClient {
+                    client: packet.client,
+                    reason: e.reason(),
+                });
+            } else {
+                client.send_chat_message(e.reason().color(Color::RED));
+            }
+        }
+    }
+}
+
+fn broadcast_chat_messages(
+    mut events: EventReader<ChatMessageEvent>,
+    mut clients: Query<(Entity, &mut Client, &mut ChatState)>,
+    usernames: Query<&Username>,
+    settings: Res<ChatSettings>,
+    codec: Res<RegistryCodec>,
+    mut commands: Commands,
+) {
+    if !settings.broadcast {
+        return;
+    }
+
+    let Some(index) = codec
+        .registry(ident!("chat_type"))
+        .iter()
+        .position(|value| value.name.as_str() == settings.chat_type)
+    else {
+        if !events.is_empty() {
+            warn!("chat type {} is not registered", settings.chat_type);
+        }
+        events.clear();
+        return;
+    };
+
+    for event in events.read() {
+        let name = usernames
+            .get(event.client)
+            .map_or_else(|_| Text::default(), |name| name.0.clone().into_text());
+
+        let chat_type = ChatTypeBinding {
+            index: index as i32,
+            name,
+            target_name: None,
+        };
+
+        for (entity, mut client, mut state) in &mut clients {
+            if let Err(e) = state.send_message(&mut client, &event.player_message, &chat_type) {
+                commands.add(DisconnectClient {
+                    client: entity,
+                    reason: e.reason(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs1v15::SigningKey;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::signature::{SignatureEncoding, Signer};
+    use rsa::RsaPrivateKey;
+    use sha2::Sha256;
+    use valence_server::protocol::packets::play::chat_command_signed_c2s::CommandArgumentSignature;
+
+    use super::*;
+
+    #[test]
+    fn verify_command_arguments() {
+        let player_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let public_key = player_key.to_public_key().to_public_key_der().unwrap();
+        let sender = Uuid::from_u128(1);
+        let session_id = Uuid::from_u128(2);
+
+        let mut state = ChatState::default();
+        state.start_session(
+            ChatSession::new(
+                sender,
+                session_id,
+                i64::MAX,
+                public_key.as_bytes(),
+                &[],
+                &[],
+            )
+            .unwrap(),
+        );
+
+        let sign = |index, message| -> MessageSignature {
+            let content = SignedContent {
+                sender,
+                index,
+                salt: 5,
+                timestamp: 1000,
+                message,
+                last_seen: &[],
+            };
+
+            SigningKey::<Sha256>::new(player_key.clone())
+                .sign(&content.payload(session_id))
+                .to_vec()
+                .try_into()
+                .unwrap()
+        };
+
+        let command = |signature| ChatCommandSignedC2s {
+            command: Bounded("msg Steve hello there"),
+            timestamp: 1000,
+            salt: 5,
+            argument_signatures: vec![CommandArgumentSignature {
+                argument_name: Bounded("message"),
+                signature,
+            }],
+            message_count: VarInt(0),
+            acknowledgement: FixedBitSet::default(),
+        };
+
+        let signature = sign(0, "hello there");
+        state.read_command(sender, &command(&signature)).unwrap();
+        assert_eq!(state.next_index, 1);
+
+        // Signed with the wrong index, so it doesn't belong to the chain.
+        assert_eq!(
+            state.read_command(sender, &command(&signature)),
+            Err(ChatError::InvalidSignature)
+        );
+        assert_eq!(
+            state.read_command(sender, &command(&sign(1, "hello there"))),
+            Err(ChatError::ChainBroken)
+        );
+    }
+}
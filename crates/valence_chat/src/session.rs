@@ -0,0 +1,207 @@
+//! Chat sessions and the verification of message signatures.
+
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use rsa::RsaPublicKey;
+use sha1::Sha1;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{ChatError, MessageSignature};
+
+/// The profile key a client signs its messages with. Clients start a new
+/// session every time they join.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChatSession {
+    pub session_id: Uuid,
+    /// When the key expires, in milliseconds since the Unix epoch.
+    pub expires_at: i64,
+    public_key: RsaPublicKey,
+}
+
+impl ChatSession {
+    /// Creates a session from the DER encoded `public_key` of a client.
+    ///
+    /// If `certificate_keys` is not empty, `key_signature` must be a signature
+    /// of the key by one of them, which proves that Mojang issued the key to
+    /// the player with the UUID `profile_id`.
+    pub fn new(
+        profile_id: Uuid,
+        session_id: Uuid,
+        expires_at: i64,
+        public_key: &[u8],
+        key_signature: &[u8],
+        certificate_keys: &[RsaPublicKey],
+    ) -> Result<Self, ChatError> {
+        let key = RsaPublicKey::from_public_key_der(public_key)
+            .map_err(|_| ChatError::InvalidPublicKey)?;
+
+        if !certificate_keys.is_empty() {
+            let mut payload = Vec::with_capacity(24 + public_key.len());
+            payload.extend_from_slice(profile_id.as_bytes());
+            payload.extend_from_slice(&expires_at.to_be_bytes());
+            payload.extend_from_slice(public_key);
+
+            let signature =
+                Signature::try_from(key_signature).map_err(|_| ChatError::InvalidPublicKey)?;
+
+            let certified = certificate_keys.iter().any(|certificate_key| {
+                VerifyingKey::<Sha1>::new(certificate_key.clone())
+                    .verify(&payload, &signature)
+                    .is_ok()
+            });
+
+            if !certified {
+                return Err(ChatError::InvalidPublicKey);
+            }
+        }
+
+        Ok(Self {
+            session_id,
+            expires_at,
+            public_key: key,
+        })
+    }
+
+    pub fn public_key(&self) -> &RsaPublicKey {
+        &self.public_key
+    }
+
+    /// Returns whether the key has expired at `now`, in milliseconds since
+    /// the Unix epoch.
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Checks the signature of a message sent in this session.
+    pub(crate) fn verify(
+        &self,
+        signature: &MessageSignature,
+        content: &SignedContent,
+    ) -> Result<(), ChatError> {
+        let signature =
+            Signature::try_from(&signature[..]).map_err(|_| ChatError::InvalidSignature)?;
+
+        VerifyingKey::<Sha256>::new(self.public_key.clone())
+            .verify(&content.payload(self.session_id), &signature)
+            .map_err(|_| ChatError::InvalidSignature)
+    }
+}
+
+/// Everything that is covered by the signature of a message.
+pub(crate) struct SignedContent<'a> {
+    pub(crate) sender: Uuid,
+    pub(crate) index: i32,
+    pub(crate) salt: u64,
+    /// In milliseconds since the Unix epoch.
+    pub(crate) timestamp: u64,
+    pub(crate) message: &'a str,
+    pub(crate) last_seen: &'a [MessageSignature],
+}
+
+impl SignedContent<'_> {
+    /// The bytes that are signed, in the same layout as the client.
+    pub(crate) fn payload(&self, session_id: Uuid) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(64 + self.message.len() + size_of_val(self.last_seen));
+
+        // Version
+        payload.extend_from_slice(&1_i32.to_be_bytes());
+
+        // Link to the previous message
+        payload.extend_from_slice(self.sender.as_bytes());
+        payload.extend_from_slice(session_id.as_bytes());
+        payload.extend_from_slice(&self.index.to_be_bytes());
+
+        // Body, which only has a precision of seconds
+        payload.extend_from_slice(&self.salt.to_be_bytes());
+        payload.extend_from_slice(&(self.timestamp / 1000).to_be_bytes());
+        payload.extend_from_slice(&(self.message.len() as i32).to_be_bytes());
+        payload.extend_from_slice(self.message.as_bytes());
+        payload.extend_from_slice(&(self.last_seen.len() as i32).to_be_bytes());
+        for signature in self.last_seen {
+            payload.extend_from_slice(signature);
+        }
+
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs1v15::SigningKey;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::signature::{SignatureEncoding, Signer};
+    use rsa::RsaPrivateKey;
+
+    use super::*;
+
+    #[test]
+    fn verify_session_and_message() {
+        let mut rng = rand::thread_rng();
+        let mojang_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let player_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+
+        let profile_id = Uuid::from_u128(1);
+        let session_id = Uuid::from_u128(2);
+        let expires_at = 1_000_000_i64;
+        let public_key = player_key.to_public_key().to_public_key_der().unwrap();
+
+        let mut certificate = profile_id.as_bytes().to_vec();
+        certificate.extend_from_slice(&expires_at.to_be_bytes());
+        certificate.extend_from_slice(public_key.as_bytes());
+        let key_signature = SigningKey::<Sha1>::new(mojang_key.clone())
+            .sign(&certificate)
+            .to_vec();
+
+        let certificate_keys = [mojang_key.to_public_key()];
+
+        let session = ChatSession::new(
+            profile_id,
+            session_id,
+            expires_at,
+            public_key.as_bytes(),
+            &key_signature,
+            &certificate_keys,
+        )
+        .unwrap();
+
+        assert!(!session.is_expired(expires_at - 1));
+        assert!(session.is_expired(expires_at));
+
+        // A key for someone else is rejected.
+        assert!(ChatSession::new(
+            Uuid::from_u128(3),
+            session_id,
+            expires_at,
+            public_key.as_bytes(),
+            &key_signature,
+            &certificate_keys,
+        )
+        .is_err());
+
+        let content = SignedContent {
+            sender: profile_id,
+            index: 0,
+            salt: 1234,
+            timestamp: 1_700_000_000_123,
+            message: "hello",
+            last_seen: &[[7; 256]],
+        };
+
+        let signature: MessageSignature = SigningKey::<Sha256>::new(player_key)
+            .sign(&content.payload(session_id))
+            .to_vec()
+            .try_into()
+            .unwrap();
+
+        session.verify(&signature, &content).unwrap();
+
+        let tampered = SignedContent {
+            message: "goodbye",
+            ..content
+        };
+
+        assert!(session.verify(&signature, &tampered).is_err());
+    }
+}
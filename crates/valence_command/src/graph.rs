@@ -75,12 +75,15 @@ use std::fmt::{Display, Formatter};
 
 use petgraph::dot::Dot;
 use petgraph::prelude::*;
-use valence_server::protocol::packets::play::commands_s2c::{Node, NodeData, Parser, StringArg};
+use valence_server::protocol::packets::play::commands_s2c::{
+    Node, NodeData, Parser, StringArg, Suggestion,
+};
 use valence_server::protocol::packets::play::CommandsS2c;
 use valence_server::protocol::VarInt;
 
 use crate::modifier_value::ModifierValue;
 use crate::parsers::{CommandArg, ParseInput};
+use crate::suggestions::SuggestionProvider;
 use crate::{CommandRegistry, CommandScopeRegistry};
 
 /// This struct is used to store the command graph. (see module level docs for
@@ -258,6 +261,7 @@ pub struct CommandGraphBuilder<'a, T> {
     executables: &'a mut HashMap<NodeIndex, fn(&mut ParseInput) -> T>,
     parsers: &'a mut HashMap<NodeIndex, fn(&mut ParseInput) -> bool>,
     modifiers: &'a mut HashMap<NodeIndex, fn(String, &mut HashMap<ModifierValue, ModifierValue>)>,
+    suggestions: &'a mut HashMap<NodeIndex, SuggestionProvider>,
     scopes_added: Vec<String>, /* we need to keep track of added scopes so we can add them to
                                 * the registry later */
 }
@@ -284,6 +288,7 @@ impl<'a, T> CommandGraphBuilder<'a, T> {
             executables,
             parsers,
             modifiers,
+            suggestions: &mut registry.suggestions,
             scopes_added: Vec::new(),
         }
     }
@@ -487,7 +492,9 @@ impl<'a, T> CommandGraphBuilder<'a, T> {
     /// Sets the parser for the current node. This will decide how the argument
     /// is parsed client side and will be used to check the argument before
     /// it is passed to the executable. The node should be an argument node
-    /// or nothing will happen.
+    /// or nothing will happen. The parser also provides the tab-completions
+    /// of the node (see [`CommandArg::suggestions`]). Clients only ask the
+    /// server for them if the parser has any.
     ///
     /// # Type Parameters
     /// * `P` - the parser to use for the current node (must be [`CommandArg`])
@@ -498,13 +505,18 @@ impl<'a, T> CommandGraphBuilder<'a, T> {
         let node = graph.node_weight_mut(current_node).unwrap();
         self.parsers
             .insert(current_node, |input| P::parse_arg(input).is_ok());
+        let suggestion = if P::HAS_SUGGESTIONS {
+            self.suggestions.insert(current_node, P::suggestions);
+            Some(Suggestion::AskServer)
+        } else {
+            self.suggestions.remove(&current_node);
+            None
+        };
 
         let parser = P::display();
 
         node.data = match node.data.clone() {
-            NodeData::Argument {
-                name, suggestion, ..
-            } => NodeData::Argument {
+            NodeData::Argument { name, .. } => NodeData::Argument {
                 name,
                 parser,
                 suggestion,
@@ -516,6 +528,28 @@ impl<'a, T> CommandGraphBuilder<'a, T> {
         self
     }
 
+    /// Sets the tab-completions for the current node, replacing the ones of
+    /// its parser. This should be called after
+    /// [`with_parser`](Self::with_parser). The node should be an argument node
+    /// or nothing will happen, as literals always complete to their name.
+    ///
+    /// # Arguments
+    /// * provider - the function returning the completions (see
+    ///   [`suggestions`](crate::suggestions))
+    pub fn with_suggestions(&mut self, provider: SuggestionProvider) -> &mut Self {
+        let graph = &mut self.graph.graph;
+        let current_node = self.current_node;
+
+        let node = graph.node_weight_mut(current_node).unwrap();
+
+        if let NodeData::Argument { suggestion, .. } = &mut node.data {
+            *suggestion = Some(Suggestion::AskServer);
+            self.suggestions.insert(current_node, provider);
+        }
+
+        self
+    }
+
     /// Transitions to the node specified.
     pub fn at(&mut self, node: NodeIndex) -> &mut Self {
         self.current_node = node;
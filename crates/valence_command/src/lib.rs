@@ -4,6 +4,8 @@ pub mod manager;
 mod modifier_value;
 pub mod parsers;
 pub mod scopes;
+pub mod selector;
+pub mod suggestions;
 
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
@@ -14,6 +16,7 @@ pub use manager::{CommandExecutionEvent, CommandProcessedEvent};
 pub use modifier_value::ModifierValue;
 use petgraph::prelude::NodeIndex;
 pub use scopes::CommandScopeRegistry;
+use suggestions::SuggestionProvider;
 
 use crate::graph::{CommandGraph, CommandGraphBuilder};
 use crate::handler::CommandHandlerPlugin;
@@ -29,6 +32,7 @@ pub struct CommandRegistry {
     pub parsers: HashMap<NodeIndex, fn(&mut ParseInput) -> bool>,
     pub modifiers: HashMap<NodeIndex, fn(String, &mut HashMap<ModifierValue, ModifierValue>)>,
     pub executables: HashSet<NodeIndex>,
+    pub suggestions: HashMap<NodeIndex, SuggestionProvider>,
 }
 
 pub trait Command {
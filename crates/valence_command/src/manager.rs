@@ -1,25 +1,32 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 
 use bevy_app::{App, Plugin, PreUpdate};
 use bevy_ecs::entity::Entity;
 use bevy_ecs::prelude::{
     Added, Changed, Commands, DetectChanges, Event, EventReader, EventWriter, IntoSystemConfigs,
-    Mut, Or, Query, Res,
+    Mut, Or, Query, Res, With,
 };
 use petgraph::graph::NodeIndex;
 use petgraph::prelude::EdgeRef;
 use petgraph::{Direction, Graph};
 use tracing::{debug, info, trace, warn};
-use valence_server::client::{Client, SpawnClientsSet};
+use valence_server::client::{Client, SpawnClientsSet, Username};
+use valence_server::entity::Position;
 use valence_server::event_loop::PacketEvent;
+use valence_server::protocol::packets::play::command_suggestions_s2c::CommandSuggestionsMatch;
 use valence_server::protocol::packets::play::commands_s2c::NodeData;
-use valence_server::protocol::packets::play::{ChatCommandSignedC2s, CommandsS2c};
-use valence_server::protocol::WritePacket;
+use valence_server::protocol::packets::play::{
+    ChatCommandSignedC2s, CommandSuggestionC2s, CommandSuggestionsS2c, CommandsS2c,
+};
+use valence_server::protocol::{VarInt, WritePacket};
+use valence_server::reconfigure::Rejoined;
 use valence_server::EventLoopPreUpdate;
 
 use crate::graph::{CommandEdgeType, CommandGraph, CommandNode};
 use crate::parsers::ParseInput;
 use crate::scopes::{CommandScopePlugin, CommandScopes};
+use crate::suggestions::SuggestionContext;
 use crate::{CommandRegistry, CommandScopeRegistry, CommandSystemSet, ModifierValue};
 
 pub struct CommandPlugin;
@@ -36,6 +43,7 @@ impl Plugin for CommandPlugin {
                     update_command_tree,
                     command_tree_update_with_client,
                     read_incoming_packets.before(CommandSystemSet),
+                    handle_suggestion_requests,
                     parse_incoming_commands.in_set(CommandSystemSet),
                 ),
             );
@@ -44,12 +52,14 @@ impl Plugin for CommandPlugin {
         let modifiers = HashMap::new();
         let parsers = HashMap::new();
         let executables = HashSet::new();
+        let suggestions = HashMap::new();
 
         app.insert_resource(CommandRegistry {
             graph,
             parsers,
             modifiers,
             executables,
+            suggestions,
         });
     }
 }
@@ -101,13 +111,70 @@ fn read_incoming_packets(
     }
 }
 
+/// Answers tab-completion requests with the completions of the command
+/// graph, skipping the nodes the client has no scopes for.
+fn handle_suggestion_requests(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&mut Client, Option<&CommandScopes>, Option<&Position>)>,
+    players: Query<&Username, With<Client>>,
+    command_registry: Res<CommandRegistry>,
+    scope_registry: Res<CommandScopeRegistry>,
+) {
+    for packet in packets.read() {
+        let Some(pkt) = packet.decode::<CommandSuggestionC2s>() else {
+            continue;
+        };
+
+        let Ok((mut client, scopes, position)) = clients.get_mut(packet.client) else {
+            continue;
+        };
+
+        let client_scopes: Vec<&str> = scopes
+            .map(|scopes| scopes.0.iter().map(|scope| scope.as_str()).collect())
+            .unwrap_or_default();
+
+        let ctx = SuggestionContext {
+            executor: packet.client,
+            player_names: players.iter().map(|name| name.0.clone()).collect(),
+            position: position.map(|pos| pos.0),
+        };
+
+        let command = pkt.text.0;
+        let suggestions = command_registry.suggest(command, &ctx, |node| {
+            node.scopes.is_empty()
+                || node
+                    .scopes
+                    .iter()
+                    .any(|scope| scope_registry.any_grants(&client_scopes, scope))
+        });
+
+        // The client counts in UTF-16 code units.
+        let start = command[..suggestions.start].encode_utf16().count();
+        let length = command[suggestions.start..].encode_utf16().count();
+
+        client.write_packet(&CommandSuggestionsS2c {
+            id: pkt.transaction_id,
+            start: VarInt(start as i32),
+            length: VarInt(length as i32),
+            matches: suggestions
+                .matches
+                .iter()
+                .map(|suggestion| CommandSuggestionsMatch {
+                    suggested_match: &suggestion.text,
+                    tooltip: suggestion.tooltip.as_ref().map(Cow::Borrowed),
+                })
+                .collect(),
+        });
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn command_tree_update_with_client(
     command_registry: Res<CommandRegistry>,
     scope_registry: Res<CommandScopeRegistry>,
     mut updated_clients: Query<
         (&mut Client, &CommandScopes),
-        Or<(Added<Client>, Changed<CommandScopes>)>,
+        Or<(Added<Client>, Added<Rejoined>, Changed<CommandScopes>)>,
     >,
 ) {
     update_client_command_tree(
@@ -35,6 +35,8 @@ pub(crate) use valence_server::protocol::packets::play::commands_s2c::Parser;
 pub use vec2::Vec2;
 pub use vec3::Vec3;
 
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
+
 pub trait CommandArg: Sized {
     fn arg_from_str(string: &str) -> Result<Self, CommandArgParseError> {
         Self::parse_arg(&mut ParseInput::new(string))
@@ -43,6 +45,59 @@ pub trait CommandArg: Sized {
     fn parse_arg(input: &mut ParseInput) -> Result<Self, CommandArgParseError>;
     /// what will the client be sent
     fn display() -> Parser;
+
+    /// Whether [`suggestions`](Self::suggestions) is overridden. Clients only
+    /// ask the server for completions of arguments whose parser has them.
+    const HAS_SUGGESTIONS: bool = false;
+
+    /// Tab-completions for the partially typed argument `input` (see
+    /// [`SuggestionProvider`](crate::suggestions::SuggestionProvider)). There
+    /// are none by default.
+    fn suggestions(_input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        Vec::new()
+    }
+}
+
+/// Completions for an argument that is one of the given names.
+pub(crate) fn name_suggestions(names: &[&str]) -> Vec<CommandSuggestion> {
+    names
+        .iter()
+        .map(|&name| CommandSuggestion::new(name))
+        .collect()
+}
+
+/// Completions for `count` space separated coordinates. The coordinates that
+/// have not been typed yet are filled in with `~`, or with `absolute` if it is
+/// given.
+pub(crate) fn coordinate_suggestions(
+    input: &str,
+    count: usize,
+    absolute: Option<&[String]>,
+) -> Vec<CommandSuggestion> {
+    let mut words: Vec<&str> = input.split(' ').collect();
+
+    if words.len() > count {
+        return Vec::new();
+    }
+
+    let last = words.pop().unwrap_or_default();
+    let typed = words.len();
+
+    let complete = |fill: &[&str]| {
+        let mut coords = words.clone();
+        coords.push(if last.is_empty() { fill[typed] } else { last });
+        coords.extend_from_slice(&fill[typed + 1..]);
+        CommandSuggestion::new(coords.join(" "))
+    };
+
+    let mut suggestions = vec![complete(&vec!["~"; count])];
+
+    if let Some(absolute) = absolute {
+        let absolute: Vec<&str> = absolute.iter().map(|s| s.as_str()).collect();
+        suggestions.push(complete(&absolute));
+    }
+
+    suggestions
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -225,6 +280,14 @@ where
     fn display() -> Parser {
         T::display()
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(input: &str, ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        let mut suggestions = T::suggestions(input, ctx);
+        suggestions.insert(0, CommandSuggestion::new("~"));
+        suggestions
+    }
 }
 
 impl<T: Default> Default for AbsoluteOrRelative<T> {
@@ -306,4 +369,26 @@ mod test {
         );
         assert!(input.is_done());
     }
+
+    #[test]
+    fn test_coordinate_suggestions() {
+        let texts = |suggestions: Vec<CommandSuggestion>| {
+            suggestions
+                .into_iter()
+                .map(|suggestion| suggestion.text)
+                .collect::<Vec<_>>()
+        };
+        let absolute = ["1".to_owned(), "64".to_owned(), "-3".to_owned()];
+
+        assert_eq!(
+            texts(coordinate_suggestions("", 3, Some(&absolute))),
+            ["~ ~ ~", "1 64 -3"]
+        );
+        assert_eq!(
+            texts(coordinate_suggestions("5 ", 3, Some(&absolute))),
+            ["5 ~ ~", "5 64 -3"]
+        );
+        assert_eq!(texts(coordinate_suggestions("5 ~1", 3, None)), ["5 ~1 ~"]);
+        assert!(coordinate_suggestions("1 2 3 4", 3, None).is_empty());
+    }
 }
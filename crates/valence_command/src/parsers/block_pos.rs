@@ -1,5 +1,8 @@
 use super::Parser;
-use crate::parsers::{AbsoluteOrRelative, CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{
+    coordinate_suggestions, AbsoluteOrRelative, CommandArg, CommandArgParseError, ParseInput,
+};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct BlockPos {
@@ -23,6 +26,17 @@ impl CommandArg for BlockPos {
     fn display() -> Parser {
         Parser::BlockPos
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(input: &str, ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        let absolute = ctx.position.map(|pos| {
+            let pos = pos.floor().as_ivec3();
+            [pos.x, pos.y, pos.z].map(|coord| coord.to_string())
+        });
+
+        coordinate_suggestions(input, 3, absolute.as_ref().map(|coords| coords.as_slice()))
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,6 @@
 use super::Parser;
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{name_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 impl CommandArg for bool {
     fn parse_arg(input: &mut ParseInput) -> Result<Self, CommandArgParseError> {
@@ -19,6 +20,12 @@ impl CommandArg for bool {
     fn display() -> Parser {
         Parser::Bool
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(_input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        name_suggestions(&["true", "false"])
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,8 @@
 use valence_text::Color;
 
 use super::Parser;
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{name_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 impl CommandArg for Color {
     fn parse_arg(input: &mut ParseInput) -> Result<Self, CommandArgParseError> {
@@ -51,4 +52,28 @@ impl CommandArg for Color {
     fn display() -> Parser {
         Parser::Color
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(_input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        name_suggestions(&[
+            "black",
+            "dark_blue",
+            "dark_green",
+            "dark_aqua",
+            "dark_red",
+            "dark_purple",
+            "gold",
+            "gray",
+            "dark_gray",
+            "blue",
+            "green",
+            "aqua",
+            "red",
+            "light_purple",
+            "yellow",
+            "white",
+            "reset",
+        ])
+    }
 }
@@ -1,5 +1,6 @@
 use super::Parser;
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{name_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum EntityAnchor {
@@ -26,4 +27,10 @@ impl CommandArg for EntityAnchor {
     fn display() -> Parser {
         Parser::EntityAnchor
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(_input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        name_suggestions(&["eyes", "feet"])
+    }
 }
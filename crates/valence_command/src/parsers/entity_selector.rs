@@ -1,10 +1,20 @@
+use std::str::FromStr;
+
+use valence_nbt::snbt::from_snbt_str;
+use valence_nbt::{Compound, Value};
+use valence_server::GameMode;
+
 use super::Parser;
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{name_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// An entity selector like `@e[type=zombie,distance=..10]` or a player name.
+/// Use [`EntitySelectorQuery`](crate::selector::EntitySelectorQuery) to find
+/// the entities it selects.
+#[derive(Debug, Clone, PartialEq)]
 pub enum EntitySelector {
     SimpleSelector(EntitySelectors),
-    ComplexSelector(EntitySelectors, String),
+    ComplexSelector(EntitySelectors, Box<SelectorArguments>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -56,13 +66,30 @@ impl CommandArg for EntitySelector {
                             got: c.to_string(),
                         });
                     }
+                    // brackets and braces can be nested inside of the arguments (e.g. in
+                    // `scores` and `nbt`) and quoted strings can contain anything
                     let mut s = String::new();
+                    let mut depth = 0;
+                    let mut quote = None;
+                    let mut escaped = false;
                     while let Some(c) = input.pop() {
-                        if c == ']' {
-                            return Ok(EntitySelector::ComplexSelector(
-                                simple_selector.unwrap(),
-                                s.trim().to_owned(),
-                            ));
+                        match quote {
+                            Some(_) if escaped => escaped = false,
+                            Some(_) if c == '\\' => escaped = true,
+                            Some(q) if c == q => quote = None,
+                            Some(_) => {}
+                            None => match c {
+                                '"' | '\'' => quote = Some(c),
+                                '{' | '[' => depth += 1,
+                                ']' if depth == 0 => {
+                                    return Ok(EntitySelector::ComplexSelector(
+                                        simple_selector.unwrap(),
+                                        Box::new(SelectorArguments::parse(s.trim())?),
+                                    ));
+                                }
+                                '}' | ']' => depth -= 1,
+                                _ => {}
+                            },
                         }
 
                         s.push(c);
@@ -84,12 +111,326 @@ impl CommandArg for EntitySelector {
             single: false,
         }
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(_input: &str, ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        let mut suggestions = name_suggestions(&["@a", "@e", "@p", "@r", "@s"]);
+        suggestions.extend(ctx.player_names.iter().map(CommandSuggestion::new));
+        suggestions
+    }
+}
+
+/// The arguments between the brackets of a selector, e.g.
+/// `type=zombie,distance=..10`. Arguments that are not given are `None` or
+/// empty.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SelectorArguments {
+    /// Overrides the x coordinate the selector is relative to.
+    pub x: Option<f64>,
+    /// Overrides the y coordinate the selector is relative to.
+    pub y: Option<f64>,
+    /// Overrides the z coordinate the selector is relative to.
+    pub z: Option<f64>,
+    /// The distance from the selector's origin.
+    pub distance: Option<SelectorRange<f64>>,
+    /// The size of the volume to select entities in along the x axis.
+    pub dx: Option<f64>,
+    /// The size of the volume to select entities in along the y axis.
+    pub dy: Option<f64>,
+    /// The size of the volume to select entities in along the z axis.
+    pub dz: Option<f64>,
+    /// The pitch of the entity in degrees.
+    pub x_rotation: Option<SelectorRange<f32>>,
+    /// The yaw of the entity in degrees.
+    pub y_rotation: Option<SelectorRange<f32>>,
+    /// The maximum number of entities to select.
+    pub limit: Option<usize>,
+    pub sort: Option<SelectorSort>,
+    /// The experience level of the player.
+    pub level: Option<SelectorRange<i32>>,
+    pub gamemode: Vec<Negatable<GameMode>>,
+    pub name: Vec<Negatable<String>>,
+    /// The entity type, always with a namespace (e.g. `minecraft:zombie`).
+    pub entity_type: Vec<Negatable<String>>,
+    /// An empty tag matches entities without any tags.
+    pub tag: Vec<Negatable<String>>,
+    /// An empty team matches entities that are not on a team.
+    pub team: Vec<Negatable<String>>,
+    /// The objective names and the range the score must be in.
+    pub scores: Vec<(String, SelectorRange<i32>)>,
+    pub nbt: Vec<Negatable<Compound>>,
+}
+
+/// A value that may be prefixed with `!` to select entities that don't match
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negatable<T> {
+    pub value: T,
+    pub negated: bool,
+}
+
+impl<T> Negatable<T> {
+    /// Returns whether `matches` is the outcome that is being selected for.
+    pub fn test(&self, matches: bool) -> bool {
+        matches != self.negated
+    }
+}
+
+/// A range like `1..5`, `..5`, `1..` or `3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorRange<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: PartialOrd> SelectorRange<T> {
+    pub fn contains(&self, value: T) -> bool {
+        self.min.as_ref().is_none_or(|min| *min <= value)
+            && self.max.as_ref().is_none_or(|max| value <= *max)
+    }
+}
+
+impl<T: FromStr> SelectorRange<T> {
+    fn parse(key: &str, s: &str) -> Result<Self, CommandArgParseError> {
+        let bound = |s: &str| {
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                s.parse().map(Some).map_err(|_| invalid(key, s))
+            }
+        };
+
+        let range = match s.split_once("..") {
+            Some((min, max)) => Self {
+                min: bound(min)?,
+                max: bound(max)?,
+            },
+            None => {
+                let exact = bound(s)?;
+                Self {
+                    min: bound(s)?,
+                    max: exact,
+                }
+            }
+        };
+
+        if range.min.is_none() && range.max.is_none() {
+            return Err(invalid(key, s));
+        }
+
+        Ok(range)
+    }
+}
+
+/// The order in which selected entities are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectorSort {
+    Nearest,
+    Furthest,
+    Random,
+    Arbitrary,
+}
+
+fn invalid(key: &str, got: &str) -> CommandArgParseError {
+    CommandArgParseError::InvalidArgument {
+        expected: format!("selector argument {key}"),
+        got: got.to_owned(),
+    }
+}
+
+/// Splits `s` at every `separator` that is not inside of quotes, brackets or
+/// braces.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '{' | '[' => depth += 1,
+                '}' | ']' => depth -= 1,
+                c if c == separator && depth == 0 => {
+                    parts.push(&s[start..i]);
+                    start = i + c.len_utf8();
+                }
+                _ => {}
+            },
+        }
+    }
+
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Removes the quotes around `s` if there are any.
+fn unquote(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(q @ ('"' | '\'')) if s.len() >= 2 && s.ends_with(q) => {
+            let mut out = String::new();
+            let mut escaped = false;
+            for c in s[1..s.len() - 1].chars() {
+                if escaped || c != '\\' {
+                    out.push(c);
+                    escaped = false;
+                } else {
+                    escaped = true;
+                }
+            }
+            out
+        }
+        _ => s.to_owned(),
+    }
+}
+
+fn negatable(value: &str) -> (bool, &str) {
+    match value.strip_prefix('!') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, value),
+    }
+}
+
+impl SelectorArguments {
+    /// Parses the contents of the brackets of a selector.
+    pub fn parse(s: &str) -> Result<Self, CommandArgParseError> {
+        let mut args = Self::default();
+
+        if s.is_empty() {
+            return Ok(args);
+        }
+
+        for arg in split_top_level(s, ',') {
+            let Some((key, value)) = arg.split_once('=') else {
+                return Err(invalid("key=value", arg.trim()));
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+
+            let number = |value: &str| value.parse::<f64>().map_err(|_| invalid(key, value));
+
+            match key {
+                "x" => args.x = Some(number(value)?),
+                "y" => args.y = Some(number(value)?),
+                "z" => args.z = Some(number(value)?),
+                "dx" => args.dx = Some(number(value)?),
+                "dy" => args.dy = Some(number(value)?),
+                "dz" => args.dz = Some(number(value)?),
+                "distance" => {
+                    let range = SelectorRange::<f64>::parse(key, value)?;
+                    if range.min.is_some_and(|min| min < 0.0) {
+                        return Err(invalid(key, value));
+                    }
+                    args.distance = Some(range);
+                }
+                "x_rotation" => args.x_rotation = Some(SelectorRange::parse(key, value)?),
+                "y_rotation" => args.y_rotation = Some(SelectorRange::parse(key, value)?),
+                "level" => args.level = Some(SelectorRange::parse(key, value)?),
+                "limit" => match value.parse() {
+                    Ok(limit) if limit > 0 => args.limit = Some(limit),
+                    _ => return Err(invalid(key, value)),
+                },
+                "sort" => {
+                    args.sort = Some(match value {
+                        "nearest" => SelectorSort::Nearest,
+                        "furthest" => SelectorSort::Furthest,
+                        "random" => SelectorSort::Random,
+                        "arbitrary" => SelectorSort::Arbitrary,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                "gamemode" => {
+                    let (negated, name) = negatable(value);
+                    let value = match name {
+                        "survival" => GameMode::Survival,
+                        "creative" => GameMode::Creative,
+                        "adventure" => GameMode::Adventure,
+                        "spectator" => GameMode::Spectator,
+                        _ => return Err(invalid(key, name)),
+                    };
+                    args.gamemode.push(Negatable { value, negated });
+                }
+                "name" | "tag" | "team" => {
+                    let (negated, name) = negatable(value);
+                    let value = unquote(name);
+                    match key {
+                        "name" => &mut args.name,
+                        "tag" => &mut args.tag,
+                        _ => &mut args.team,
+                    }
+                    .push(Negatable { value, negated });
+                }
+                "type" => {
+                    let (negated, name) = negatable(value);
+                    if name.is_empty() || name.starts_with('#') {
+                        // entity type tags are not supported
+                        return Err(invalid(key, name));
+                    }
+                    let value = if name.contains(':') {
+                        name.to_owned()
+                    } else {
+                        format!("minecraft:{name}")
+                    };
+                    args.entity_type.push(Negatable { value, negated });
+                }
+                "scores" => {
+                    let Some(scores) = value
+                        .strip_prefix('{')
+                        .and_then(|scores| scores.strip_suffix('}'))
+                    else {
+                        return Err(invalid(key, value));
+                    };
+                    for score in split_top_level(scores, ',') {
+                        if score.trim().is_empty() {
+                            continue;
+                        }
+                        let Some((objective, range)) = score.split_once('=') else {
+                            return Err(invalid(key, score.trim()));
+                        };
+                        args.scores.push((
+                            unquote(objective.trim()),
+                            SelectorRange::parse(key, range.trim())?,
+                        ));
+                    }
+                }
+                "nbt" => {
+                    let (negated, snbt) = negatable(value);
+                    let Ok(Value::Compound(value)) = from_snbt_str(snbt) else {
+                        return Err(invalid(key, snbt));
+                    };
+                    args.nbt.push(Negatable { value, negated });
+                }
+                _ => return Err(invalid("known selector argument", key)),
+            }
+        }
+
+        Ok(args)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn distance_5() -> Box<SelectorArguments> {
+        Box::new(SelectorArguments {
+            distance: Some(SelectorRange {
+                min: None,
+                max: Some(5.0),
+            }),
+            ..Default::default()
+        })
+    }
+
     #[test]
     fn test_entity_selector() {
         let mut input = ParseInput::new("@e");
@@ -102,10 +443,7 @@ mod tests {
         let mut input = ParseInput::new("@e[distance=..5]");
         assert_eq!(
             EntitySelector::parse_arg(&mut input).unwrap(),
-            EntitySelector::ComplexSelector(
-                EntitySelectors::AllEntities,
-                "distance=..5".to_owned()
-            )
+            EntitySelector::ComplexSelector(EntitySelectors::AllEntities, distance_5())
         );
         assert!(input.is_done());
 
@@ -116,41 +454,114 @@ mod tests {
         let mut input = ParseInput::new("@r[distance=..5] hello");
         assert_eq!(
             EntitySelector::parse_arg(&mut input).unwrap(),
-            EntitySelector::ComplexSelector(
-                EntitySelectors::RandomPlayer,
-                "distance=..5".to_owned()
-            )
+            EntitySelector::ComplexSelector(EntitySelectors::RandomPlayer, distance_5())
         );
         assert!(!input.is_done());
 
         let mut input = ParseInput::new("@p[distance=..5]hello");
         assert_eq!(
             EntitySelector::parse_arg(&mut input).unwrap(),
-            EntitySelector::ComplexSelector(
-                EntitySelectors::NearestPlayer,
-                "distance=..5".to_owned()
-            )
+            EntitySelector::ComplexSelector(EntitySelectors::NearestPlayer, distance_5())
         );
         assert!(!input.is_done());
 
         let mut input = ParseInput::new("@e[distance=..5] hello world");
         assert_eq!(
             EntitySelector::parse_arg(&mut input).unwrap(),
-            EntitySelector::ComplexSelector(
-                EntitySelectors::AllEntities,
-                "distance=..5".to_owned()
-            )
+            EntitySelector::ComplexSelector(EntitySelectors::AllEntities, distance_5())
         );
         assert!(!input.is_done());
 
         let mut input = ParseInput::new("@e[distance=..5]hello world");
         assert_eq!(
             EntitySelector::parse_arg(&mut input).unwrap(),
-            EntitySelector::ComplexSelector(
-                EntitySelectors::AllEntities,
-                "distance=..5".to_owned()
-            )
+            EntitySelector::ComplexSelector(EntitySelectors::AllEntities, distance_5())
         );
         assert!(!input.is_done());
     }
+
+    #[test]
+    fn test_selector_arguments() {
+        let mut input = ParseInput::new(
+            "@e[type=!minecraft:zombie, type=!skeleton,limit=3,sort=nearest,x=1,dy=-2.5,\
+             name=\"Some Name\",tag=,gamemode=!creative,level=5,scores={kills=1..,\"deaths\"=..2},\
+             nbt={Tags:[\"a]\"]}] rest",
+        );
+        let EntitySelector::ComplexSelector(selector, args) =
+            EntitySelector::parse_arg(&mut input).unwrap()
+        else {
+            panic!("expected a complex selector");
+        };
+
+        assert_eq!(selector, EntitySelectors::AllEntities);
+        assert_eq!(input.into_inner(), " rest");
+        assert_eq!(
+            args.entity_type,
+            [
+                Negatable {
+                    value: "minecraft:zombie".to_owned(),
+                    negated: true
+                },
+                Negatable {
+                    value: "minecraft:skeleton".to_owned(),
+                    negated: true
+                }
+            ]
+        );
+        assert_eq!(args.limit, Some(3));
+        assert_eq!(args.sort, Some(SelectorSort::Nearest));
+        assert_eq!(args.x, Some(1.0));
+        assert_eq!(args.dy, Some(-2.5));
+        assert_eq!(args.name[0].value, "Some Name");
+        assert_eq!(args.tag[0].value, "");
+        assert_eq!(
+            args.gamemode,
+            [Negatable {
+                value: GameMode::Creative,
+                negated: true
+            }]
+        );
+        assert_eq!(
+            args.level,
+            Some(SelectorRange {
+                min: Some(5),
+                max: Some(5)
+            })
+        );
+        assert_eq!(
+            args.scores,
+            [
+                (
+                    "kills".to_owned(),
+                    SelectorRange {
+                        min: Some(1),
+                        max: None
+                    }
+                ),
+                (
+                    "deaths".to_owned(),
+                    SelectorRange {
+                        min: None,
+                        max: Some(2)
+                    }
+                )
+            ]
+        );
+        assert_eq!(args.nbt.len(), 1);
+
+        for invalid in [
+            "@e[limit=0]",
+            "@e[distance=-1..]",
+            "@e[sort=sideways]",
+            "@e[type=#skeletons]",
+            "@e[unknown=1]",
+            "@e[level=..]",
+            "@e[nbt=5]",
+        ] {
+            assert!(
+                EntitySelector::parse_arg(&mut ParseInput::new(invalid)).is_err(),
+                "{invalid}"
+            );
+        }
+    }
 }
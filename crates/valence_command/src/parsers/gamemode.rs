@@ -1,7 +1,8 @@
 use valence_server::protocol::packets::play::commands_s2c::Parser;
 use valence_server::GameMode;
 
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{name_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 impl CommandArg for GameMode {
     fn parse_arg(input: &mut ParseInput) -> Result<Self, CommandArgParseError> {
@@ -25,4 +26,10 @@ impl CommandArg for GameMode {
     fn display() -> Parser {
         Parser::GameMode
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(_input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        name_suggestions(&["survival", "creative", "adventure", "spectator"])
+    }
 }
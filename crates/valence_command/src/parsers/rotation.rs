@@ -2,7 +2,8 @@ use bevy_derive::Deref;
 
 use super::Parser;
 use crate::parsers::vec2::Vec2;
-use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{coordinate_suggestions, CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 #[derive(Debug, Clone, Copy, PartialEq, Default, Deref)]
 pub struct Rotation(pub Vec2);
@@ -18,4 +19,10 @@ impl CommandArg for Rotation {
     fn display() -> Parser {
         Parser::Rotation
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        coordinate_suggestions(input, 2, None)
+    }
 }
@@ -1,8 +1,9 @@
 use super::Parser;
 use crate::parsers::entity_selector::EntitySelector;
 use crate::parsers::{CommandArg, CommandArgParseError, ParseInput};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum ScoreHolder {
     Entity(EntitySelector),
     #[default]
@@ -24,4 +25,12 @@ impl CommandArg for ScoreHolder {
             allow_multiple: false,
         }
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(input: &str, ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        let mut suggestions = vec![CommandSuggestion::new("*")];
+        suggestions.extend(EntitySelector::suggestions(input, ctx));
+        suggestions
+    }
 }
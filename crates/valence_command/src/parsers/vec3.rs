@@ -1,5 +1,8 @@
 use super::Parser;
-use crate::parsers::{AbsoluteOrRelative, CommandArg, CommandArgParseError, ParseInput};
+use crate::parsers::{
+    coordinate_suggestions, AbsoluteOrRelative, CommandArg, CommandArgParseError, ParseInput,
+};
+use crate::suggestions::{CommandSuggestion, SuggestionContext};
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Vec3 {
@@ -20,6 +23,12 @@ impl CommandArg for Vec3 {
     fn display() -> Parser {
         Parser::Vec3
     }
+
+    const HAS_SUGGESTIONS: bool = true;
+
+    fn suggestions(input: &str, _ctx: &SuggestionContext) -> Vec<CommandSuggestion> {
+        coordinate_suggestions(input, 3, None)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,488 @@
+//! Resolving [`EntitySelector`]s to the entities they select.
+//!
+//! Selectors are resolved relative to the executor of the command: its
+//! [`Position`] is the origin of `distance`, `dx`/`dy`/`dz` and the sorting
+//! order, and only entities on its [`EntityLayerId`] are selected.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use bevy_derive::{Deref, DerefMut};
+use bevy_ecs::prelude::*;
+use bevy_ecs::query::QueryData;
+use bevy_ecs::system::SystemParam;
+use thiserror::Error;
+use valence_scoreboard::{Objective, ObjectiveScores, Team, TeamMembers};
+use valence_server::client::Username;
+use valence_server::entity::entity::CustomName;
+use valence_server::entity::hitbox::Hitbox;
+use valence_server::entity::{EntityKind, EntityLayerId, Look, Position};
+use valence_server::math::{Aabb, DVec3};
+use valence_server::rand::seq::SliceRandom;
+use valence_server::{GameMode, UniqueId};
+
+use crate::parsers::entity_selector::{
+    EntitySelector, EntitySelectors, SelectorArguments, SelectorSort,
+};
+
+/// The tags of an entity, as matched by the `tag` selector argument.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug, Deref, DerefMut)]
+pub struct EntityTags(pub BTreeSet<String>);
+
+#[derive(Debug, Error)]
+pub enum SelectorError {
+    /// The selector uses an argument that can't be resolved by
+    /// [`EntitySelectorQuery::resolve`]. Use
+    /// [`EntitySelectorQuery::resolve_with`] to handle it yourself.
+    #[error("the selector argument `{0}` is not supported")]
+    Unsupported(&'static str),
+}
+
+#[derive(QueryData)]
+pub struct SelectableEntity {
+    entity: Entity,
+    kind: &'static EntityKind,
+    pos: &'static Position,
+    layer: &'static EntityLayerId,
+    look: Option<&'static Look>,
+    uuid: Option<&'static UniqueId>,
+    username: Option<&'static Username>,
+    custom_name: Option<&'static CustomName>,
+    game_mode: Option<&'static GameMode>,
+    tags: Option<&'static EntityTags>,
+    hitbox: Option<&'static Hitbox>,
+}
+
+impl SelectableEntityItem<'_> {
+    fn is_player(&self) -> bool {
+        *self.kind == EntityKind::PLAYER
+    }
+
+    /// The name matched by the `name` argument and by bare player names.
+    fn name(&self) -> Option<String> {
+        if let Some(username) = self.username {
+            return Some(username.0.clone());
+        }
+
+        self.custom_name
+            .and_then(|name| name.0.as_ref())
+            .map(|name| name.to_legacy_lossy())
+    }
+
+    /// The name the entity is listed under in [`ObjectiveScores`].
+    fn score_holder(&self) -> Option<String> {
+        match (self.username, self.uuid) {
+            (Some(username), _) => Some(username.0.clone()),
+            (None, Some(uuid)) => Some(uuid.0.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the entity type is the resource location `id`, e.g.
+    /// `minecraft:zombie`.
+    fn is_type(&self, id: &str) -> bool {
+        id.strip_prefix("minecraft:")
+            .is_some_and(|name| self.kind.to_str() == Some(name))
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.hitbox
+            .map_or_else(|| Aabb::new_point(self.pos.0), |hitbox| hitbox.get())
+    }
+}
+
+/// A [`SystemParam`] that finds the entities selected by an
+/// [`EntitySelector`].
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use valence_command::parsers::EntitySelector;
+/// # use valence_command::selector::EntitySelectorQuery;
+/// fn kill(executor: Entity, target: &EntitySelector, selector: EntitySelectorQuery) {
+///     for entity in selector.resolve(target, executor).unwrap_or_default() {
+///         // ...
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct EntitySelectorQuery<'w, 's> {
+    entities: Query<'w, 's, SelectableEntity>,
+    objectives: Query<'w, 's, (&'static Objective, &'static ObjectiveScores)>,
+    teams: Query<'w, 's, (&'static Team, &'static TeamMembers)>,
+}
+
+impl EntitySelectorQuery<'_, '_> {
+    /// Returns the entities selected by `selector` when it is used by
+    /// `executor`, in the order given by the selector's `sort` argument.
+    ///
+    /// The `level` and `nbt` arguments depend on data that is not stored in
+    /// components, so they result in an error.
+    pub fn resolve(
+        &self,
+        selector: &EntitySelector,
+        executor: Entity,
+    ) -> Result<Vec<Entity>, SelectorError> {
+        if let EntitySelector::ComplexSelector(_, args) = selector {
+            if args.level.is_some() {
+                return Err(SelectorError::Unsupported("level"));
+            }
+
+            if !args.nbt.is_empty() {
+                return Err(SelectorError::Unsupported("nbt"));
+            }
+        }
+
+        Ok(self.resolve_with(selector, executor, |_, _| true))
+    }
+
+    /// Like [`Self::resolve`], but supports every argument. Entities are only
+    /// selected if `filter` returns `true` for them, which is where arguments
+    /// like `level` and `nbt` can be checked.
+    pub fn resolve_with<F>(
+        &self,
+        selector: &EntitySelector,
+        executor: Entity,
+        mut filter: F,
+    ) -> Vec<Entity>
+    where
+        F: FnMut(Entity, &SelectorArguments) -> bool,
+    {
+        let no_args = SelectorArguments::default();
+        let (kind, args) = match selector {
+            EntitySelector::SimpleSelector(kind) => (kind, &no_args),
+            EntitySelector::ComplexSelector(kind, args) => (kind, &**args),
+        };
+
+        let executor_item = self.entities.get(executor).ok();
+        let base = executor_item
+            .as_ref()
+            .map_or(DVec3::ZERO, |executor| executor.pos.0);
+        let origin = DVec3::new(
+            args.x.unwrap_or(base.x),
+            args.y.unwrap_or(base.y),
+            args.z.unwrap_or(base.z),
+        );
+        let layer = executor_item.as_ref().map(|executor| executor.layer.0);
+
+        let (players_only, default_sort, default_limit) = match kind {
+            EntitySelectors::AllEntities => (false, SelectorSort::Arbitrary, None),
+            EntitySelectors::AllPlayers => (true, SelectorSort::Arbitrary, None),
+            EntitySelectors::NearestPlayer => (true, SelectorSort::Nearest, Some(1)),
+            EntitySelectors::RandomPlayer => (true, SelectorSort::Random, Some(1)),
+            EntitySelectors::SelfPlayer | EntitySelectors::SinglePlayer(_) => {
+                (false, SelectorSort::Arbitrary, Some(1))
+            }
+        };
+
+        let volume = (args.dx.is_some() || args.dy.is_some() || args.dz.is_some()).then(|| {
+            let corner = origin
+                + DVec3::new(
+                    args.dx.unwrap_or(0.0),
+                    args.dy.unwrap_or(0.0),
+                    args.dz.unwrap_or(0.0),
+                );
+            Aabb::new(origin.min(corner), origin.max(corner) + DVec3::ONE)
+        });
+
+        let mut selected: Vec<_> = self
+            .entities
+            .iter()
+            .filter(|item| match kind {
+                EntitySelectors::SelfPlayer => item.entity == executor,
+                EntitySelectors::SinglePlayer(name) => {
+                    item.is_player()
+                        && (item
+                            .username
+                            .is_some_and(|username| username.0.eq_ignore_ascii_case(name))
+                            || item
+                                .uuid
+                                .is_some_and(|uuid| uuid.0.to_string().eq_ignore_ascii_case(name)))
+                }
+                _ => layer.is_none_or(|layer| item.layer.0 == layer),
+            })
+            .filter(|item| !players_only || item.is_player())
+            .filter(|item| self.matches(item, args, origin, volume))
+            .filter(|item| filter(item.entity, args))
+            .map(|item| (item.entity, item.pos.0.distance_squared(origin)))
+            .collect();
+
+        match args.sort.unwrap_or(default_sort) {
+            SelectorSort::Nearest => {
+                selected.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            }
+            SelectorSort::Furthest => {
+                selected.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal))
+            }
+            SelectorSort::Random => selected.shuffle(&mut valence_server::rand::thread_rng()),
+            SelectorSort::Arbitrary => {}
+        }
+
+        if let Some(limit) = args.limit.or(default_limit) {
+            selected.truncate(limit);
+        }
+
+        selected.into_iter().map(|(entity, _)| entity).collect()
+    }
+
+    fn matches(
+        &self,
+        item: &SelectableEntityItem,
+        args: &SelectorArguments,
+        origin: DVec3,
+        volume: Option<Aabb>,
+    ) -> bool {
+        if let Some(distance) = &args.distance {
+            if !distance.contains(item.pos.0.distance(origin)) {
+                return false;
+            }
+        }
+
+        if let Some(volume) = volume {
+            if !volume.intersects(item.bounds()) {
+                return false;
+            }
+        }
+
+        let look = item.look.copied().unwrap_or_default();
+
+        if args
+            .x_rotation
+            .is_some_and(|range| !range.contains(look.pitch))
+            || args
+                .y_rotation
+                .is_some_and(|range| !range.contains(look.yaw))
+        {
+            return false;
+        }
+
+        if !args.gamemode.is_empty() {
+            // non-players never match a game mode, not even a negated one
+            let Some(game_mode) = item.game_mode.filter(|_| item.is_player()) else {
+                return false;
+            };
+
+            if !args
+                .gamemode
+                .iter()
+                .all(|mode| mode.test(mode.value == *game_mode))
+            {
+                return false;
+            }
+        }
+
+        if !args.name.is_empty() {
+            let name = item.name();
+
+            if !args
+                .name
+                .iter()
+                .all(|arg| arg.test(name.as_deref() == Some(arg.value.as_str())))
+            {
+                return false;
+            }
+        }
+
+        if !args
+            .entity_type
+            .iter()
+            .all(|arg| arg.test(item.is_type(&arg.value)))
+        {
+            return false;
+        }
+
+        if !args.tag.is_empty() {
+            let tags = item.tags.map(|tags| &tags.0);
+
+            let has_tag = |tag: &str| {
+                if tag.is_empty() {
+                    // `tag=` matches entities without any tags
+                    tags.is_none_or(BTreeSet::is_empty)
+                } else {
+                    tags.is_some_and(|tags| tags.contains(tag))
+                }
+            };
+
+            if !args.tag.iter().all(|arg| arg.test(has_tag(&arg.value))) {
+                return false;
+            }
+        }
+
+        if !args.team.is_empty() {
+            let holder = item.score_holder();
+            let team = holder.as_deref().and_then(|holder| {
+                self.teams
+                    .iter()
+                    .find(|(_, members)| members.contains(holder))
+                    .map(|(team, _)| team.name())
+            });
+
+            // `team=` matches entities that are not on a team
+            if !args
+                .team
+                .iter()
+                .all(|arg| arg.test(team.unwrap_or_default() == arg.value))
+            {
+                return false;
+            }
+        }
+
+        if !args.scores.is_empty() {
+            let Some(holder) = item.score_holder() else {
+                return false;
+            };
+
+            for (objective, range) in &args.scores {
+                let score = self
+                    .objectives
+                    .iter()
+                    .find(|(name, _)| name.name() == objective)
+                    .and_then(|(_, scores)| scores.get(&holder));
+
+                if !score.is_some_and(|score| range.contains(*score)) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::SystemState;
+    use valence_server::entity::EntityKind;
+    use valence_server::uuid::Uuid;
+
+    use super::*;
+    use crate::parsers::{CommandArg, ParseInput};
+
+    struct Test {
+        world: World,
+        executor: Entity,
+        zombie: Entity,
+        far_player: Entity,
+    }
+
+    fn spawn_player(world: &mut World, layer: Entity, name: &str, pos: DVec3) -> Entity {
+        world
+            .spawn((
+                EntityKind::PLAYER,
+                Position(pos),
+                EntityLayerId(layer),
+                Username(name.to_owned()),
+                UniqueId(Uuid::from_u128(name.len() as u128)),
+                GameMode::Survival,
+            ))
+            .id()
+    }
+
+    fn setup() -> Test {
+        let mut world = World::new();
+        let layer = world.spawn_empty().id();
+        let other_layer = world.spawn_empty().id();
+
+        let executor = spawn_player(&mut world, layer, "Executor", DVec3::ZERO);
+        let far_player = spawn_player(&mut world, layer, "FarAway", DVec3::new(100.0, 0.0, 0.0));
+        spawn_player(&mut world, other_layer, "Elsewhere", DVec3::ZERO);
+
+        let zombie = world
+            .spawn((
+                EntityKind::ZOMBIE,
+                Position(DVec3::new(3.0, 0.0, 0.0)),
+                EntityLayerId(layer),
+                EntityTags(BTreeSet::from(["boss".to_owned()])),
+            ))
+            .id();
+
+        world.spawn((
+            EntityKind::PIG,
+            Position(DVec3::new(0.0, 0.0, 8.0)),
+            EntityLayerId(layer),
+        ));
+
+        world.spawn((Objective::new("kills"), {
+            let mut scores = ObjectiveScores::new();
+            scores.insert("FarAway", 10);
+            scores
+        }));
+
+        world.spawn((Team::new("red"), TeamMembers::from_iter(["FarAway"])));
+
+        Test {
+            world,
+            executor,
+            zombie,
+            far_player,
+        }
+    }
+
+    fn select(test: &mut Test, selector: &str) -> Result<Vec<Entity>, SelectorError> {
+        let selector = EntitySelector::parse_arg(&mut ParseInput::new(selector)).unwrap();
+        let mut state = SystemState::<EntitySelectorQuery>::new(&mut test.world);
+        let query = state.get(&test.world);
+        query.resolve(&selector, test.executor)
+    }
+
+    #[test]
+    fn resolve_simple_selectors() {
+        let mut test = setup();
+
+        assert_eq!(select(&mut test, "@s").unwrap(), [test.executor]);
+        assert_eq!(select(&mut test, "@p").unwrap(), [test.executor]);
+        assert_eq!(select(&mut test, "@a").unwrap().len(), 2);
+        assert_eq!(select(&mut test, "@e").unwrap().len(), 4);
+        assert_eq!(select(&mut test, "faraway").unwrap(), [test.far_player]);
+        assert!(select(&mut test, "Nobody").unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolve_selector_arguments() {
+        let mut test = setup();
+
+        assert_eq!(select(&mut test, "@e[type=zombie]").unwrap(), [test.zombie]);
+        assert_eq!(
+            select(&mut test, "@e[type=minecraft:zombie]").unwrap(),
+            [test.zombie]
+        );
+        assert!(select(&mut test, "@e[type=example:zombie]")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            select(&mut test, "@e[sort=furthest,limit=1]").unwrap(),
+            [test.far_player]
+        );
+        assert_eq!(
+            select(&mut test, "@e[distance=1..5]").unwrap(),
+            [test.zombie]
+        );
+        assert_eq!(
+            select(&mut test, "@e[x=2,dx=2,dy=1,dz=0]").unwrap(),
+            [test.zombie]
+        );
+        assert_eq!(select(&mut test, "@e[tag=boss]").unwrap(), [test.zombie]);
+        assert_eq!(select(&mut test, "@e[tag=]").unwrap().len(), 3);
+        assert_eq!(
+            select(&mut test, "@a[scores={kills=5..}]").unwrap(),
+            [test.far_player]
+        );
+        assert_eq!(
+            select(&mut test, "@e[type=!player,type=!pig]").unwrap(),
+            [test.zombie]
+        );
+        assert!(select(&mut test, "@a[gamemode=creative]")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            select(&mut test, "@a[team=red]").unwrap(),
+            [test.far_player]
+        );
+        assert_eq!(select(&mut test, "@a[team=]").unwrap(), [test.executor]);
+        assert_eq!(select(&mut test, "@a[team=!]").unwrap(), [test.far_player]);
+
+        assert!(matches!(
+            select(&mut test, "@a[level=1..]"),
+            Err(SelectorError::Unsupported("level"))
+        ));
+    }
+}
@@ -0,0 +1,377 @@
+//! Tab-completion of commands.
+//!
+//! When a client types a command, it sends the text typed so far to the server
+//! for every argument node marked with [`Suggestion::AskServer`]. The server
+//! walks the [`CommandGraph`](crate::graph::CommandGraph) along that text and
+//! replies with completions for the last, partially typed argument. Literals
+//! complete to their name, and arguments complete through the
+//! [`SuggestionProvider`] of their node. Providers are registered by
+//! [`CommandGraphBuilder::with_parser`] from [`CommandArg::suggestions`] and can
+//! be replaced with [`CommandGraphBuilder::with_suggestions`].
+//!
+//! [`Suggestion::AskServer`]: valence_server::protocol::packets::play::commands_s2c::Suggestion::AskServer
+//! [`CommandGraphBuilder::with_parser`]: crate::graph::CommandGraphBuilder::with_parser
+//! [`CommandGraphBuilder::with_suggestions`]: crate::graph::CommandGraphBuilder::with_suggestions
+//! [`CommandArg::suggestions`]: crate::parsers::CommandArg::suggestions
+
+use std::collections::HashSet;
+
+use bevy_ecs::prelude::Entity;
+use petgraph::prelude::*;
+use valence_server::math::DVec3;
+use valence_server::protocol::packets::play::commands_s2c::NodeData;
+use valence_text::Text;
+
+use crate::graph::{CommandEdgeType, CommandNode};
+use crate::parsers::ParseInput;
+use crate::CommandRegistry;
+
+/// Returns the completions for the partially typed argument `input`. The
+/// returned completions replace all of `input`. Completions that don't start
+/// with `input` are filtered out, so providers don't need to check that
+/// themselves.
+pub type SuggestionProvider = fn(&str, &SuggestionContext) -> Vec<CommandSuggestion>;
+
+/// A single completion sent to the client.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommandSuggestion {
+    pub text: String,
+    /// Shown when the client hovers over the completion.
+    pub tooltip: Option<Text>,
+}
+
+impl CommandSuggestion {
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip<T: Into<Text>>(mut self, tooltip: T) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+}
+
+/// Information about the entity asking for completions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SuggestionContext {
+    /// The entity typing the command. Usually a client.
+    pub executor: Entity,
+    /// The usernames of all online players.
+    pub player_names: Vec<String>,
+    /// The position of the executor, if it has one.
+    pub position: Option<DVec3>,
+}
+
+/// The completions for a command, as returned by
+/// [`CommandRegistry::suggest`].
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Suggestions {
+    /// The byte offset in the command text where the completed text starts.
+    /// The completions replace everything from here to the end of the text.
+    pub start: usize,
+    pub matches: Vec<CommandSuggestion>,
+}
+
+impl CommandRegistry {
+    /// Returns completions for the partially typed `command`, which may start
+    /// with a `/`. Nodes for which `is_allowed` returns `false` are skipped
+    /// along with their children.
+    pub fn suggest<F>(&self, command: &str, ctx: &SuggestionContext, is_allowed: F) -> Suggestions
+    where
+        F: Fn(&CommandNode) -> bool,
+    {
+        let start = usize::from(command.starts_with('/'));
+
+        let mut walker = SuggestionWalker {
+            registry: self,
+            command,
+            ctx,
+            is_allowed,
+            visited: HashSet::new(),
+            found: vec![],
+        };
+
+        walker.walk(self.graph.root, start);
+
+        let found = walker.found;
+
+        let Some(start) = found.iter().map(|(pos, _)| *pos).min() else {
+            return Suggestions {
+                start: command.len(),
+                matches: vec![],
+            };
+        };
+
+        // All completions have to replace the same range, so extend the ones
+        // that start later with the text in between.
+        let mut matches: Vec<_> = found
+            .into_iter()
+            .map(|(pos, mut suggestion)| {
+                suggestion.text.insert_str(0, &command[start..pos]);
+                suggestion
+            })
+            .collect();
+
+        matches.sort_by(|a, b| a.text.cmp(&b.text));
+        matches.dedup_by(|a, b| a.text == b.text);
+
+        Suggestions { start, matches }
+    }
+}
+
+struct SuggestionWalker<'a, F> {
+    registry: &'a CommandRegistry,
+    command: &'a str,
+    ctx: &'a SuggestionContext,
+    is_allowed: F,
+    /// Prevents redirect cycles.
+    visited: HashSet<(NodeIndex, usize)>,
+    found: Vec<(usize, CommandSuggestion)>,
+}
+
+impl<F> SuggestionWalker<'_, F>
+where
+    F: Fn(&CommandNode) -> bool,
+{
+    /// Collects completions for the children of `node`, whose input starts at
+    /// byte offset `pos`.
+    fn walk(&mut self, node: NodeIndex, pos: usize) {
+        if !self.visited.insert((node, pos)) {
+            return;
+        }
+
+        let graph = &self.registry.graph.graph;
+        let edges: Vec<_> = graph
+            .edges_directed(node, Direction::Outgoing)
+            .map(|edge| (*edge.weight(), edge.target()))
+            .collect();
+
+        for (edge, child) in edges {
+            match edge {
+                // The children of the redirect target continue from here.
+                CommandEdgeType::Redirect => self.walk(child, pos),
+                CommandEdgeType::Child => self.visit(child, pos),
+            }
+        }
+    }
+
+    fn visit(&mut self, node: NodeIndex, pos: usize) {
+        let registry = self.registry;
+        let command_node = &registry.graph.graph[node];
+
+        if !(self.is_allowed)(command_node) {
+            return;
+        }
+
+        let rest = &self.command[pos..];
+
+        match &command_node.data {
+            NodeData::Root => {}
+            NodeData::Literal { name } => match rest.find(' ') {
+                Some(end) => {
+                    if rest[..end].eq_ignore_ascii_case(name) {
+                        self.walk(node, pos + end + 1);
+                    }
+                }
+                None => {
+                    if starts_with_ignore_case(name, rest) {
+                        self.found.push((pos, CommandSuggestion::new(name.clone())));
+                    }
+                }
+            },
+            NodeData::Argument { .. } => {
+                let mut input = ParseInput::new(rest);
+                let parsed = registry
+                    .parsers
+                    .get(&node)
+                    .is_some_and(|parser| parser(&mut input));
+
+                if parsed && input.peek() == Some(' ') {
+                    // The argument is complete, so continue with the next one.
+                    let end = pos + rest.len() - input.len();
+                    self.walk(node, end + 1);
+                } else if parsed && !input.is_done() {
+                    // The argument is followed by garbage.
+                } else if let Some(provider) = registry.suggestions.get(&node) {
+                    for suggestion in provider(rest, self.ctx) {
+                        if starts_with_ignore_case(&suggestion.text, rest) {
+                            self.found.push((pos, suggestion));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn starts_with_ignore_case(string: &str, prefix: &str) -> bool {
+    string
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use valence_server::GameMode;
+
+    use valence_server::protocol::packets::play::commands_s2c::Suggestion;
+
+    use super::*;
+    use crate::graph::CommandGraphBuilder;
+    use crate::parsers::EntitySelector;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        let mut executables = HashMap::new();
+        let mut parsers = HashMap::new();
+        let mut modifiers = HashMap::new();
+
+        let mut builder = CommandGraphBuilder::<()>::new(
+            &mut registry,
+            &mut executables,
+            &mut parsers,
+            &mut modifiers,
+        );
+
+        let gamemode = builder.root().literal("gamemode").id();
+        builder
+            .argument("mode")
+            .with_parser::<GameMode>()
+            .argument("target")
+            .with_parser::<EntitySelector>()
+            .with_executable(|_| {});
+        builder
+            .at(gamemode)
+            .literal("query")
+            .with_executable(|_| {});
+
+        builder
+            .root()
+            .literal("give")
+            .with_scopes(vec!["give"])
+            .with_executable(|_| {});
+
+        builder
+            .root()
+            .literal("warp")
+            .argument("name")
+            .with_parser::<String>()
+            .with_suggestions(|_, _| {
+                vec![
+                    CommandSuggestion::new("spawn").with_tooltip("The spawn point"),
+                    CommandSuggestion::new("arena"),
+                ]
+            })
+            .with_executable(|_| {});
+
+        builder
+            .root()
+            .literal("say")
+            .argument("message")
+            .with_parser::<String>()
+            .with_executable(|_| {});
+
+        let execute = builder.root().literal("execute").id();
+        builder.at(execute).literal("run").redirect_to(gamemode);
+
+        registry.parsers.extend(parsers);
+        registry.executables.extend(executables.keys());
+        registry
+    }
+
+    fn ctx() -> SuggestionContext {
+        SuggestionContext {
+            executor: Entity::PLACEHOLDER,
+            player_names: vec!["Alice".into(), "bob".into()],
+            position: None,
+        }
+    }
+
+    fn texts(suggestions: &Suggestions) -> Vec<&str> {
+        suggestions
+            .matches
+            .iter()
+            .map(|suggestion| suggestion.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn suggest_literals() {
+        let registry = registry();
+        let no_give = |node: &CommandNode| node.scopes.is_empty();
+
+        let suggestions = registry.suggest("/g", &ctx(), no_give);
+        assert_eq!(suggestions.start, 1);
+        assert_eq!(texts(&suggestions), ["gamemode"]);
+
+        let suggestions = registry.suggest("/G", &ctx(), |_| true);
+        assert_eq!(texts(&suggestions), ["gamemode", "give"]);
+
+        let suggestions = registry.suggest("/nothing", &ctx(), |_| true);
+        assert_eq!(suggestions.start, 8);
+        assert!(suggestions.matches.is_empty());
+    }
+
+    #[test]
+    fn suggest_arguments() {
+        let registry = registry();
+
+        let suggestions = registry.suggest("/gamemode ", &ctx(), |_| true);
+        assert_eq!(suggestions.start, 10);
+        assert_eq!(
+            texts(&suggestions),
+            ["adventure", "creative", "query", "spectator", "survival"]
+        );
+
+        let suggestions = registry.suggest("/gamemode creative a", &ctx(), |_| true);
+        assert_eq!(suggestions.start, 19);
+        assert_eq!(texts(&suggestions), ["Alice"]);
+
+        let suggestions = registry.suggest("/gamemode creative @", &ctx(), |_| true);
+        assert_eq!(texts(&suggestions), ["@a", "@e", "@p", "@r", "@s"]);
+
+        let suggestions = registry.suggest("/warp ", &ctx(), |_| true);
+        assert_eq!(texts(&suggestions), ["arena", "spawn"]);
+        assert!(suggestions.matches[1].tooltip.is_some());
+    }
+
+    #[test]
+    fn ask_server_only_with_provider() {
+        let registry = registry();
+        let suggestion = |arg: &str| {
+            registry
+                .graph
+                .graph
+                .node_weights()
+                .find_map(|node| match &node.data {
+                    NodeData::Argument {
+                        name, suggestion, ..
+                    } if name == arg => Some(*suggestion),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        assert_eq!(suggestion("mode"), Some(Suggestion::AskServer));
+        assert_eq!(suggestion("name"), Some(Suggestion::AskServer));
+        assert_eq!(suggestion("message"), None);
+        assert!(registry
+            .suggest("/say ", &ctx(), |_| true)
+            .matches
+            .is_empty());
+    }
+
+    #[test]
+    fn suggest_through_redirect() {
+        let registry = registry();
+
+        let suggestions = registry.suggest("/execute run cr", &ctx(), |_| true);
+        assert_eq!(suggestions.start, 13);
+        assert_eq!(texts(&suggestions), ["creative"]);
+    }
+}
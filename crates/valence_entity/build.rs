@@ -144,7 +144,7 @@ impl Value {
             Value::String(_) => quote!(String),
             Value::TextComponent(_) => quote!(valence_protocol::Text),
             Value::OptionalTextComponent(_) => quote!(Option<valence_protocol::Text>),
-            Value::ItemStack(_) => quote!(valence_protocol::ItemStack<'a>),
+            Value::ItemStack(_) => quote!(valence_protocol::ItemStack<'static>),
             Value::Boolean(_) => quote!(bool),
             Value::Rotation { .. } => quote!(crate::EulerAngle),
             Value::BlockPos(_) => quote!(valence_protocol::BlockPos),
@@ -155,10 +155,10 @@ impl Value {
             Value::OptionalBlockState(_) => quote!(valence_protocol::BlockState),
             Value::NbtCompound(_) => quote!(valence_nbt::Compound),
             Value::Particle(_) => {
-                quote!(valence_protocol::packets::play::level_particles_s2c::Particle<'a>)
+                quote!(valence_protocol::packets::play::level_particles_s2c::Particle<'static>)
             }
             Value::ParticleList(_) => {
-                quote!(Vec<valence_protocol::packets::play::level_particles_s2c::Particle>)
+                quote!(Vec<valence_protocol::packets::play::level_particles_s2c::Particle<'static>>)
             }
             Value::VillagerData { .. } => quote!(crate::VillagerData),
             Value::OptionalInt(_) => quote!(Option<i32>),
@@ -342,6 +342,8 @@ fn build_entities() -> anyhow::Result<TokenStream> {
     let mut entity_kind_consts = TokenStream::new();
     let mut entity_kind_fmt_args = TokenStream::new();
     let mut translation_key_arms = TokenStream::new();
+    let mut entity_kind_from_str_arms = TokenStream::new();
+    let mut entity_kind_to_str_arms = TokenStream::new();
     let mut modules = TokenStream::new();
     let mut systems = TokenStream::new();
     let mut system_names = vec![];
@@ -390,6 +392,14 @@ fn build_entities() -> anyhow::Result<TokenStream> {
                 EntityKind::#stripped_shouty_entity_name_ident => #translation_key_expr,
             }]);
 
+            entity_kind_from_str_arms.extend([quote! {
+                #entity_type => Some(EntityKind::#stripped_shouty_entity_name_ident),
+            }]);
+
+            entity_kind_to_str_arms.extend([quote! {
+                EntityKind::#stripped_shouty_entity_name_ident => Some(#entity_type),
+            }]);
+
             // Create bundle type.
             let mut bundle_fields = TokenStream::new();
             let mut bundle_init_fields = TokenStream::new();
@@ -748,6 +758,26 @@ fn build_entities() -> anyhow::Result<TokenStream> {
                     _ => None,
                 }
             }
+
+            /// Construct an entity kind from its registry name, e.g. `zombie`.
+            ///
+            /// Returns `None` if the name is invalid.
+            #[allow(clippy::should_implement_trait)]
+            pub fn from_str(name: &str) -> Option<Self> {
+                match name {
+                    #entity_kind_from_str_arms
+                    _ => None,
+                }
+            }
+
+            /// Gets the registry name of this entity kind, or `None` if it is not a
+            /// known entity kind.
+            pub const fn to_str(self) -> Option<&'static str> {
+                match self {
+                    #entity_kind_to_str_arms
+                    _ => None,
+                }
+            }
         }
 
         impl std::fmt::Debug for EntityKind {
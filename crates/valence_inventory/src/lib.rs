@@ -21,6 +21,7 @@ use valence_server::protocol::packets::play::{
     SetHeldSlotS2c,
 };
 use valence_server::protocol::{VarInt, WritePacket};
+use valence_server::reconfigure::Rejoined;
 use valence_server::text::IntoText;
 use valence_server::{GameMode, ItemKind, ItemStack, Text};
 
@@ -39,6 +40,9 @@ impl Plugin for InventoryPlugin {
             PostUpdate,
             (
                 update_client_on_close_inventory.before(update_open_inventories),
+                resend_rejoined_client_inventories
+                    .before(update_player_selected_slot)
+                    .before(update_player_inventories),
                 update_player_selected_slot,
                 update_open_inventories,
                 update_player_inventories,
@@ -598,6 +602,17 @@ fn init_new_client_inventories(clients: Query<Entity, Added<Client>>, mut comman
     }
 }
 
+/// Send the whole inventory again to clients that rejoined after being
+/// reconfigured.
+fn resend_rejoined_client_inventories(
+    mut clients: Query<(&mut Inventory, &mut HeldItem), Added<Rejoined>>,
+) {
+    for (mut inventory, mut held_item) in &mut clients {
+        inventory.changed = u64::MAX;
+        held_item.set_changed();
+    }
+}
+
 /// Send updates for each client's player inventory.
 fn update_player_inventories(
     mut query: Query<
@@ -0,0 +1,278 @@
+use bevy_ecs::prelude::Component;
+use valence_server::protocol::packets::play::map_item_data_s2c::Data;
+
+use crate::color::{rgb_to_map_color, TRANSPARENT};
+
+/// The width and height of a map in pixels.
+pub const MAP_SIZE: usize = 128;
+
+/// The pixels of a map as map colors, which are converted from and to RGB
+/// values in the [`color`](crate::color) module.
+///
+/// The canvas keeps track of the smallest rectangle containing every pixel
+/// that changed, so that only that part is sent to clients.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct MapCanvas {
+    pixels: Box<[u8; MAP_SIZE * MAP_SIZE]>,
+    dirty: Option<DirtyRect>,
+}
+
+/// A rectangle of pixels on a map, with inclusive bounds.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DirtyRect {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+impl DirtyRect {
+    pub const FULL: Self = Self {
+        min_x: 0,
+        min_y: 0,
+        max_x: MAP_SIZE - 1,
+        max_y: MAP_SIZE - 1,
+    };
+
+    /// The number of columns in the rectangle, which is 0 if `max_x` is less
+    /// than `min_x`.
+    pub fn width(&self) -> usize {
+        (self.max_x + 1).saturating_sub(self.min_x)
+    }
+
+    /// The number of rows in the rectangle, which is 0 if `max_y` is less than
+    /// `min_y`.
+    pub fn height(&self) -> usize {
+        (self.max_y + 1).saturating_sub(self.min_y)
+    }
+
+    /// Returns the part of the rectangle that is on the map, or `None` if
+    /// there is no such part or the rectangle is inverted.
+    pub fn clamped(self) -> Option<Self> {
+        let rect = Self {
+            max_x: self.max_x.min(MAP_SIZE - 1),
+            max_y: self.max_y.min(MAP_SIZE - 1),
+            ..self
+        };
+
+        (rect.min_x <= rect.max_x && rect.min_y <= rect.max_y).then_some(rect)
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+impl Default for MapCanvas {
+    fn default() -> Self {
+        Self {
+            pixels: Box::new([TRANSPARENT; MAP_SIZE * MAP_SIZE]),
+            dirty: Some(DirtyRect::FULL),
+        }
+    }
+}
+
+impl MapCanvas {
+    /// Returns the map color at `(x, y)`, or `None` if it is out of bounds.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u8> {
+        (x < MAP_SIZE && y < MAP_SIZE).then(|| self.pixels[y * MAP_SIZE + x])
+    }
+
+    /// Sets the map color at `(x, y)`. Pixels out of bounds are ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
+        if x >= MAP_SIZE || y >= MAP_SIZE {
+            return;
+        }
+
+        let pixel = &mut self.pixels[y * MAP_SIZE + x];
+
+        if *pixel != color {
+            *pixel = color;
+            self.mark_dirty(DirtyRect {
+                min_x: x,
+                min_y: y,
+                max_x: x,
+                max_y: y,
+            });
+        }
+    }
+
+    /// Sets every pixel to the same map color.
+    pub fn fill(&mut self, color: u8) {
+        if self.pixels.iter().any(|&pixel| pixel != color) {
+            self.pixels.fill(color);
+            self.mark_dirty(DirtyRect::FULL);
+        }
+    }
+
+    /// Draws an image of RGB values with its top left corner at `(x, y)`,
+    /// using the closest map color for each pixel. The rows of the image are
+    /// `width` pixels long. Parts of the image outside the map are cut off.
+    pub fn draw_image(&mut self, x: usize, y: usize, width: usize, pixels: &[[u8; 3]]) {
+        if width == 0 {
+            return;
+        }
+
+        for (row, line) in pixels.chunks(width).enumerate() {
+            for (column, &rgb) in line.iter().enumerate() {
+                self.set_pixel(x + column, y + row, rgb_to_map_color(rgb));
+            }
+        }
+    }
+
+    /// The region that changed since the last call to
+    /// [`MapCanvas::clear_dirty`].
+    pub fn dirty_rect(&self) -> Option<DirtyRect> {
+        self.dirty
+    }
+
+    /// Marks a region as changed, so that it is sent again. The parts of the
+    /// rectangle outside the map are ignored.
+    pub fn mark_dirty(&mut self, rect: DirtyRect) {
+        let Some(rect) = rect.clamped() else {
+            return;
+        };
+
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(rect),
+            None => rect,
+        });
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = None;
+    }
+
+    /// Copies the pixels in `rect` into `buf`, row by row, and returns them as
+    /// packet data. Only the part of `rect` on the map is copied, and `None`
+    /// is returned if there is no such part.
+    pub(crate) fn region<'a>(&self, rect: DirtyRect, buf: &'a mut Vec<u8>) -> Option<Data<'a>> {
+        let rect = rect.clamped()?;
+
+        buf.clear();
+
+        for y in rect.min_y..=rect.max_y {
+            let start = y * MAP_SIZE;
+            buf.extend_from_slice(&self.pixels[start + rect.min_x..=start + rect.max_x]);
+        }
+
+        Some(Data {
+            columns: rect.width() as u8,
+            rows: rect.height() as u8,
+            position: [rect.min_x as i8, rect.min_y as i8],
+            data: buf,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_tracking() {
+        let mut canvas = MapCanvas::default();
+        assert_eq!(canvas.dirty_rect(), Some(DirtyRect::FULL));
+        canvas.clear_dirty();
+
+        // Setting a pixel to the color it already has changes nothing.
+        canvas.set_pixel(3, 3, TRANSPARENT);
+        assert_eq!(canvas.dirty_rect(), None);
+
+        canvas.set_pixel(10, 20, 34);
+        canvas.set_pixel(5, 30, 34);
+        canvas.set_pixel(500, 500, 34);
+
+        let rect = canvas.dirty_rect().unwrap();
+        assert_eq!(
+            rect,
+            DirtyRect {
+                min_x: 5,
+                min_y: 20,
+                max_x: 10,
+                max_y: 30
+            }
+        );
+
+        let mut buf = vec![];
+        let data = canvas.region(rect, &mut buf).unwrap();
+        assert_eq!((data.columns, data.rows), (6, 11));
+        assert_eq!(data.position, [5, 20]);
+        assert_eq!(data.data.len(), 66);
+        assert_eq!(data.data[5], 34);
+        assert_eq!(data.data[60], 34);
+    }
+
+    #[test]
+    fn draw_clipped_image() {
+        let mut canvas = MapCanvas::default();
+        canvas.clear_dirty();
+
+        canvas.draw_image(127, 127, 2, &[[255, 255, 255]; 4]);
+
+        assert_eq!(
+            canvas.dirty_rect(),
+            Some(DirtyRect {
+                min_x: 127,
+                min_y: 127,
+                max_x: 127,
+                max_y: 127
+            })
+        );
+        assert_ne!(canvas.pixel(127, 127), Some(TRANSPARENT));
+        assert_eq!(canvas.pixel(128, 127), None);
+    }
+
+    #[test]
+    fn invalid_rects() {
+        let mut canvas = MapCanvas::default();
+        canvas.clear_dirty();
+
+        let inverted = DirtyRect {
+            min_x: 10,
+            min_y: 0,
+            max_x: 5,
+            max_y: 0,
+        };
+        assert_eq!(inverted.width(), 0);
+
+        canvas.mark_dirty(inverted);
+        assert_eq!(canvas.dirty_rect(), None);
+        assert!(canvas.region(inverted, &mut vec![]).is_none());
+
+        canvas.mark_dirty(DirtyRect {
+            min_x: 200,
+            min_y: 0,
+            max_x: 300,
+            max_y: 10,
+        });
+        assert_eq!(canvas.dirty_rect(), None);
+
+        let oversized = DirtyRect {
+            min_x: 100,
+            min_y: 120,
+            max_x: 1000,
+            max_y: usize::MAX,
+        };
+        canvas.mark_dirty(oversized);
+        assert_eq!(
+            canvas.dirty_rect(),
+            Some(DirtyRect {
+                min_x: 100,
+                min_y: 120,
+                max_x: 127,
+                max_y: 127
+            })
+        );
+
+        let mut buf = vec![];
+        let data = canvas.region(oversized, &mut buf).unwrap();
+        assert_eq!((data.columns, data.rows), (28, 8));
+        assert_eq!(data.data.len(), 28 * 8);
+    }
+}
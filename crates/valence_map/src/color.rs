@@ -0,0 +1,114 @@
+//! The fixed palette of colors a map can display.
+//!
+//! Every map color is one of 62 base colors in one of four shades. Color `n`
+//! is base color `n / 4` in shade `n % 4`. Base color 0 is transparent.
+
+/// The transparent map color.
+pub const TRANSPARENT: u8 = 0;
+
+/// The RGB values of the base colors.
+const BASE_COLORS: [u32; 62] = [
+    0x000000, 0x7fb238, 0xf7e9a3, 0xc7c7c7, 0xff0000, 0xa0a0ff, 0xa7a7a7, 0x007c00, 0xffffff,
+    0xa4a8b8, 0x976d4d, 0x707070, 0x4040ff, 0x8f7748, 0xfffcf5, 0xd87f33, 0xb24cd8, 0x6699d8,
+    0xe5e533, 0x7fcc19, 0xf27fa5, 0x4c4c4c, 0x999999, 0x4c7f99, 0x7f3fb2, 0x334cb2, 0x664c33,
+    0x667f33, 0x993333, 0x191919, 0xfaee4d, 0x5cdbd5, 0x4a80ff, 0x00d93a, 0x815631, 0x700200,
+    0xd1b1a1, 0x9f5224, 0x95576c, 0x706c8a, 0xba8524, 0x677535, 0xa04d4e, 0x392923, 0x876b62,
+    0x575c5c, 0x7a4958, 0x4c3e5c, 0x4c3223, 0x4c522a, 0x8e3c2e, 0x251610, 0xbd3031, 0x943f61,
+    0x5c191d, 0x167e86, 0x3a8e8c, 0x562c3e, 0x14b485, 0x646464, 0xd8af93, 0x7fa796,
+];
+
+/// How bright each shade is, out of 255.
+const SHADES: [u32; 4] = [180, 220, 255, 135];
+
+const PALETTE_LEN: usize = BASE_COLORS.len() * SHADES.len();
+
+const PALETTE: [[u8; 3]; PALETTE_LEN] = {
+    let mut palette = [[0; 3]; PALETTE_LEN];
+    let mut i = 0;
+
+    while i < PALETTE_LEN {
+        let base = BASE_COLORS[i / 4];
+        let shade = SHADES[i % 4];
+
+        palette[i] = [
+            ((base >> 16 & 0xff) * shade / 255) as u8,
+            ((base >> 8 & 0xff) * shade / 255) as u8,
+            ((base & 0xff) * shade / 255) as u8,
+        ];
+
+        i += 1;
+    }
+
+    palette
+};
+
+/// Returns the RGB value of a map color, or `None` if it is transparent or
+/// not a valid color.
+pub fn map_color_to_rgb(color: u8) -> Option<[u8; 3]> {
+    if usize::from(color) < 4 {
+        return None;
+    }
+
+    PALETTE.get(usize::from(color)).copied()
+}
+
+/// Returns the opaque map color closest to an RGB value.
+pub fn rgb_to_map_color(rgb: [u8; 3]) -> u8 {
+    let distance = |color: &[u8; 3]| -> u32 {
+        color
+            .iter()
+            .zip(rgb)
+            .map(|(&a, b)| u32::from(a.abs_diff(b)).pow(2))
+            .sum()
+    };
+
+    let mut closest = 4;
+    let mut closest_distance = u32::MAX;
+
+    // Skip the transparent shades.
+    for (i, color) in PALETTE.iter().enumerate().skip(4) {
+        let distance = distance(color);
+
+        if distance < closest_distance {
+            closest = i;
+            closest_distance = distance;
+
+            if distance == 0 {
+                break;
+            }
+        }
+    }
+
+    closest as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_round_trip() {
+        assert_eq!(map_color_to_rgb(TRANSPARENT), None);
+        assert_eq!(map_color_to_rgb(PALETTE_LEN as u8), None);
+
+        // Grass in the brightest shade.
+        assert_eq!(map_color_to_rgb(6), Some([0x7f, 0xb2, 0x38]));
+
+        for color in 4..PALETTE_LEN as u8 {
+            let rgb = map_color_to_rgb(color).unwrap();
+            assert_eq!(map_color_to_rgb(rgb_to_map_color(rgb)), Some(rgb));
+        }
+    }
+
+    #[test]
+    fn nearest_color() {
+        assert_eq!(
+            map_color_to_rgb(rgb_to_map_color([255, 0, 0])),
+            Some([255, 0, 0])
+        );
+        assert_eq!(
+            map_color_to_rgb(rgb_to_map_color([254, 254, 254])),
+            Some([255, 255, 255])
+        );
+    }
+}
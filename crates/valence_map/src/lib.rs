@@ -0,0 +1,196 @@
+#![doc = include_str!("../README.md")]
+
+use std::borrow::Cow;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use derive_more::{Deref, DerefMut};
+use valence_entity::EntityLayerId;
+use valence_server::client::{Client, OldVisibleEntityLayers, VisibleEntityLayers};
+use valence_server::layer::UpdateLayersPreClientSet;
+use valence_server::protocol::item::ItemComponent;
+use valence_server::protocol::packets::play::map_item_data_s2c::Icon;
+pub use valence_server::protocol::packets::play::map_item_data_s2c::IconType;
+use valence_server::protocol::packets::play::MapItemDataS2c;
+use valence_server::protocol::{VarInt, WritePacket};
+use valence_server::{EntityLayer, ItemKind, ItemStack, Text};
+
+mod canvas;
+pub mod color;
+
+pub use canvas::*;
+pub use color::{map_color_to_rgb, rgb_to_map_color};
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (init_map_viewers, update_maps)
+                .chain()
+                .before(UpdateLayersPreClientSet),
+        );
+    }
+}
+
+/// The bundle of components that make up a map.
+#[derive(Bundle, Default)]
+pub struct MapBundle {
+    pub id: MapId,
+    pub canvas: MapCanvas,
+    pub decorations: MapDecorations,
+    pub scale: MapScale,
+    pub locked: MapLocked,
+    pub layer: EntityLayerId,
+}
+
+/// The ID of a map, which links it to map items with the same ID. IDs should
+/// be unique across the maps in an entity layer.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug, Deref)]
+pub struct MapId(pub i32);
+
+impl MapId {
+    /// Returns a filled map item showing this map.
+    pub fn item_stack(self) -> ItemStack<'static> {
+        ItemStack::new(
+            ItemKind::FilledMap,
+            1,
+            vec![ItemComponent::MapId { id: VarInt(self.0) }],
+        )
+    }
+}
+
+/// How zoomed out a map is, from 0 to 4. Only affects the scale shown in the
+/// tooltip of map items.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug, Deref, DerefMut)]
+pub struct MapScale(pub i8);
+
+/// Whether a map is shown as locked in the tooltip of map items.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug, Deref, DerefMut)]
+pub struct MapLocked(pub bool);
+
+/// The icons drawn on top of a map.
+#[derive(Component, Clone, PartialEq, Default, Debug, Deref, DerefMut)]
+pub struct MapDecorations(pub Vec<MapDecoration>);
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapDecoration {
+    pub icon: IconType,
+    /// The position of the icon, from -128 at the left or top edge to 127 at
+    /// the right or bottom edge. Note that this is twice the resolution of
+    /// the canvas.
+    pub position: [i8; 2],
+    /// The rotation of the icon in steps of 22.5°, clockwise from pointing
+    /// up.
+    pub direction: i8,
+    pub display_name: Option<Text>,
+}
+
+impl MapDecoration {
+    pub fn new(icon: IconType, position: [i8; 2]) -> Self {
+        Self {
+            icon,
+            position,
+            direction: 0,
+            display_name: None,
+        }
+    }
+
+    fn to_icon(&self) -> Icon<'_> {
+        Icon {
+            icon_type: self.icon,
+            position: self.position,
+            direction: self.direction & 0xf,
+            display_name: self.display_name.as_ref().map(Cow::Borrowed),
+        }
+    }
+}
+
+impl MapDecorations {
+    fn to_icons(&self) -> Vec<Icon<'_>> {
+        self.iter().map(MapDecoration::to_icon).collect()
+    }
+}
+
+/// Sends the whole map to clients that started viewing its layer.
+fn init_map_viewers(
+    mut clients: Query<
+        (&mut Client, &VisibleEntityLayers, &OldVisibleEntityLayers),
+        Changed<VisibleEntityLayers>,
+    >,
+    maps: Query<(
+        &MapId,
+        &MapCanvas,
+        &MapDecorations,
+        &MapScale,
+        &MapLocked,
+        &EntityLayerId,
+    )>,
+    mut buf: Local<Vec<u8>>,
+) {
+    for (mut client, visible, old_visible) in &mut clients {
+        for &added_layer in visible.0.difference(old_visible.get()) {
+            for (id, canvas, decorations, scale, locked, _) in maps
+                .iter()
+                .filter(|(_, _, _, _, _, layer)| layer.0 == added_layer)
+            {
+                client.write_packet(&MapItemDataS2c {
+                    map_id: VarInt(id.0),
+                    scale: scale.0,
+                    locked: locked.0,
+                    icons: Some(decorations.to_icons()),
+                    data: canvas.region(DirtyRect::FULL, &mut buf),
+                });
+            }
+        }
+    }
+}
+
+/// Sends the parts of maps that changed to the clients viewing them.
+#[allow(clippy::type_complexity)]
+fn update_maps(
+    mut maps: Query<
+        (
+            &MapId,
+            &mut MapCanvas,
+            Ref<MapDecorations>,
+            Ref<MapScale>,
+            Ref<MapLocked>,
+            &EntityLayerId,
+        ),
+        Or<(
+            Changed<MapCanvas>,
+            Changed<MapDecorations>,
+            Changed<MapScale>,
+            Changed<MapLocked>,
+        )>,
+    >,
+    mut layers: Query<&mut EntityLayer>,
+    mut buf: Local<Vec<u8>>,
+) {
+    for (id, mut canvas, decorations, scale, locked, layer) in &mut maps {
+        let dirty = canvas.dirty_rect();
+
+        if dirty.is_none()
+            && !decorations.is_changed()
+            && !scale.is_changed()
+            && !locked.is_changed()
+        {
+            continue;
+        }
+
+        if let Ok(mut layer) = layers.get_mut(layer.0) {
+            layer.write_packet(&MapItemDataS2c {
+                map_id: VarInt(id.0),
+                scale: scale.0,
+                locked: locked.0,
+                icons: decorations.is_changed().then(|| decorations.to_icons()),
+                data: dirty.and_then(|rect| canvas.region(rect, &mut buf)),
+            });
+        }
+
+        // Don't trigger change detection again for clearing the region.
+        canvas.bypass_change_detection().clear_dirty();
+    }
+}
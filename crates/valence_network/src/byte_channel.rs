@@ -56,7 +56,9 @@ impl ByteSender {
         lck.bytes.split_off(len)
     }
 
-    pub(crate) fn try_send(&mut self, mut bytes: BytesMut) -> Result<(), TrySendError> {
+    /// Returns the number of bytes in the channel that weren't received yet,
+    /// including the ones just sent.
+    pub(crate) fn try_send(&mut self, mut bytes: BytesMut) -> Result<usize, TrySendError> {
         let mut lck = self.shared.mtx.lock().unwrap();
 
         if lck.disconnected {
@@ -64,7 +66,7 @@ impl ByteSender {
         }
 
         if bytes.is_empty() {
-            return Ok(());
+            return Ok(lck.bytes.len());
         }
 
         let available = self.shared.limit - lck.bytes.len();
@@ -81,7 +83,7 @@ impl ByteSender {
         lck.bytes.unsplit(bytes);
         self.shared.notify.notify_waiters();
 
-        Ok(())
+        Ok(lck.bytes.len())
     }
 
     pub(crate) async fn send_async(&mut self, mut bytes: BytesMut) -> Result<(), SendError> {
@@ -1,5 +1,6 @@
 //! Handles new connections to the server and the log-in process.
 
+use std::collections::BTreeMap;
 use std::io;
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -19,31 +20,36 @@ use tokio::net::{TcpListener, TcpStream};
 use tracing::{error, info, trace, warn};
 use uuid::Uuid;
 use valence_lang::keys;
-use valence_protocol::packets::configuration::select_known_packs_s2c::KnownPack;
+use valence_protocol::packets::configuration::custom_report_details_s2c::CustomReportDetail;
 use valence_protocol::packets::configuration::{
-    ClientInformationC2s, CustomPayloadS2c, FinishConfigurationC2s, FinishConfigurationS2c,
-    RegistryDataS2c, SelectKnownPacksC2s, SelectKnownPacksS2c, UpdateEnabledFeaturesS2c,
+    ClientInformationC2s, CustomPayloadC2s, CustomPayloadS2c, CustomReportDetailsS2c,
+    FinishConfigurationC2s, FinishConfigurationS2c, SelectKnownPacksC2s, SelectKnownPacksS2c,
+    ServerLinksS2c, UpdateEnabledFeaturesS2c,
 };
 use valence_protocol::packets::login::{LoginAcknowledgedC2s, LoginFinishedS2c};
 use valence_protocol::packets::status::{
     PingRequestC2s, PongResponseS2c, StatusRequestC2s, StatusResponseS2c,
 };
 use valence_protocol::profile::Property;
-use valence_protocol::{Bounded, Decode};
+use valence_protocol::{Bounded, Decode, Encode};
 use valence_server::client::Properties;
+use valence_server::cookie::MAX_COOKIE_LEN;
 use valence_server::protocol::packets::handshake::intention_c2s::HandshakeNextState;
 use valence_server::protocol::packets::handshake::IntentionC2s;
 use valence_server::protocol::packets::login::{
-    CustomQueryAnswerC2s, CustomQueryS2c, HelloC2s, HelloS2c, KeyC2s, LoginCompressionS2c,
-    LoginDisconnectS2c,
+    CookieRequestS2c, CookieResponseC2s, CustomQueryAnswerC2s, CustomQueryS2c, HelloC2s, HelloS2c,
+    KeyC2s, LoginCompressionS2c, LoginDisconnectS2c,
 };
 use valence_server::protocol::{PacketDecoder, PacketEncoder, RawBytes, VarInt};
-use valence_server::registry::{RegistryCodec, TagsRegistry};
+use valence_server::reconfigure::ClientFeatures;
+use valence_server::registry::TagsRegistry;
 use valence_server::text::{Color, IntoText};
-use valence_server::{ident, Ident, Text, MINECRAFT_VERSION, PROTOCOL_VERSION};
+use valence_server::{ident, Text, MINECRAFT_VERSION, PROTOCOL_VERSION};
 
 use crate::legacy_ping::try_handle_legacy_ping;
+use crate::login_query::LoginQueries;
 use crate::packet_io::PacketIo;
+use crate::proxy_protocol::read_proxy_header;
 use crate::{CleanupOnDrop, ConnectionMode, NewClientInfo, ServerListPing, SharedNetworkState};
 
 /// Accepts new connections to the server as they occur.
@@ -98,6 +104,19 @@ async fn handle_connection(
         error!("failed to set TCP_NODELAY: {e}");
     }
 
+    let remote_addr = if shared.0.proxy_protocol {
+        match read_proxy_header(&mut stream).await {
+            Ok(Some(source_addr)) => source_addr,
+            Ok(None) => remote_addr,
+            Err(e) => {
+                warn!("rejected connection from {remote_addr}: {e:#}");
+                return;
+            }
+        }
+    } else {
+        remote_addr
+    };
+
     match try_handle_legacy_ping(&shared, &mut stream, remote_addr).await {
         Ok(true) => return, // Legacy ping succeeded.
         Ok(false) => {}     // No legacy ping.
@@ -131,6 +150,9 @@ pub struct HandshakeData {
     pub server_address: String,
     /// The port that the client used to connect.
     pub server_port: u16,
+    /// Whether the client was sent here from another server with a transfer
+    /// packet.
+    pub transferred: bool,
 }
 
 async fn handle_handshake(
@@ -146,6 +168,7 @@ async fn handle_handshake(
         protocol_version: handshake.protocol_version.0,
         server_address: handshake.server_address.0.to_owned(),
         server_port: handshake.server_port,
+        transferred: next_state == HandshakeNextState::Transfer,
     };
 
     // TODO: this is borked.
@@ -159,14 +182,15 @@ async fn handle_handshake(
         HandshakeNextState::Status => handle_status(shared, io, remote_addr, handshake)
             .await
             .context("handling status"),
-        HandshakeNextState::Login => {
+        HandshakeNextState::Login | HandshakeNextState::Transfer => {
             match handle_login(&shared, &mut io, remote_addr, handshake)
                 .await
                 .context("handling login")?
             {
-                Some((info, cleanup)) => {
+                Some((info, features, cleanup)) => {
                     let client = io.into_client_args(
                         info,
+                        features,
                         shared.0.incoming_byte_limit,
                         shared.0.outgoing_byte_limit,
                         cleanup,
@@ -262,7 +286,7 @@ async fn handle_login(
     io: &mut PacketIo,
     remote_addr: SocketAddr,
     handshake: HandshakeData,
-) -> anyhow::Result<Option<(NewClientInfo, CleanupOnDrop)>> {
+) -> anyhow::Result<Option<(NewClientInfo, ClientFeatures, CleanupOnDrop)>> {
     if handshake.protocol_version != PROTOCOL_VERSION {
         io.send_packet(&LoginDisconnectS2c {
             // TODO: use correct translation key.
@@ -282,7 +306,16 @@ async fn handle_login(
 
     let username = username.0.to_owned();
 
-    let info = match shared.connection_mode() {
+    if handshake.transferred && !shared.0.accept_transfers {
+        io.send_packet(&LoginDisconnectS2c {
+            reason: Text::translate(keys::MULTIPLAYER_DISCONNECT_TRANSFERS_DISABLED, []).into(),
+        })
+        .await?;
+
+        return Ok(None);
+    }
+
+    let mut info = match shared.connection_mode() {
         ConnectionMode::Online { .. } => login_online(shared, io, remote_addr, username).await?,
         ConnectionMode::Offline => login_offline(remote_addr, username)?,
         ConnectionMode::BungeeCord => {
@@ -297,7 +330,56 @@ async fn handle_login(
         })
         .await?;
 
-        io.set_compression(shared.0.threshold);
+        io.set_compression(shared.0.threshold, shared.0.compression);
+    }
+
+    info.transferred = handshake.transferred;
+
+    let cookie_keys = shared.0.callbacks.inner.login_cookies(shared, &info).await;
+
+    for key in &cookie_keys {
+        io.send_packet(&CookieRequestS2c {
+            key: key.as_str_ident().into(),
+        })
+        .await?;
+    }
+
+    // Clients answer every request in order.
+    for key in cookie_keys {
+        let CookieResponseC2s {
+            key: response_key,
+            payload,
+        } = io.recv_packet().await?;
+
+        ensure!(
+            response_key.as_str() == key.as_str(),
+            "cookie response for `{response_key}` does not match request for `{key}`"
+        );
+
+        if let Some(payload) = payload {
+            ensure!(
+                payload.len() <= MAX_COOKIE_LEN,
+                "cookie `{key}` is too long"
+            );
+            info.cookies.insert(key, payload.into_owned());
+        }
+    }
+
+    let mut queries = LoginQueries::new(io, VELOCITY_MESSAGE_ID + 1);
+
+    if let Err(reason) = shared
+        .0
+        .callbacks
+        .inner
+        .login_queries(shared, &info, &mut queries)
+        .await
+    {
+        info!("disconnect at login queries: \"{reason}\"");
+        io.send_packet(&LoginDisconnectS2c {
+            reason: reason.into(),
+        })
+        .await?;
+        return Ok(None);
     }
 
     let cleanup = match shared.0.callbacks.inner.login(shared, &info).await {
@@ -320,40 +402,73 @@ async fn handle_login(
     .await?;
 
     let LoginAcknowledgedC2s {} = io.recv_packet().await?;
-    let _: CustomQueryAnswerC2s = io.recv_packet().await?;
+    // The client's brand.
+    let _: CustomPayloadC2s = io.recv_packet().await?;
     let _: ClientInformationC2s = io.recv_packet().await?;
 
+    let config = shared.0.callbacks.inner.configuration(shared, &info).await;
+
+    let mut brand = vec![];
+    config.brand.encode(&mut brand)?;
+
     io.send_packet(&CustomPayloadS2c {
-        channel: Ident::new("minecraft:brand").unwrap(),
-        data: Bounded(RawBytes(&[&[0x07], "vanilla".as_bytes()].concat())),
+        channel: ident!("minecraft:brand").into(),
+        data: Bounded(RawBytes(&brand)),
     })
     .await?;
 
+    if !config.report_details.is_empty() {
+        io.send_packet(&CustomReportDetailsS2c {
+            details: config
+                .report_details
+                .iter()
+                .map(|(title, description)| CustomReportDetail {
+                    title: Bounded(title.as_str()),
+                    description: Bounded(description.as_str()),
+                })
+                .collect(),
+        })
+        .await?;
+    }
+
+    if !config.server_links.is_empty() {
+        io.send_packet(&ServerLinksS2c {
+            links: config.server_links,
+        })
+        .await?;
+    }
+
     io.send_packet(&UpdateEnabledFeaturesS2c {
-        features: vec![ident!("minecraft:vanilla").into()],
+        features: config
+            .features
+            .iter()
+            .map(|f| f.as_str_ident().into())
+            .collect(),
     })
     .await?;
 
     io.send_packet(&SelectKnownPacksS2c {
-        packs: vec![KnownPack {
-            namespace: "minecraft".into(),
-            id: "core".into(),
-            version: "1.21.3".into(),
-        }],
+        packs: config.known_packs.clone(),
     })
     .await?;
 
-    let _: SelectKnownPacksC2s = io.recv_packet().await?;
+    let SelectKnownPacksC2s { packs } = io.recv_packet().await?;
 
-    for (id, entries) in RegistryCodec::default().registries.into_iter() {
-        io.send_packet(&RegistryDataS2c {
-            id: id.into(),
-            entries: entries
-                .into_iter()
-                .map(|value| (value.name.into(), None))
-                .collect(),
-        })
-        .await?;
+    let has_core_pack = packs.iter().any(|pack| {
+        pack.namespace == "minecraft" && pack.id == "core" && pack.version == MINECRAFT_VERSION
+    });
+
+    // Clone the `Arc` so the lock isn't held across awaits.
+    let registry_data = shared.0.registry_data.read().unwrap().clone();
+
+    let packets = if has_core_pack {
+        &registry_data.known_pack
+    } else {
+        &registry_data.full
+    };
+
+    for pkt in packets {
+        io.send_packet(pkt).await?;
     }
 
     io.send_packet(&TagsRegistry::default_tags()).await?;
@@ -361,7 +476,13 @@ async fn handle_login(
 
     let _: FinishConfigurationC2s = io.recv_packet().await?;
 
-    Ok(Some((info, cleanup)))
+    // Sent again when the client is reconfigured.
+    let features = ClientFeatures {
+        features: config.features,
+        known_packs: config.known_packs,
+    };
+
+    Ok(Some((info, features, cleanup)))
 }
 
 /// Login procedure for online mode.
@@ -460,6 +581,8 @@ async fn login_online(
         username,
         ip: remote_addr.ip(),
         properties: Properties(profile.properties),
+        transferred: false,
+        cookies: BTreeMap::new(),
     })
 }
 
@@ -479,6 +602,8 @@ fn login_offline(remote_addr: SocketAddr, username: String) -> anyhow::Result<Ne
         username,
         properties: Default::default(),
         ip: remote_addr.ip(),
+        transferred: false,
+        cookies: BTreeMap::new(),
     })
 }
 
@@ -517,9 +642,14 @@ fn login_bungeecord(
         username,
         properties: Properties(properties),
         ip,
+        transferred: false,
+        cookies: BTreeMap::new(),
     })
 }
 
+/// The message ID of the login query for Velocity's player info.
+const VELOCITY_MESSAGE_ID: i32 = 0;
+
 /// Login procedure for Velocity.
 async fn login_velocity(
     io: &mut PacketIo,
@@ -529,7 +659,7 @@ async fn login_velocity(
     const VELOCITY_MIN_SUPPORTED_VERSION: u8 = 1;
     const VELOCITY_MODERN_FORWARDING_WITH_KEY_V2: i32 = 3;
 
-    let message_id: i32 = 0; // TODO: make this random?
+    let message_id = VELOCITY_MESSAGE_ID;
 
     // Send Player Info Request into the Plugin Channel
     io.send_packet(&CustomQueryS2c {
@@ -550,8 +680,9 @@ async fn login_velocity(
 
     let data = plugin_response
         .data
-        // .context("missing plugin response data")?
-        .0;
+        .context("missing plugin response data")?
+        .0
+         .0;
 
     ensure!(data.len() >= 32, "invalid plugin response data length");
     let (signature, mut data_without_signature) = data.split_at(32);
@@ -591,6 +722,8 @@ async fn login_velocity(
         username,
         properties: Properties(properties),
         ip: remote_addr,
+        transferred: false,
+        cookies: BTreeMap::new(),
     })
 }
 
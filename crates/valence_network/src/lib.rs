@@ -3,12 +3,17 @@
 mod byte_channel;
 mod connect;
 mod legacy_ping;
+mod login_query;
 mod packet_io;
+mod proxy_protocol;
+mod query;
+mod stats;
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use anyhow::Context;
@@ -19,19 +24,32 @@ use connect::do_accept_loop;
 pub use connect::HandshakeData;
 use flume::{Receiver, Sender};
 pub use legacy_ping::{ServerListLegacyPingPayload, ServerListLegacyPingResponse};
+pub use login_query::LoginQueries;
+use query::do_query_loop;
 use rand::rngs::OsRng;
 use rsa::traits::PublicKeyParts;
 use rsa::RsaPrivateKey;
 use serde::Serialize;
+pub use stats::ConnectionStats;
+use stats::update_connection_stats;
 use tokio::net::UdpSocket;
 use tokio::runtime::{Handle, Runtime};
 use tokio::sync::Semaphore;
 use tokio::time;
 use tracing::error;
 use uuid::Uuid;
+use valence_protocol::compression::CompressionOptions;
+use valence_protocol::packets::configuration::select_known_packs_s2c::KnownPack;
+use valence_protocol::packets::configuration::server_links_s2c::ServerLink;
+use valence_protocol::packets::configuration::RegistryDataS2c;
 use valence_protocol::text::IntoText;
-use valence_server::client::{ClientBundle, ClientBundleArgs, Properties, SpawnClientsSet};
-use valence_server::{CompressionThreshold, Server, Text, MINECRAFT_VERSION, PROTOCOL_VERSION};
+use valence_server::client::{
+    ClientBundle, ClientBundleArgs, FlushPacketsSet, Properties, SpawnClientsSet,
+};
+use valence_server::registry::{RegistryCodec, RegistrySet};
+use valence_server::{
+    ident, CompressionThreshold, Ident, Server, Text, MINECRAFT_VERSION, PROTOCOL_VERSION,
+};
 
 pub struct NetworkPlugin;
 
@@ -44,11 +62,13 @@ impl Plugin for NetworkPlugin {
 }
 
 fn build_plugin(app: &mut App) -> anyhow::Result<()> {
-    let threshold = app
+    let server = app
         .world()
         .get_resource::<Server>()
-        .context("missing server resource")?
-        .compression_threshold();
+        .context("missing server resource")?;
+
+    let threshold = server.compression_threshold();
+    let compression = server.compression_options();
 
     let settings = app
         .world_mut()
@@ -74,11 +94,15 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
         None => settings.tokio_handle.clone().unwrap(),
     };
 
+    let query_address = settings.query_address;
+
     let shared = SharedNetworkState(Arc::new(SharedNetworkStateInner {
         callbacks: settings.callbacks.clone(),
         address: settings.address,
         incoming_byte_limit: settings.incoming_byte_limit,
         outgoing_byte_limit: settings.outgoing_byte_limit,
+        proxy_protocol: settings.proxy_protocol,
+        accept_transfers: settings.accept_transfers,
         connection_sema: Arc::new(Semaphore::new(
             settings.max_connections.min(Semaphore::MAX_PERMITS),
         )),
@@ -86,6 +110,7 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
         max_players: settings.max_players,
         connection_mode: settings.connection_mode.clone(),
         threshold,
+        compression,
         tokio_handle,
         _tokio_runtime: runtime,
         new_clients_send,
@@ -93,6 +118,7 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
         rsa_key,
         public_key_der,
         http_client: reqwest::Client::new(),
+        registry_data: RwLock::default(),
     }));
 
     app.insert_resource(shared.clone());
@@ -105,6 +131,14 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
         tokio::spawn(do_accept_loop(shared.clone()));
     };
 
+    let start_query_loop = move |shared: Res<SharedNetworkState>| {
+        if let Some(address) = query_address {
+            let _guard = shared.0.tokio_handle.enter();
+
+            tokio::spawn(do_query_loop(shared.clone(), address));
+        }
+    };
+
     let start_broadcast_to_lan_loop = move |shared: Res<SharedNetworkState>| {
         let _guard = shared.0.tokio_handle.enter();
 
@@ -115,7 +149,7 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
     let spawn_new_clients = move |world: &mut World| {
         for _ in 0..shared.0.new_clients_recv.len() {
             match shared.0.new_clients_recv.try_recv() {
-                Ok(args) => world.spawn(ClientBundle::new(args)),
+                Ok((args, stats)) => world.spawn((ClientBundle::new(args), stats)),
                 Err(_) => break,
             };
         }
@@ -123,17 +157,40 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
 
     // Start accepting connections in `PostStartup` to allow user startup code to
     // run first.
-    app.add_systems(PostStartup, start_accept_loop);
+    app.add_systems(
+        PostStartup,
+        (update_registry_data, start_accept_loop).chain(),
+    );
+
+    // Keep the registry data sent during the configuration phase in sync with
+    // the registry codec.
+    app.add_systems(PostUpdate, update_registry_data.after(RegistrySet));
 
     // Start the loop that will broadcast messages for the LAN discovery list.
     app.add_systems(PostStartup, start_broadcast_to_lan_loop);
 
+    // Start answering queries if enabled.
+    app.add_systems(PostStartup, start_query_loop);
+
     // Spawn new clients before the event loop starts.
     app.add_systems(PreUpdate, spawn_new_clients.in_set(SpawnClientsSet));
 
+    app.add_systems(PostUpdate, update_connection_stats.after(FlushPacketsSet));
+
     Ok(())
 }
 
+fn update_registry_data(codec: Res<RegistryCodec>, shared: Res<SharedNetworkState>) {
+    if codec.is_changed() {
+        let data = RegistryData {
+            known_pack: codec.registry_data_packets(true),
+            full: codec.registry_data_packets(false),
+        };
+
+        *shared.0.registry_data.write().unwrap() = Arc::new(data);
+    }
+}
+
 #[derive(Resource, Clone)]
 pub struct SharedNetworkState(Arc<SharedNetworkStateInner>);
 
@@ -155,6 +212,8 @@ struct SharedNetworkStateInner {
     address: SocketAddr,
     incoming_byte_limit: usize,
     outgoing_byte_limit: usize,
+    proxy_protocol: bool,
+    accept_transfers: bool,
     /// Limits the number of simultaneous connections to the server before the
     /// play state.
     connection_sema: Arc<Semaphore>,
@@ -163,14 +222,15 @@ struct SharedNetworkStateInner {
     max_players: usize,
     connection_mode: ConnectionMode,
     threshold: CompressionThreshold,
+    compression: CompressionOptions,
     tokio_handle: Handle,
     // Holding a runtime handle is not enough to keep tokio working. We need
     // to store the runtime here so we don't drop it.
     _tokio_runtime: Option<Runtime>,
     /// Sender for new clients past the login stage.
-    new_clients_send: Sender<ClientBundleArgs>,
+    new_clients_send: Sender<(ClientBundleArgs, ConnectionStats)>,
     /// Receiver for new clients past the login stage.
-    new_clients_recv: Receiver<ClientBundleArgs>,
+    new_clients_recv: Receiver<(ClientBundleArgs, ConnectionStats)>,
     /// The RSA keypair used for encryption with clients.
     rsa_key: RsaPrivateKey,
     /// The public part of `rsa_key` encoded in DER, which is an ASN.1 format.
@@ -178,6 +238,18 @@ struct SharedNetworkStateInner {
     public_key_der: Box<[u8]>,
     /// For session server requests.
     http_client: reqwest::Client,
+    /// A snapshot of the [`RegistryCodec`] sent to clients during the
+    /// configuration phase.
+    registry_data: RwLock<Arc<RegistryData>>,
+}
+
+/// The registry data packets sent to clients during the configuration phase.
+#[derive(Default)]
+struct RegistryData {
+    /// Sent to clients that know the vanilla `minecraft:core` pack.
+    known_pack: Vec<RegistryDataS2c<'static>>,
+    /// Sent to clients that don't know the vanilla `minecraft:core` pack.
+    full: Vec<RegistryDataS2c<'static>>,
 }
 
 /// Contains information about a new client joining the server.
@@ -193,6 +265,50 @@ pub struct NewClientInfo {
     /// The client's properties from the game profile. Typically contains a
     /// `textures` property with the skin and cape of the player.
     pub properties: Properties,
+    /// Whether another server sent the client here with
+    /// [`Client::transfer`](valence_server::client::Client::transfer).
+    pub transferred: bool,
+    /// The cookies that were requested with
+    /// [`NetworkCallbacks::login_cookies`], by key. Cookies the client
+    /// doesn't have are missing.
+    pub cookies: BTreeMap<Ident<String>, Vec<u8>>,
+}
+
+/// What is sent to a client in the configuration phase after it logged in. See
+/// [`NetworkCallbacks::configuration`].
+#[derive(Clone, Debug)]
+pub struct ClientConfiguration {
+    /// The server brand shown in the client's debug screen.
+    pub brand: String,
+    /// The enabled feature flags, like `minecraft:vanilla` or experimental
+    /// ones like `minecraft:bundle` and `minecraft:trade_rebalance`.
+    pub features: Vec<Ident<String>>,
+    /// The data packs the server expects the client to have. Registry entries
+    /// from the `minecraft:core` pack are only sent to clients that don't have
+    /// it.
+    pub known_packs: Vec<KnownPack<'static>>,
+    /// Links shown in the pause menu, like the website or where to report
+    /// bugs.
+    pub server_links: Vec<ServerLink<'static>>,
+    /// Extra details added to the client's crash reports, as pairs of titles
+    /// and descriptions.
+    pub report_details: Vec<(String, String)>,
+}
+
+impl Default for ClientConfiguration {
+    fn default() -> Self {
+        Self {
+            brand: "vanilla".into(),
+            features: vec![ident!("minecraft:vanilla").into()],
+            known_packs: vec![KnownPack {
+                namespace: "minecraft".into(),
+                id: "core".into(),
+                version: MINECRAFT_VERSION.into(),
+            }],
+            server_links: vec![],
+            report_details: vec![],
+        }
+    }
 }
 
 /// Settings for [`NetworkPlugin`]. Note that mutations to these fields have no
@@ -258,6 +374,40 @@ pub struct NetworkSettings {
     ///
     /// The default value is left unspecified and may change in future versions.
     pub outgoing_byte_limit: usize,
+    /// Whether every connection starts with a [PROXY protocol] header of
+    /// version 1 or 2. This should be enabled when the server is behind a TCP
+    /// load balancer or proxy that sends the header, so that the address of
+    /// the client is used instead of the address of the proxy. Connections
+    /// without a valid header are closed.
+    ///
+    /// **NOTE:** Only enable this if the server can't be reached without going
+    /// through the proxy, since anyone can send the header.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    ///
+    /// [PROXY protocol]: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+    pub proxy_protocol: bool,
+    /// Whether clients that were sent here from another server with a
+    /// transfer packet may join. Clients can be transferred to any server, so
+    /// they should be authenticated as usual.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub accept_transfers: bool,
+    /// The UDP address to answer the [Query] protocol on, or `None` to not
+    /// answer it. This is usually the same address as the one used for
+    /// connections. The information sent comes from
+    /// [`NetworkCallbacks::query`].
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    ///
+    /// [Query]: https://minecraft.wiki/w/Query
+    pub query_address: Option<SocketAddr>,
 }
 
 impl Default for NetworkSettings {
@@ -273,6 +423,9 @@ impl Default for NetworkSettings {
             },
             incoming_byte_limit: 2097152, // 2 MiB
             outgoing_byte_limit: 8388608, // 8 MiB
+            proxy_protocol: false,
+            accept_transfers: false,
+            query_address: None,
         }
     }
 }
@@ -361,6 +514,7 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
                 protocol_version: protocol,
                 server_address: hostname,
                 server_port: port,
+                transferred: false,
             },
             _ => HandshakeData::default(),
         };
@@ -400,6 +554,95 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
         BroadcastToLan::Disabled
     }
 
+    /// Called when the server receives a [Query] stat request, if
+    /// [`NetworkSettings::query_address`] is set. The number of players
+    /// and the address are taken from the [`SharedNetworkState`].
+    ///
+    /// This function is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// A placeholder response without players is returned.
+    ///
+    /// [Query]: https://minecraft.wiki/w/Query
+    async fn query(&self, shared: &SharedNetworkState, remote_addr: SocketAddr) -> ServerQuery {
+        #![allow(unused_variables)]
+
+        ServerQuery::Respond {
+            motd: "A Valence Server".into(),
+            map: "world".into(),
+            players: vec![],
+            plugins: String::new(),
+        }
+    }
+
+    /// Called for each client before [`login`](Self::login) to get the keys of
+    /// the cookies to request from it. The cookies are then available in
+    /// [`NewClientInfo::cookies`]. This is mostly useful for clients that were
+    /// [transferred] here, since the server that sent them can store cookies
+    /// on the client beforehand.
+    ///
+    /// This function is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// No cookies are requested.
+    ///
+    /// [transferred]: NewClientInfo::transferred
+    async fn login_cookies(
+        &self,
+        shared: &SharedNetworkState,
+        info: &NewClientInfo,
+    ) -> Vec<Ident<String>> {
+        #![allow(unused_variables)]
+
+        vec![]
+    }
+
+    /// Called for each client before [`login`](Self::login) to exchange login
+    /// plugin messages with it, like mod handshakes or checks for bots. This
+    /// happens before a [`Client`] is spawned. Any number of queries can be
+    /// sent with [`LoginQueries::query`].
+    ///
+    /// If `Err(reason)` is returned, then the client is immediately
+    /// disconnected with `reason` as the displayed message.
+    ///
+    /// This function is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// No queries are sent.
+    ///
+    /// [`Client`]: valence_server::client::Client
+    async fn login_queries(
+        &self,
+        shared: &SharedNetworkState,
+        info: &NewClientInfo,
+        queries: &mut LoginQueries<'_>,
+    ) -> Result<(), Text> {
+        #![allow(unused_variables)]
+
+        Ok(())
+    }
+
+    /// Called for each client that logged in to get what is sent to it in the
+    /// configuration phase.
+    ///
+    /// This function is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// [`ClientConfiguration::default`] is returned.
+    async fn configuration(
+        &self,
+        shared: &SharedNetworkState,
+        info: &NewClientInfo,
+    ) -> ClientConfiguration {
+        #![allow(unused_variables)]
+
+        ClientConfiguration::default()
+    }
+
     /// Called for each client (after successful authentication if online mode
     /// is enabled) to determine if they can join the server.
     /// - If `Err(reason)` is returned, then the client is immediately
@@ -620,6 +863,32 @@ pub enum ServerListLegacyPing {
     Ignore,
 }
 
+/// The result of the Query [callback].
+///
+/// [callback]: NetworkCallbacks::query
+#[derive(Clone, Default, Debug)]
+pub enum ServerQuery {
+    /// Responds to the query with the given information.
+    Respond {
+        /// A description of the server.
+        ///
+        /// This string can contain
+        /// [legacy formatting codes](https://minecraft.wiki/w/Formatting_codes).
+        motd: String,
+        /// The name of the world.
+        map: String,
+        /// The names of the players online. Only sent for full stat requests.
+        players: Vec<String>,
+        /// The server software and its plugins, like
+        /// `Valence: Plugin 1.0; Other Plugin 2.1`. Only sent for full stat
+        /// requests.
+        plugins: String,
+    },
+    /// Ignores the query.
+    #[default]
+    Ignore,
+}
+
 /// The result of the Broadcast To Lan [callback].
 ///
 /// [callback]: NetworkCallbacks::broadcast_to_lan
@@ -0,0 +1,156 @@
+use anyhow::ensure;
+use valence_server::protocol::packets::login::{CustomQueryAnswerC2s, CustomQueryS2c};
+use valence_server::protocol::{RawBytes, VarInt};
+use valence_server::Ident;
+
+use crate::packet_io::PacketIo;
+
+/// Sends login plugin queries to a client that is logging in. See
+/// [`NetworkCallbacks::login_queries`](crate::NetworkCallbacks::login_queries).
+pub struct LoginQueries<'a> {
+    io: &'a mut PacketIo,
+    next_message_id: i32,
+}
+
+impl<'a> LoginQueries<'a> {
+    pub(crate) fn new(io: &'a mut PacketIo, next_message_id: i32) -> Self {
+        Self {
+            io,
+            next_message_id,
+        }
+    }
+
+    /// Sends `data` to the client on `channel` and waits for the answer.
+    ///
+    /// Returns the data the client answered with, or `None` if the client
+    /// doesn't understand the channel. Vanilla clients never understand any
+    /// channel. An error is returned if the connection fails or the client
+    /// answers with the wrong message ID.
+    pub async fn query(
+        &mut self,
+        channel: Ident<&str>,
+        data: &[u8],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        self.io
+            .send_packet(&CustomQueryS2c {
+                message_id: VarInt(message_id),
+                channel: channel.into(),
+                data: RawBytes(data).into(),
+            })
+            .await?;
+
+        let answer: CustomQueryAnswerC2s = self.io.recv_packet().await?;
+
+        ensure!(
+            answer.message_id.0 == message_id,
+            "mismatched login query answer ID (got {}, expected {message_id})",
+            answer.message_id.0,
+        );
+
+        Ok(answer.data.map(|data| data.0 .0.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::{TcpListener, TcpStream};
+    use valence_server::protocol::{PacketDecoder, PacketEncoder};
+
+    use super::*;
+
+    /// Returns the server and client ends of a connection.
+    async fn connect() -> (PacketIo, PacketIo) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        (
+            PacketIo::new(server, PacketEncoder::new(), PacketDecoder::new()),
+            PacketIo::new(client, PacketEncoder::new(), PacketDecoder::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn query_round_trip() {
+        let (mut server, mut client) = connect().await;
+
+        let client = tokio::spawn(async move {
+            let query: CustomQueryS2c = client.recv_packet().await.unwrap();
+            assert_eq!(query.message_id.0, 5);
+            assert_eq!(query.channel.as_str(), "valence:test");
+            assert_eq!(query.data.0 .0, b"ping");
+
+            client
+                .send_packet(&CustomQueryAnswerC2s {
+                    message_id: VarInt(5),
+                    data: Some(RawBytes(b"pong").into()),
+                })
+                .await
+                .unwrap();
+
+            let query: CustomQueryS2c = client.recv_packet().await.unwrap();
+            assert_eq!(query.message_id.0, 6);
+
+            // Like a vanilla client.
+            client
+                .send_packet(&CustomQueryAnswerC2s {
+                    message_id: VarInt(6),
+                    data: None,
+                })
+                .await
+                .unwrap();
+
+            client.recv_packet::<CustomQueryS2c>().await.unwrap();
+
+            client
+                .send_packet(&CustomQueryAnswerC2s {
+                    message_id: VarInt(100),
+                    data: None,
+                })
+                .await
+                .unwrap();
+        });
+
+        let mut queries = LoginQueries::new(&mut server, 5);
+        let channel = Ident::new("valence:test").unwrap();
+        let channel = channel.as_str_ident();
+
+        assert_eq!(
+            queries.query(channel, b"ping").await.unwrap(),
+            Some(b"pong".to_vec())
+        );
+        assert_eq!(queries.query(channel, b"ping").await.unwrap(), None);
+        assert!(queries.query(channel, b"ping").await.is_err());
+
+        client.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn query_timeout() {
+        let (mut server, mut client) = connect().await;
+        let channel = Ident::new("valence:test").unwrap();
+        let channel = channel.as_str_ident();
+
+        // A client that never answers holds the query until the login times
+        // out.
+        let mut queries = LoginQueries::new(&mut server, 0);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), queries.query(channel, &[]))
+                .await
+                .is_err()
+        );
+
+        client.recv_packet::<CustomQueryS2c>().await.unwrap();
+
+        // A client that disconnects fails the query.
+        drop(client);
+        assert!(queries.query(channel, &[]).await.is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use std::io::ErrorKind;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
 use std::{io, mem};
@@ -10,12 +11,15 @@ use tokio::net::TcpStream;
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
 use tracing::{debug, warn};
+use valence_protocol::compression::CompressionOptions;
 use valence_protocol::CompressionThreshold;
 use valence_server::client::{ClientBundleArgs, ClientConnection, ReceivedPacket};
 use valence_server::protocol::decode::PacketFrame;
 use valence_server::protocol::{Decode, Encode, Packet, PacketDecoder, PacketEncoder};
+use valence_server::reconfigure::ClientFeatures;
 
 use crate::byte_channel::{byte_channel, ByteSender, TrySendError};
+use crate::stats::{ConnectionCounters, ConnectionStats};
 use crate::{CleanupOnDrop, NewClientInfo};
 
 pub(crate) struct PacketIo {
@@ -95,9 +99,15 @@ impl PacketIo {
     }
 
     #[allow(dead_code)]
-    pub(crate) fn set_compression(&mut self, threshold: CompressionThreshold) {
+    pub(crate) fn set_compression(
+        &mut self,
+        threshold: CompressionThreshold,
+        options: CompressionOptions,
+    ) {
         self.enc.set_compression(threshold);
+        self.enc.set_compression_options(options);
         self.dec.set_compression(threshold);
+        self.dec.set_compression_options(options);
     }
 
     pub(crate) fn enable_encryption(&mut self, key: &[u8; 16]) {
@@ -108,12 +118,17 @@ impl PacketIo {
     pub(crate) fn into_client_args(
         mut self,
         info: NewClientInfo,
+        features: ClientFeatures,
         incoming_byte_limit: usize,
         outgoing_byte_limit: usize,
         cleanup: CleanupOnDrop,
-    ) -> ClientBundleArgs {
+    ) -> (ClientBundleArgs, ConnectionStats) {
         let (incoming_sender, incoming_receiver) = flume::unbounded();
 
+        let counters = Arc::new(ConnectionCounters::default());
+        let reader_counters = counters.clone();
+        let writer_counters = counters.clone();
+
         let incoming_byte_limit = incoming_byte_limit.min(Semaphore::MAX_PERMITS);
 
         let recv_sem = Arc::new(Semaphore::new(incoming_byte_limit));
@@ -133,7 +148,11 @@ impl PacketIo {
                         buf.reserve(READ_BUF_SIZE);
                         match reader.read_buf(&mut buf).await {
                             Ok(0) => break, // Reader is at EOF.
-                            Ok(_) => {}
+                            Ok(n) => {
+                                reader_counters
+                                    .bytes_received
+                                    .fetch_add(n as u64, Ordering::Relaxed);
+                            }
                             Err(e) => {
                                 debug!("error reading data from stream: {e}");
                                 break;
@@ -152,6 +171,8 @@ impl PacketIo {
 
                 let timestamp = Instant::now();
 
+                reader_counters.record_received_packet(frame.id);
+
                 // Estimate memory usage of this packet.
                 let cost = mem::size_of::<ReceivedPacket>() + frame.body.len();
 
@@ -202,11 +223,15 @@ impl PacketIo {
 
                 if let Err(e) = writer.write_all(&bytes).await {
                     debug!("error writing data to stream: {e}");
+                } else {
+                    writer_counters
+                        .bytes_sent
+                        .fetch_add(bytes.len() as u64, Ordering::Relaxed);
                 }
             }
         });
 
-        ClientBundleArgs {
+        let args = ClientBundleArgs {
             username: info.username,
             uuid: info.uuid,
             ip: info.ip,
@@ -217,10 +242,14 @@ impl PacketIo {
                 recv_sem: recv_sem_clone,
                 reader_task,
                 writer_task,
+                counters: counters.clone(),
                 _cleanup: cleanup,
             }),
             enc: self.enc,
-        }
+            features,
+        };
+
+        (args, ConnectionStats::new(counters))
     }
 }
 
@@ -230,6 +259,7 @@ struct RealClientConnection {
     /// Limits the amount of data queued in the `recv` channel. Each permit
     /// represents one byte.
     recv_sem: Arc<Semaphore>,
+    counters: Arc<ConnectionCounters>,
     _cleanup: CleanupOnDrop,
     reader_task: JoinHandle<()>,
     writer_task: JoinHandle<()>,
@@ -238,7 +268,10 @@ struct RealClientConnection {
 impl ClientConnection for RealClientConnection {
     fn try_send(&mut self, bytes: BytesMut) -> anyhow::Result<()> {
         match self.send.try_send(bytes) {
-            Ok(()) => Ok(()),
+            Ok(queued) => {
+                self.counters.queued_bytes.store(queued, Ordering::Relaxed);
+                Ok(())
+            }
             Err(TrySendError::Full(_)) => bail!(
                 "reached configured outgoing limit of {} bytes",
                 self.send.limit()
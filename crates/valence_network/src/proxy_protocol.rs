@@ -0,0 +1,205 @@
+//! Reads the [PROXY protocol] header that load balancers and proxies in front
+//! of the server send before any other data, to learn the real address of
+//! the client.
+//!
+//! [PROXY protocol]: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{bail, ensure, Context};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The signature every version 2 header starts with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of a version 1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Reads a PROXY protocol header of either version from `stream` without
+/// reading past its end.
+///
+/// Returns the source address of the proxied connection, or `None` if the
+/// proxy didn't forward one, like for its own health checks. In that case the
+/// address of the proxy should be used.
+pub(crate) async fn read_proxy_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> anyhow::Result<Option<SocketAddr>> {
+    // Both versions are at least this long, so this doesn't read too much.
+    let mut start = [0; V2_SIGNATURE.len()];
+    stream
+        .read_exact(&mut start)
+        .await
+        .context("reading PROXY header")?;
+
+    if start == V2_SIGNATURE {
+        let mut fixed = [0; 4];
+        stream.read_exact(&mut fixed).await?;
+
+        let mut addresses = vec![0; u16::from_be_bytes([fixed[2], fixed[3]]).into()];
+        stream.read_exact(&mut addresses).await?;
+
+        parse_v2(fixed[0], fixed[1], &addresses)
+    } else if start.starts_with(b"PROXY ") {
+        let mut line = start.to_vec();
+
+        while !line.ends_with(b"\r\n") {
+            ensure!(line.len() < V1_MAX_LEN, "PROXY header is too long");
+            line.push(stream.read_u8().await?);
+        }
+
+        let line = std::str::from_utf8(&line).context("PROXY header is not UTF-8")?;
+        parse_v1(line)
+    } else {
+        bail!("missing PROXY header")
+    }
+}
+
+/// Parses a version 1 header, like `PROXY TCP4 1.2.3.4 5.6.7.8 1234 25565\r\n`.
+fn parse_v1(line: &str) -> anyhow::Result<Option<SocketAddr>> {
+    let line = line
+        .strip_prefix("PROXY ")
+        .and_then(|line| line.strip_suffix("\r\n"))
+        .context("malformed PROXY header")?;
+
+    let mut parts = line.split(' ');
+
+    match parts.next() {
+        Some("TCP4" | "TCP6") => {}
+        // The rest of the line is ignored.
+        Some("UNKNOWN") => return Ok(None),
+        _ => bail!("unknown protocol in PROXY header"),
+    }
+
+    let (Some(source), Some(_destination), Some(source_port), Some(_destination_port), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        bail!("malformed PROXY header");
+    };
+
+    let ip: IpAddr = source.parse().context("invalid source address")?;
+    let port: u16 = source_port.parse().context("invalid source port")?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+/// Parses the rest of a version 2 header after the signature and length.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> anyhow::Result<Option<SocketAddr>> {
+    ensure!(
+        version_command >> 4 == 2,
+        "unsupported PROXY header version"
+    );
+
+    match version_command & 0xf {
+        // LOCAL, sent by the proxy itself.
+        0 => return Ok(None),
+        // PROXY
+        1 => {}
+        _ => bail!("unknown command in PROXY header"),
+    }
+
+    // Addresses are followed by optional TLVs, which are ignored.
+    match family >> 4 {
+        // AF_INET
+        1 => {
+            ensure!(addresses.len() >= 12, "PROXY header is too short");
+
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addresses[..4]).unwrap());
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        2 => {
+            ensure!(addresses.len() >= 36, "PROXY header is too short");
+
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addresses[..16]).unwrap());
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_UNSPEC and AF_UNIX don't carry an IP address.
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut data: &[u8]) -> anyhow::Result<(Option<SocketAddr>, &[u8])> {
+        let addr = read_proxy_header(&mut data).await?;
+        Ok((addr, data))
+    }
+
+    #[tokio::test]
+    async fn proxy_header_v1() {
+        let (addr, rest) = read(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 25565\r\n\x10\x00")
+            .await
+            .unwrap();
+        assert_eq!(addr, Some("192.168.0.1:56324".parse().unwrap()));
+        // The handshake after the header is left alone.
+        assert_eq!(rest, b"\x10\x00");
+
+        let (addr, _) = read(b"PROXY TCP6 ::1 ::2 1234 25565\r\n").await.unwrap();
+        assert_eq!(addr, Some("[::1]:1234".parse().unwrap()));
+
+        let (addr, _) = read(b"PROXY UNKNOWN whatever\r\n").await.unwrap();
+        assert_eq!(addr, None);
+
+        assert!(read(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324\r\n")
+            .await
+            .is_err());
+        assert!(read(b"PROXY TCP4 nonsense 10.0.0.1 1 2\r\n").await.is_err());
+        assert!(read(&[b"PROXY ".as_slice(), &[b'1'; 200]].concat())
+            .await
+            .is_err());
+        // A handshake without a header.
+        assert!(read(b"\x10\x00\xfd\x05\tlocalhost\x63\xdd\x01")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn proxy_header_v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x21, 0x11, 0, 12]);
+        header.extend([127, 0, 0, 2, 127, 0, 0, 1]);
+        header.extend(40000_u16.to_be_bytes());
+        header.extend(25565_u16.to_be_bytes());
+        header.push(0x10);
+
+        let (addr, rest) = read(&header).await.unwrap();
+        assert_eq!(addr, Some("127.0.0.2:40000".parse().unwrap()));
+        assert_eq!(rest, [0x10]);
+
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x21, 0x21, 0, 36]);
+        header.extend(Ipv6Addr::LOCALHOST.octets());
+        header.extend(Ipv6Addr::UNSPECIFIED.octets());
+        header.extend(1234_u16.to_be_bytes());
+        header.extend(25565_u16.to_be_bytes());
+
+        let (addr, _) = read(&header).await.unwrap();
+        assert_eq!(addr, Some("[::1]:1234".parse().unwrap()));
+
+        // A health check from the proxy.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x20, 0x00, 0, 0]);
+
+        let (addr, _) = read(&header).await.unwrap();
+        assert_eq!(addr, None);
+
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x21, 0x11, 0, 4, 1, 2, 3, 4]);
+
+        assert!(read(&header).await.is_err());
+    }
+}
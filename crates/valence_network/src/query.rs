@@ -0,0 +1,410 @@
+//! Answers the UDP [Query] protocol, which is used by monitoring tools and
+//! server lists to get information about the server and the names of the
+//! players online.
+//!
+//! [Query]: https://minecraft.wiki/w/Query
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::net::UdpSocket;
+use tokio::sync::Semaphore;
+use tracing::{error, warn};
+use valence_server::MINECRAFT_VERSION;
+
+use crate::{ServerQuery, SharedNetworkState};
+
+/// The bytes every request starts with.
+const MAGIC: [u8; 2] = [0xfe, 0xfd];
+
+const TYPE_HANDSHAKE: u8 = 9;
+const TYPE_STAT: u8 = 0;
+
+/// How often the secret used for challenge tokens is replaced. Tokens are valid
+/// for at least this long.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// The maximum number of stat requests that are answered at once. Requests are
+/// ignored while this many [`query`](crate::NetworkCallbacks::query) callbacks
+/// are running.
+const MAX_PENDING_STATS: usize = 64;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Request {
+    Handshake {
+        session_id: i32,
+    },
+    Stat {
+        session_id: i32,
+        token: i32,
+        full: bool,
+    },
+}
+
+impl Request {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let (magic, rest) = packet.split_first_chunk::<2>()?;
+
+        if *magic != MAGIC {
+            return None;
+        }
+
+        let (&kind, rest) = rest.split_first()?;
+        let (session_id, rest) = rest.split_first_chunk::<4>()?;
+        // Only the lower 4 bits of each byte are used by clients.
+        let session_id = i32::from_be_bytes(*session_id) & 0x0f0f0f0f;
+
+        match kind {
+            TYPE_HANDSHAKE => Some(Self::Handshake { session_id }),
+            TYPE_STAT => {
+                let (token, rest) = rest.split_first_chunk::<4>()?;
+
+                Some(Self::Stat {
+                    session_id,
+                    token: i32::from_be_bytes(*token),
+                    // A full stat request is padded with four bytes.
+                    full: rest.len() == 4,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The information sent in stat responses.
+struct Stat<'a> {
+    motd: &'a str,
+    map: &'a str,
+    online_players: usize,
+    max_players: usize,
+    players: &'a [String],
+    plugins: &'a str,
+    address: SocketAddr,
+}
+
+fn write_header(buf: &mut Vec<u8>, kind: u8, session_id: i32) {
+    buf.push(kind);
+    buf.extend_from_slice(&session_id.to_be_bytes());
+}
+
+/// Writes a null-terminated string. Strings are encoded in ISO-8859-1, so other
+/// characters are replaced.
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(
+        s.chars()
+            .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?')),
+    );
+    buf.push(0);
+}
+
+fn handshake_response(session_id: i32, token: i32) -> Vec<u8> {
+    let mut buf = vec![];
+    write_header(&mut buf, TYPE_HANDSHAKE, session_id);
+    write_str(&mut buf, &token.to_string());
+    buf
+}
+
+fn basic_stat_response(session_id: i32, stat: &Stat) -> Vec<u8> {
+    let mut buf = vec![];
+    write_header(&mut buf, TYPE_STAT, session_id);
+    write_str(&mut buf, stat.motd);
+    write_str(&mut buf, "SMP");
+    write_str(&mut buf, stat.map);
+    write_str(&mut buf, &stat.online_players.to_string());
+    write_str(&mut buf, &stat.max_players.to_string());
+    // This is the only little-endian number.
+    buf.extend_from_slice(&stat.address.port().to_le_bytes());
+    write_str(&mut buf, &stat.address.ip().to_string());
+    buf
+}
+
+fn full_stat_response(session_id: i32, stat: &Stat) -> Vec<u8> {
+    let mut buf = vec![];
+    write_header(&mut buf, TYPE_STAT, session_id);
+    buf.extend_from_slice(b"splitnum\0\x80\0");
+
+    for (key, value) in [
+        ("hostname", stat.motd),
+        ("gametype", "SMP"),
+        ("game_id", "MINECRAFT"),
+        ("version", MINECRAFT_VERSION),
+        ("plugins", stat.plugins),
+        ("map", stat.map),
+        ("numplayers", &stat.online_players.to_string()),
+        ("maxplayers", &stat.max_players.to_string()),
+        ("hostport", &stat.address.port().to_string()),
+        ("hostip", &stat.address.ip().to_string()),
+    ] {
+        write_str(&mut buf, key);
+        write_str(&mut buf, value);
+    }
+
+    buf.push(0);
+    buf.extend_from_slice(b"\x01player_\0\0");
+
+    for player in stat.players {
+        write_str(&mut buf, player);
+    }
+
+    buf.push(0);
+    buf
+}
+
+/// Hands out challenge tokens without keeping track of the addresses they were
+/// sent to. A token is an HMAC of the remote address, so it can be checked
+/// again later. The secret is replaced every [`CHALLENGE_LIFETIME`] and tokens
+/// made with the previous secret are still accepted.
+struct Challenges {
+    secret: [u8; 32],
+    previous_secret: [u8; 32],
+    rotated: Instant,
+}
+
+impl Challenges {
+    fn new() -> Self {
+        Self {
+            secret: rand::random(),
+            previous_secret: rand::random(),
+            rotated: Instant::now(),
+        }
+    }
+
+    /// Replaces the secret if it is older than [`CHALLENGE_LIFETIME`].
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.rotated);
+
+        if elapsed < CHALLENGE_LIFETIME {
+            return;
+        }
+
+        self.previous_secret = if elapsed < CHALLENGE_LIFETIME * 2 {
+            self.secret
+        } else {
+            rand::random()
+        };
+        self.secret = rand::random();
+        self.rotated = now;
+    }
+
+    fn issue(&mut self, addr: SocketAddr) -> i32 {
+        self.rotate(Instant::now());
+        token(&self.secret, addr)
+    }
+
+    fn check(&mut self, addr: SocketAddr, token: i32) -> bool {
+        self.rotate(Instant::now());
+        token == self::token(&self.secret, addr)
+            || token == self::token(&self.previous_secret, addr)
+    }
+}
+
+fn token(secret: &[u8], addr: SocketAddr) -> i32 {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+
+    match addr.ip() {
+        IpAddr::V4(ip) => mac.update(&ip.octets()),
+        IpAddr::V6(ip) => mac.update(&ip.octets()),
+    }
+    mac.update(&addr.port().to_be_bytes());
+
+    let bytes = mac.finalize().into_bytes();
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[allow(clippy::infinite_loop)]
+pub(crate) async fn do_query_loop(shared: SharedNetworkState, address: SocketAddr) {
+    let socket = match UdpSocket::bind(address).await {
+        Ok(socket) => Arc::new(socket),
+        Err(e) => {
+            error!("failed to bind query socket to {address}: {e}");
+            return;
+        }
+    };
+
+    let mut challenges = Challenges::new();
+    let pending_stats = Arc::new(Semaphore::new(MAX_PENDING_STATS));
+    let mut buf = [0; 1024];
+
+    loop {
+        let (len, remote_addr) = match socket.recv_from(&mut buf).await {
+            Ok(res) => res,
+            Err(e) => {
+                warn!("failed to receive query packet: {e}");
+                continue;
+            }
+        };
+
+        match Request::parse(&buf[..len]) {
+            Some(Request::Handshake { session_id }) => {
+                let token = challenges.issue(remote_addr);
+                send_response(&socket, &handshake_response(session_id, token), remote_addr).await;
+            }
+            Some(Request::Stat {
+                session_id,
+                token,
+                full,
+            }) => {
+                if !challenges.check(remote_addr, token) {
+                    continue;
+                }
+
+                let Ok(permit) = pending_stats.clone().try_acquire_owned() else {
+                    continue;
+                };
+
+                let shared = shared.clone();
+                let socket = socket.clone();
+
+                // The callback may take a while, so other requests are answered
+                // in the meantime.
+                tokio::spawn(async move {
+                    if let Some(response) =
+                        stat_response(&shared, remote_addr, session_id, full).await
+                    {
+                        send_response(&socket, &response, remote_addr).await;
+                    }
+
+                    drop(permit);
+                });
+            }
+            None => {}
+        }
+    }
+}
+
+async fn stat_response(
+    shared: &SharedNetworkState,
+    remote_addr: SocketAddr,
+    session_id: i32,
+    full: bool,
+) -> Option<Vec<u8>> {
+    let ServerQuery::Respond {
+        motd,
+        map,
+        players,
+        plugins,
+    } = shared.0.callbacks.inner.query(shared, remote_addr).await
+    else {
+        return None;
+    };
+
+    let stat = Stat {
+        motd: &motd,
+        map: &map,
+        online_players: shared.player_count().load(Ordering::Relaxed),
+        max_players: shared.max_players(),
+        players: &players,
+        plugins: &plugins,
+        address: shared.0.address,
+    };
+
+    Some(if full {
+        full_stat_response(session_id, &stat)
+    } else {
+        basic_stat_response(session_id, &stat)
+    })
+}
+
+async fn send_response(socket: &UdpSocket, response: &[u8], remote_addr: SocketAddr) {
+    if let Err(e) = socket.send_to(response, remote_addr).await {
+        warn!("failed to send query response to {remote_addr}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        assert_eq!(
+            Request::parse(&[0xfe, 0xfd, 9, 0x01, 0x02, 0x03, 0xf4]),
+            Some(Request::Handshake {
+                session_id: 0x01020304
+            })
+        );
+
+        let mut stat = vec![0xfe, 0xfd, 0, 0, 0, 0, 1];
+        stat.extend_from_slice(&9513307_i32.to_be_bytes());
+
+        assert_eq!(
+            Request::parse(&stat),
+            Some(Request::Stat {
+                session_id: 1,
+                token: 9513307,
+                full: false
+            })
+        );
+
+        stat.extend_from_slice(&[0; 4]);
+
+        assert_eq!(
+            Request::parse(&stat),
+            Some(Request::Stat {
+                session_id: 1,
+                token: 9513307,
+                full: true
+            })
+        );
+
+        assert_eq!(Request::parse(&[0xfe, 0xfd, 0, 0, 0, 0, 1]), None);
+        assert_eq!(Request::parse(&[0xfe, 0xfc, 9, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn stat_responses() {
+        assert_eq!(handshake_response(1, -5), b"\x09\0\0\0\x01-5\0");
+
+        let players = ["Alice".to_owned(), "Bob".to_owned()];
+        let stat = Stat {
+            motd: "A Minecraft Server",
+            map: "world",
+            online_players: 2,
+            max_players: 20,
+            players: &players,
+            plugins: "",
+            address: "127.0.0.1:25565".parse().unwrap(),
+        };
+
+        assert_eq!(
+            basic_stat_response(1, &stat),
+            b"\0\0\0\0\x01A Minecraft Server\0SMP\0world\x002\x0020\0\xdd\x63127.0.0.1\0"
+        );
+
+        let full = full_stat_response(1, &stat);
+        assert!(full.starts_with(b"\0\0\0\0\x01splitnum\0\x80\0hostname\0A Minecraft Server\0"));
+        assert!(full.ends_with(b"\0\0\x01player_\0\0Alice\0Bob\0\0"));
+    }
+
+    #[test]
+    fn challenge_tokens() {
+        let mut challenges = Challenges::new();
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        let other = "127.0.0.1:1235".parse().unwrap();
+
+        let token = challenges.issue(addr);
+        assert_eq!(challenges.issue(addr), token);
+        assert!(challenges.check(addr, token));
+        assert!(!challenges.check(addr, token.wrapping_add(1)));
+        assert!(!challenges.check(other, token));
+    }
+
+    #[test]
+    fn challenge_rotation() {
+        let mut challenges = Challenges::new();
+        let addr = "[::1]:1234".parse().unwrap();
+        let token = challenges.issue(addr);
+
+        // Tokens made with the previous secret are still accepted.
+        challenges.rotate(challenges.rotated + CHALLENGE_LIFETIME);
+        assert!(challenges.check(addr, token));
+        assert_ne!(challenges.issue(addr), token);
+
+        challenges.rotate(challenges.rotated + CHALLENGE_LIFETIME);
+        assert!(!challenges.check(addr, token));
+    }
+}
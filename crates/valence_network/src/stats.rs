@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+use valence_server::client::Client;
+use valence_server::protocol::encode::EncoderStats;
+
+/// How long the rates in [`ConnectionStats`] are averaged over.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Traffic statistics of a client's connection. This component is added to
+/// every client spawned by [`NetworkPlugin`](crate::NetworkPlugin).
+///
+/// The rates are averaged over the last second and updated once a second,
+/// after packets are flushed. This can be used to find the clients or systems
+/// that use the most bandwidth, or to disconnect clients that send too many
+/// packets.
+#[derive(Component, Debug)]
+pub struct ConnectionStats {
+    /// Bytes written to the socket per second.
+    pub bytes_sent_per_sec: u64,
+    /// Bytes read from the socket per second.
+    pub bytes_received_per_sec: u64,
+    /// Packets written to the client per second. Packets broadcast by the
+    /// client's layers are not counted, but their bytes are.
+    pub packets_sent_per_sec: u64,
+    /// Packets received from the client per second.
+    pub packets_received_per_sec: u64,
+    /// Packets written to the client per second by packet ID, like
+    /// [`Self::packets_sent_per_sec`].
+    pub packets_sent_per_sec_by_id: HashMap<i32, u64>,
+    /// Packets received from the client per second by packet ID.
+    pub packets_received_per_sec_by_id: HashMap<i32, u64>,
+    /// The length of the packets written during the last second after
+    /// compression divided by their length before compression. This is `1.0`
+    /// when compression is disabled or nothing was written.
+    pub compression_ratio: f32,
+    /// Bytes waiting to be written to the socket after packets were last
+    /// flushed. Clients are disconnected once this reaches
+    /// [`NetworkSettings::outgoing_byte_limit`].
+    ///
+    /// [`NetworkSettings::outgoing_byte_limit`]: crate::NetworkSettings::outgoing_byte_limit
+    pub queued_bytes: usize,
+    counters: Arc<ConnectionCounters>,
+    last: Snapshot,
+    window_start: Instant,
+}
+
+/// Counters shared with the tasks of a connection.
+#[derive(Default, Debug)]
+pub(crate) struct ConnectionCounters {
+    pub(crate) bytes_sent: AtomicU64,
+    pub(crate) bytes_received: AtomicU64,
+    pub(crate) packets_received: AtomicU64,
+    /// Received packets by ID since the last update.
+    pub(crate) packets_received_by_id: Mutex<HashMap<i32, u64>>,
+    pub(crate) queued_bytes: AtomicUsize,
+}
+
+impl ConnectionCounters {
+    pub(crate) fn record_received_packet(&self, id: i32) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        *self
+            .packets_received_by_id
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_default() += 1;
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+struct Snapshot {
+    bytes_sent: u64,
+    bytes_received: u64,
+    packets_received: u64,
+    encoder: EncoderStats,
+}
+
+impl ConnectionStats {
+    pub(crate) fn new(counters: Arc<ConnectionCounters>) -> Self {
+        Self {
+            bytes_sent_per_sec: 0,
+            bytes_received_per_sec: 0,
+            packets_sent_per_sec: 0,
+            packets_received_per_sec: 0,
+            packets_sent_per_sec_by_id: HashMap::new(),
+            packets_received_per_sec_by_id: HashMap::new(),
+            compression_ratio: 1.0,
+            queued_bytes: 0,
+            counters,
+            last: Snapshot::default(),
+            window_start: Instant::now(),
+        }
+    }
+
+    fn update(&mut self, now: Instant, encoder: &EncoderStats) {
+        self.queued_bytes = self.counters.queued_bytes.load(Ordering::Relaxed);
+
+        let elapsed = now - self.window_start;
+
+        if elapsed < WINDOW {
+            return;
+        }
+
+        let secs = elapsed.as_secs_f64();
+        let rate = |new: u64, old: u64| ((new - old) as f64 / secs).round() as u64;
+
+        let next = Snapshot {
+            bytes_sent: self.counters.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.counters.bytes_received.load(Ordering::Relaxed),
+            packets_received: self.counters.packets_received.load(Ordering::Relaxed),
+            encoder: encoder.clone(),
+        };
+
+        self.bytes_sent_per_sec = rate(next.bytes_sent, self.last.bytes_sent);
+        self.bytes_received_per_sec = rate(next.bytes_received, self.last.bytes_received);
+        self.packets_sent_per_sec = rate(next.encoder.packets, self.last.encoder.packets);
+        self.packets_received_per_sec = rate(next.packets_received, self.last.packets_received);
+
+        let old_by_id = &self.last.encoder.packets_by_id;
+        self.packets_sent_per_sec_by_id = next
+            .encoder
+            .packets_by_id
+            .iter()
+            .enumerate()
+            .map(|(id, &count)| {
+                (
+                    id as i32,
+                    rate(count, old_by_id.get(id).copied().unwrap_or(0)),
+                )
+            })
+            .filter(|&(_, rate)| rate > 0)
+            .collect();
+
+        let by_id = mem::take(&mut *self.counters.packets_received_by_id.lock().unwrap());
+        self.packets_received_per_sec_by_id = by_id
+            .into_iter()
+            .map(|(id, count)| (id, rate(count, 0)))
+            .collect();
+
+        let bytes = next.encoder.packet_bytes - self.last.encoder.packet_bytes;
+        let uncompressed_bytes =
+            next.encoder.uncompressed_bytes - self.last.encoder.uncompressed_bytes;
+
+        self.compression_ratio = if uncompressed_bytes == 0 {
+            1.0
+        } else {
+            bytes as f32 / uncompressed_bytes as f32
+        };
+
+        self.last = next;
+        self.window_start = now;
+    }
+}
+
+pub(crate) fn update_connection_stats(mut clients: Query<(&Client, &mut ConnectionStats)>) {
+    let now = Instant::now();
+
+    for (client, mut stats) in &mut clients {
+        stats.update(now, client.encoder_stats());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates() {
+        let counters = Arc::new(ConnectionCounters::default());
+        let mut stats = ConnectionStats::new(counters.clone());
+        let start = stats.window_start;
+
+        counters.bytes_sent.store(1000, Ordering::Relaxed);
+        counters.bytes_received.store(300, Ordering::Relaxed);
+        counters.queued_bytes.store(42, Ordering::Relaxed);
+        for id in [1, 1, 2] {
+            counters.record_received_packet(id);
+        }
+
+        let encoder = EncoderStats {
+            packets: 10,
+            packets_by_id: vec![0, 4, 0, 6],
+            bytes: 1200,
+            packet_bytes: 1000,
+            uncompressed_bytes: 4000,
+        };
+
+        // Only the queued bytes are updated before a second has passed.
+        stats.update(start + Duration::from_millis(500), &encoder);
+        assert_eq!(stats.queued_bytes, 42);
+        assert_eq!(stats.bytes_sent_per_sec, 0);
+
+        stats.update(start + Duration::from_secs(2), &encoder);
+        assert_eq!(stats.bytes_sent_per_sec, 500);
+        assert_eq!(stats.bytes_received_per_sec, 150);
+        assert_eq!(stats.packets_sent_per_sec, 5);
+        assert_eq!(
+            stats.packets_sent_per_sec_by_id,
+            HashMap::from([(1, 2), (3, 3)])
+        );
+        assert_eq!(stats.packets_received_per_sec, 2);
+        assert_eq!(
+            stats.packets_received_per_sec_by_id,
+            HashMap::from([(1, 1), (2, 1)])
+        );
+        assert_eq!(stats.compression_ratio, 0.25);
+
+        // Nothing happened during the next second.
+        stats.update(start + Duration::from_secs(3), &encoder);
+        assert_eq!(stats.bytes_sent_per_sec, 0);
+        assert!(stats.packets_sent_per_sec_by_id.is_empty());
+        assert_eq!(stats.packets_received_per_sec, 0);
+        assert!(stats.packets_received_per_sec_by_id.is_empty());
+        assert_eq!(stats.compression_ratio, 1.0);
+    }
+}
@@ -13,6 +13,7 @@ use valence_server::protocol::packets::play::{
     player_info_update_s2c as packet, PlayerInfoRemoveS2c, PlayerInfoUpdateS2c, TabListS2c,
 };
 use valence_server::protocol::WritePacket;
+use valence_server::reconfigure::Rejoined;
 use valence_server::text::IntoText;
 use valence_server::uuid::Uuid;
 use valence_server::{Despawned, GameMode, Server, Text, UniqueId};
@@ -140,9 +141,10 @@ fn update_header_footer(player_list: ResMut<PlayerList>, server: Res<Server>) {
     if player_list.changed_header_or_footer {
         let player_list = player_list.into_inner();
 
-        let mut w = PacketWriter::new(
+        let mut w = PacketWriter::with_options(
             &mut player_list.cached_update_packets,
             server.compression_threshold(),
+            server.compression_options(),
         );
 
         w.write_packet(&TabListS2c {
@@ -171,7 +173,7 @@ fn add_new_clients_to_player_list(
 }
 
 fn init_player_list_for_clients(
-    mut clients: Query<&mut Client, (Added<Client>, Without<Despawned>)>,
+    mut clients: Query<&mut Client, (Or<(Added<Client>, Added<Rejoined>)>, Without<Despawned>)>,
     player_list: Res<PlayerList>,
     entries: Query<
         (
@@ -246,9 +248,10 @@ fn remove_despawned_entries(
         if !removed.is_empty() {
             let player_list = player_list.into_inner();
 
-            let mut w = PacketWriter::new(
+            let mut w = PacketWriter::with_options(
                 &mut player_list.cached_update_packets,
                 server.compression_threshold(),
+                server.compression_options(),
             );
 
             w.write_packet(&PlayerInfoRemoveS2c {
@@ -289,9 +292,10 @@ fn update_entries(
 ) {
     let player_list = player_list.into_inner();
 
-    let mut writer = PacketWriter::new(
+    let mut writer = PacketWriter::with_options(
         &mut player_list.cached_update_packets,
         server.compression_threshold(),
+        server.compression_options(),
     );
 
     for (uuid, username, props, game_mode, ping, display_name, listed) in &entries {
@@ -359,11 +363,11 @@ fn update_entries(
 
 fn write_player_list_changes(
     mut player_list: ResMut<PlayerList>,
-    mut clients: Query<&mut Client, Without<Despawned>>,
+    mut clients: Query<(&mut Client, Has<Rejoined>), Without<Despawned>>,
 ) {
     if !player_list.cached_update_packets.is_empty() {
-        for mut client in &mut clients {
-            if !client.is_added() {
+        for (mut client, rejoined) in &mut clients {
+            if !client.is_added() && !rejoined {
                 client.write_packet_bytes(&player_list.cached_update_packets);
             }
         }
@@ -0,0 +1,234 @@
+//! Pluggable zlib implementations for packet compression.
+//!
+//! [`PacketEncoder`], [`PacketDecoder`] and [`PacketWriter`] compress whole
+//! packets at once through a [`CompressionBackend`]. The backend and the
+//! compression level are chosen with [`CompressionOptions`].
+//!
+//! With the `compression` feature, the [`Flate2`] backend is available and
+//! used by default. The `libdeflate` feature adds the usually much faster
+//! [`Libdeflate`] backend.
+//!
+//! [`PacketEncoder`]: crate::PacketEncoder
+//! [`PacketDecoder`]: crate::PacketDecoder
+//! [`PacketWriter`]: crate::encode::PacketWriter
+
+use std::fmt;
+
+/// A zlib implementation used to compress and decompress packet data.
+pub trait CompressionBackend: Send + Sync + fmt::Debug {
+    /// Compresses all of `data` with the given level and appends the zlib
+    /// stream to `out`. Levels the backend does not support are clamped to its
+    /// highest level.
+    fn compress(&self, data: &[u8], level: u32, out: &mut Vec<u8>) -> anyhow::Result<()>;
+
+    /// Decompresses the zlib stream in `data` into `out`. An error is returned
+    /// unless the decompressed data is exactly as long as `out`.
+    fn decompress(&self, data: &[u8], out: &mut [u8]) -> anyhow::Result<()>;
+}
+
+/// How packets larger than the compression threshold are compressed.
+#[derive(Copy, Clone, Debug)]
+pub struct CompressionOptions {
+    /// The zlib implementation to use.
+    pub backend: &'static dyn CompressionBackend,
+    /// The compression level. Lower levels are faster while higher levels
+    /// produce smaller packets. Level 0 stores the data without compressing
+    /// it.
+    pub level: u32,
+}
+
+impl CompressionOptions {
+    /// The default options, which use level 4 of the default backend.
+    pub const DEFAULT: Self = Self {
+        #[cfg(feature = "compression")]
+        backend: &Flate2,
+        #[cfg(not(feature = "compression"))]
+        backend: &Disabled,
+        level: 4,
+    };
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Used when the `compression` feature is disabled, so no backend exists.
+#[cfg(not(feature = "compression"))]
+#[derive(Debug)]
+struct Disabled;
+
+#[cfg(not(feature = "compression"))]
+impl CompressionBackend for Disabled {
+    fn compress(&self, _data: &[u8], _level: u32, _out: &mut Vec<u8>) -> anyhow::Result<()> {
+        anyhow::bail!("\"compression\" feature must be enabled to compress packets")
+    }
+
+    fn decompress(&self, _data: &[u8], _out: &mut [u8]) -> anyhow::Result<()> {
+        anyhow::bail!("\"compression\" feature must be enabled to decompress packets")
+    }
+}
+
+/// The backend using the [`flate2`] crate. Supports levels 0 through 9.
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, Debug)]
+pub struct Flate2;
+
+#[cfg(feature = "compression")]
+impl CompressionBackend for Flate2 {
+    fn compress(&self, data: &[u8], level: u32, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        use std::io::Read;
+
+        use flate2::bufread::ZlibEncoder;
+        use flate2::Compression;
+
+        let mut z = ZlibEncoder::new(data, Compression::new(level.min(9)));
+        z.read_to_end(out)?;
+
+        Ok(())
+    }
+
+    fn decompress(&self, data: &[u8], out: &mut [u8]) -> anyhow::Result<()> {
+        use std::io::Write;
+
+        use anyhow::ensure;
+        use flate2::write::ZlibDecoder;
+
+        let mut z = ZlibDecoder::new(out);
+
+        z.write_all(data)?;
+
+        ensure!(
+            z.finish()?.is_empty(),
+            "decompressed packet length is shorter than expected"
+        );
+
+        Ok(())
+    }
+}
+
+/// The backend using the [`libdeflater`] crate. Supports levels 0 through 12.
+///
+/// libdeflate only works on whole buffers, which is all packet compression
+/// needs, and is considerably faster than [`Flate2`] in both directions.
+#[cfg(feature = "libdeflate")]
+#[derive(Copy, Clone, Debug)]
+pub struct Libdeflate;
+
+#[cfg(feature = "libdeflate")]
+mod libdeflate {
+    use std::cell::RefCell;
+
+    use anyhow::{ensure, Context};
+    use libdeflater::{CompressionLvl, Compressor, Decompressor};
+
+    use super::{CompressionBackend, Libdeflate};
+
+    const MAX_LEVEL: u32 = 12;
+
+    thread_local! {
+        // libdeflate allocates a fair amount of state per (de)compressor, so
+        // keep one around per thread and level.
+        static COMPRESSORS: RefCell<Vec<Option<Compressor>>> =
+            RefCell::new((0..=MAX_LEVEL).map(|_| None).collect());
+        static DECOMPRESSOR: RefCell<Decompressor> = RefCell::new(Decompressor::new());
+    }
+
+    impl CompressionBackend for Libdeflate {
+        fn compress(&self, data: &[u8], level: u32, out: &mut Vec<u8>) -> anyhow::Result<()> {
+            let level = level.min(MAX_LEVEL);
+
+            COMPRESSORS.with_borrow_mut(|compressors| {
+                let compressor = match &mut compressors[level as usize] {
+                    Some(c) => c,
+                    slot => slot.insert(Compressor::new(
+                        CompressionLvl::new(level as i32)
+                            .ok()
+                            .context("invalid compression level")?,
+                    )),
+                };
+
+                let start = out.len();
+                out.resize(start + compressor.zlib_compress_bound(data.len()), 0);
+
+                let len = compressor
+                    .zlib_compress(data, &mut out[start..])
+                    .context("failed to compress packet")?;
+
+                out.truncate(start + len);
+
+                Ok(())
+            })
+        }
+
+        fn decompress(&self, data: &[u8], out: &mut [u8]) -> anyhow::Result<()> {
+            DECOMPRESSOR.with_borrow_mut(|decompressor| {
+                let len = decompressor
+                    .zlib_decompress(data, out)
+                    .context("failed to decompress packet")?;
+
+                ensure!(
+                    len == out.len(),
+                    "decompressed packet length is shorter than expected"
+                );
+
+                Ok(())
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "compression")]
+mod tests {
+    use super::*;
+
+    fn round_trip(backend: &dyn CompressionBackend) {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+
+        for level in [0, 1, 4, 6, 9, 12] {
+            let mut compressed = vec![1, 2, 3];
+            backend.compress(&data, level, &mut compressed).unwrap();
+            assert_eq!(&compressed[..3], &[1, 2, 3]);
+
+            let mut out = vec![0; data.len()];
+            backend.decompress(&compressed[3..], &mut out).unwrap();
+            assert_eq!(out, data);
+
+            // The output must have the exact length.
+            let mut short = vec![0; data.len() - 1];
+            assert!(backend.decompress(&compressed[3..], &mut short).is_err());
+            let mut long = vec![0; data.len() + 1];
+            assert!(backend.decompress(&compressed[3..], &mut long).is_err());
+        }
+    }
+
+    #[test]
+    fn flate2_round_trip() {
+        round_trip(&Flate2);
+    }
+
+    #[cfg(feature = "libdeflate")]
+    #[test]
+    fn libdeflate_round_trip() {
+        round_trip(&Libdeflate);
+    }
+
+    #[cfg(feature = "libdeflate")]
+    #[test]
+    fn backends_are_compatible() {
+        let data = b"hello hello hello hello hello hello hello".repeat(20);
+
+        let mut compressed = vec![];
+        Flate2.compress(&data, 6, &mut compressed).unwrap();
+        let mut out = vec![0; data.len()];
+        Libdeflate.decompress(&compressed, &mut out).unwrap();
+        assert_eq!(out, data);
+
+        compressed.clear();
+        Libdeflate.compress(&data, 1, &mut compressed).unwrap();
+        Flate2.decompress(&compressed, &mut out).unwrap();
+        assert_eq!(out, data);
+    }
+}
@@ -3,6 +3,8 @@ use aes::cipher::{generic_array::GenericArray, BlockDecryptMut, BlockSizeUser, K
 use anyhow::{bail, ensure, Context};
 use bytes::{Buf, BytesMut};
 
+#[cfg(feature = "compression")]
+use crate::compression::CompressionOptions;
 use crate::var_int::{VarInt, VarIntDecodeError};
 #[cfg(feature = "compression")]
 use crate::CompressionThreshold;
@@ -20,6 +22,8 @@ pub struct PacketDecoder {
     decompress_buf: BytesMut,
     #[cfg(feature = "compression")]
     threshold: CompressionThreshold,
+    #[cfg(feature = "compression")]
+    compression: CompressionOptions,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
 }
@@ -54,10 +58,7 @@ impl PacketDecoder {
 
         #[cfg(feature = "compression")]
         if self.threshold.0 >= 0 {
-            use std::io::Write;
-
             use bytes::BufMut;
-            use flate2::write::ZlibDecoder;
 
             r = &r[..packet_len as usize];
 
@@ -81,15 +82,14 @@ impl PacketDecoder {
 
                 self.decompress_buf.put_bytes(0, data_len as usize);
 
-                // TODO: use libdeflater or zune-inflate?
-                let mut z = ZlibDecoder::new(&mut self.decompress_buf[..]);
-
-                z.write_all(r)?;
-
-                ensure!(
-                    z.finish()?.is_empty(),
-                    "decompressed packet length is shorter than expected"
-                );
+                if let Err(e) = self
+                    .compression
+                    .backend
+                    .decompress(r, &mut self.decompress_buf[..])
+                {
+                    self.decompress_buf.clear();
+                    return Err(e);
+                }
 
                 let total_packet_len = VarInt(packet_len).written_size() + packet_len as usize;
 
@@ -147,6 +147,13 @@ impl PacketDecoder {
         self.threshold = threshold;
     }
 
+    /// Sets the backend used to decompress packets. The compression level is
+    /// ignored.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_options(&mut self, options: CompressionOptions) {
+        self.compression = options;
+    }
+
     #[cfg(feature = "encryption")]
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
         assert!(self.cipher.is_none(), "encryption is already enabled");
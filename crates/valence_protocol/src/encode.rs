@@ -8,6 +8,7 @@ use anyhow::ensure;
 use bytes::{BufMut, BytesMut};
 use tracing::warn;
 
+use crate::compression::CompressionOptions;
 use crate::var_int::VarInt;
 use crate::{CompressionThreshold, Encode, Packet, MAX_PACKET_SIZE};
 
@@ -23,8 +24,48 @@ pub struct PacketEncoder {
     compress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     threshold: CompressionThreshold,
+    #[cfg(feature = "compression")]
+    compression: CompressionOptions,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
+    stats: EncoderStats,
+}
+
+/// Counts of the data written to a [`PacketEncoder`] since it was created.
+///
+/// Packets are counted when they are encoded. Already encoded bytes, like the
+/// packets a layer broadcasts to all of its viewers, only count towards
+/// [`EncoderStats::bytes`].
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct EncoderStats {
+    /// The number of packets encoded.
+    pub packets: u64,
+    /// The number of packets encoded, indexed by packet ID.
+    pub packets_by_id: Vec<u64>,
+    /// The length of all data written after compression, but before
+    /// encryption. This includes already encoded bytes.
+    pub bytes: u64,
+    /// The length of the encoded packets after compression.
+    pub packet_bytes: u64,
+    /// The length the encoded packets would have without compression.
+    pub uncompressed_bytes: u64,
+}
+
+impl EncoderStats {
+    fn record(&mut self, id: i32, packet_len: usize, data_len: usize) {
+        self.packets += 1;
+        self.bytes += packet_len as u64;
+        self.packet_bytes += packet_len as u64;
+        self.uncompressed_bytes += (VarInt(data_len as i32).written_size() + data_len) as u64;
+
+        let id = id as usize;
+
+        if id >= self.packets_by_id.len() {
+            self.packets_by_id.resize(id + 1, 0);
+        }
+
+        self.packets_by_id[id] += 1;
+    }
 }
 
 impl PacketEncoder {
@@ -34,9 +75,16 @@ impl PacketEncoder {
 
     #[inline]
     pub fn append_bytes(&mut self, bytes: &[u8]) {
+        self.stats.bytes += bytes.len() as u64;
         self.buf.extend_from_slice(bytes)
     }
 
+    /// Returns the counts of the data written so far, including the packets
+    /// that were already taken.
+    pub fn stats(&self) -> &EncoderStats {
+        &self.stats
+    }
+
     pub fn prepend_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
     where
         P: Packet + Encode,
@@ -58,8 +106,22 @@ impl PacketEncoder {
         Ok(())
     }
 
-    #[allow(clippy::needless_borrows_for_generic_args)]
     pub fn append_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
+    where
+        P: Packet + Encode,
+    {
+        let start_len = self.buf.len();
+        let data_len = self.encode_packet(pkt)?;
+
+        self.stats
+            .record(P::ID, self.buf.len() - start_len, data_len);
+
+        Ok(())
+    }
+
+    /// Appends the packet and returns its length before compression.
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    fn encode_packet<P>(&mut self, pkt: &P) -> anyhow::Result<usize>
     where
         P: Packet + Encode,
     {
@@ -71,27 +133,24 @@ impl PacketEncoder {
 
         #[cfg(feature = "compression")]
         if self.threshold.0 >= 0 {
-            use std::io::Read;
-
-            use flate2::bufread::ZlibEncoder;
-            use flate2::Compression;
-
             if data_len > self.threshold.0 as usize {
-                let mut z = ZlibEncoder::new(&self.buf[start_len..], Compression::new(4));
-
                 self.compress_buf.clear();
 
+                self.compression.backend.compress(
+                    &self.buf[start_len..],
+                    self.compression.level,
+                    &mut self.compress_buf,
+                )?;
+
                 let data_len_size = VarInt(data_len as i32).written_size();
 
-                let packet_len = data_len_size + z.read_to_end(&mut self.compress_buf)?;
+                let packet_len = data_len_size + self.compress_buf.len();
 
                 ensure!(
                     packet_len <= MAX_PACKET_SIZE as usize,
                     "packet exceeds maximum length"
                 );
 
-                drop(z);
-
                 self.buf.truncate(start_len);
 
                 let mut writer = (&mut self.buf).writer();
@@ -123,7 +182,7 @@ impl PacketEncoder {
                 VarInt(0).encode(front)?;
             }
 
-            return Ok(());
+            return Ok(data_len);
         }
 
         let packet_len = data_len;
@@ -142,7 +201,7 @@ impl PacketEncoder {
         let front = &mut self.buf[start_len..];
         VarInt(packet_len as i32).encode(front)?;
 
-        Ok(())
+        Ok(data_len)
     }
 
     /// Takes all the packets written so far and encrypts them if encryption is
@@ -168,6 +227,13 @@ impl PacketEncoder {
         self.threshold = threshold;
     }
 
+    /// Sets the backend and level used to compress packets over the
+    /// compression threshold.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_options(&mut self, options: CompressionOptions) {
+        self.compression = options;
+    }
+
     /// Initializes the cipher with the given key. All future packets **and any
     /// that have not been [taken] yet** are encrypted.
     ///
@@ -241,11 +307,33 @@ impl<T: WritePacket> WritePacket for bevy_ecs::world::Mut<'_, T> {
 pub struct PacketWriter<'a> {
     pub buf: &'a mut Vec<u8>,
     pub threshold: CompressionThreshold,
+    #[cfg(feature = "compression")]
+    compression: CompressionOptions,
 }
 
 impl<'a> PacketWriter<'a> {
+    /// Returns a new writer which compresses with the default
+    /// [`CompressionOptions`].
     pub fn new(buf: &'a mut Vec<u8>, threshold: CompressionThreshold) -> Self {
-        Self { buf, threshold }
+        Self::with_options(buf, threshold, CompressionOptions::DEFAULT)
+    }
+
+    /// Returns a new writer which compresses packets over the compression
+    /// threshold with the given [`CompressionOptions`].
+    pub fn with_options(
+        buf: &'a mut Vec<u8>,
+        threshold: CompressionThreshold,
+        compression: CompressionOptions,
+    ) -> Self {
+        #[cfg(not(feature = "compression"))]
+        let _ = compression;
+
+        Self {
+            buf,
+            threshold,
+            #[cfg(feature = "compression")]
+            compression,
+        }
     }
 }
 
@@ -262,7 +350,12 @@ impl WritePacket for PacketWriter<'_> {
         if self.threshold.0 >= 0 {
             #[cfg(feature = "compression")]
             {
-                res = encode_packet_compressed(self.buf, pkt, self.threshold.0 as u32);
+                res = encode_packet_compressed(
+                    self.buf,
+                    pkt,
+                    self.threshold.0 as u32,
+                    self.compression,
+                );
             }
 
             #[cfg(not(feature = "compression"))]
@@ -331,15 +424,15 @@ where
 
 #[cfg(feature = "compression")]
 #[allow(clippy::needless_borrows_for_generic_args)]
-fn encode_packet_compressed<P>(buf: &mut Vec<u8>, pkt: &P, threshold: u32) -> anyhow::Result<()>
+fn encode_packet_compressed<P>(
+    buf: &mut Vec<u8>,
+    pkt: &P,
+    threshold: u32,
+    compression: CompressionOptions,
+) -> anyhow::Result<()>
 where
     P: Packet + Encode,
 {
-    use std::io::Read;
-
-    use flate2::bufread::ZlibEncoder;
-    use flate2::Compression;
-
     let start_len = buf.len();
 
     pkt.encode_with_id(&mut *buf)?;
@@ -347,19 +440,19 @@ where
     let data_len = buf.len() - start_len;
 
     if data_len > threshold as usize {
-        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(4));
-
         let mut scratch = vec![];
 
-        let packet_len = VarInt(data_len as i32).written_size() + z.read_to_end(&mut scratch)?;
+        compression
+            .backend
+            .compress(&buf[start_len..], compression.level, &mut scratch)?;
+
+        let packet_len = VarInt(data_len as i32).written_size() + scratch.len();
 
         ensure!(
             packet_len <= MAX_PACKET_SIZE as usize,
             "packet exceeds maximum length"
         );
 
-        drop(z);
-
         buf.truncate(start_len);
 
         VarInt(packet_len as i32).encode(&mut *buf)?;
@@ -687,7 +687,7 @@ impl<'a> Encode for ItemStack<'a> {
         } else {
             self.count.encode(&mut w)?;
             self.item.encode(&mut w)?;
-            self.components.encode(&mut w)
+            self.components.encode(&mut w as &mut dyn Write)
         }
     }
 }
@@ -20,6 +20,7 @@ mod bounded;
 mod byte_angle;
 pub mod chunk_pos;
 pub mod chunk_section_pos;
+pub mod compression;
 pub mod decode;
 mod difficulty;
 mod direction;
@@ -459,4 +460,42 @@ mod tests {
         check_test_packet(&mut dec, "fourth");
         check_test_packet(&mut dec, "third");
     }
+
+    #[test]
+    fn encoder_stats() {
+        let mut enc = PacketEncoder::new();
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        let uncompressed = enc.stats().clone();
+        assert_eq!(uncompressed.packets, 1);
+        assert_eq!(uncompressed.packets_by_id[TestPacket::ID as usize], 1);
+        assert_eq!(uncompressed.bytes, enc.take().len() as u64);
+        assert_eq!(uncompressed.packet_bytes, uncompressed.bytes);
+        assert_eq!(uncompressed.uncompressed_bytes, uncompressed.bytes);
+
+        #[cfg(feature = "compression")]
+        {
+            enc.set_compression(0.into());
+            enc.append_packet(&TestPacket::new(&"a".repeat(1000)))
+                .unwrap();
+            let bytes = enc.take();
+
+            let stats = enc.stats();
+            assert_eq!(stats.packets, 2);
+            assert_eq!(stats.packets_by_id[TestPacket::ID as usize], 2);
+            assert_eq!(stats.bytes, uncompressed.bytes + bytes.len() as u64);
+            assert!(stats.packet_bytes < stats.uncompressed_bytes);
+
+            // Packets that were encoded elsewhere only count as bytes.
+            let mut other = PacketEncoder::new();
+            other.set_compression(0.into());
+            other.append_bytes(&bytes);
+            other.append_bytes(&bytes);
+
+            let other_stats = other.stats();
+            assert_eq!(other_stats.packets, 0);
+            assert_eq!(other_stats.bytes, 2 * bytes.len() as u64);
+            assert_eq!(other_stats.packet_bytes, 0);
+        }
+    }
 }
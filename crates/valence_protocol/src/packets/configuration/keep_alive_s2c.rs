@@ -6,4 +6,6 @@ use crate::{Decode, Encode, Packet, PacketState};
 /// should respond with a [`KeepAliveC2s`](crate::packets::configuration::KeepAliveC2s) packet with
 /// the same id. If the client does not receive a `KeepAliveS2c` packet within 20 seconds, it should
 /// disconnect.
-pub struct KeepAliveS2c(pub i32);
\ No newline at end of file
+pub struct KeepAliveS2c {
+    pub id: u64,
+}
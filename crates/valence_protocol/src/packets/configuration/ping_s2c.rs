@@ -4,4 +4,4 @@ use crate::{Decode, Encode, Packet, PacketState};
 #[packet(state = PacketState::Configuration)]
 /// The client should respond with a [`PongC2s`](crate::packets::configuration::PongC2s) packet with
 /// the same id.
-pub struct PingS2c(pub i32);
\ No newline at end of file
+pub struct PingS2c(pub i32);
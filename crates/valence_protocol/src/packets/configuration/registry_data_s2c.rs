@@ -1,6 +1,5 @@
 use crate::{Decode, Encode, Packet, PacketState};
 use std::borrow::Cow;
-use std::collections::HashMap;
 use valence_ident::Ident;
 use valence_nbt::Compound;
 
@@ -11,6 +10,7 @@ use valence_nbt::Compound;
 pub struct RegistryDataS2c<'a> {
     // The id of the registry
     pub id: Ident<Cow<'a, str>>,
-    // The id of the entries and the entry data itself
-    pub entries: HashMap<Ident<Cow<'a, str>>, Option<Compound>>,
+    // The id of the entries and the entry data itself. The order of the entries determines their
+    // numeric protocol IDs. Entries without data are taken from one of the client's known packs.
+    pub entries: Vec<(Ident<Cow<'a, str>>, Option<Compound>)>,
 }
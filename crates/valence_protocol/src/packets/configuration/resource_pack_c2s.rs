@@ -1,10 +1,11 @@
 use uuid::Uuid;
 
 use crate::packets::play::resource_pack_c2s::ResourcePackStatus;
-use crate::{Decode, Encode, Packet};
+use crate::{Decode, Encode, Packet, PacketState};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode, Packet)]
+#[packet(state = PacketState::Configuration)]
 pub struct ResourcePackC2s {
-    uuid: Uuid,
-    result: ResourcePackStatus,
+    pub uuid: Uuid,
+    pub result: ResourcePackStatus,
 }
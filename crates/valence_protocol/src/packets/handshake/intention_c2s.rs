@@ -3,7 +3,8 @@ use crate::{Bounded, Decode, Encode, Packet, PacketState, VarInt};
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(state = PacketState::Handshake)]
 /// Sent by the client to the server to indicate its intention to switch to the
-/// given state. Either the `Status` or `Login` state will be selected.
+/// given state. Either the `Status` or `Login` state will be selected, where
+/// `Transfer` also selects the `Login` state.
 pub struct IntentionC2s<'a> {
     pub protocol_version: VarInt,
     pub server_address: Bounded<&'a str, 255>,
@@ -17,4 +18,7 @@ pub enum HandshakeNextState {
     Status,
     #[packet(tag = 2)]
     Login,
+    /// Logging in after being sent to this server with a transfer packet.
+    #[packet(tag = 3)]
+    Transfer,
 }
@@ -6,5 +6,6 @@ use crate::{Bounded, Decode, Encode, Packet, PacketState, RawBytes, VarInt};
 /// [`CustomQueryS2c`](crate::packets::login::CustomQueryS2c) packet.
 pub struct CustomQueryAnswerC2s<'a> {
     pub message_id: VarInt,
-    pub data: Bounded<RawBytes<'a>, 1048576>,
+    /// The answer, or `None` if the client doesn't understand the channel.
+    pub data: Option<Bounded<RawBytes<'a>, 1048576>>,
 }
@@ -3,4 +3,4 @@ use crate::{Decode, Encode, Packet, PacketState};
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(state = PacketState::Login)]
 /// Sent by the client to the server to acknowledge the login process.
-pub struct LoginAcknowledgedC2s;
\ No newline at end of file
+pub struct LoginAcknowledgedC2s;
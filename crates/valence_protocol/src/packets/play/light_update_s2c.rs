@@ -2,7 +2,6 @@ use std::borrow::Cow;
 
 use crate::array::FixedArray;
 use crate::{Decode, Encode, Packet, VarInt};
-// TODO: fix this
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 pub struct LightUpdateS2c<'a> {
     pub chunk_x: VarInt,
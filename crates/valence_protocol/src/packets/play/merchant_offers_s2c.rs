@@ -12,9 +12,9 @@ pub struct MerchantOffersS2c<'a> {
 
 #[derive(Clone, PartialEq, Debug, Encode, Decode)]
 pub struct TradeOffer<'a> {
-    pub input_one:   ItemStack<'a>,
+    pub input_one: ItemStack<'a>,
     pub output_item: ItemStack<'a>,
-    pub input_two:   ItemStack<'a>,
+    pub input_two: ItemStack<'a>,
     pub trade_disabled: bool,
     pub number_of_trade_uses: i32,
     pub max_trade_uses: i32,
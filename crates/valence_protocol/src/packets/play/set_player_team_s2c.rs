@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::io::Write;
 
 use anyhow::bail;
+use bevy_ecs::prelude::Component;
 use bitfield_struct::bitfield;
 use valence_text::Text;
 
@@ -181,7 +182,7 @@ impl<'a> Decode<'a> for Mode<'a> {
 }
 
 #[bitfield(u8)]
-#[derive(PartialEq, Eq, Encode, Decode)]
+#[derive(PartialEq, Eq, Encode, Decode, Component)]
 pub struct TeamFlags {
     pub friendly_fire: bool,
     pub see_invisible_teammates: bool,
@@ -189,23 +190,25 @@ pub struct TeamFlags {
     _pad: u8,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Component, Default)]
 pub enum NameTagVisibility {
+    #[default]
     Always,
     Never,
     HideForOtherTeams,
     HideForOwnTeam,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Component, Default)]
 pub enum CollisionRule {
+    #[default]
     Always,
     Never,
     PushOtherTeams,
     PushOwnTeam,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode, Component, Default)]
 pub enum TeamColor {
     Black,
     DarkBlue,
@@ -228,5 +231,6 @@ pub enum TeamColor {
     Strikethrough,
     Underlined,
     Italic,
+    #[default]
     Reset,
 }
@@ -0,0 +1,101 @@
+use std::collections::BTreeSet;
+
+use bevy_ecs::prelude::*;
+use valence_command::scopes::CommandScopes;
+use valence_command::CommandExecutionEvent;
+use valence_server::protocol::packets::play::SystemChatS2c;
+use valence_server::protocol::{Encode, Packet, PacketDecoder, PacketEncoder, WritePacket};
+
+use crate::{RconCommand, RconState};
+
+/// The console entity that executes an RCON command.
+///
+/// Each command gets its own console, which has every command scope but is not
+/// a [`Client`]. Command handlers respond to it by writing to this component
+/// instead of the executor's [`Client`], for example with
+/// [`SendMessage`](valence_server::message::SendMessage). The system messages
+/// written to it until the end of the tick are the response.
+///
+/// [`Client`]: valence_server::client::Client
+#[derive(Component)]
+pub struct RconConsole {
+    enc: PacketEncoder,
+    reply: flume::Sender<String>,
+}
+
+impl RconConsole {
+    /// The lines of the system messages written to this console so far.
+    fn response(&mut self) -> String {
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(self.enc.take());
+
+        let mut lines = vec![];
+
+        while let Ok(Some(frame)) = dec.try_next_packet() {
+            if frame.id != SystemChatS2c::ID {
+                continue;
+            }
+
+            if let Ok(pkt) = frame.decode::<SystemChatS2c>() {
+                if !pkt.overlay {
+                    lines.push(pkt.chat.to_legacy_lossy());
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl WritePacket for RconConsole {
+    fn write_packet_fallible<P>(&mut self, packet: &P) -> anyhow::Result<()>
+    where
+        P: Packet + Encode,
+    {
+        // Only system messages are part of the response.
+        if P::ID == SystemChatS2c::ID {
+            self.enc.append_packet(packet)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_packet_bytes(&mut self, bytes: &[u8]) {
+        self.enc.append_bytes(bytes)
+    }
+}
+
+pub(crate) fn spawn_consoles(
+    state: Res<RconState>,
+    mut events: EventWriter<CommandExecutionEvent>,
+    mut commands: Commands,
+) {
+    for RconCommand { command, reply } in state.commands_recv.try_iter() {
+        let executor = commands
+            .spawn((
+                RconConsole {
+                    enc: PacketEncoder::new(),
+                    reply,
+                },
+                // The root scope grants every other scope.
+                CommandScopes(BTreeSet::from(["root".to_owned()])),
+            ))
+            .id();
+
+        events.send(CommandExecutionEvent { command, executor });
+    }
+}
+
+/// Sends the responses and despawns the consoles.
+pub(crate) fn despawn_consoles(
+    mut consoles: Query<(Entity, &mut RconConsole)>,
+    mut commands: Commands,
+) {
+    for (entity, mut console) in &mut consoles {
+        let response = console.response();
+        // The connection might be gone already.
+        let _ = console.reply.send(response);
+
+        commands.entity(entity).despawn();
+    }
+}
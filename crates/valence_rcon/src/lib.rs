@@ -0,0 +1,375 @@
+#![doc = include_str!("../README.md")]
+
+mod console;
+mod packet;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+pub use console::RconConsole;
+use packet::{
+    read_packet, write_packet, write_response, AUTH_FAILURE_ID, TYPE_AUTH, TYPE_AUTH_RESPONSE,
+    TYPE_EXEC_COMMAND,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{Handle, Runtime};
+use tracing::{error, info, warn};
+
+/// The number of failed authentication attempts after which an IP address is
+/// refused until [`FAILED_AUTH_TIMEOUT`] has passed since its last attempt.
+const MAX_FAILED_AUTHS: u32 = 5;
+
+/// How long failed authentication attempts are remembered.
+const FAILED_AUTH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait before answering a failed authentication attempt.
+const FAILED_AUTH_DELAY: Duration = Duration::from_secs(1);
+
+/// Accepts RCON connections and runs their commands through
+/// [`valence_command`]. Nothing happens unless [`RconSettings::password`] is
+/// set before the plugin is added.
+pub struct RconPlugin;
+
+impl Plugin for RconPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = app
+            .world_mut()
+            .get_resource_or_insert_with(RconSettings::default)
+            .clone();
+
+        if settings.password.is_empty() {
+            warn!("RCON is disabled because no password is set");
+            return;
+        }
+
+        let runtime = match &settings.tokio_handle {
+            Some(_) => None,
+            None => match Runtime::new() {
+                Ok(rt) => Some(rt),
+                Err(e) => {
+                    error!("failed to create tokio runtime for RCON: {e}");
+                    return;
+                }
+            },
+        };
+
+        let tokio_handle = match &runtime {
+            Some(rt) => rt.handle().clone(),
+            None => settings.tokio_handle.clone().unwrap(),
+        };
+
+        let (commands_send, commands_recv) = flume::bounded(64);
+
+        let start_listener = move |state: Res<RconState>| {
+            state.tokio_handle.spawn(do_accept_loop(
+                settings.address,
+                settings.password.as_str().into(),
+                commands_send.clone(),
+            ));
+        };
+
+        app.insert_resource(RconState {
+            commands_recv,
+            tokio_handle,
+            _tokio_runtime: runtime,
+        })
+        .add_systems(PostStartup, start_listener)
+        .add_systems(First, console::spawn_consoles)
+        .add_systems(Last, console::despawn_consoles);
+    }
+}
+
+/// Settings for [`RconPlugin`]. Note that mutations to these fields have no
+/// effect after the plugin is built.
+#[derive(Resource, Clone)]
+pub struct RconSettings {
+    /// The socket address RCON clients connect to.
+    ///
+    /// # Default Value
+    ///
+    /// `0.0.0.0:25575`
+    pub address: SocketAddr,
+    /// The password clients need to authenticate with. RCON is disabled while
+    /// this is empty.
+    ///
+    /// The password is sent in plain text, so RCON should only be reachable
+    /// from trusted networks. Addresses that send the wrong password too often
+    /// are refused for a while.
+    ///
+    /// # Default Value
+    ///
+    /// An empty string.
+    pub password: String,
+    /// The [`Handle`] to the tokio runtime the listener will use. If `None` is
+    /// provided, the plugin will create its own tokio runtime.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub tokio_handle: Option<Handle>,
+}
+
+impl Default for RconSettings {
+    fn default() -> Self {
+        Self {
+            address: (Ipv4Addr::UNSPECIFIED, 25575).into(),
+            password: String::new(),
+            tokio_handle: None,
+        }
+    }
+}
+
+impl fmt::Debug for RconSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't leak the password in logs.
+        f.debug_struct("RconSettings")
+            .field("address", &self.address)
+            .field("tokio_handle", &self.tokio_handle)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Resource)]
+struct RconState {
+    commands_recv: flume::Receiver<RconCommand>,
+    tokio_handle: Handle,
+    // Holding a runtime handle is not enough to keep tokio working. We need
+    // to store the runtime here so we don't drop it.
+    _tokio_runtime: Option<Runtime>,
+}
+
+/// The failed authentication attempts of each IP address, used to slow down
+/// password guessing.
+#[derive(Default, Debug)]
+struct FailedAuths {
+    /// The number of failed attempts and the time of the last one.
+    attempts: HashMap<IpAddr, (u32, Instant)>,
+}
+
+impl FailedAuths {
+    /// Returns whether `ip` failed to authenticate too often recently.
+    fn is_refused(&self, ip: IpAddr, now: Instant) -> bool {
+        self.attempts.get(&ip).is_some_and(|&(count, last)| {
+            count >= MAX_FAILED_AUTHS && now - last < FAILED_AUTH_TIMEOUT
+        })
+    }
+
+    fn record_failure(&mut self, ip: IpAddr, now: Instant) {
+        self.attempts
+            .retain(|_, (_, last)| now - *last < FAILED_AUTH_TIMEOUT);
+
+        let (count, last) = self.attempts.entry(ip).or_insert((0, now));
+        *count = count.saturating_add(1);
+        *last = now;
+    }
+
+    fn record_success(&mut self, ip: IpAddr) {
+        self.attempts.remove(&ip);
+    }
+}
+
+/// Compares the passwords in constant time, so that the time it takes doesn't
+/// reveal how much of the password was guessed right.
+fn passwords_match(password: &str, attempt: &str) -> bool {
+    // Hashing first hides the length of the password as well.
+    let password = Sha256::digest(password);
+    let attempt = Sha256::digest(attempt);
+
+    password
+        .iter()
+        .zip(attempt.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// A command received from an authenticated connection.
+struct RconCommand {
+    command: String,
+    /// Receives the response once the command was executed.
+    reply: flume::Sender<String>,
+}
+
+#[allow(clippy::infinite_loop)]
+async fn do_accept_loop(
+    address: SocketAddr,
+    password: Arc<str>,
+    commands: flume::Sender<RconCommand>,
+) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to bind RCON listener to {address}: {e}");
+            return;
+        }
+    };
+
+    info!("RCON listening on {address}");
+
+    let failed_auths = Arc::new(Mutex::new(FailedAuths::default()));
+
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(res) => res,
+            Err(e) => {
+                warn!("failed to accept RCON connection: {e}");
+                continue;
+            }
+        };
+
+        if failed_auths
+            .lock()
+            .unwrap()
+            .is_refused(remote_addr.ip(), Instant::now())
+        {
+            continue;
+        }
+
+        let password = password.clone();
+        let commands = commands.clone();
+        let failed_auths = failed_auths.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) =
+                handle_connection(stream, remote_addr, &password, &commands, &failed_auths).await
+            {
+                warn!("RCON connection from {remote_addr} failed: {e:#}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    remote_addr: SocketAddr,
+    password: &str,
+    commands: &flume::Sender<RconCommand>,
+    failed_auths: &Mutex<FailedAuths>,
+) -> anyhow::Result<()> {
+    let mut authenticated = false;
+    let mut buf = vec![];
+
+    while let Some(packet) = read_packet(&mut stream).await? {
+        buf.clear();
+
+        match packet.kind {
+            TYPE_AUTH => {
+                authenticated = passwords_match(password, &packet.body);
+
+                if authenticated {
+                    info!("RCON connection from {remote_addr} authenticated");
+                    failed_auths
+                        .lock()
+                        .unwrap()
+                        .record_success(remote_addr.ip());
+                    write_packet(&mut buf, packet.id, TYPE_AUTH_RESPONSE, "");
+                } else {
+                    warn!("RCON connection from {remote_addr} sent the wrong password");
+
+                    let refused = {
+                        let mut failed_auths = failed_auths.lock().unwrap();
+                        let now = Instant::now();
+                        failed_auths.record_failure(remote_addr.ip(), now);
+                        failed_auths.is_refused(remote_addr.ip(), now)
+                    };
+
+                    tokio::time::sleep(FAILED_AUTH_DELAY).await;
+
+                    write_packet(&mut buf, AUTH_FAILURE_ID, TYPE_AUTH_RESPONSE, "");
+
+                    if refused {
+                        stream.write_all(&buf).await?;
+                        return Ok(());
+                    }
+                }
+            }
+            TYPE_EXEC_COMMAND if !authenticated => {
+                write_packet(&mut buf, AUTH_FAILURE_ID, TYPE_AUTH_RESPONSE, "");
+                stream.write_all(&buf).await?;
+                return Ok(());
+            }
+            TYPE_EXEC_COMMAND => {
+                let (reply_send, reply_recv) = flume::bounded(1);
+
+                info!("RCON connection from {remote_addr} ran /{}", packet.body);
+
+                let command = RconCommand {
+                    // Commands typed in the console don't need the slash.
+                    command: packet
+                        .body
+                        .strip_prefix('/')
+                        .unwrap_or(&packet.body)
+                        .to_owned(),
+                    reply: reply_send,
+                };
+
+                if commands.send_async(command).await.is_err() {
+                    // The server is shutting down.
+                    return Ok(());
+                }
+
+                let Ok(response) = reply_recv.recv_async().await else {
+                    return Ok(());
+                };
+
+                write_response(&mut buf, packet.id, &response);
+            }
+            kind => {
+                write_response(
+                    &mut buf,
+                    packet.id,
+                    &format!("Unknown request {:x}", kind as u32),
+                );
+            }
+        }
+
+        stream.write_all(&buf).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_passwords() {
+        assert!(passwords_match("hunter2", "hunter2"));
+        assert!(!passwords_match("hunter2", "hunter3"));
+        assert!(!passwords_match("hunter2", "hunter"));
+        assert!(!passwords_match("hunter2", ""));
+    }
+
+    #[test]
+    fn refuse_after_failed_auths() {
+        let mut failed_auths = FailedAuths::default();
+        let ip = IpAddr::from([192, 168, 0, 1]);
+        let other = IpAddr::from([192, 168, 0, 2]);
+        let start = Instant::now();
+
+        for _ in 0..MAX_FAILED_AUTHS {
+            assert!(!failed_auths.is_refused(ip, start));
+            failed_auths.record_failure(ip, start);
+        }
+
+        assert!(failed_auths.is_refused(ip, start));
+        assert!(!failed_auths.is_refused(other, start));
+
+        let later = start + FAILED_AUTH_TIMEOUT;
+        assert!(!failed_auths.is_refused(ip, later));
+
+        // Old failures are forgotten.
+        failed_auths.record_failure(other, later);
+        assert_eq!(failed_auths.attempts.len(), 1);
+
+        failed_auths.record_success(other);
+        assert!(failed_auths.attempts.is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! Reading and writing RCON packets. Every packet is a little-endian length,
+//! request ID and type, followed by a null-terminated body and another null
+//! byte.
+
+use std::io::ErrorKind;
+
+use anyhow::{ensure, Context};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub(crate) const TYPE_RESPONSE: i32 = 0;
+pub(crate) const TYPE_EXEC_COMMAND: i32 = 2;
+pub(crate) const TYPE_AUTH_RESPONSE: i32 = 2;
+pub(crate) const TYPE_AUTH: i32 = 3;
+
+/// The request ID of an authentication response when the password is wrong.
+pub(crate) const AUTH_FAILURE_ID: i32 = -1;
+
+/// The length of a packet with an empty body, excluding the length itself.
+const MIN_LEN: usize = 10;
+
+/// The maximum length of the body of a single response packet. Longer
+/// responses are split into multiple packets with the same request ID.
+const MAX_RESPONSE_BODY_LEN: usize = 4096;
+
+/// The maximum length of an incoming packet. Vanilla clients send at most 1446
+/// bytes, so this leaves plenty of room.
+const MAX_LEN: usize = MAX_RESPONSE_BODY_LEN + MIN_LEN;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct RconPacket {
+    pub(crate) id: i32,
+    pub(crate) kind: i32,
+    pub(crate) body: String,
+}
+
+/// Reads the next packet from `stream`, or returns `None` if the connection
+/// was closed between packets.
+pub(crate) async fn read_packet<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> anyhow::Result<Option<RconPacket>> {
+    let len = match stream.read_i32_le().await {
+        Ok(len) => len,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let len = usize::try_from(len).context("negative RCON packet length")?;
+    ensure!(
+        (MIN_LEN..=MAX_LEN).contains(&len),
+        "invalid RCON packet length of {len}"
+    );
+
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+
+    let id = i32::from_le_bytes(buf[..4].try_into().unwrap());
+    let kind = i32::from_le_bytes(buf[4..8].try_into().unwrap());
+
+    // Some clients don't send the second null byte, so the body ends at the
+    // first one.
+    let body = &buf[8..];
+    let body = &body[..body.iter().position(|&b| b == 0).unwrap_or(body.len())];
+
+    Ok(Some(RconPacket {
+        id,
+        kind,
+        body: String::from_utf8_lossy(body).into_owned(),
+    }))
+}
+
+pub(crate) fn write_packet(buf: &mut Vec<u8>, id: i32, kind: i32, body: &str) {
+    let len = (body.len() + MIN_LEN) as i32;
+
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&kind.to_le_bytes());
+    buf.extend_from_slice(body.as_bytes());
+    buf.extend_from_slice(&[0, 0]);
+}
+
+/// Writes a command response, split into as many packets as needed.
+pub(crate) fn write_response(buf: &mut Vec<u8>, id: i32, mut body: &str) {
+    loop {
+        let mut split = body.len().min(MAX_RESPONSE_BODY_LEN);
+        while !body.is_char_boundary(split) {
+            split -= 1;
+        }
+
+        let (chunk, rest) = body.split_at(split);
+        write_packet(buf, id, TYPE_RESPONSE, chunk);
+
+        if rest.is_empty() {
+            break;
+        }
+
+        body = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_and_write_packets() {
+        let mut buf = vec![];
+        write_packet(&mut buf, 7, TYPE_AUTH, "hunter2");
+        assert_eq!(buf, b"\x11\0\0\0\x07\0\0\0\x03\0\0\0hunter2\0\0");
+
+        // Only one null byte at the end.
+        buf.extend_from_slice(b"\x0d\0\0\0\x08\0\0\0\x02\0\0\0list\0");
+
+        let mut stream = buf.as_slice();
+
+        assert_eq!(
+            read_packet(&mut stream).await.unwrap(),
+            Some(RconPacket {
+                id: 7,
+                kind: TYPE_AUTH,
+                body: "hunter2".into()
+            })
+        );
+        assert_eq!(
+            read_packet(&mut stream).await.unwrap(),
+            Some(RconPacket {
+                id: 8,
+                kind: TYPE_EXEC_COMMAND,
+                body: "list".into()
+            })
+        );
+        assert_eq!(read_packet(&mut stream).await.unwrap(), None);
+
+        assert!(read_packet(&mut b"\x02\0\0\0\0\0".as_slice())
+            .await
+            .is_err());
+        assert!(read_packet(&mut b"\xff\xff\xff\xff".as_slice())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn split_long_responses() {
+        let body = "é".repeat(MAX_RESPONSE_BODY_LEN);
+
+        let mut buf = vec![];
+        write_response(&mut buf, 3, &body);
+
+        let mut stream = buf.as_slice();
+        let mut received = String::new();
+        let mut count = 0;
+
+        while let Some(packet) = read_packet(&mut stream).await.unwrap() {
+            assert_eq!(packet.id, 3);
+            assert_eq!(packet.kind, TYPE_RESPONSE);
+            received += &packet.body;
+            count += 1;
+        }
+
+        assert_eq!(count, 2);
+        assert_eq!(received, body);
+
+        let mut buf = vec![];
+        write_response(&mut buf, 3, "");
+        assert_eq!(buf.len(), 4 + MIN_LEN);
+    }
+}
@@ -5,6 +5,7 @@ use bevy_ecs::prelude::*;
 use tracing::error;
 use valence_ident::Ident;
 use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::packets::configuration::RegistryDataS2c;
 
 use crate::RegistrySet;
 
@@ -21,6 +22,9 @@ pub(super) fn build(app: &mut App) {
 #[derive(Resource, Debug)]
 pub struct RegistryCodec {
     pub registries: BTreeMap<Ident<String>, Vec<RegistryValue>>,
+    /// The vanilla registry entries, which clients already have through the
+    /// `minecraft:core` known pack.
+    known_pack: BTreeMap<Ident<String>, BTreeMap<Ident<String>, Compound>>,
     // TODO: store this in binary form?
     cached_codec: Compound,
 }
@@ -47,6 +51,69 @@ impl RegistryCodec {
             .get_mut(registry_key.as_str())
             .unwrap_or_else(|| panic!("missing registry for {registry_key}"))
     }
+
+    /// Returns whether the given registry entry is identical to the vanilla
+    /// entry of the same name from the `minecraft:core` known pack. Such
+    /// entries don't need to be sent to clients that have the pack.
+    pub fn is_known_pack_entry(&self, registry_key: Ident<&str>, value: &RegistryValue) -> bool {
+        self.known_pack
+            .get(registry_key.as_str())
+            .and_then(|reg| reg.get(value.name.as_str()))
+            .is_some_and(|known| compound_matches(&value.element, known))
+    }
+
+    /// Builds the [`RegistryDataS2c`] packets sent to clients during the
+    /// configuration phase, in registry order.
+    ///
+    /// If `has_known_pack` is `true`, the client has confirmed that it knows
+    /// the vanilla `minecraft:core` pack. Entries from that pack that were not
+    /// modified are then sent without element data. Every other entry,
+    /// including custom biomes and dimension types, is sent in full.
+    pub fn registry_data_packets(&self, has_known_pack: bool) -> Vec<RegistryDataS2c<'static>> {
+        self.registries
+            .iter()
+            .map(|(reg_name, reg)| RegistryDataS2c {
+                id: reg_name.clone().into(),
+                entries: reg
+                    .iter()
+                    .map(|value| {
+                        let element = (!has_known_pack
+                            || !self.is_known_pack_entry(reg_name.as_str_ident(), value))
+                        .then(|| value.element.clone());
+
+                        (value.name.clone().into(), element)
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Returns whether every field of `element` has an equivalent value in
+/// `known`. Fields missing from `element` are ignored because the typed
+/// registries (like [`Biome`](crate::biome::Biome)) don't round-trip every
+/// vanilla field.
+fn compound_matches(element: &Compound, known: &Compound) -> bool {
+    element
+        .iter()
+        .all(|(k, v)| known.get(k).is_some_and(|known| value_matches(v, known)))
+}
+
+fn value_matches(value: &Value, known: &Value) -> bool {
+    match (value, known) {
+        (Value::Compound(a), Value::Compound(b)) => compound_matches(a, b),
+        // Numbers may change type when going through serde, e.g. `double` to `float`.
+        (a, b) if a.is_number() && b.is_number() => {
+            let is_float = |v: &Value| matches!(v, Value::Float(_) | Value::Double(_));
+
+            if is_float(a) || is_float(b) {
+                a.as_f32() == b.as_f32()
+            } else {
+                a.as_i64() == b.as_i64()
+            }
+        }
+        (a, b) => a == b,
+    }
 }
 
 impl Default for RegistryCodec {
@@ -93,6 +160,17 @@ impl Default for RegistryCodec {
         }
 
         Self {
+            known_pack: registries
+                .iter()
+                .map(|(reg_name, reg)| {
+                    let entries = reg
+                        .iter()
+                        .map(|value| (value.name.clone(), value.element.clone()))
+                        .collect();
+
+                    (reg_name.clone(), entries)
+                })
+                .collect(),
             registries,
             // Cache will be created later.
             cached_codec: Compound::new(),
@@ -126,3 +204,44 @@ fn cache_registry_codec(codec: ResMut<RegistryCodec>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_ident::ident;
+
+    use super::*;
+
+    #[test]
+    fn registry_data_omits_known_pack_entries() {
+        let mut codec = RegistryCodec::default();
+
+        let biomes = codec.registry_mut(ident!("worldgen/biome"));
+        let plains = biomes
+            .iter()
+            .position(|v| v.name.as_str() == "minecraft:plains")
+            .unwrap();
+
+        // Typed registries drop unknown fields, which must not count as a modification.
+        biomes[plains].element.remove("effects");
+
+        biomes.push(RegistryValue {
+            name: ident!("valence:custom").into(),
+            element: compound! { "temperature" => 0.5_f32 },
+        });
+
+        let packets = codec.registry_data_packets(true);
+        let biome_packet = packets
+            .iter()
+            .find(|p| p.id.as_str() == "minecraft:worldgen/biome")
+            .unwrap();
+
+        for (name, element) in &biome_packet.entries {
+            assert_eq!(element.is_some(), name.as_str() == "valence:custom");
+        }
+
+        // Clients without the known pack get everything.
+        for packet in codec.registry_data_packets(false) {
+            assert!(packet.entries.iter().all(|(_, element)| element.is_some()));
+        }
+    }
+}
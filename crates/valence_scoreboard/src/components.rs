@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use bevy_ecs::prelude::*;
 use derive_more::{Deref, DerefMut};
 use valence_server::entity::EntityLayerId;
 use valence_server::protocol::packets::play::set_display_objective_s2c::ScoreboardPosition;
 use valence_server::protocol::packets::play::set_objective_s2c::ObjectiveRenderType;
+use valence_server::protocol::packets::play::set_player_team_s2c::{
+    CollisionRule, NameTagVisibility, TeamColor, TeamFlags,
+};
 use valence_server::text::IntoText;
 use valence_server::Text;
 
@@ -119,3 +122,103 @@ impl Default for ObjectiveBundle {
         }
     }
 }
+
+/// A string that identifies a team. It's generally not safe to modify this
+/// after it's been created.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Component, Deref)]
+pub struct Team(pub(crate) String);
+
+impl Team {
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Self(name.into())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The display name of a team.
+#[derive(Debug, Clone, PartialEq, Component, Deref, DerefMut)]
+pub struct TeamDisplay(pub Text);
+
+/// Text shown before the names of the team's members.
+#[derive(Debug, Clone, PartialEq, Component, Default, Deref, DerefMut)]
+pub struct TeamPrefix(pub Text);
+
+/// Text shown after the names of the team's members.
+#[derive(Debug, Clone, PartialEq, Component, Default, Deref, DerefMut)]
+pub struct TeamSuffix(pub Text);
+
+/// The members of a team. Players are referred to by their username and other
+/// entities by their UUID.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Default)]
+pub struct TeamMembers(pub(crate) BTreeSet<String>);
+
+impl TeamMembers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        self.0.contains(member)
+    }
+
+    pub fn insert<M: Into<String>>(&mut self, member: M) -> bool {
+        self.0.insert(member.into())
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        self.0.remove(member)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl<M: Into<String>> FromIterator<M> for TeamMembers {
+    fn from_iter<I: IntoIterator<Item = M>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// The members of a team as of the last tick.
+#[derive(Debug, Clone, Default, PartialEq, Component)]
+pub struct OldTeamMembers(pub(crate) BTreeSet<String>);
+
+#[derive(Bundle)]
+pub struct TeamBundle {
+    pub name: Team,
+    pub display: TeamDisplay,
+    pub prefix: TeamPrefix,
+    pub suffix: TeamSuffix,
+    pub color: TeamColor,
+    /// Whether members can hurt each other and see invisible teammates.
+    pub flags: TeamFlags,
+    pub name_tag_visibility: NameTagVisibility,
+    pub collision_rule: CollisionRule,
+    pub members: TeamMembers,
+    pub old_members: OldTeamMembers,
+    pub layer: EntityLayerId,
+}
+
+impl Default for TeamBundle {
+    fn default() -> Self {
+        Self {
+            name: Team::new(""),
+            display: TeamDisplay("".into_text()),
+            prefix: Default::default(),
+            suffix: Default::default(),
+            color: Default::default(),
+            flags: TeamFlags::new()
+                .with_friendly_fire(true)
+                .with_see_invisible_teammates(true),
+            name_tag_visibility: Default::default(),
+            collision_rule: Default::default(),
+            members: Default::default(),
+            old_members: Default::default(),
+            layer: Default::default(),
+        }
+    }
+}
@@ -14,10 +14,14 @@ use valence_server::protocol::packets::play::set_display_objective_s2c::Scoreboa
 use valence_server::protocol::packets::play::set_objective_s2c::{
     ObjectiveMode, ObjectiveRenderType,
 };
+use valence_server::protocol::packets::play::set_player_team_s2c::{
+    CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags,
+};
 use valence_server::protocol::packets::play::{
-    ResetScoreS2c, SetDisplayObjectiveS2c, SetObjectiveS2c, SetScoreS2c,
+    ResetScoreS2c, SetDisplayObjectiveS2c, SetObjectiveS2c, SetPlayerTeamS2c, SetScoreS2c,
 };
 use valence_server::protocol::{VarInt, WritePacket};
+use valence_server::reconfigure::Rejoined;
 use valence_server::text::IntoText;
 use valence_server::{Despawned, EntityLayer};
 
@@ -47,6 +51,17 @@ impl Plugin for ScoreboardPlugin {
                 .after(create_or_update_objectives)
                 .after(handle_new_clients)
                 .in_set(ScoreboardSet),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                create_or_update_teams,
+                update_team_members
+                    .after(create_or_update_teams)
+                    .after(handle_new_clients),
+                remove_despawned_teams,
+            )
+                .in_set(ScoreboardSet),
         );
     }
 }
@@ -149,8 +164,13 @@ fn remove_despawned_objectives(
 
 fn handle_new_clients(
     mut clients: Query<
-        (&mut Client, &VisibleEntityLayers, &OldVisibleEntityLayers),
-        Or<(Added<Client>, Changed<VisibleEntityLayers>)>,
+        (
+            &mut Client,
+            &VisibleEntityLayers,
+            &OldVisibleEntityLayers,
+            Has<Rejoined>,
+        ),
+        Or<(Added<Client>, Added<Rejoined>, Changed<VisibleEntityLayers>)>,
     >,
     objectives: Query<
         (
@@ -163,10 +183,11 @@ fn handle_new_clients(
         ),
         Without<Despawned>,
     >,
+    teams: Query<TeamQuery, Without<Despawned>>,
 ) {
     // Remove objectives from the old visible layers that are not in the new visible
     // layers.
-    for (mut client, visible_layers, old_visible_layers) in &mut clients {
+    for (mut client, visible_layers, old_visible_layers, _) in &mut clients {
         let removed_layers: BTreeSet<_> = old_visible_layers
             .get()
             .difference(&visible_layers.0)
@@ -181,13 +202,23 @@ fn handle_new_clients(
                 mode: ObjectiveMode::Remove,
             });
         }
+
+        for team in teams.iter() {
+            if !removed_layers.contains(&team.layer.0) {
+                continue;
+            }
+            client.write_packet(&SetPlayerTeamS2c {
+                team_name: &team.name.0,
+                mode: Mode::RemoveTeam,
+            });
+        }
     }
 
     // Add objectives from the new visible layers that are not in the old visible
     // layers, or send all objectives if the client is new.
-    for (mut client, visible_layers, old_visible_layers) in &mut clients {
+    for (mut client, visible_layers, old_visible_layers, rejoined) in &mut clients {
         // not sure how to avoid the clone here
-        let added_layers = if client.is_added() {
+        let added_layers = if client.is_added() || rejoined {
             debug!("client is new, sending all objectives");
             visible_layers.0.clone()
         } else {
@@ -228,6 +259,17 @@ fn handle_new_clients(
                 client.write_packet(&packet);
             }
         }
+
+        for team in teams.iter() {
+            if !added_layers.contains(&team.layer.0) {
+                continue;
+            }
+
+            client.write_packet(&SetPlayerTeamS2c {
+                team_name: &team.name.0,
+                mode: team.create_mode(),
+            });
+        }
     }
 }
 
@@ -279,3 +321,160 @@ fn update_scores(
         old_scores.0.clone_from(&scores.0);
     }
 }
+
+#[derive(bevy_ecs::query::QueryData)]
+struct TeamQuery {
+    name: Ref<'static, Team>,
+    display: &'static TeamDisplay,
+    prefix: &'static TeamPrefix,
+    suffix: &'static TeamSuffix,
+    color: &'static TeamColor,
+    flags: &'static TeamFlags,
+    name_tag_visibility: &'static NameTagVisibility,
+    collision_rule: &'static CollisionRule,
+    members: &'static TeamMembers,
+    layer: &'static EntityLayerId,
+}
+
+impl TeamQueryItem<'_> {
+    fn create_mode(&self) -> Mode<'_> {
+        Mode::CreateTeam {
+            team_display_name: (&self.display.0).into_cow_text(),
+            friendly_flags: *self.flags,
+            name_tag_visibility: *self.name_tag_visibility,
+            collision_rule: *self.collision_rule,
+            team_color: *self.color,
+            team_prefix: (&self.prefix.0).into_cow_text(),
+            team_suffix: (&self.suffix.0).into_cow_text(),
+            entities: self.members.iter().collect(),
+        }
+    }
+
+    fn update_mode(&self) -> Mode<'_> {
+        Mode::UpdateTeamInfo {
+            team_display_name: (&self.display.0).into_cow_text(),
+            friendly_flags: *self.flags,
+            name_tag_visibility: *self.name_tag_visibility,
+            collision_rule: *self.collision_rule,
+            team_color: *self.color,
+            team_prefix: (&self.prefix.0).into_cow_text(),
+            team_suffix: (&self.suffix.0).into_cow_text(),
+        }
+    }
+}
+
+fn create_or_update_teams(
+    teams: Query<
+        TeamQuery,
+        (
+            Or<(
+                Added<Team>,
+                Changed<TeamDisplay>,
+                Changed<TeamPrefix>,
+                Changed<TeamSuffix>,
+                Changed<TeamColor>,
+                Changed<TeamFlags>,
+                Changed<NameTagVisibility>,
+                Changed<CollisionRule>,
+            )>,
+            Without<Despawned>,
+        ),
+    >,
+    mut layers: Query<&mut EntityLayer>,
+) {
+    for team in teams.iter() {
+        let Ok(mut layer) = layers.get_mut(team.layer.0) else {
+            warn!(
+                "No layer found for entity layer ID {:?}, can't update team",
+                team.layer
+            );
+            continue;
+        };
+
+        // The members of a new team are sent along with it.
+        let mode = if team.name.is_added() {
+            team.create_mode()
+        } else {
+            team.update_mode()
+        };
+
+        layer.write_packet(&SetPlayerTeamS2c {
+            team_name: &team.name.0,
+            mode,
+        });
+    }
+}
+
+/// Must occur after `create_or_update_teams`.
+fn update_team_members(
+    mut teams: Query<
+        (Ref<Team>, &TeamMembers, &mut OldTeamMembers, &EntityLayerId),
+        (Changed<TeamMembers>, Without<Despawned>),
+    >,
+    mut layers: Query<&mut EntityLayer>,
+) {
+    for (team, members, mut old_members, entity_layer) in &mut teams {
+        if team.is_added() {
+            // The members were already sent when the team was created.
+            old_members.0.clone_from(&members.0);
+            continue;
+        }
+
+        let Ok(mut layer) = layers.get_mut(entity_layer.0) else {
+            warn!(
+                "No layer found for entity layer ID {:?}, can't update team members",
+                entity_layer
+            );
+            continue;
+        };
+
+        let added: Vec<_> = members
+            .0
+            .difference(&old_members.0)
+            .map(String::as_str)
+            .collect();
+        let removed: Vec<_> = old_members
+            .0
+            .difference(&members.0)
+            .map(String::as_str)
+            .collect();
+
+        if !removed.is_empty() {
+            layer.write_packet(&SetPlayerTeamS2c {
+                team_name: &team.0,
+                mode: Mode::RemoveEntities { entities: removed },
+            });
+        }
+
+        if !added.is_empty() {
+            layer.write_packet(&SetPlayerTeamS2c {
+                team_name: &team.0,
+                mode: Mode::AddEntities { entities: added },
+            });
+        }
+
+        old_members.0.clone_from(&members.0);
+    }
+}
+
+fn remove_despawned_teams(
+    mut commands: Commands,
+    teams: Query<(Entity, &Team, &EntityLayerId), With<Despawned>>,
+    mut layers: Query<&mut EntityLayer>,
+) {
+    for (entity, team, entity_layer) in teams.iter() {
+        commands.entity(entity).despawn();
+        let Ok(mut layer) = layers.get_mut(entity_layer.0) else {
+            warn!(
+                "No layer found for entity layer ID {:?}, can't remove team",
+                entity_layer
+            );
+            continue;
+        };
+
+        layer.write_packet(&SetPlayerTeamS2c {
+            team_name: &team.0,
+            mode: Mode::RemoveTeam,
+        });
+    }
+}
@@ -0,0 +1,285 @@
+//! Sends chunks to clients in batches at the rate the client asks for.
+//!
+//! Chunks entering a client's view are queued instead of being written right
+//! away. Every tick, the nearest queued chunks are sent between a
+//! [`ChunkBatchStartS2c`] and a [`ChunkBatchFinishedS2c`]. The client answers
+//! every batch with a [`ChunkBatchReceivedC2s`] containing the number of chunks
+//! per tick it wants to receive, so slow connections aren't flooded with chunk
+//! data when joining or teleporting.
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use valence_entity::Position;
+use valence_protocol::packets::play::{
+    ChunkBatchFinishedS2c, ChunkBatchReceivedC2s, ChunkBatchStartS2c,
+};
+use valence_protocol::{ChunkPos, VarInt, WritePacket};
+
+use crate::client::{Client, VisibleChunkLayer};
+use crate::event_loop::PacketEvent;
+use crate::layer::ChunkLayer;
+
+/// The chunks per tick used until the client reports its own rate.
+const INITIAL_CHUNKS_PER_TICK: f32 = 9.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+/// The number of batches that can be sent before the first one is
+/// acknowledged.
+const INITIAL_UNACKNOWLEDGED_BATCHES: u32 = 1;
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+/// The chunks waiting to be sent to a client and the state of its chunk
+/// batches.
+///
+/// Queued chunks already count as viewed by the client, but they are dropped
+/// from the queue without being sent if they leave the client's view first.
+#[derive(Component, Debug)]
+pub struct ChunkBatchState {
+    pending: FxHashMap<ChunkPos, PendingChunk>,
+    desired_chunks_per_tick: f32,
+    batch_quota: f32,
+    unacknowledged_batches: u32,
+    max_unacknowledged_batches: u32,
+}
+
+/// Why a chunk is queued.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum PendingChunk {
+    /// The client doesn't have the chunk yet.
+    New,
+    /// The client has an older version of the chunk, which is sent again.
+    Resend,
+}
+
+impl Default for ChunkBatchState {
+    fn default() -> Self {
+        Self {
+            pending: FxHashMap::default(),
+            desired_chunks_per_tick: INITIAL_CHUNKS_PER_TICK,
+            batch_quota: 0.0,
+            unacknowledged_batches: 0,
+            max_unacknowledged_batches: INITIAL_UNACKNOWLEDGED_BATCHES,
+        }
+    }
+}
+
+impl ChunkBatchState {
+    /// Returns whether the chunk at `pos` is queued, but wasn't sent yet.
+    pub fn is_pending(&self, pos: ChunkPos) -> bool {
+        self.pending.contains_key(&pos)
+    }
+
+    /// Returns the number of chunks that are queued.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The number of chunks per tick the client last asked for.
+    pub fn desired_chunks_per_tick(&self) -> f32 {
+        self.desired_chunks_per_tick
+    }
+
+    /// The number of batches sent to the client that it hasn't acknowledged
+    /// yet.
+    pub fn unacknowledged_batches(&self) -> u32 {
+        self.unacknowledged_batches
+    }
+
+    /// Queues a chunk the client doesn't have.
+    pub(crate) fn queue(&mut self, pos: ChunkPos) {
+        self.pending.insert(pos, PendingChunk::New);
+    }
+
+    /// Queues a chunk the client has an older version of, unless it was never
+    /// sent and is still queued.
+    pub(crate) fn queue_resend(&mut self, pos: ChunkPos) {
+        self.pending.entry(pos).or_insert(PendingChunk::Resend);
+    }
+
+    /// Removes the chunk from the queue. Returns `true` if the client never
+    /// received the chunk, which isn't the case for chunks that were queued to
+    /// be sent again.
+    pub(crate) fn unqueue(&mut self, pos: ChunkPos) -> bool {
+        self.pending.remove(&pos) == Some(PendingChunk::New)
+    }
+
+    /// Forgets all queued chunks and batches in flight. Used when the client
+    /// (re)joins, since it won't acknowledge the batches sent before.
+    pub(crate) fn reset(&mut self) {
+        self.pending.clear();
+        self.batch_quota = 0.0;
+        self.unacknowledged_batches = 0;
+        self.max_unacknowledged_batches = INITIAL_UNACKNOWLEDGED_BATCHES;
+    }
+
+    fn on_batch_received(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+
+        self.desired_chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+
+        if self.unacknowledged_batches == 0 {
+            self.batch_quota = 1.0;
+        }
+
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+
+    /// Takes the chunks that should be sent this tick from the queue, nearest
+    /// to `center` first.
+    fn next_batch(&mut self, center: ChunkPos) -> Vec<ChunkPos> {
+        if self.pending.is_empty() || self.unacknowledged_batches >= self.max_unacknowledged_batches
+        {
+            return vec![];
+        }
+
+        self.batch_quota = (self.batch_quota + self.desired_chunks_per_tick)
+            .min(self.desired_chunks_per_tick.max(1.0));
+
+        if self.batch_quota < 1.0 {
+            return vec![];
+        }
+
+        let count = self.batch_quota as usize;
+        let mut batch: Vec<_> = self.pending.keys().copied().collect();
+
+        if batch.len() > count {
+            batch.select_nth_unstable_by_key(count, |pos| center.distance_squared(*pos));
+            batch.truncate(count);
+        }
+
+        batch.sort_unstable_by_key(|pos| center.distance_squared(*pos));
+
+        for pos in &batch {
+            self.pending.remove(pos);
+        }
+
+        self.batch_quota -= batch.len() as f32;
+        self.unacknowledged_batches += 1;
+
+        batch
+    }
+}
+
+pub(crate) fn send_chunk_batches(
+    mut clients: Query<(
+        &mut Client,
+        &mut ChunkBatchState,
+        &VisibleChunkLayer,
+        &Position,
+    )>,
+    chunk_layers: Query<&ChunkLayer>,
+) {
+    clients
+        .par_iter_mut()
+        .for_each(|(mut client, mut state, chunk_layer, pos)| {
+            let Ok(layer) = chunk_layers.get(chunk_layer.0) else {
+                return;
+            };
+
+            let batch = state.next_batch(ChunkPos::from(pos.0));
+
+            if batch.is_empty() {
+                return;
+            }
+
+            client.write_packet(&ChunkBatchStartS2c {});
+
+            let mut batch_size = 0;
+
+            for pos in batch {
+                // The chunk could have been removed from the layer after it was queued.
+                if let Some(chunk) = layer.chunk(pos) {
+                    chunk.write_init_packets(&mut *client, pos, layer.info());
+                    batch_size += 1;
+                }
+            }
+
+            client.write_packet(&ChunkBatchFinishedS2c {
+                batch_size: VarInt(batch_size),
+            });
+        });
+}
+
+pub(crate) fn handle_chunk_batch_received(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<&mut ChunkBatchState>,
+) {
+    for packet in packets.read() {
+        if let Some(pkt) = packet.decode::<ChunkBatchReceivedC2s>() {
+            if let Ok(mut state) = clients.get_mut(packet.client) {
+                state.on_batch_received(pkt.chunks_per_tick);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_follow_client_rate() {
+        let mut state = ChunkBatchState::default();
+
+        for x in -10..=10 {
+            for z in -10..=10 {
+                state.queue(ChunkPos::new(x, z));
+            }
+        }
+
+        let first = state.next_batch(ChunkPos::new(0, 0));
+        assert_eq!(first.len(), INITIAL_CHUNKS_PER_TICK as usize);
+        assert_eq!(first[0], ChunkPos::new(0, 0));
+        assert!(first.iter().all(|pos| pos.x.abs() <= 1 && pos.z.abs() <= 1));
+
+        // Nothing is sent until the first batch is acknowledged.
+        assert!(state.next_batch(ChunkPos::new(0, 0)).is_empty());
+
+        state.on_batch_received(0.5);
+
+        assert_eq!(state.next_batch(ChunkPos::new(0, 0)).len(), 1);
+        // The quota is used up.
+        assert!(state.next_batch(ChunkPos::new(0, 0)).is_empty());
+        assert_eq!(state.next_batch(ChunkPos::new(0, 0)).len(), 1);
+
+        state.on_batch_received(f32::NAN);
+        assert_eq!(state.desired_chunks_per_tick(), MIN_CHUNKS_PER_TICK);
+
+        state.on_batch_received(1000.0);
+        assert_eq!(state.desired_chunks_per_tick(), MAX_CHUNKS_PER_TICK);
+        assert_eq!(state.next_batch(ChunkPos::new(0, 0)).len(), 64);
+    }
+
+    #[test]
+    fn unqueued_chunks_are_not_sent() {
+        let mut state = ChunkBatchState::default();
+
+        state.queue(ChunkPos::new(0, 0));
+        state.queue(ChunkPos::new(1, 0));
+
+        assert!(state.unqueue(ChunkPos::new(1, 0)));
+        assert!(!state.unqueue(ChunkPos::new(1, 0)));
+
+        assert_eq!(state.next_batch(ChunkPos::new(0, 0)), [ChunkPos::new(0, 0)]);
+        assert_eq!(state.pending_len(), 0);
+    }
+
+    #[test]
+    fn unqueued_resends_were_received() {
+        let mut state = ChunkBatchState::default();
+
+        state.queue_resend(ChunkPos::new(0, 0));
+        assert!(state.is_pending(ChunkPos::new(0, 0)));
+        // The client has the chunk, so it has to be unloaded.
+        assert!(!state.unqueue(ChunkPos::new(0, 0)));
+
+        // A chunk that was never sent stays new when it's sent again.
+        state.queue(ChunkPos::new(1, 0));
+        state.queue_resend(ChunkPos::new(1, 0));
+        assert!(state.unqueue(ChunkPos::new(1, 0)));
+    }
+}
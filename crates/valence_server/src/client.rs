@@ -22,15 +22,15 @@ use valence_entity::{
     ClearEntityChangesSet, EntityId, EntityStatus, OldPosition, Position, Velocity,
 };
 use valence_math::{DVec3, Vec3};
-use valence_protocol::encode::{PacketEncoder, WritePacket};
+use valence_protocol::encode::{EncoderStats, PacketEncoder, WritePacket};
 use valence_protocol::packets::play::chunks_biomes_s2c::ChunkBiome;
 use valence_protocol::packets::play::game_event_s2c::GameEventKind;
 use valence_protocol::packets::play::level_particles_s2c::Particle;
 use valence_protocol::packets::play::{
     ChunksBiomesS2c, DisconnectS2c, EntityEventS2c, ForgetLevelChunkS2c, GameEventS2c,
     LevelParticlesS2c, PlayerCombatKillS2c, RemoveEntitiesS2c, SetChunkCacheCenterS2c,
-    SetChunkCacheRadiusS2c, SetEntityDataS2c, SetEntityMotionS2c, SetHealthS2c, SoundS2c,
-    UpdateAttributesS2c,
+    SetChunkCacheRadiusS2c, SetEntityDataS2c, SetEntityMotionS2c, SetHealthS2c,
+    SetSimulationDistanceS2c, SoundS2c, UpdateAttributesS2c,
 };
 use valence_protocol::profile::Property;
 use valence_protocol::sound::{Sound, SoundCategory, SoundId};
@@ -40,7 +40,10 @@ use valence_protocol::{BlockPos, ChunkPos, Encode, GameMode, Packet};
 use valence_registry::RegistrySet;
 use valence_server_common::{Despawned, UniqueId};
 
+use crate::chunk_batch::ChunkBatchState;
+use crate::event_loop::EventLoopPreUpdate;
 use crate::layer::{ChunkLayer, EntityLayer, UpdateLayersPostClientSet, UpdateLayersPreClientSet};
+use crate::reconfigure::Rejoined;
 use crate::ChunkView;
 
 pub struct ClientPlugin;
@@ -70,6 +73,7 @@ impl Plugin for ClientPlugin {
                 (
                     crate::spawn::initial_join.after(RegistrySet),
                     update_chunk_load_dist,
+                    update_simulation_dist,
                     handle_layer_messages.after(update_chunk_load_dist),
                     update_view_and_layers
                         .after(crate::spawn::initial_join)
@@ -78,6 +82,9 @@ impl Plugin for ClientPlugin {
                     crate::spawn::update_respawn_position.after(update_view_and_layers),
                     crate::spawn::respawn.after(crate::spawn::update_respawn_position),
                     update_old_view_dist.after(update_view_and_layers),
+                    crate::chunk_batch::send_chunk_batches
+                        .after(update_view_and_layers)
+                        .after(crate::spawn::respawn),
                     update_game_mode,
                     update_food_saturation_health,
                     update_tracked_data,
@@ -89,6 +96,10 @@ impl Plugin for ClientPlugin {
                 flush_packets.in_set(FlushPacketsSet),
             ),
         )
+        .add_systems(
+            EventLoopPreUpdate,
+            crate::chunk_batch::handle_chunk_batch_received,
+        )
         .configure_sets(PreUpdate, SpawnClientsSet)
         .configure_sets(
             PostUpdate,
@@ -120,6 +131,9 @@ pub struct ClientBundle {
     pub action_sequence: crate::action::ActionSequence,
     pub view_distance: ViewDistance,
     pub old_view_distance: OldViewDistance,
+    pub simulation_distance: SimulationDistance,
+    pub chunk_batch: crate::chunk_batch::ChunkBatchState,
+    pub features: crate::reconfigure::ClientFeatures,
     pub visible_chunk_layer: VisibleChunkLayer,
     pub old_visible_chunk_layer: OldVisibleChunkLayer,
     pub visible_entity_layers: VisibleEntityLayers,
@@ -127,6 +141,7 @@ pub struct ClientBundle {
     pub keepalive_state: crate::keepalive::KeepaliveState,
     pub ping: crate::keepalive::Ping,
     pub teleport_state: crate::teleport::TeleportState,
+    pub movement_state: crate::movement::MovementState,
     pub game_mode: GameMode,
     pub prev_game_mode: crate::spawn::PrevGameMode,
     pub death_location: crate::spawn::DeathLocation,
@@ -161,6 +176,9 @@ impl ClientBundle {
             action_sequence: Default::default(),
             view_distance: Default::default(),
             old_view_distance: OldViewDistance(2),
+            simulation_distance: Default::default(),
+            chunk_batch: Default::default(),
+            features: args.features,
             visible_chunk_layer: Default::default(),
             old_visible_chunk_layer: OldVisibleChunkLayer(Entity::PLACEHOLDER),
             visible_entity_layers: Default::default(),
@@ -168,6 +186,7 @@ impl ClientBundle {
             keepalive_state: crate::keepalive::KeepaliveState::new(),
             ping: Default::default(),
             teleport_state: crate::teleport::TeleportState::new(),
+            movement_state: Default::default(),
             game_mode: GameMode::default(),
             prev_game_mode: Default::default(),
             death_location: Default::default(),
@@ -203,6 +222,8 @@ pub struct ClientBundleArgs {
     pub conn: Box<dyn ClientConnection>,
     /// The packet encoder to use. This should be in sync with [`Self::conn`].
     pub enc: PacketEncoder,
+    /// The feature flags and data packs the client was configured with.
+    pub features: crate::reconfigure::ClientFeatures,
 }
 
 /// Marker [`Component`] for client entities. This component should exist even
@@ -280,6 +301,11 @@ impl Client {
         self.conn.as_mut()
     }
 
+    /// Returns the counts of the data written to this client so far.
+    pub fn encoder_stats(&self) -> &EncoderStats {
+        self.enc.stats()
+    }
+
     /// Flushes the packet queue to the underlying connection.
     ///
     /// This is called automatically at the end of the tick and when the client
@@ -348,10 +374,10 @@ impl Client {
         let position = position.into();
 
         self.write_packet(&SoundS2c {
-            id: SoundId::Direct {
+            id: SoundId::Inline(valence_protocol::sound::SoundDirect {
                 id: sound.to_ident().into(),
                 range: None,
-            },
+            }),
             category,
             position: (position * 8.0).as_ivec3(),
             volume,
@@ -529,6 +555,39 @@ impl OldViewDistance {
     }
 }
 
+/// The distance in chunks around the client where the world is simulated.
+///
+/// The client uses this to decide what it predicts locally. Valence itself
+/// doesn't tick chunks, so this is also meant for game systems like random
+/// ticks or mob AI, which can skip chunks that are outside the simulation
+/// distance of every client. See [`SimulationView`].
+#[derive(Component, Clone, PartialEq, Eq, Debug, Deref)]
+pub struct SimulationDistance(u8);
+
+impl SimulationDistance {
+    pub fn new(dist: u8) -> Self {
+        let mut new = Self(0);
+        new.set(dist);
+        new
+    }
+
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+
+    /// `dist` is clamped to `2..=32`.
+    pub fn set(&mut self, dist: u8) {
+        self.0 = dist.clamp(2, 32);
+    }
+}
+
+/// The vanilla server's default of 10 chunks.
+impl Default for SimulationDistance {
+    fn default() -> Self {
+        Self(10)
+    }
+}
+
 #[derive(QueryData, Copy, Clone, Debug)]
 pub struct View {
     pub pos: &'static Position,
@@ -541,6 +600,44 @@ impl ViewItem<'_> {
     }
 }
 
+/// A [`QueryData`] for checking whether chunks are within the
+/// [`SimulationDistance`] of a client.
+///
+/// # Examples
+///
+/// ```
+/// use valence_server::client::SimulationView;
+/// use valence_server::ecs::prelude::*;
+/// use valence_server::ChunkPos;
+///
+/// fn is_simulated(clients: &Query<SimulationView>, pos: ChunkPos) -> bool {
+///     clients.iter().any(|view| view.contains(pos))
+/// }
+/// ```
+#[derive(QueryData, Copy, Clone, Debug)]
+pub struct SimulationView {
+    pub pos: &'static Position,
+    pub simulation_dist: &'static SimulationDistance,
+}
+
+impl SimulationViewItem<'_> {
+    /// The chunk the client is in.
+    pub fn center(&self) -> ChunkPos {
+        self.pos.0.into()
+    }
+
+    /// Returns whether the chunk at `pos` is within the simulation distance.
+    /// Like in vanilla, the simulated area is a square around the client's
+    /// chunk.
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        let center = self.center();
+        let dist = i64::from(self.simulation_dist.0);
+
+        (i64::from(pos.x) - i64::from(center.x)).abs() <= dist
+            && (i64::from(pos.z) - i64::from(center.z)).abs() <= dist
+    }
+}
+
 #[derive(QueryData, Copy, Clone, Debug)]
 pub struct OldView {
     pub old_pos: &'static OldPosition,
@@ -589,7 +686,7 @@ pub struct VisibleEntityLayers(pub BTreeSet<Entity>);
 
 /// The value of [`VisibleEntityLayers`] from the end of the previous tick.
 #[derive(Component, Default, Debug, Deref)]
-pub struct OldVisibleEntityLayers(BTreeSet<Entity>);
+pub struct OldVisibleEntityLayers(pub(crate) BTreeSet<Entity>);
 
 impl OldVisibleEntityLayers {
     pub fn get(&self) -> &BTreeSet<Entity> {
@@ -628,12 +725,28 @@ fn update_chunk_load_dist(
     }
 }
 
+fn update_simulation_dist(
+    mut clients: Query<(&mut Client, &SimulationDistance), Changed<SimulationDistance>>,
+) {
+    for (mut client, dist) in &mut clients {
+        if client.is_added() {
+            // Join game packet includes the simulation distance.
+            continue;
+        }
+
+        client.write_packet(&SetSimulationDistanceS2c {
+            simulation_distance: VarInt(dist.0.into()),
+        });
+    }
+}
+
 fn handle_layer_messages(
     mut clients: Query<(
         Entity,
         &EntityId,
         &mut Client,
         &mut EntityRemoveBuf,
+        &mut ChunkBatchState,
         OldView,
         &OldVisibleChunkLayer,
         &mut VisibleEntityLayers,
@@ -649,6 +762,7 @@ fn handle_layer_messages(
             self_entity_id,
             mut client,
             mut remove_buf,
+            mut chunk_batch,
             old_view,
             old_visible_chunk_layer,
             mut visible_entity_layers,
@@ -719,21 +833,40 @@ fn handle_layer_messages(
                         });
                     }
                     crate::layer::chunk::LocalMsg::ChangeChunkState { pos } => {
-                        match &bytes[range] {
+                        // A resend always comes after the other states of the chunk.
+                        let states = &bytes[range];
+                        let (states, resend) = match states.split_last() {
+                            Some((&ChunkLayer::RESEND, rest)) => (rest, true),
+                            _ => (states, false),
+                        };
+
+                        match states {
+                            [] if resend => {
+                                // Send the whole chunk again. Nothing happens if it's
+                                // still queued.
+                                chunk_batch.queue_resend(pos);
+                            }
                             [ChunkLayer::LOAD, .., ChunkLayer::UNLOAD] => {
                                 // Chunk is being loaded and unloaded on the
                                 // same tick, so there's no need to do anything.
                                 debug_assert!(chunk_layer.chunk(pos).is_none());
                             }
                             [.., ChunkLayer::LOAD | ChunkLayer::OVERWRITE] => {
-                                // Load chunk.
+                                // Load chunk. A chunk that existed before this tick is
+                                // sent again, since the client might have the old one.
                                 let chunk = chunk_layer.chunk(pos).expect("chunk must exist");
-                                chunk.write_init_packets(&mut *client, pos, chunk_layer.info());
+                                if states[0] == ChunkLayer::LOAD {
+                                    chunk_batch.queue(pos);
+                                } else {
+                                    chunk_batch.queue_resend(pos);
+                                }
                                 chunk.inc_viewer_count();
                             }
                             [.., ChunkLayer::UNLOAD] => {
-                                // Unload chunk.
-                                client.write_packet(&ForgetLevelChunkS2c { pos });
+                                // Unload chunk, unless the client never received it.
+                                if !chunk_batch.unqueue(pos) {
+                                    client.write_packet(&ForgetLevelChunkS2c { pos });
+                                }
                                 debug_assert!(chunk_layer.chunk(pos).is_none());
                             }
                             _ => unreachable!("invalid message data while changing chunk state"),
@@ -884,6 +1017,7 @@ pub(crate) fn update_view_and_layers(
             Entity,
             &mut Client,
             &mut EntityRemoveBuf,
+            &mut ChunkBatchState,
             &VisibleChunkLayer,
             &mut OldVisibleChunkLayer,
             Ref<VisibleEntityLayers>,
@@ -892,12 +1026,15 @@ pub(crate) fn update_view_and_layers(
             &OldPosition,
             &ViewDistance,
             &OldViewDistance,
+            Has<Rejoined>,
         ),
         Or<(
             Changed<VisibleChunkLayer>,
             Changed<VisibleEntityLayers>,
             Changed<Position>,
             Changed<ViewDistance>,
+            Added<Client>,
+            Added<Rejoined>,
         )>,
     >,
     chunk_layers: Query<&ChunkLayer>,
@@ -910,6 +1047,7 @@ pub(crate) fn update_view_and_layers(
             self_entity,
             mut client,
             mut remove_buf,
+            mut chunk_batch,
             chunk_layer,
             mut old_chunk_layer,
             visible_entity_layers,
@@ -918,6 +1056,7 @@ pub(crate) fn update_view_and_layers(
             old_pos,
             view_dist,
             old_view_dist,
+            rejoined,
         )| {
             let view = ChunkView::new(ChunkPos::from(pos.0), view_dist.0);
             let old_view = ChunkView::new(ChunkPos::from(old_pos.get()), old_view_dist.0);
@@ -931,24 +1070,33 @@ pub(crate) fn update_view_and_layers(
                 });
             }
 
-            // Was the client's chunk layer changed?
-            if old_chunk_layer.0 != chunk_layer.0 {
+            // Was the client's chunk layer changed, or did it rejoin after being
+            // reconfigured?
+            if old_chunk_layer.0 != chunk_layer.0 || rejoined {
                 // Unload all chunks in the old view.
                 // TODO: can we skip this step if old dimension != new dimension?
                 if let Ok(layer) = chunk_layers.get(old_chunk_layer.0) {
                     for pos in old_view.iter() {
                         if let Some(chunk) = layer.chunk(pos) {
-                            client.write_packet(&ForgetLevelChunkS2c { pos });
+                            if !chunk_batch.unqueue(pos) {
+                                client.write_packet(&ForgetLevelChunkS2c { pos });
+                            }
                             chunk.dec_viewer_count();
                         }
                     }
                 }
 
-                // Load all chunks in the new view.
+                // A rejoining client won't acknowledge the batches sent before it was
+                // reconfigured.
+                if rejoined {
+                    chunk_batch.reset();
+                }
+
+                // Queue all chunks in the new view.
                 if let Ok(layer) = chunk_layers.get(chunk_layer.0) {
                     for pos in view.iter() {
                         if let Some(chunk) = layer.chunk(pos) {
-                            chunk.write_init_packets(&mut *client, pos, layer.info());
+                            chunk_batch.queue(pos);
                             chunk.inc_viewer_count();
                         }
                     }
@@ -1034,21 +1182,24 @@ pub(crate) fn update_view_and_layers(
                     // the new view. We don't need to do any work where the old and new view
                     // overlap.
 
-                    // Unload chunks in the old view.
+                    // Unload chunks in the old view. Chunks that are still queued are
+                    // dropped without being sent.
                     if let Ok(layer) = chunk_layers.get(chunk_layer.0) {
                         for pos in old_view.diff(view) {
                             if let Some(chunk) = layer.chunk(pos) {
-                                client.write_packet(&ForgetLevelChunkS2c { pos });
+                                if !chunk_batch.unqueue(pos) {
+                                    client.write_packet(&ForgetLevelChunkS2c { pos });
+                                }
                                 chunk.dec_viewer_count();
                             }
                         }
                     }
 
-                    // Load chunks in the new view.
+                    // Queue chunks in the new view.
                     if let Ok(layer) = chunk_layers.get(chunk_layer.0) {
                         for pos in view.diff(old_view) {
                             if let Some(chunk) = layer.chunk(pos) {
-                                chunk.write_init_packets(&mut *client, pos, layer.info());
+                                chunk_batch.queue(pos);
                                 chunk.inc_viewer_count();
                             }
                         }
@@ -1136,7 +1287,7 @@ fn update_old_view_dist(
     }
 }
 
-fn flush_packets(
+pub(crate) fn flush_packets(
     mut clients: Query<(Entity, &mut Client), Changed<Client>>,
     mut commands: Commands,
 ) {
@@ -1148,7 +1299,12 @@ fn flush_packets(
     }
 }
 
-fn init_tracked_data(mut clients: Query<(&mut Client, &TrackedData), Added<TrackedData>>) {
+fn init_tracked_data(
+    mut clients: Query<
+        (&mut Client, &TrackedData),
+        Or<(Added<TrackedData>, Added<Client>, Added<Rejoined>)>,
+    >,
+) {
     for (mut client, tracked_data) in &mut clients {
         if let Some(init_data) = tracked_data.init_data() {
             client.write_packet(&SetEntityDataS2c {
@@ -1171,7 +1327,10 @@ fn update_tracked_data(mut clients: Query<(&mut Client, &TrackedData)>) {
 }
 
 fn init_tracked_attributes(
-    mut clients: Query<(&mut Client, &EntityAttributes), Added<EntityAttributes>>,
+    mut clients: Query<
+        (&mut Client, &EntityAttributes),
+        Or<(Added<EntityAttributes>, Added<Client>, Added<Rejoined>)>,
+    >,
 ) {
     for (mut client, attributes) in &mut clients {
         client.write_packet(&UpdateAttributesS2c {
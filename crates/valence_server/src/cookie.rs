@@ -0,0 +1,264 @@
+//! Cookies are small pieces of data that the server stores on a client. The
+//! client keeps them until it quits the game, including when it is sent to
+//! another server with [`Client::transfer`]. This makes them useful to pass
+//! state between servers, but clients can change them at will, so any cookie
+//! that is trusted should be signed with a [`CookieSigner`].
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+use valence_protocol::packets::play::{CookieRequestS2c, CookieResponseC2s, StoreCookieS2c};
+use valence_protocol::{Ident, WritePacket};
+
+use crate::client::Client;
+use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
+
+/// The maximum length of a cookie in bytes. Clients disconnect when they are
+/// sent a longer one.
+pub const MAX_COOKIE_LEN: usize = 5120;
+
+/// The length of the signature added by [`CookieSigner::sign`].
+const SIGNATURE_LEN: usize = 32;
+/// The length of the expiry time added by [`CookieSigner::sign`].
+const EXPIRY_LEN: usize = 8;
+
+/// How long signed cookies are valid for by default.
+pub const DEFAULT_COOKIE_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+pub struct CookiePlugin;
+
+impl Plugin for CookiePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CookieResponseEvent>()
+            .add_systems(EventLoopPreUpdate, handle_cookie_response);
+    }
+}
+
+/// Sent when a client responds to [`Client::request_cookie`].
+#[derive(Event, Clone, PartialEq, Eq, Debug)]
+pub struct CookieResponseEvent {
+    pub client: Entity,
+    pub key: Ident<String>,
+    /// The cookie, or `None` if the client has no cookie with this key.
+    pub payload: Option<Vec<u8>>,
+}
+
+impl Client {
+    /// Stores a cookie on the client, replacing any cookie with the same key.
+    /// The payload must not be longer than [`MAX_COOKIE_LEN`].
+    pub fn store_cookie(&mut self, key: Ident<&str>, payload: &[u8]) {
+        debug_assert!(
+            payload.len() <= MAX_COOKIE_LEN,
+            "cookie is {} bytes long, but the maximum is {MAX_COOKIE_LEN}",
+            payload.len()
+        );
+
+        self.write_packet(&StoreCookieS2c {
+            key: key.into(),
+            payload: payload.into(),
+        });
+    }
+
+    /// Stores a cookie on the client that is signed by `signer` for the player
+    /// with the given UUID, so that it can be checked with
+    /// [`CookieSigner::verify`] when it is requested again. The payload must
+    /// not be longer than [`MAX_COOKIE_LEN`] minus the 40 bytes of the expiry
+    /// time and signature.
+    pub fn store_signed_cookie(
+        &mut self,
+        key: Ident<&str>,
+        uuid: Uuid,
+        payload: &[u8],
+        signer: &CookieSigner,
+    ) {
+        let cookie = signer.sign(key, uuid, payload);
+        self.store_cookie(key, &cookie);
+    }
+
+    /// Asks the client for the cookie with the given key. The answer arrives
+    /// as a [`CookieResponseEvent`].
+    pub fn request_cookie(&mut self, key: Ident<&str>) {
+        self.write_packet(&CookieRequestS2c { key: key.into() });
+    }
+}
+
+/// Signs cookies with a secret key, so that the server can tell whether a
+/// cookie was changed by the client. All servers that share cookies need to
+/// use the same secret.
+///
+/// Signed cookies are only valid for one player and expire after
+/// [`CookieSigner::max_age`], so they can't be handed to another player or
+/// replayed later.
+#[derive(Clone)]
+pub struct CookieSigner {
+    secret: Arc<[u8]>,
+    max_age: Duration,
+}
+
+impl CookieSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.into(),
+            max_age: DEFAULT_COOKIE_MAX_AGE,
+        }
+    }
+
+    /// Sets how long the cookies signed from now on are valid for.
+    #[must_use]
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// How long signed cookies are valid for. Defaults to
+    /// [`DEFAULT_COOKIE_MAX_AGE`].
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Returns the payload followed by its expiry time and a signature of the
+    /// key, the player's UUID, the expiry time and the payload. Including the
+    /// key prevents a signed cookie from being stored under another key.
+    pub fn sign(&self, key: Ident<&str>, uuid: Uuid, payload: &[u8]) -> Vec<u8> {
+        self.sign_at(key, uuid, payload, SystemTime::now())
+    }
+
+    /// Returns the payload of a cookie created by [`CookieSigner::sign`] with
+    /// the same key for the same player, or `None` if the signature doesn't
+    /// match or the cookie expired.
+    pub fn verify<'a>(&self, key: Ident<&str>, uuid: Uuid, cookie: &'a [u8]) -> Option<&'a [u8]> {
+        self.verify_at(key, uuid, cookie, SystemTime::now())
+    }
+
+    fn sign_at(&self, key: Ident<&str>, uuid: Uuid, payload: &[u8], now: SystemTime) -> Vec<u8> {
+        let expiry = unix_secs(now).saturating_add(self.max_age.as_secs());
+        let signature = self.mac(key, uuid, expiry, payload).finalize().into_bytes();
+
+        let mut cookie = Vec::with_capacity(payload.len() + EXPIRY_LEN + SIGNATURE_LEN);
+        cookie.extend_from_slice(payload);
+        cookie.extend_from_slice(&expiry.to_be_bytes());
+        cookie.extend_from_slice(&signature);
+        cookie
+    }
+
+    fn verify_at<'a>(
+        &self,
+        key: Ident<&str>,
+        uuid: Uuid,
+        cookie: &'a [u8],
+        now: SystemTime,
+    ) -> Option<&'a [u8]> {
+        let split = cookie.len().checked_sub(EXPIRY_LEN + SIGNATURE_LEN)?;
+        let (payload, rest) = cookie.split_at(split);
+        let (expiry, signature) = rest.split_at(EXPIRY_LEN);
+        let expiry = u64::from_be_bytes(expiry.try_into().unwrap());
+
+        self.mac(key, uuid, expiry, payload)
+            .verify_slice(signature)
+            .ok()?;
+
+        (unix_secs(now) < expiry).then_some(payload)
+    }
+
+    fn mac(&self, key: Ident<&str>, uuid: Uuid, expiry: u64, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+
+        mac.update(&(key.as_str().len() as u32).to_be_bytes());
+        mac.update(key.as_str().as_bytes());
+        mac.update(uuid.as_bytes());
+        mac.update(&expiry.to_be_bytes());
+        mac.update(payload);
+        mac
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl fmt::Debug for CookieSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't leak the secret in logs.
+        f.debug_struct("CookieSigner").finish_non_exhaustive()
+    }
+}
+
+fn handle_cookie_response(
+    mut packets: EventReader<PacketEvent>,
+    mut events: EventWriter<CookieResponseEvent>,
+) {
+    for packet in packets.read() {
+        if let Some(pkt) = packet.decode::<CookieResponseC2s>() {
+            events.send(CookieResponseEvent {
+                client: packet.client,
+                key: pkt.key.into(),
+                payload: pkt.payload.map(|payload| payload.into_owned()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ident;
+
+    use super::*;
+
+    #[test]
+    fn signed_cookies() {
+        let signer = CookieSigner::new(b"secret");
+        let key = ident!("valence:session");
+        let uuid = Uuid::from_u128(1);
+
+        let cookie = signer.sign(key, uuid, b"lobby");
+        assert_eq!(cookie.len(), 5 + EXPIRY_LEN + SIGNATURE_LEN);
+        assert_eq!(signer.verify(key, uuid, &cookie), Some(b"lobby".as_slice()));
+
+        // Changed payload.
+        let mut tampered = cookie.clone();
+        tampered[0] ^= 1;
+        assert_eq!(signer.verify(key, uuid, &tampered), None);
+
+        // Changed expiry time.
+        let mut tampered = cookie.clone();
+        tampered[5] ^= 1;
+        assert_eq!(signer.verify(key, uuid, &tampered), None);
+
+        // Stored under another key.
+        assert_eq!(signer.verify(ident!("valence:other"), uuid, &cookie), None);
+
+        // Sent by another player.
+        assert_eq!(signer.verify(key, Uuid::from_u128(2), &cookie), None);
+
+        // Signed with another secret.
+        assert_eq!(CookieSigner::new(b"other").verify(key, uuid, &cookie), None);
+
+        assert_eq!(signer.verify(key, uuid, &[]), None);
+    }
+
+    #[test]
+    fn signed_cookies_expire() {
+        let signer = CookieSigner::new(b"secret").with_max_age(Duration::from_secs(60));
+        let key = ident!("valence:session");
+        let uuid = Uuid::from_u128(1);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let cookie = signer.sign_at(key, uuid, b"lobby", now);
+
+        let later = now + Duration::from_secs(59);
+        assert_eq!(
+            signer.verify_at(key, uuid, &cookie, later),
+            Some(b"lobby".as_slice())
+        );
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(signer.verify_at(key, uuid, &cookie, later), None);
+    }
+}
@@ -7,9 +7,10 @@ use bevy_ecs::schedule::ScheduleLabel;
 use bevy_ecs::system::SystemState;
 use bytes::Bytes;
 use tracing::{debug, warn};
-use valence_protocol::{Decode, Packet};
+use valence_protocol::{Decode, Packet, PacketState};
 
 use crate::client::Client;
+use crate::reconfigure::Reconfigure;
 
 pub struct EventLoopPlugin;
 
@@ -52,6 +53,11 @@ pub struct PacketEvent {
     pub timestamp: Instant,
     /// This packet's ID.
     pub id: i32,
+    /// The state the client was in when it sent this packet. This is
+    /// [`PacketState::Play`] unless the client is being [reconfigured].
+    ///
+    /// [reconfigured]: Reconfigure
+    pub state: PacketState,
     /// The content of the packet, excluding the leading varint packet ID.
     pub data: Bytes,
 }
@@ -59,14 +65,15 @@ pub struct PacketEvent {
 impl PacketEvent {
     /// Attempts to decode this packet as the packet `P`.
     ///
-    /// If the packet ID is mismatched or an error occurs, `None` is returned.
+    /// If the packet ID or state is mismatched or an error occurs, `None` is
+    /// returned.
     /// Otherwise, `Some` is returned containing the decoded packet.
     #[inline]
     pub fn decode<'a, P>(&'a self) -> Option<P>
     where
         P: Packet + Decode<'a>,
     {
-        if self.id == P::ID {
+        if self.id == P::ID && self.state == P::STATE {
             let mut r = &self.data[..];
 
             match P::decode(&mut r) {
@@ -104,7 +111,7 @@ fn run_event_loop_schedules(world: &mut World) {
 fn run_event_loop(
     world: &mut World,
     state: &mut SystemState<(
-        Query<(Entity, &mut Client)>,
+        Query<(Entity, &mut Client, Option<&Reconfigure>)>,
         EventWriter<PacketEvent>,
         Commands,
     )>,
@@ -114,13 +121,14 @@ fn run_event_loop(
 
     let (mut clients, mut event_writer, mut commands) = state.get_mut(world);
 
-    for (entity, mut client) in &mut clients {
+    for (entity, mut client, reconfigure) in &mut clients {
         match client.connection_mut().try_recv() {
             Ok(Some(pkt)) => {
                 event_writer.send(PacketEvent {
                     client: entity,
                     timestamp: pkt.timestamp,
                     id: pkt.id,
+                    state: reconfigure.map_or(PacketState::Play, Reconfigure::incoming_state),
                     data: pkt.body,
                 });
 
@@ -148,13 +156,15 @@ fn run_event_loop(
         check_again.retain_mut(|(entity, remaining)| {
             debug_assert!(*remaining > 0);
 
-            if let Ok((_, mut client)) = clients.get_mut(*entity) {
+            if let Ok((_, mut client, reconfigure)) = clients.get_mut(*entity) {
                 match client.connection_mut().try_recv() {
                     Ok(Some(pkt)) => {
                         event_writer.send(PacketEvent {
                             client: *entity,
                             timestamp: pkt.timestamp,
                             id: pkt.id,
+                            state: reconfigure
+                                .map_or(PacketState::Play, Reconfigure::incoming_state),
                             data: pkt.body,
                         });
                         *remaining -= 1;
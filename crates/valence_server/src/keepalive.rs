@@ -4,11 +4,13 @@ use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use derive_more::Deref;
 use tracing::warn;
+use valence_protocol::packets::configuration;
 use valence_protocol::packets::play::{KeepAliveC2s, KeepAliveS2c};
 use valence_protocol::WritePacket;
 
 use crate::client::{Client, UpdateClientsSet};
 use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
+use crate::reconfigure::Reconfigure;
 
 pub struct KeepalivePlugin;
 
@@ -67,17 +69,29 @@ impl KeepaliveState {
 }
 
 fn send_keepalive(
-    mut clients: Query<(Entity, &mut Client, &mut KeepaliveState)>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        &mut KeepaliveState,
+        Option<&mut Reconfigure>,
+    )>,
     settings: Res<KeepaliveSettings>,
     mut commands: Commands,
 ) {
     let now = Instant::now();
 
-    for (entity, mut client, mut state) in &mut clients {
+    for (entity, mut client, mut state, reconfigure) in &mut clients {
         if now.duration_since(state.last_send) >= settings.period {
             if state.got_keepalive {
                 let id = rand::random();
-                client.write_packet(&KeepAliveS2c { id });
+
+                match reconfigure {
+                    // Play packets would be dropped while the client is being configured.
+                    Some(mut reconfigure) if reconfigure.drops_play_packets() => {
+                        reconfigure.write_packet(&configuration::KeepAliveS2c { id });
+                    }
+                    _ => client.write_packet(&KeepAliveS2c { id }),
+                }
 
                 state.got_keepalive = false;
                 state.last_keepalive_id = id;
@@ -97,15 +111,22 @@ fn handle_keepalive_response(
     mut commands: Commands,
 ) {
     for packet in packets.read() {
-        if let Some(pkt) = packet.decode::<KeepAliveC2s>() {
+        let id = match packet.decode::<KeepAliveC2s>() {
+            Some(pkt) => Some(pkt.id),
+            None => packet
+                .decode::<configuration::KeepAliveC2s>()
+                .map(|pkt| pkt.id),
+        };
+
+        if let Some(id) = id {
             if let Ok((entity, mut state, mut ping)) = clients.get_mut(packet.client) {
                 if state.got_keepalive {
                     warn!("unexpected keepalive from client {entity:?}");
                     commands.entity(entity).remove::<Client>();
-                } else if pkt.id != state.last_keepalive_id {
+                } else if id != state.last_keepalive_id {
                     warn!(
-                        "keepalive IDs don't match for client {entity:?} (expected {}, got {})",
-                        state.last_keepalive_id, pkt.id,
+                        "keepalive IDs don't match for client {entity:?} (expected {}, got {id})",
+                        state.last_keepalive_id,
                     );
                     commands.entity(entity).remove::<Client>();
                 } else {
@@ -57,6 +57,11 @@ impl Plugin for LayerPlugin {
 ///
 /// Layers themselves implement the [`WritePacket`] trait. Writing directly to a
 /// layer will send packets to all viewers unconditionally.
+///
+/// Packets written to a layer are encoded and compressed only once, using the
+/// [`Server`]'s compression threshold and options. The finished frames are
+/// then copied into the packet buffer of every viewer, so only encryption is
+/// done per client.
 pub trait Layer: WritePacket {
     /// Packet writer returned by [`except_writer`](Self::except_writer).
     type ExceptWriter<'a>: WritePacket
@@ -1,7 +1,11 @@
 #[allow(clippy::module_inception)]
 mod chunk;
+mod heightmap;
+mod journal;
+mod light;
 pub mod loaded;
 mod paletted_container;
+mod region;
 pub mod unloaded;
 
 use std::collections::hash_map::{Entry, OccupiedEntry, VacantEntry};
@@ -10,11 +14,17 @@ use std::fmt;
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 pub use chunk::{MAX_HEIGHT, *};
+pub use heightmap::{HeightmapKind, Heightmaps};
+pub use journal::{BlockJournal, JournalEntry, JournalQuery, DEFAULT_JOURNAL_CAPACITY};
+use light::LightEngine;
+pub use light::{LightSettings, MAX_LIGHT_LEVEL};
 pub use loaded::LoadedChunk;
+pub use region::{BlockRegion, Clipboard, Mirror, Rotation};
 use rustc_hash::FxHashMap;
 pub use unloaded::UnloadedChunk;
 use valence_math::{DVec3, Vec3};
 use valence_nbt::Compound;
+use valence_protocol::compression::CompressionOptions;
 use valence_protocol::encode::{PacketWriter, WritePacket};
 use valence_protocol::packets::play::level_particles_s2c::Particle;
 use valence_protocol::packets::play::{LevelParticlesS2c, SoundS2c};
@@ -37,6 +47,11 @@ pub struct ChunkLayer {
     messages: ChunkLayerMessages,
     chunks: FxHashMap<ChunkPos, LoadedChunk>,
     info: ChunkLayerInfo,
+    light: LightEngine,
+    /// The current tick of the server, which journal entries are stamped
+    /// with.
+    tick: i64,
+    journal: Option<BlockJournal>,
 }
 
 /// Chunk layer information.
@@ -44,8 +59,10 @@ pub(crate) struct ChunkLayerInfo {
     dimension_type: DimensionTypeId,
     height: u32,
     min_y: i32,
+    has_skylight: bool,
     biome_registry_len: usize,
     threshold: CompressionThreshold,
+    compression: CompressionOptions,
 }
 
 impl fmt::Debug for ChunkLayerInfo {
@@ -54,9 +71,10 @@ impl fmt::Debug for ChunkLayerInfo {
             .field("dimension_type", &self.dimension_type)
             .field("height", &self.height)
             .field("min_y", &self.min_y)
+            .field("has_skylight", &self.has_skylight)
             .field("biome_registry_len", &self.biome_registry_len)
             .field("threshold", &self.threshold)
-            // Ignore sky light mask and array.
+            .field("compression", &self.compression)
             .finish()
     }
 }
@@ -123,6 +141,9 @@ impl ChunkLayer {
     pub(crate) const LOAD: u8 = 0;
     pub(crate) const UNLOAD: u8 = 1;
     pub(crate) const OVERWRITE: u8 = 2;
+    /// Sent after the other states of a chunk with so many block changes that
+    /// its viewers receive the whole chunk again.
+    pub(crate) const RESEND: u8 = 3;
 
     /// Creates a new chunk layer.
     #[track_caller]
@@ -149,9 +170,14 @@ impl ChunkLayer {
                 dimension_type: dimensions.index_of(dimension_type).unwrap(),
                 height: dim.height as u32,
                 min_y: dim.min_y,
+                has_skylight: dim.has_skylight,
                 biome_registry_len: biomes.iter().len(),
                 threshold: server.compression_threshold(),
+                compression: server.compression_options(),
             },
+            light: LightEngine::default(),
+            tick: server.current_tick(),
+            journal: None,
         }
     }
 
@@ -170,6 +196,16 @@ impl ChunkLayer {
         self.info.min_y
     }
 
+    /// The settings of this layer's light engine.
+    pub fn light_settings(&self) -> &LightSettings {
+        &self.light.settings
+    }
+
+    /// Mutable access to the settings of this layer's light engine.
+    pub fn light_settings_mut(&mut self) -> &mut LightSettings {
+        &mut self.light.settings
+    }
+
     /// Get a reference to the chunk at the given position, if it is loaded.
     pub fn chunk<P: Into<ChunkPos>>(&self, pos: P) -> Option<&LoadedChunk> {
         self.chunks.get(&pos.into())
@@ -287,6 +323,61 @@ impl ChunkLayer {
         Some(chunk.block(x, y, z))
     }
 
+    /// Returns the sky light level at the given position, or `None` if the
+    /// position is not in a loaded chunk. Light is computed at the end of the
+    /// tick, so changes made to blocks during this tick are not reflected.
+    pub fn sky_light<P: Into<BlockPos>>(&self, pos: P) -> Option<u8> {
+        let (chunk, x, y, z) = self.locate_block(pos.into())?;
+
+        Some(chunk.sky_light(x, y, z))
+    }
+
+    /// Returns the block light level at the given position, or `None` if the
+    /// position is not in a loaded chunk. Light is computed at the end of the
+    /// tick, so changes made to blocks during this tick are not reflected.
+    pub fn block_light<P: Into<BlockPos>>(&self, pos: P) -> Option<u8> {
+        let (chunk, x, y, z) = self.locate_block(pos.into())?;
+
+        Some(chunk.block_light(x, y, z))
+    }
+
+    /// Returns the loaded chunk containing `pos` and the offsets of `pos` in
+    /// it, or `None` if the position is outside the world or not loaded.
+    fn locate_block(&self, pos: BlockPos) -> Option<(&LoadedChunk, u32, u32, u32)> {
+        let y = pos
+            .y
+            .checked_sub(self.info.min_y)
+            .and_then(|y| y.try_into().ok())?;
+
+        if y >= self.info.height {
+            return None;
+        }
+
+        let chunk = self.chunk(pos)?;
+
+        let x = pos.x.rem_euclid(16) as u32;
+        let z = pos.z.rem_euclid(16) as u32;
+
+        Some((chunk, x, y, z))
+    }
+
+    /// Returns the Y coordinate of the highest block in the column at `x` and
+    /// `z` that matches the heightmap `kind`, or `None` if the column is not in
+    /// a loaded chunk or has no matching block. Nothing above the highest
+    /// [`HeightmapKind::MotionBlocking`] block is solid or contains a fluid,
+    /// which makes it useful for finding spawn points.
+    pub fn highest_block(&self, x: i32, z: i32, kind: HeightmapKind) -> Option<i32> {
+        let chunk = self.chunk(BlockPos::new(x, 0, z))?;
+
+        let y = chunk.heightmaps().highest_block(
+            x.rem_euclid(16) as u32,
+            z.rem_euclid(16) as u32,
+            kind,
+        )?;
+
+        Some(self.info.min_y + y as i32)
+    }
+
     pub fn set_block<P, B>(&mut self, pos: P, block: B) -> Option<Block>
     where
         P: Into<BlockPos>,
@@ -303,12 +394,24 @@ impl ChunkLayer {
             return None;
         }
 
-        let chunk = self.chunk_mut(pos)?;
+        self.flush_journal();
+
+        let chunk = self.chunks.get_mut(&ChunkPos::from(pos))?;
 
         let x = pos.x.rem_euclid(16) as u32;
         let z = pos.z.rem_euclid(16) as u32;
 
-        Some(chunk.set_block(x, y, z, block))
+        match &mut self.journal {
+            Some(journal) => {
+                let block = block.into_block();
+                let old = chunk.set_block(x, y, z, block.clone());
+
+                journal.record(pos, old.clone(), block, self.tick);
+
+                Some(old)
+            }
+            None => Some(chunk.set_block(x, y, z, block)),
+        }
     }
 
     pub fn block_entity_mut<P: Into<BlockPos>>(&mut self, pos: P) -> Option<&mut Compound> {
@@ -323,12 +426,21 @@ impl ChunkLayer {
             return None;
         }
 
-        let chunk = self.chunk_mut(pos)?;
-
         let x = pos.x.rem_euclid(16) as u32;
         let z = pos.z.rem_euclid(16) as u32;
 
-        chunk.block_entity_mut(x, y, z)
+        if self.journal.is_some() {
+            self.flush_journal();
+
+            let chunk = self.chunk(pos)?;
+
+            if let Some(nbt) = chunk.block_entity(x, y, z) {
+                let old = Block::new(chunk.block_state(x, y, z), Some(nbt.clone()));
+                self.record_block_entity_edit(pos, old);
+            }
+        }
+
+        self.chunk_mut(pos)?.block_entity_mut(x, y, z)
     }
 
     pub fn biome<P: Into<BiomePos>>(&self, pos: P) -> Option<BiomeId> {
@@ -422,10 +534,10 @@ impl ChunkLayer {
         let position = position.into();
 
         self.view_writer(position).write_packet(&SoundS2c {
-            id: SoundId::Direct {
+            id: SoundId::Inline(valence_protocol::sound::SoundDirect {
                 id: sound.to_ident().into(),
                 range: None,
-            },
+            }),
             category,
             position: (position * 8.0).as_ivec3(),
             volume,
@@ -505,7 +617,8 @@ impl WritePacket for ChunkLayer {
         P: Packet + Encode,
     {
         self.messages.send_global(GlobalMsg::Packet, |b| {
-            PacketWriter::new(b, self.info.threshold).write_packet_fallible(packet)
+            PacketWriter::with_options(b, self.info.threshold, self.info.compression)
+                .write_packet_fallible(packet)
         })
     }
 
@@ -529,7 +642,14 @@ impl WritePacket for ExceptWriter<'_> {
             GlobalMsg::PacketExcept {
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.info.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(
+                    b,
+                    self.layer.info.threshold,
+                    self.layer.info.compression,
+                )
+                .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -556,7 +676,12 @@ impl WritePacket for ViewWriter<'_> {
         self.layer
             .messages
             .send_local(LocalMsg::PacketAt { pos: self.pos }, |b| {
-                PacketWriter::new(b, self.layer.info.threshold).write_packet_fallible(packet)
+                PacketWriter::with_options(
+                    b,
+                    self.layer.info.threshold,
+                    self.layer.info.compression,
+                )
+                .write_packet_fallible(packet)
             })
     }
 
@@ -585,7 +710,14 @@ impl WritePacket for ViewExceptWriter<'_> {
                 pos: self.pos,
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.info.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(
+                    b,
+                    self.layer.info.threshold,
+                    self.layer.info.compression,
+                )
+                .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -616,7 +748,14 @@ impl WritePacket for RadiusWriter<'_> {
                 center: self.center,
                 radius_squared: self.radius,
             },
-            |b| PacketWriter::new(b, self.layer.info.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(
+                    b,
+                    self.layer.info.threshold,
+                    self.layer.info.compression,
+                )
+                .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -649,7 +788,14 @@ impl WritePacket for RadiusExceptWriter<'_> {
                 radius_squared: self.radius,
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.info.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(
+                    b,
+                    self.layer.info.threshold,
+                    self.layer.info.compression,
+                )
+                .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -772,19 +918,30 @@ impl<'a> VacantChunkEntry<'a> {
 }
 
 pub(super) fn build(app: &mut App) {
-    app.add_systems(
-        PostUpdate,
-        (
-            update_chunk_layers_pre_client.in_set(UpdateLayersPreClientSet),
-            update_chunk_layers_post_client.in_set(UpdateLayersPostClientSet),
-        ),
-    );
+    app.add_systems(First, update_chunk_layer_ticks)
+        .add_systems(
+            PostUpdate,
+            (
+                update_chunk_layers_pre_client.in_set(UpdateLayersPreClientSet),
+                update_chunk_layers_post_client.in_set(UpdateLayersPostClientSet),
+            ),
+        );
+}
+
+fn update_chunk_layer_ticks(mut layers: Query<&mut ChunkLayer>, server: Res<Server>) {
+    for mut layer in &mut layers {
+        layer.bypass_change_detection().tick = server.current_tick();
+    }
 }
 
 fn update_chunk_layers_pre_client(mut layers: Query<&mut ChunkLayer>) {
     for layer in &mut layers {
         let layer = layer.into_inner();
 
+        layer.end_journal_tick();
+
+        layer.light.update(&mut layer.chunks, &layer.info);
+
         for (&pos, chunk) in &mut layer.chunks {
             chunk.update_pre_client(pos, &layer.info, &mut layer.messages);
         }
@@ -798,3 +955,35 @@ fn update_chunk_layers_post_client(mut layers: Query<&mut ChunkLayer>) {
         layer.messages.unready();
     }
 }
+
+#[cfg(test)]
+impl ChunkLayer {
+    /// A layer with a height of 64 and the chunks from -2 to 1 on both axes
+    /// loaded, for tests that don't need a whole app.
+    fn with_test_chunks() -> Self {
+        let mut layer = Self {
+            messages: Messages::new(),
+            chunks: FxHashMap::default(),
+            info: ChunkLayerInfo {
+                dimension_type: DimensionTypeId::new(0),
+                height: 64,
+                min_y: -16,
+                has_skylight: true,
+                biome_registry_len: 200,
+                threshold: CompressionThreshold(-1),
+                compression: CompressionOptions::DEFAULT,
+            },
+            light: LightEngine::default(),
+            tick: 0,
+            journal: None,
+        };
+
+        for z in -2..2 {
+            for x in -2..2 {
+                layer.insert_chunk([x, z], UnloadedChunk::new());
+            }
+        }
+
+        layer
+    }
+}
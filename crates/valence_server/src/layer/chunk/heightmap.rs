@@ -0,0 +1,430 @@
+use valence_generated::block::{BlockKind, PropName, PropValue};
+use valence_nbt::{Compound, Value};
+use valence_protocol::BlockState;
+
+use super::chunk::{bit_width, Chunk};
+
+/// The kinds of heightmaps kept for every chunk. Each heightmap tracks the
+/// highest block in every column of the chunk that [matches] its kind.
+///
+/// [matches]: Self::matches
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum HeightmapKind {
+    /// The highest block that isn't air.
+    WorldSurface,
+    /// The highest block that blocks motion or contains a fluid. Clients use
+    /// this to decide where rain and snow stop falling.
+    MotionBlocking,
+    /// Like [`Self::MotionBlocking`], but leaves are ignored.
+    MotionBlockingNoLeaves,
+    /// The highest block that blocks motion. Unlike
+    /// [`Self::MotionBlocking`], fluids are ignored.
+    OceanFloor,
+}
+
+impl HeightmapKind {
+    /// All heightmap kinds.
+    pub const ALL: [Self; 4] = [
+        Self::WorldSurface,
+        Self::MotionBlocking,
+        Self::MotionBlockingNoLeaves,
+        Self::OceanFloor,
+    ];
+
+    /// The name of the heightmap in chunk data, e.g. `"WORLD_SURFACE"`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::WorldSurface => "WORLD_SURFACE",
+            Self::MotionBlocking => "MOTION_BLOCKING",
+            Self::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+            Self::OceanFloor => "OCEAN_FLOOR",
+        }
+    }
+
+    /// The inverse of [`Self::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Returns whether the block counts towards this heightmap.
+    pub fn matches(self, state: BlockState) -> bool {
+        match self {
+            Self::WorldSurface => !state.is_air(),
+            Self::MotionBlocking => state.blocks_motion() || has_fluid(state),
+            Self::MotionBlockingNoLeaves => {
+                // Only leaves have the `persistent` property.
+                (state.blocks_motion() || has_fluid(state))
+                    && state.get(PropName::Persistent).is_none()
+            }
+            Self::OceanFloor => state.blocks_motion(),
+        }
+    }
+
+    /// Whether the heightmap is included in the chunk packets sent to clients.
+    /// The other heightmaps are only used by the server.
+    const fn is_sent_to_clients(self) -> bool {
+        matches!(self, Self::WorldSurface | Self::MotionBlocking)
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Returns whether the block contains water or lava.
+fn has_fluid(state: BlockState) -> bool {
+    state.is_liquid()
+        || state.get(PropName::Waterlogged) == Some(PropValue::True)
+        || matches!(
+            state.to_kind(),
+            BlockKind::BubbleColumn
+                | BlockKind::Kelp
+                | BlockKind::KelpPlant
+                | BlockKind::Seagrass
+                | BlockKind::TallSeagrass
+        )
+}
+
+/// The heightmaps of a chunk for every [`HeightmapKind`].
+///
+/// For every column, a heightmap stores the offset of the highest matching
+/// block plus one, so the value is the height of the first empty space above
+/// the block. A value of zero means that no block in the column matches.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Heightmaps {
+    heights: Box<[[u16; 16 * 16]; 4]>,
+}
+
+impl Default for Heightmaps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heightmaps {
+    /// Creates the heightmaps of a chunk that is entirely air.
+    pub fn new() -> Self {
+        Self {
+            heights: Box::new([[0; 16 * 16]; 4]),
+        }
+    }
+
+    /// Computes the heightmaps of the chunk by scanning every column from the
+    /// top.
+    pub fn compute<C: Chunk + ?Sized>(chunk: &C) -> Self {
+        let mut heightmaps = Self::new();
+
+        for z in 0..16 {
+            for x in 0..16 {
+                let mut remaining = HeightmapKind::ALL.len();
+
+                for y in (0..chunk.height()).rev() {
+                    let state = chunk.block_state(x, y, z);
+
+                    for kind in HeightmapKind::ALL {
+                        if heightmaps.get(x, z, kind) == 0 && kind.matches(state) {
+                            heightmaps.set(x, z, kind, y + 1);
+                            remaining -= 1;
+                        }
+                    }
+
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+
+        heightmaps
+    }
+
+    /// Returns the value of the heightmap in the column at the given offsets.
+    /// This is the offset of the highest matching block plus one, or zero if
+    /// no block in the column matches. `x` and `z` are in the range `0..16`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if the offsets are out of bounds.
+    #[track_caller]
+    pub fn get(&self, x: u32, z: u32, kind: HeightmapKind) -> u32 {
+        u32::from(self.heights[kind.index()][column(x, z)])
+    }
+
+    /// Returns the offset of the highest block in the column that matches the
+    /// heightmap, or `None` if no block in the column matches. `x` and `z` are
+    /// in the range `0..16`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if the offsets are out of bounds.
+    #[track_caller]
+    pub fn highest_block(&self, x: u32, z: u32, kind: HeightmapKind) -> Option<u32> {
+        self.get(x, z, kind).checked_sub(1)
+    }
+
+    /// Returns whether no value is above `height`, the height of a chunk.
+    pub(super) fn fits_height(&self, height: u32) -> bool {
+        self.heights
+            .iter()
+            .flatten()
+            .all(|&value| u32::from(value) <= height)
+    }
+
+    /// Encodes all heightmaps in the format used by region files. `height` is
+    /// the height of the chunk.
+    pub fn to_nbt(&self, height: u32) -> Compound {
+        self.encode(height, |_| true)
+    }
+
+    /// Decodes heightmaps in the format used by region files. Returns `None` if
+    /// any heightmap is missing or malformed.
+    pub fn from_nbt(nbt: &Compound, height: u32) -> Option<Self> {
+        let bits_per_entry = bit_width(height as usize).max(1);
+        let entries_per_long = 64 / bits_per_entry;
+        let long_count = (16 * 16_usize).div_ceil(entries_per_long);
+        let mask = (1 << bits_per_entry) - 1;
+
+        let mut heightmaps = Self::new();
+
+        for kind in HeightmapKind::ALL {
+            let Some(Value::LongArray(data)) = nbt.get(kind.name()) else {
+                return None;
+            };
+
+            if data.len() != long_count {
+                return None;
+            }
+
+            for (i, entry) in heightmaps.heights[kind.index()].iter_mut().enumerate() {
+                let long = data[i / entries_per_long] as u64;
+                let value = (long >> (i % entries_per_long * bits_per_entry)) & mask;
+
+                if value > u64::from(height) {
+                    return None;
+                }
+
+                *entry = value as u16;
+            }
+        }
+
+        Some(heightmaps)
+    }
+
+    /// Encodes the heightmaps that are sent to clients in chunk packets.
+    pub(super) fn to_client_nbt(&self, height: u32) -> Compound {
+        self.encode(height, HeightmapKind::is_sent_to_clients)
+    }
+
+    /// Updates the heightmaps after the block at the given offsets was set to
+    /// `state`. `block_at` returns the block state at an offset in the chunk
+    /// and is used to find the next highest block when the highest block of a
+    /// column is removed.
+    pub(super) fn block_state_changed<F>(
+        &mut self,
+        x: u32,
+        y: u32,
+        z: u32,
+        state: BlockState,
+        block_at: F,
+    ) where
+        F: Fn(u32, u32, u32) -> BlockState,
+    {
+        for kind in HeightmapKind::ALL {
+            let height = self.get(x, z, kind);
+
+            if kind.matches(state) {
+                if y + 1 > height {
+                    self.set(x, z, kind, y + 1);
+                }
+            } else if y + 1 == height {
+                let height = scan_column(x, y, z, kind, &block_at);
+                self.set(x, z, kind, height);
+            }
+        }
+    }
+
+    /// Updates the heightmaps after the section at `sect_y` was filled with
+    /// `state`. See [`Self::block_state_changed`] for `block_at`.
+    pub(super) fn section_filled<F>(&mut self, sect_y: u32, state: BlockState, block_at: F)
+    where
+        F: Fn(u32, u32, u32) -> BlockState,
+    {
+        let bottom = sect_y * 16;
+        let top = bottom + 16;
+
+        for kind in HeightmapKind::ALL {
+            let matches = kind.matches(state);
+
+            for z in 0..16 {
+                for x in 0..16 {
+                    let height = self.get(x, z, kind);
+
+                    if matches {
+                        if height < top {
+                            self.set(x, z, kind, top);
+                        }
+                    } else if height > bottom && height <= top {
+                        let height = scan_column(x, bottom, z, kind, &block_at);
+                        self.set(x, z, kind, height);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set(&mut self, x: u32, z: u32, kind: HeightmapKind, height: u32) {
+        self.heights[kind.index()][column(x, z)] = height as u16;
+    }
+
+    /// Packs the heightmaps of the given kinds into long arrays. Every entry
+    /// uses just enough bits to store the height of the chunk, and entries
+    /// don't span across longs.
+    fn encode<F>(&self, height: u32, mut include: F) -> Compound
+    where
+        F: FnMut(HeightmapKind) -> bool,
+    {
+        let bits_per_entry = bit_width(height as usize).max(1);
+        let entries_per_long = 64 / bits_per_entry;
+
+        HeightmapKind::ALL
+            .into_iter()
+            .filter(|&kind| include(kind))
+            .map(|kind| {
+                let mut data = vec![0_i64; (16 * 16_usize).div_ceil(entries_per_long)];
+
+                for (i, &entry) in self.heights[kind.index()].iter().enumerate() {
+                    data[i / entries_per_long] |=
+                        i64::from(entry) << (i % entries_per_long * bits_per_entry);
+                }
+
+                (kind.name().to_owned(), Value::LongArray(data))
+            })
+            .collect()
+    }
+}
+
+#[inline]
+#[track_caller]
+fn column(x: u32, z: u32) -> usize {
+    debug_assert!(
+        x < 16 && z < 16,
+        "heightmap column offsets of ({x}, {z}) are out of bounds"
+    );
+
+    (x + z * 16) as usize
+}
+
+/// Returns the heightmap value of the highest matching block below `y`.
+fn scan_column<F>(x: u32, y: u32, z: u32, kind: HeightmapKind, block_at: &F) -> u32
+where
+    F: Fn(u32, u32, u32) -> BlockState,
+{
+    (0..y)
+        .rev()
+        .find(|&y| kind.matches(block_at(x, y, z)))
+        .map_or(0, |y| y + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::chunk::{LoadedChunk, UnloadedChunk};
+
+    #[test]
+    fn heightmap_kinds() {
+        let leaves = BlockState::OAK_LEAVES;
+        let water = BlockState::WATER;
+        let flower = BlockState::DANDELION;
+
+        assert!(HeightmapKind::MotionBlocking.matches(leaves));
+        assert!(!HeightmapKind::MotionBlockingNoLeaves.matches(leaves));
+        assert!(HeightmapKind::MotionBlocking.matches(water));
+        assert!(!HeightmapKind::OceanFloor.matches(water));
+        assert!(HeightmapKind::WorldSurface.matches(flower));
+        assert!(!HeightmapKind::MotionBlocking.matches(flower));
+
+        for kind in HeightmapKind::ALL {
+            assert_eq!(HeightmapKind::from_name(kind.name()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn heightmaps_follow_block_changes() {
+        let mut chunk = LoadedChunk::new(64);
+
+        chunk.fill_block_state_section(0, BlockState::STONE);
+        chunk.set_block_state(3, 20, 4, BlockState::OAK_LEAVES);
+        chunk.set_block_state(3, 30, 4, BlockState::WATER);
+        chunk.set_block_state(3, 40, 4, BlockState::TORCH);
+
+        let heightmaps = chunk.heightmaps();
+        assert_eq!(heightmaps.get(0, 0, HeightmapKind::WorldSurface), 16);
+        assert_eq!(heightmaps.get(3, 4, HeightmapKind::WorldSurface), 41);
+        assert_eq!(heightmaps.get(3, 4, HeightmapKind::MotionBlocking), 31);
+        assert_eq!(heightmaps.get(3, 4, HeightmapKind::OceanFloor), 21);
+        assert_eq!(
+            heightmaps.get(3, 4, HeightmapKind::MotionBlockingNoLeaves),
+            31
+        );
+
+        // Removing the highest block rescans the column.
+        chunk.set_block_state(3, 40, 4, BlockState::AIR);
+        chunk.set_block_state(3, 30, 4, BlockState::AIR);
+
+        let heightmaps = chunk.heightmaps();
+        assert_eq!(heightmaps.get(3, 4, HeightmapKind::WorldSurface), 21);
+        assert_eq!(heightmaps.get(3, 4, HeightmapKind::MotionBlocking), 21);
+        assert_eq!(
+            heightmaps.get(3, 4, HeightmapKind::MotionBlockingNoLeaves),
+            16
+        );
+
+        chunk.fill_block_state_section(0, BlockState::AIR);
+        assert_eq!(
+            chunk
+                .heightmaps()
+                .highest_block(0, 0, HeightmapKind::WorldSurface),
+            None
+        );
+        assert_eq!(
+            chunk
+                .heightmaps()
+                .highest_block(3, 4, HeightmapKind::WorldSurface),
+            Some(20)
+        );
+
+        assert_eq!(chunk.heightmaps(), &Heightmaps::compute(&chunk));
+    }
+
+    #[test]
+    fn heightmaps_nbt_round_trip() {
+        let mut chunk = UnloadedChunk::with_height(384);
+
+        chunk.fill_block_state_section(3, BlockState::DIRT);
+        chunk.set_block_state(15, 383, 15, BlockState::STONE);
+        chunk.set_block_state(7, 100, 2, BlockState::WATER);
+
+        let heightmaps = Heightmaps::compute(&chunk);
+        assert_eq!(heightmaps.get(15, 15, HeightmapKind::OceanFloor), 384);
+
+        let nbt = heightmaps.to_nbt(chunk.height());
+        assert_eq!(Heightmaps::from_nbt(&nbt, chunk.height()), Some(heightmaps));
+
+        // The number of bits per entry depends on the height.
+        assert_eq!(Heightmaps::from_nbt(&nbt, 4096), None);
+    }
+
+    #[test]
+    fn heightmaps_must_fit_chunk() {
+        let mut tall = UnloadedChunk::with_height(384);
+        tall.set_block_state(0, 300, 0, BlockState::STONE);
+
+        let mut chunk = UnloadedChunk::with_height(64);
+
+        chunk.set_heightmaps(Some(Heightmaps::compute(&tall)));
+        assert_eq!(chunk.heightmaps(), None);
+
+        chunk.set_heightmaps(Some(Heightmaps::compute(&chunk)));
+        assert_eq!(chunk.heightmaps(), Some(&Heightmaps::new()));
+    }
+}
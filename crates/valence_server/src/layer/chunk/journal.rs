@@ -0,0 +1,441 @@
+//! An optional record of the block changes made to a [`ChunkLayer`].
+//!
+//! Once [enabled](ChunkLayer::enable_journal), every change made through
+//! [`ChunkLayer::set_block`], [`ChunkLayer::block_entity_mut`] and the region
+//! operations is recorded together with the tick it happened on and the
+//! [cause](ChunkLayer::set_change_cause) of the change. The journal can be
+//! inspected to find out who changed a block, and changes can be undone with
+//! [`ChunkLayer::rollback`], for example to revert griefing or to reset a
+//! minigame arena to its state before the round.
+//!
+//! Changes made to a [`LoadedChunk`](super::LoadedChunk) directly are not
+//! recorded. The journal keeps at most [`BlockJournal::capacity`] changes and
+//! forgets the oldest ones beyond that.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::RangeInclusive;
+
+use bevy_ecs::prelude::*;
+use valence_protocol::{BlockPos, ChunkPos};
+
+use super::chunk::{Block, IntoBlock};
+use super::{BlockRegion, ChunkLayer};
+
+/// A single recorded block change.
+#[derive(Clone, PartialEq, Debug)]
+pub struct JournalEntry {
+    /// The position of the changed block.
+    pub pos: BlockPos,
+    /// The block before the change.
+    pub old: Block,
+    /// The block after the change.
+    pub new: Block,
+    /// The entity responsible for the change, if one was set with
+    /// [`ChunkLayer::set_change_cause`].
+    pub cause: Option<Entity>,
+    /// The [tick](valence_server_common::Server::current_tick) the change was
+    /// made on.
+    pub tick: i64,
+}
+
+/// Selects journal entries. Every entry matches the default query.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct JournalQuery {
+    /// Only select changes inside this region.
+    pub region: Option<BlockRegion>,
+    /// Only select changes made during these ticks.
+    pub ticks: Option<RangeInclusive<i64>>,
+    /// Only select changes caused by this entity.
+    pub cause: Option<Entity>,
+}
+
+impl JournalQuery {
+    /// Returns whether the entry is selected by this query.
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        self.region.is_none_or(|region| region.contains(entry.pos))
+            && self
+                .ticks
+                .as_ref()
+                .is_none_or(|ticks| ticks.contains(&entry.tick))
+            && self.cause.is_none_or(|cause| entry.cause == Some(cause))
+    }
+}
+
+/// The default maximum number of changes kept by a [`BlockJournal`].
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1 << 18;
+
+/// The block changes recorded by a [`ChunkLayer`], oldest first.
+#[derive(Clone, Debug)]
+pub struct BlockJournal {
+    entries: VecDeque<JournalEntry>,
+    capacity: usize,
+    /// Block entities handed out by [`ChunkLayer::block_entity_mut`] whose new
+    /// NBT isn't known yet. They are moved to `entries` before the next change
+    /// is recorded, and at the end of the tick.
+    pending: Vec<JournalEntry>,
+    cause: Option<Entity>,
+}
+
+impl Default for BlockJournal {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: DEFAULT_JOURNAL_CAPACITY,
+            pending: vec![],
+            cause: None,
+        }
+    }
+}
+
+impl BlockJournal {
+    /// All recorded changes, oldest first.
+    pub fn entries(&self) -> &VecDeque<JournalEntry> {
+        &self.entries
+    }
+
+    /// The number of recorded changes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no changes are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The maximum number of recorded changes. Once it is reached, the oldest
+    /// change is forgotten for every new one. Defaults to
+    /// [`DEFAULT_JOURNAL_CAPACITY`].
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum number of recorded changes, forgetting the oldest
+    /// changes beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+    }
+
+    /// The entity that changes are currently attributed to.
+    pub fn cause(&self) -> Option<Entity> {
+        self.cause
+    }
+
+    /// Returns the changes selected by `query`, newest first.
+    pub fn query<'a>(
+        &'a self,
+        query: &'a JournalQuery,
+    ) -> impl Iterator<Item = &'a JournalEntry> + 'a {
+        self.entries
+            .iter()
+            .rev()
+            .filter(move |entry| query.matches(entry))
+    }
+
+    /// Returns the changes made to the block at `pos`, newest first. The
+    /// [`cause`](JournalEntry::cause) of the first one is whoever changed the
+    /// block last.
+    pub fn changes_at<P: Into<BlockPos>>(
+        &self,
+        pos: P,
+    ) -> impl Iterator<Item = &JournalEntry> + '_ {
+        let pos = pos.into();
+
+        self.entries
+            .iter()
+            .rev()
+            .filter(move |entry| entry.pos == pos)
+    }
+
+    /// Forgets the changes made before `tick`. They can no longer be rolled
+    /// back.
+    pub fn forget_before(&mut self, tick: i64) {
+        self.entries.retain(|entry| entry.tick >= tick);
+    }
+
+    /// Forgets all recorded changes.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(super) fn record(&mut self, pos: BlockPos, old: Block, new: Block, tick: i64) {
+        if old != new {
+            self.push(JournalEntry {
+                pos,
+                old,
+                new,
+                cause: self.cause,
+                tick,
+            });
+        }
+    }
+
+    fn push(&mut self, entry: JournalEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+}
+
+impl ChunkLayer {
+    /// Starts recording block changes in a [`BlockJournal`]. Does nothing if
+    /// the journal is already enabled.
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(BlockJournal::default);
+    }
+
+    /// Stops recording block changes and returns the journal, if it was
+    /// enabled.
+    pub fn disable_journal(&mut self) -> Option<BlockJournal> {
+        self.flush_journal();
+        self.journal.take()
+    }
+
+    /// The journal of block changes, if it is enabled. Changes to block
+    /// entities made through [`ChunkLayer::block_entity_mut`] show up once
+    /// the next block is changed, or at the end of the tick.
+    pub fn journal(&self) -> Option<&BlockJournal> {
+        self.journal.as_ref()
+    }
+
+    /// Mutable access to the journal of block changes, if it is enabled.
+    pub fn journal_mut(&mut self) -> Option<&mut BlockJournal> {
+        self.flush_journal();
+        self.journal.as_mut()
+    }
+
+    /// Attributes the following block changes to `cause`, usually the player
+    /// making them, until the end of the tick or until it is set again. Does
+    /// nothing if the journal is disabled.
+    pub fn set_change_cause(&mut self, cause: Option<Entity>) {
+        if let Some(journal) = &mut self.journal {
+            journal.cause = cause;
+        }
+    }
+
+    /// Undoes the recorded changes selected by `query`, newest first, and
+    /// removes them from the journal. Every block is set back to what it was
+    /// before the change, even if it was changed again afterwards by a change
+    /// that isn't selected. Changes in chunks that aren't loaded are kept.
+    /// Returns the number of changes undone.
+    pub fn rollback(&mut self, query: &JournalQuery) -> usize {
+        self.flush_journal();
+
+        let Some(mut journal) = self.journal.take() else {
+            return 0;
+        };
+
+        let (undone, kept): (VecDeque<_>, VecDeque<_>) = mem::take(&mut journal.entries)
+            .into_iter()
+            .partition(|entry| {
+                query.matches(entry) && self.chunks.contains_key(&ChunkPos::from(entry.pos))
+            });
+
+        journal.entries = kept;
+
+        let count = undone.len();
+
+        // The journal is taken out, so undoing isn't recorded.
+        for entry in undone.into_iter().rev() {
+            self.set_block(entry.pos, entry.old);
+        }
+
+        self.journal = Some(journal);
+
+        count
+    }
+
+    /// Records the new NBT of the block entities handed out by
+    /// [`ChunkLayer::block_entity_mut`].
+    pub(super) fn flush_journal(&mut self) {
+        let pending = match &mut self.journal {
+            Some(journal) if !journal.pending.is_empty() => mem::take(&mut journal.pending),
+            _ => return,
+        };
+
+        for mut entry in pending {
+            if let Some(block) = self.block(entry.pos) {
+                entry.new = block.into_block();
+            }
+
+            if let Some(journal) = &mut self.journal {
+                if entry.old != entry.new {
+                    journal.push(entry);
+                }
+            }
+        }
+    }
+
+    pub(super) fn record_block_entity_edit(&mut self, pos: BlockPos, old: Block) {
+        if let Some(journal) = &mut self.journal {
+            journal.pending.push(JournalEntry {
+                pos,
+                new: old.clone(),
+                old,
+                cause: journal.cause,
+                tick: self.tick,
+            });
+        }
+    }
+
+    pub(super) fn end_journal_tick(&mut self) {
+        self.flush_journal();
+
+        if let Some(journal) = &mut self.journal {
+            journal.cause = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::{compound, Value};
+    use valence_protocol::BlockState;
+
+    use super::*;
+
+    #[test]
+    fn rollback_by_cause_and_ticks() {
+        let mut layer = ChunkLayer::with_test_chunks();
+        let griefer = Entity::from_raw(1);
+        let builder = Entity::from_raw(2);
+
+        layer.set_block([0, 0, 0], BlockState::STONE);
+        layer.enable_journal();
+
+        layer.set_change_cause(Some(builder));
+        layer.set_block([0, 1, 0], BlockState::OAK_PLANKS);
+        layer.set_block([1, 1, 0], BlockState::OAK_PLANKS);
+        layer.end_journal_tick();
+
+        layer.tick = 1;
+        layer.set_change_cause(Some(griefer));
+        layer.set_block([0, 0, 0], BlockState::TNT);
+        layer.set_block([0, 1, 0], BlockState::AIR);
+        // Setting the same block isn't a change.
+        layer.set_block([0, 1, 0], BlockState::AIR);
+        layer.end_journal_tick();
+
+        layer.tick = 2;
+        layer.set_block([0, 0, 0], BlockState::FIRE);
+
+        let journal = layer.journal().unwrap();
+        assert_eq!(journal.len(), 5);
+        assert_eq!(journal.entries()[4].cause, None);
+
+        let last = journal.changes_at([0, 0, 0]).nth(1).unwrap();
+        assert_eq!(last.cause, Some(griefer));
+        assert_eq!(last.old.state, BlockState::STONE);
+        assert_eq!(last.tick, 1);
+
+        let undone = layer.rollback(&JournalQuery {
+            cause: Some(griefer),
+            ..Default::default()
+        });
+
+        assert_eq!(undone, 2);
+        assert_eq!(layer.block([0, 0, 0]).unwrap().state, BlockState::STONE);
+        assert_eq!(
+            layer.block([0, 1, 0]).unwrap().state,
+            BlockState::OAK_PLANKS
+        );
+        // Rolling back isn't recorded.
+        assert_eq!(layer.journal().unwrap().len(), 3);
+
+        let undone = layer.rollback(&JournalQuery {
+            ticks: Some(0..=1),
+            region: Some(BlockRegion::new([1, 0, 0], [1, 1, 0])),
+            ..Default::default()
+        });
+
+        assert_eq!(undone, 1);
+        assert_eq!(layer.block([1, 1, 0]).unwrap().state, BlockState::AIR);
+        assert_eq!(
+            layer.block([0, 1, 0]).unwrap().state,
+            BlockState::OAK_PLANKS
+        );
+    }
+
+    #[test]
+    fn journal_block_entities_and_regions() {
+        let mut layer = ChunkLayer::with_test_chunks();
+
+        layer.set_block([3, 0, 3], BlockState::CHEST);
+        layer.enable_journal();
+
+        layer
+            .block_entity_mut([3, 0, 3])
+            .unwrap()
+            .insert("Lock", "key");
+        layer
+            .block_entity_mut([3, 0, 3])
+            .unwrap()
+            .insert("CustomName", "Chest");
+
+        layer.fill(
+            BlockRegion::new([-20, 0, -20], [20, 15, 20]),
+            BlockState::SAND,
+        );
+
+        let journal = layer.journal_mut().unwrap();
+        let entries = journal.changes_at([3, 0, 3]).collect::<Vec<_>>();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].new.state, BlockState::SAND);
+        assert_eq!(entries[1].old.nbt, Some(compound! { "Lock" => "key" }));
+        assert_eq!(
+            entries[1].new.nbt.as_ref().unwrap().get("CustomName"),
+            Some(&Value::from("Chest"))
+        );
+        assert_eq!(entries[2].old.nbt, Some(compound! {}));
+
+        // Resetting the arena.
+        layer.rollback(&JournalQuery::default());
+
+        assert!(layer.journal().unwrap().is_empty());
+        assert_eq!(layer.block([-20, 0, -20]).unwrap().state, BlockState::AIR);
+        assert_eq!(layer.block([3, 0, 3]).unwrap().nbt, Some(&compound! {}));
+    }
+
+    #[test]
+    fn journal_capacity() {
+        let mut layer = ChunkLayer::with_test_chunks();
+
+        layer.enable_journal();
+        layer.journal_mut().unwrap().set_capacity(100);
+
+        layer.fill(
+            BlockRegion::new([-20, 0, -20], [20, 15, 20]),
+            BlockState::SAND,
+        );
+
+        let journal = layer.journal().unwrap();
+        assert_eq!(journal.len(), 100);
+        assert_eq!(journal.entries()[99].pos, BlockPos::new(20, 15, 20));
+
+        layer.set_block([0, 0, 0], BlockState::STONE);
+
+        let journal = layer.journal_mut().unwrap();
+        assert_eq!(journal.len(), 100);
+        assert_eq!(journal.entries()[99].new.state, BlockState::STONE);
+
+        journal.set_capacity(10);
+        assert_eq!(journal.len(), 10);
+        assert_eq!(journal.entries()[9].new.state, BlockState::STONE);
+
+        // Only the changes that are still recorded are undone.
+        assert_eq!(layer.rollback(&JournalQuery::default()), 10);
+        assert_eq!(layer.block([0, 0, 0]).unwrap().state, BlockState::SAND);
+        assert_eq!(layer.block([-20, 0, -20]).unwrap().state, BlockState::SAND);
+    }
+}
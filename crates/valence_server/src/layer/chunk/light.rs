@@ -0,0 +1,1069 @@
+//! Server-side sky and block light.
+//!
+//! Light is stored per [`LoadedChunk`] and computed by the [`LightEngine`] of
+//! the owning [`ChunkLayer`](super::ChunkLayer). Newly inserted chunks are lit
+//! from scratch over the next few ticks (see
+//! [`LightSettings::max_relights_per_tick`]), while block changes in lit chunks
+//! are applied incrementally with the usual flood fill removal and increase
+//! passes. Light changes are sent to clients with [`LightUpdateS2c`] packets.
+//!
+//! [`LightUpdateS2c`]: valence_protocol::packets::play::LightUpdateS2c
+
+use std::collections::{BTreeSet, VecDeque};
+use std::mem;
+
+use rustc_hash::FxHashMap;
+use valence_protocol::{BlockState, ChunkPos, FixedArray};
+
+use super::chunk::{Chunk, SECTION_BLOCK_COUNT};
+use super::loaded::LoadedChunk;
+use super::ChunkLayerInfo;
+
+/// The maximum light level of sky and block light.
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// The number of bytes in the light array of a single section. Every byte
+/// holds the light levels of two blocks.
+const LIGHT_ARRAY_LEN: usize = SECTION_BLOCK_COUNT / 2;
+
+/// Settings for the light engine of a [`ChunkLayer`](super::ChunkLayer).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LightSettings {
+    /// Whether sky and block light are computed for the chunk layer. When
+    /// disabled, chunks are sent to clients without any light data.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub enabled: bool,
+    /// The maximum number of chunks that are lit from scratch each tick.
+    /// Chunks that are in view of clients are lit first. Lower values spread
+    /// the cost of lighting large amounts of newly generated chunks over more
+    /// ticks.
+    ///
+    /// # Default Value
+    ///
+    /// The default value is left unspecified and may change in future versions.
+    pub max_relights_per_tick: usize,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_relights_per_tick: 32,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum LightKind {
+    Sky,
+    Block,
+}
+
+impl LightKind {
+    const ALL: [Self; 2] = [Self::Sky, Self::Block];
+
+    const fn index(self) -> usize {
+        match self {
+            Self::Sky => 0,
+            Self::Block => 1,
+        }
+    }
+}
+
+/// The light levels of a 16x16x16 chunk section.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) enum LightSection {
+    /// Every block in the section has the same light level.
+    Uniform(u8),
+    /// Light levels packed as nibbles, indexed by `x + z * 16 + y * 16 * 16`.
+    Array(Box<[u8; LIGHT_ARRAY_LEN]>),
+}
+
+impl Default for LightSection {
+    fn default() -> Self {
+        Self::Uniform(0)
+    }
+}
+
+impl LightSection {
+    fn get(&self, idx: usize) -> u8 {
+        match self {
+            Self::Uniform(level) => *level,
+            Self::Array(arr) => arr[idx / 2] >> (idx % 2 * 4) & 0xf,
+        }
+    }
+
+    /// Sets the light level at `idx`. Returns `true` if the level changed.
+    fn set(&mut self, idx: usize, level: u8) -> bool {
+        debug_assert!(level <= MAX_LIGHT_LEVEL);
+
+        match self {
+            Self::Uniform(old) => {
+                if *old == level {
+                    return false;
+                }
+
+                let mut arr = Box::new([*old | *old << 4; LIGHT_ARRAY_LEN]);
+                set_nibble(&mut arr, idx, level);
+                *self = Self::Array(arr);
+
+                true
+            }
+            Self::Array(arr) => {
+                if arr[idx / 2] >> (idx % 2 * 4) & 0xf == level {
+                    return false;
+                }
+
+                set_nibble(arr, idx, level);
+
+                true
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Self::Uniform(0))
+    }
+
+    /// Converts the section back to [`LightSection::Uniform`] if all light
+    /// levels are the same.
+    fn shrink_to_fit(&mut self) {
+        if let Self::Array(arr) = self {
+            let first = arr[0];
+
+            if first >> 4 == first & 0xf && arr.iter().all(|&b| b == first) {
+                *self = Self::Uniform(first & 0xf);
+            }
+        }
+    }
+
+    fn to_array(&self) -> FixedArray<u8, LIGHT_ARRAY_LEN> {
+        match self {
+            Self::Uniform(level) => FixedArray([*level | *level << 4; LIGHT_ARRAY_LEN]),
+            Self::Array(arr) => FixedArray(**arr),
+        }
+    }
+}
+
+fn set_nibble(arr: &mut [u8; LIGHT_ARRAY_LEN], idx: usize, level: u8) {
+    let shift = idx % 2 * 4;
+    arr[idx / 2] = arr[idx / 2] & !(0xf << shift) | level << shift;
+}
+
+/// The sky and block light of a [`LoadedChunk`].
+#[derive(Clone, Debug)]
+pub(super) struct ChunkLight {
+    sections: [Box<[LightSection]>; 2],
+    /// Whether the light of this chunk has been computed. Chunks that are not
+    /// lit don't take part in light propagation until the light engine gets to
+    /// them.
+    is_lit: bool,
+    /// Whether the light of this chunk needs to be computed from scratch. The
+    /// previous light stays visible to clients until then.
+    needs_relight: bool,
+    /// Blocks that had a light-relevant change since the last update, stored
+    /// as `x + z * 16 + y * 16 * 16`.
+    pending: Vec<u32>,
+    /// Sections with light changes that clients haven't been sent yet.
+    changed: [BTreeSet<u32>; 2],
+}
+
+impl ChunkLight {
+    pub(super) fn new(section_count: usize) -> Self {
+        Self {
+            sections: [
+                vec![LightSection::default(); section_count].into(),
+                vec![LightSection::default(); section_count].into(),
+            ],
+            is_lit: false,
+            needs_relight: true,
+            pending: vec![],
+            changed: [BTreeSet::new(), BTreeSet::new()],
+        }
+    }
+
+    pub(super) fn sky_light(&self, idx: u32) -> u8 {
+        self.get(LightKind::Sky, idx)
+    }
+
+    pub(super) fn block_light(&self, idx: u32) -> u8 {
+        self.get(LightKind::Block, idx)
+    }
+
+    /// Records a block state change at `idx` so that light can be updated
+    /// around it.
+    pub(super) fn block_state_changed(&mut self, idx: u32, old: BlockState, new: BlockState) {
+        if self.is_lit
+            && !self.needs_relight
+            && (old.luminance() != new.luminance() || old.is_opaque() != new.is_opaque())
+        {
+            self.pending.push(idx);
+        }
+    }
+
+    /// Discards the light of this chunk. The chunk is unlit until the light
+    /// engine lights it from scratch.
+    pub(super) fn invalidate(&mut self) {
+        self.is_lit = false;
+        self.needs_relight = true;
+        self.pending.clear();
+        self.clear_changes();
+    }
+
+    /// Schedules this chunk to be lit from scratch, keeping the current light
+    /// until then. Used when too many blocks change at once to update the
+    /// light incrementally.
+    pub(super) fn schedule_relight(&mut self) {
+        self.needs_relight = true;
+        self.pending.clear();
+    }
+
+    pub(super) fn has_changes(&self) -> bool {
+        self.changed.iter().any(|c| !c.is_empty())
+    }
+
+    pub(super) fn clear_changes(&mut self) {
+        for changed in &mut self.changed {
+            changed.clear();
+        }
+    }
+
+    pub(super) fn shrink_to_fit(&mut self) {
+        for sections in &mut self.sections {
+            for sect in sections.iter_mut() {
+                sect.shrink_to_fit();
+            }
+        }
+
+        self.pending.shrink_to_fit();
+    }
+
+    /// Returns the light data for the chunk initialization packet.
+    pub(super) fn init_data(&self, info: &ChunkLayerInfo) -> LightData {
+        let mut data = LightData::default();
+
+        if !self.is_lit {
+            return data;
+        }
+
+        let sect_count = self.sections[0].len();
+
+        // The packet includes one extra section below and above the world. There is
+        // never any light below the world, but the sky is fully lit above it.
+        set_bit(&mut data.empty_sky_mask, 0);
+        set_bit(&mut data.empty_block_mask, 0);
+
+        for sect_y in 0..sect_count {
+            data.push_section(self, LightKind::Sky, sect_y);
+            data.push_section(self, LightKind::Block, sect_y);
+        }
+
+        if info.has_skylight {
+            set_bit(&mut data.sky_mask, sect_count + 1);
+            data.sky_arrays
+                .push(LightSection::Uniform(MAX_LIGHT_LEVEL).to_array());
+        } else {
+            set_bit(&mut data.empty_sky_mask, sect_count + 1);
+        }
+
+        set_bit(&mut data.empty_block_mask, sect_count + 1);
+
+        data
+    }
+
+    /// Returns the light data of the sections that changed since the last call
+    /// to [`Self::clear_changes`].
+    pub(super) fn changed_data(&self) -> LightData {
+        let mut data = LightData::default();
+
+        for kind in LightKind::ALL {
+            for &sect_y in &self.changed[kind.index()] {
+                data.push_section(self, kind, sect_y as usize);
+            }
+        }
+
+        data
+    }
+
+    fn get(&self, kind: LightKind, idx: u32) -> u8 {
+        let idx = idx as usize;
+
+        self.sections[kind.index()][idx / SECTION_BLOCK_COUNT].get(idx % SECTION_BLOCK_COUNT)
+    }
+
+    fn set(&mut self, kind: LightKind, idx: u32, level: u8) {
+        let idx = idx as usize;
+        let sect_y = idx / SECTION_BLOCK_COUNT;
+
+        if self.sections[kind.index()][sect_y].set(idx % SECTION_BLOCK_COUNT, level) {
+            self.changed[kind.index()].insert(sect_y as u32);
+        }
+    }
+
+    fn reset(&mut self) {
+        for (sections, changed) in self.sections.iter_mut().zip(&mut self.changed) {
+            for (sect_y, sect) in sections.iter_mut().enumerate() {
+                *sect = LightSection::default();
+                changed.insert(sect_y as u32);
+            }
+        }
+
+        self.pending.clear();
+    }
+}
+
+/// The light masks and arrays as they appear in the chunk data and light
+/// update packets. Bit `n` of a mask corresponds to the section at `n - 1`
+/// because of the extra section below the world.
+#[derive(Clone, Default, Debug)]
+pub(super) struct LightData {
+    pub(super) sky_mask: Vec<u64>,
+    pub(super) block_mask: Vec<u64>,
+    pub(super) empty_sky_mask: Vec<u64>,
+    pub(super) empty_block_mask: Vec<u64>,
+    pub(super) sky_arrays: Vec<FixedArray<u8, LIGHT_ARRAY_LEN>>,
+    pub(super) block_arrays: Vec<FixedArray<u8, LIGHT_ARRAY_LEN>>,
+}
+
+impl LightData {
+    fn push_section(&mut self, light: &ChunkLight, kind: LightKind, sect_y: usize) {
+        let sect = &light.sections[kind.index()][sect_y];
+
+        let (mask, empty_mask, arrays) = match kind {
+            LightKind::Sky => (
+                &mut self.sky_mask,
+                &mut self.empty_sky_mask,
+                &mut self.sky_arrays,
+            ),
+            LightKind::Block => (
+                &mut self.block_mask,
+                &mut self.empty_block_mask,
+                &mut self.block_arrays,
+            ),
+        };
+
+        if sect.is_empty() {
+            set_bit(empty_mask, sect_y + 1);
+        } else {
+            set_bit(mask, sect_y + 1);
+            arrays.push(sect.to_array());
+        }
+    }
+}
+
+fn set_bit(mask: &mut Vec<u64>, bit: usize) {
+    let word = bit / 64;
+
+    if mask.len() <= word {
+        mask.resize(word + 1, 0);
+    }
+
+    mask[word] |= 1 << (bit % 64);
+}
+
+/// A request to propagate light into or out of a block during an increase
+/// pass.
+#[derive(Copy, Clone, Debug)]
+enum Increase {
+    /// Spread the current light level of the block to its neighbors.
+    Propagate(u32),
+    /// Raise the light level of the block to the given level if it is lower
+    /// and the block is not opaque, then spread it.
+    Raise(u32, u8),
+}
+
+/// A request to remove light during a removal pass.
+#[derive(Copy, Clone, Debug)]
+enum Removal {
+    /// The block was already darkened from the given light level. Remove the
+    /// light of its neighbors that depended on it.
+    Darkened(u32, u8),
+    /// A neighbor in another chunk with the given light level was darkened.
+    /// Darken the block if its light depended on that neighbor.
+    Check(u32, u8),
+}
+
+/// Where the neighbor of a block is.
+enum Neighbor {
+    /// In the same chunk at the given index.
+    Inside(u32),
+    /// In the chunk at the given offset from the current chunk, at the given
+    /// index.
+    Outside(ChunkPos, u32),
+    /// Above or below the world.
+    OutOfBounds,
+}
+
+/// The six directions light travels in, as `(dx, dy, dz)`.
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (0, -1, 0),
+    (0, 1, 0),
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+fn neighbor(pos: ChunkPos, idx: u32, (dx, dy, dz): (i32, i32, i32), height: u32) -> Neighbor {
+    let x = (idx % 16) as i32 + dx;
+    let z = (idx / 16 % 16) as i32 + dz;
+    let y = (idx / 16 / 16) as i32 + dy;
+
+    if y < 0 || y >= height as i32 {
+        return Neighbor::OutOfBounds;
+    }
+
+    let new_idx = (x.rem_euclid(16) + z.rem_euclid(16) * 16 + y * 16 * 16) as u32;
+
+    if (0..16).contains(&x) && (0..16).contains(&z) {
+        Neighbor::Inside(new_idx)
+    } else {
+        Neighbor::Outside(
+            ChunkPos::new(pos.x + x.div_euclid(16), pos.z + z.div_euclid(16)),
+            new_idx,
+        )
+    }
+}
+
+/// Returns the light level that travels from a block with light level `level`
+/// to its neighbor in direction `dir`. Sky light at full strength travels down
+/// without losing any strength.
+fn spread(kind: LightKind, level: u8, dir: (i32, i32, i32)) -> u8 {
+    if kind == LightKind::Sky && dir == DIRECTIONS[0] && level == MAX_LIGHT_LEVEL {
+        MAX_LIGHT_LEVEL
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+fn block_state_at(chunk: &LoadedChunk, idx: u32) -> BlockState {
+    chunk.block_state(idx % 16, idx / 16 / 16, idx / 16 % 16)
+}
+
+/// Returns the light level emitted by the block at `idx`.
+fn emitted_light(
+    kind: LightKind,
+    idx: u32,
+    state: BlockState,
+    height: u32,
+    info: &ChunkLayerInfo,
+) -> u8 {
+    match kind {
+        // The top of the world is lit by the sky.
+        LightKind::Sky => {
+            if info.has_skylight && idx / 16 / 16 == height - 1 && !state.is_opaque() {
+                MAX_LIGHT_LEVEL
+            } else {
+                0
+            }
+        }
+        LightKind::Block => state.luminance(),
+    }
+}
+
+/// Computes and updates the light of the chunks in a
+/// [`ChunkLayer`](super::ChunkLayer).
+#[derive(Debug, Default)]
+pub(super) struct LightEngine {
+    pub(super) settings: LightSettings,
+    /// Pending removal requests per light kind and chunk.
+    removals: [FxHashMap<ChunkPos, Vec<Removal>>; 2],
+    /// Pending increase requests per light kind and chunk.
+    increases: [FxHashMap<ChunkPos, Vec<Increase>>; 2],
+    /// Queue reused between propagation passes.
+    queue: VecDeque<(u32, u8)>,
+}
+
+impl LightEngine {
+    /// Lights new chunks and applies the block changes recorded since the last
+    /// update.
+    pub(super) fn update(
+        &mut self,
+        chunks: &mut FxHashMap<ChunkPos, LoadedChunk>,
+        info: &ChunkLayerInfo,
+    ) {
+        if !self.settings.enabled {
+            for chunk in chunks.values_mut() {
+                chunk.light.pending.clear();
+            }
+
+            return;
+        }
+
+        // Light chunks that were never lit, preferring those in view of clients.
+        let mut unlit: Vec<_> = chunks
+            .iter_mut()
+            .filter(|(_, chunk)| chunk.light.needs_relight)
+            .map(|(pos, chunk)| (chunk.viewer_count_mut(), *pos))
+            .collect();
+
+        if unlit.len() > self.settings.max_relights_per_tick {
+            unlit.sort_unstable_by(|a, b| b.cmp(a));
+            unlit.truncate(self.settings.max_relights_per_tick);
+        }
+
+        for &(_, pos) in &unlit {
+            self.relight_chunk(pos, chunks, info);
+        }
+
+        // Queue the incremental updates of lit chunks.
+        for (&pos, chunk) in chunks.iter_mut() {
+            if chunk.light.pending.is_empty() {
+                continue;
+            }
+
+            let height = chunk.height();
+
+            for idx in mem::take(&mut chunk.light.pending) {
+                let state = block_state_at(chunk, idx);
+
+                for kind in LightKind::ALL {
+                    if kind == LightKind::Sky && !info.has_skylight {
+                        continue;
+                    }
+
+                    let old = chunk.light.get(kind, idx);
+
+                    if old > 0 {
+                        chunk.light.set(kind, idx, 0);
+                        self.removals[kind.index()]
+                            .entry(pos)
+                            .or_default()
+                            .push(Removal::Darkened(idx, old));
+                    }
+
+                    let emitted = emitted_light(kind, idx, state, height, info);
+
+                    if emitted > 0 {
+                        chunk.light.set(kind, idx, emitted);
+                        self.push_increase(kind, pos, Increase::Propagate(idx));
+                    }
+
+                    // Let the light of the neighbors flow back into the block.
+                    for dir in DIRECTIONS {
+                        match neighbor(pos, idx, dir, height) {
+                            Neighbor::Inside(n) => {
+                                self.push_increase(kind, pos, Increase::Propagate(n))
+                            }
+                            Neighbor::Outside(npos, n) => {
+                                self.push_increase(kind, npos, Increase::Propagate(n))
+                            }
+                            Neighbor::OutOfBounds => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        for kind in LightKind::ALL {
+            self.run_removals(kind, chunks, info);
+            self.run_increases(kind, chunks);
+        }
+
+        for (_, pos) in unlit {
+            if let Some(chunk) = chunks.get_mut(&pos) {
+                chunk.light.shrink_to_fit();
+            }
+        }
+    }
+
+    fn push_increase(&mut self, kind: LightKind, pos: ChunkPos, increase: Increase) {
+        self.increases[kind.index()]
+            .entry(pos)
+            .or_default()
+            .push(increase);
+    }
+
+    /// Computes the light of the chunk at `pos` from scratch. Light flowing
+    /// into and out of neighboring chunks is queued as increase requests.
+    fn relight_chunk(
+        &mut self,
+        pos: ChunkPos,
+        chunks: &mut FxHashMap<ChunkPos, LoadedChunk>,
+        info: &ChunkLayerInfo,
+    ) {
+        let Some(chunk) = chunks.get_mut(&pos) else {
+            return;
+        };
+
+        let height = chunk.height();
+
+        chunk.light.reset();
+        chunk.light.is_lit = true;
+        chunk.light.needs_relight = false;
+
+        if info.has_skylight {
+            // For every column, find the lowest block that the sky shines on.
+            let mut lowest = [0_u32; 16 * 16];
+
+            for (col, lowest) in lowest.iter_mut().enumerate() {
+                let (x, z) = (col as u32 % 16, col as u32 / 16);
+                let mut y = height;
+
+                while y > 0 {
+                    // Skip over sections filled with a single transparent block.
+                    if y % 16 == 0
+                        && chunk
+                            .uniform_section(y / 16 - 1)
+                            .is_some_and(|state| !state.is_opaque())
+                    {
+                        y -= 16;
+                        continue;
+                    }
+
+                    if chunk.block_state(x, y - 1, z).is_opaque() {
+                        break;
+                    }
+
+                    y -= 1;
+                }
+
+                *lowest = y;
+            }
+
+            // Sections above every covered block are lit uniformly.
+            let max_lowest = lowest.iter().copied().max().unwrap_or(0);
+            let uniform_start = max_lowest.div_ceil(16) * 16;
+
+            for sect_y in uniform_start / 16..height / 16 {
+                chunk.light.sections[LightKind::Sky.index()][sect_y as usize] =
+                    LightSection::Uniform(MAX_LIGHT_LEVEL);
+            }
+
+            for (col, &low) in lowest.iter().enumerate() {
+                for y in low..uniform_start {
+                    chunk
+                        .light
+                        .set(LightKind::Sky, col as u32 + y * 16 * 16, MAX_LIGHT_LEVEL);
+                }
+            }
+
+            // Spread sky light sideways into the columns that are covered.
+            for (col, &low) in lowest.iter().enumerate() {
+                let (x, z) = (col % 16, col / 16);
+
+                let mut top = low;
+
+                for (nx, nz) in [
+                    (x as i32 - 1, z as i32),
+                    (x as i32 + 1, z as i32),
+                    (x as i32, z as i32 - 1),
+                    (x as i32, z as i32 + 1),
+                ] {
+                    top = top.max(if (0..16).contains(&nx) && (0..16).contains(&nz) {
+                        lowest[nx as usize + nz as usize * 16]
+                    } else {
+                        // The neighboring chunk may be covered anywhere.
+                        height
+                    });
+                }
+
+                for y in low..top {
+                    self.push_increase(
+                        LightKind::Sky,
+                        pos,
+                        Increase::Propagate(col as u32 + y * 16 * 16),
+                    );
+                }
+            }
+        }
+
+        for sect_y in 0..height / 16 {
+            if chunk
+                .uniform_section(sect_y)
+                .is_some_and(|state| state.luminance() == 0)
+            {
+                continue;
+            }
+
+            for i in 0..SECTION_BLOCK_COUNT as u32 {
+                let idx = sect_y * SECTION_BLOCK_COUNT as u32 + i;
+                let luminance = block_state_at(chunk, idx).luminance();
+
+                if luminance > 0 {
+                    chunk.light.set(LightKind::Block, idx, luminance);
+                    self.push_increase(LightKind::Block, pos, Increase::Propagate(idx));
+                }
+            }
+        }
+
+        // Pull in the light of lit neighbors.
+        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let npos = ChunkPos::new(pos.x + dx, pos.z + dz);
+
+            let Some(neighbor) = chunks.get(&npos) else {
+                continue;
+            };
+
+            if !neighbor.light.is_lit || neighbor.height() != height {
+                continue;
+            }
+
+            for kind in LightKind::ALL {
+                for y in 0..height {
+                    for i in 0..16 {
+                        let (x, z, nx, nz) = match (dx, dz) {
+                            (-1, _) => (0, i, 15, i),
+                            (1, _) => (15, i, 0, i),
+                            (_, -1) => (i, 0, i, 15),
+                            _ => (i, 15, i, 0),
+                        };
+
+                        let level = neighbor.light.get(kind, nx + nz * 16 + y * 16 * 16);
+
+                        if level > 1 {
+                            self.push_increase(
+                                kind,
+                                pos,
+                                Increase::Raise(x + z * 16 + y * 16 * 16, level - 1),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_removals(
+        &mut self,
+        kind: LightKind,
+        chunks: &mut FxHashMap<ChunkPos, LoadedChunk>,
+        info: &ChunkLayerInfo,
+    ) {
+        loop {
+            let batch = mem::take(&mut self.removals[kind.index()]);
+
+            if batch.is_empty() {
+                break;
+            }
+
+            for (pos, removals) in batch {
+                let Some(chunk) = chunks.get_mut(&pos) else {
+                    continue;
+                };
+
+                if !chunk.light.is_lit {
+                    continue;
+                }
+
+                let height = chunk.height();
+
+                for removal in removals {
+                    match removal {
+                        Removal::Darkened(idx, level) => self.queue.push_back((idx, level)),
+                        Removal::Check(idx, level) => {
+                            self.darken(kind, pos, chunk, idx, level, false, info)
+                        }
+                    }
+                }
+
+                while let Some((idx, level)) = self.queue.pop_front() {
+                    for dir in DIRECTIONS {
+                        match neighbor(pos, idx, dir, height) {
+                            Neighbor::Inside(n) => {
+                                let is_down = dir == DIRECTIONS[0];
+                                self.darken(kind, pos, chunk, n, level, is_down, info);
+                            }
+                            Neighbor::Outside(npos, n) => self.removals[kind.index()]
+                                .entry(npos)
+                                .or_default()
+                                .push(Removal::Check(n, level)),
+                            Neighbor::OutOfBounds => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Darkens the block at `idx` if its light came from a neighbor that had
+    /// the light level `from` before being darkened. Otherwise, the light of
+    /// the block is spread again to fill in the darkened area.
+    #[allow(clippy::too_many_arguments)]
+    fn darken(
+        &mut self,
+        kind: LightKind,
+        pos: ChunkPos,
+        chunk: &mut LoadedChunk,
+        idx: u32,
+        from: u8,
+        is_down: bool,
+        info: &ChunkLayerInfo,
+    ) {
+        let level = chunk.light.get(kind, idx);
+
+        if level == 0 {
+            return;
+        }
+
+        let is_dependent = level < from
+            || (kind == LightKind::Sky
+                && is_down
+                && from == MAX_LIGHT_LEVEL
+                && level == MAX_LIGHT_LEVEL);
+
+        if is_dependent {
+            chunk.light.set(kind, idx, 0);
+            self.queue.push_back((idx, level));
+
+            let emitted =
+                emitted_light(kind, idx, block_state_at(chunk, idx), chunk.height(), info);
+
+            if emitted > 0 {
+                chunk.light.set(kind, idx, emitted);
+                self.push_increase(kind, pos, Increase::Propagate(idx));
+            }
+        } else {
+            self.push_increase(kind, pos, Increase::Propagate(idx));
+        }
+    }
+
+    fn run_increases(&mut self, kind: LightKind, chunks: &mut FxHashMap<ChunkPos, LoadedChunk>) {
+        loop {
+            let batch = mem::take(&mut self.increases[kind.index()]);
+
+            if batch.is_empty() {
+                break;
+            }
+
+            for (pos, increases) in batch {
+                let Some(chunk) = chunks.get_mut(&pos) else {
+                    continue;
+                };
+
+                if !chunk.light.is_lit {
+                    continue;
+                }
+
+                let height = chunk.height();
+
+                for increase in increases {
+                    match increase {
+                        Increase::Propagate(idx) => {
+                            let level = chunk.light.get(kind, idx);
+
+                            if level > 0 {
+                                self.queue.push_back((idx, level));
+                            }
+                        }
+                        Increase::Raise(idx, level) => {
+                            if level > chunk.light.get(kind, idx)
+                                && !block_state_at(chunk, idx).is_opaque()
+                            {
+                                chunk.light.set(kind, idx, level);
+                                self.queue.push_back((idx, level));
+                            }
+                        }
+                    }
+                }
+
+                while let Some((idx, level)) = self.queue.pop_front() {
+                    // The light level may have been raised again after this was queued.
+                    let level = level.max(chunk.light.get(kind, idx));
+
+                    for dir in DIRECTIONS {
+                        let new_level = spread(kind, level, dir);
+
+                        if new_level == 0 {
+                            continue;
+                        }
+
+                        match neighbor(pos, idx, dir, height) {
+                            Neighbor::Inside(n) => {
+                                if new_level > chunk.light.get(kind, n)
+                                    && !block_state_at(chunk, n).is_opaque()
+                                {
+                                    chunk.light.set(kind, n, new_level);
+                                    self.queue.push_back((n, new_level));
+                                }
+                            }
+                            Neighbor::Outside(npos, n) => {
+                                self.push_increase(kind, npos, Increase::Raise(n, new_level))
+                            }
+                            Neighbor::OutOfBounds => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::compression::CompressionOptions;
+    use valence_protocol::CompressionThreshold;
+    use valence_registry::dimension_type::DimensionTypeId;
+
+    use super::*;
+
+    fn info() -> ChunkLayerInfo {
+        ChunkLayerInfo {
+            dimension_type: DimensionTypeId::new(0),
+            height: 64,
+            min_y: 0,
+            has_skylight: true,
+            biome_registry_len: 200,
+            threshold: CompressionThreshold(-1),
+            compression: CompressionOptions::DEFAULT,
+        }
+    }
+
+    fn lit_chunks(positions: &[(i32, i32)]) -> (LightEngine, FxHashMap<ChunkPos, LoadedChunk>) {
+        let mut engine = LightEngine::default();
+        let mut chunks = FxHashMap::default();
+
+        for &(x, z) in positions {
+            chunks.insert(ChunkPos::new(x, z), LoadedChunk::new(64));
+        }
+
+        engine.update(&mut chunks, &info());
+
+        (engine, chunks)
+    }
+
+    #[test]
+    fn light_section_get_set() {
+        let mut sect = LightSection::default();
+
+        assert!(sect.is_empty());
+        assert!(!sect.set(123, 0));
+        assert!(sect.set(123, 7));
+        assert!(sect.set(124, 15));
+        assert_eq!(sect.get(123), 7);
+        assert_eq!(sect.get(124), 15);
+        assert_eq!(sect.get(125), 0);
+
+        sect.set(123, 0);
+        sect.set(124, 0);
+        sect.shrink_to_fit();
+        assert_eq!(sect, LightSection::Uniform(0));
+    }
+
+    #[test]
+    fn open_sky_is_fully_lit() {
+        let (_, chunks) = lit_chunks(&[(0, 0)]);
+        let chunk = &chunks[&ChunkPos::new(0, 0)];
+
+        for y in [0, 31, 63] {
+            assert_eq!(chunk.sky_light(3, y, 9), MAX_LIGHT_LEVEL);
+            assert_eq!(chunk.block_light(3, y, 9), 0);
+        }
+    }
+
+    #[test]
+    fn block_light_spreads_and_is_removed() {
+        let (mut engine, mut chunks) = lit_chunks(&[(0, 0), (1, 0)]);
+        let pos = ChunkPos::new(0, 0);
+
+        chunks
+            .get_mut(&pos)
+            .unwrap()
+            .set_block_state(14, 10, 8, BlockState::TORCH);
+        engine.update(&mut chunks, &info());
+
+        let luminance = BlockState::TORCH.luminance();
+        let chunk = &chunks[&pos];
+        assert_eq!(chunk.block_light(14, 10, 8), luminance);
+        assert_eq!(chunk.block_light(12, 10, 8), luminance - 2);
+        assert_eq!(chunk.block_light(14, 13, 9), luminance - 4);
+
+        // Light crosses into the neighboring chunk.
+        let neighbor = &chunks[&ChunkPos::new(1, 0)];
+        assert_eq!(neighbor.block_light(0, 10, 8), luminance - 2);
+
+        chunks
+            .get_mut(&pos)
+            .unwrap()
+            .set_block_state(14, 10, 8, BlockState::AIR);
+        engine.update(&mut chunks, &info());
+
+        assert_eq!(chunks[&pos].block_light(14, 10, 8), 0);
+        assert_eq!(chunks[&pos].block_light(12, 10, 8), 0);
+        assert_eq!(chunks[&ChunkPos::new(1, 0)].block_light(0, 10, 8), 0);
+    }
+
+    #[test]
+    fn roof_blocks_sky_light() {
+        let (mut engine, mut chunks) = lit_chunks(&[(0, 0)]);
+        let pos = ChunkPos::new(0, 0);
+
+        let chunk = chunks.get_mut(&pos).unwrap();
+        for x in 5..=11 {
+            for z in 5..=11 {
+                chunk.set_block_state(x, 40, z, BlockState::STONE);
+            }
+        }
+        engine.update(&mut chunks, &info());
+
+        let chunk = &chunks[&pos];
+        assert_eq!(chunk.sky_light(8, 41, 8), MAX_LIGHT_LEVEL);
+        assert_eq!(chunk.sky_light(8, 40, 8), 0);
+        // Light enters from the sides of the roof.
+        assert_eq!(chunk.sky_light(5, 39, 8), MAX_LIGHT_LEVEL - 1);
+        assert_eq!(chunk.sky_light(8, 39, 8), MAX_LIGHT_LEVEL - 4);
+        // Below the shadow, light from the open sides spreads back in.
+        assert_eq!(chunk.sky_light(8, 10, 8), MAX_LIGHT_LEVEL - 4);
+
+        let chunk = chunks.get_mut(&pos).unwrap();
+        chunk.set_block_state(8, 40, 8, BlockState::AIR);
+        engine.update(&mut chunks, &info());
+
+        assert_eq!(chunks[&pos].sky_light(8, 39, 8), MAX_LIGHT_LEVEL);
+        assert_eq!(chunks[&pos].sky_light(8, 10, 8), MAX_LIGHT_LEVEL);
+
+        let chunk = chunks.get_mut(&pos).unwrap();
+        chunk.set_block_state(8, 40, 8, BlockState::STONE);
+        engine.update(&mut chunks, &info());
+
+        assert_eq!(chunks[&pos].sky_light(8, 39, 8), MAX_LIGHT_LEVEL - 4);
+    }
+
+    #[test]
+    fn relit_chunk_matches_incremental_updates() {
+        let (mut engine, mut chunks) = lit_chunks(&[(0, 0)]);
+        let pos = ChunkPos::new(0, 0);
+
+        let chunk = chunks.get_mut(&pos).unwrap();
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_state(x, 30, z, BlockState::STONE);
+            }
+        }
+        chunk.set_block_state(4, 30, 4, BlockState::AIR);
+        chunk.set_block_state(10, 20, 10, BlockState::TORCH);
+        engine.update(&mut chunks, &info());
+
+        let incremental = chunks[&pos].light.clone();
+
+        chunks.get_mut(&pos).unwrap().light.schedule_relight();
+        engine.update(&mut chunks, &info());
+
+        let relit = &chunks[&pos].light;
+        for idx in 0..64 * 16 * 16 {
+            assert_eq!(relit.sky_light(idx), incremental.sky_light(idx), "{idx}");
+            assert_eq!(
+                relit.block_light(idx),
+                incremental.block_light(idx),
+                "{idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn init_data_masks() {
+        let (_, chunks) = lit_chunks(&[(0, 0)]);
+        let data = chunks[&ChunkPos::new(0, 0)].light.init_data(&info());
+
+        // 4 sections plus the extra sections below and above the world.
+        assert_eq!(data.sky_mask, [0b111110]);
+        assert_eq!(data.empty_sky_mask, [0b000001]);
+        assert!(data.block_mask.is_empty());
+        assert_eq!(data.empty_block_mask, [0b111111]);
+        assert_eq!(data.sky_arrays.len(), 5);
+        assert!(data.block_arrays.is_empty());
+    }
+}
@@ -4,15 +4,15 @@ use std::mem;
 use std::sync::atomic::{AtomicU32, Ordering};
 
 use parking_lot::Mutex; // Using nonstandard mutex to avoid poisoning API.
-use valence_generated::block::{PropName, PropValue};
-use valence_nbt::{compound, Compound, Value};
+use valence_nbt::Compound;
 use valence_protocol::encode::{PacketWriter, WritePacket};
 use valence_protocol::packets::play::level_chunk_with_light_s2c::ChunkDataBlockEntity;
 use valence_protocol::packets::play::section_blocks_update_s2c::ChunkDeltaUpdateEntry;
 use valence_protocol::packets::play::{
-    BlockEntityDataS2c, BlockUpdateS2c, LevelChunkWithLightS2c, SectionBlocksUpdateS2c,
+    BlockEntityDataS2c, BlockUpdateS2c, LevelChunkWithLightS2c, LightUpdateS2c,
+    SectionBlocksUpdateS2c,
 };
-use valence_protocol::{BlockPos, BlockState, ChunkPos, ChunkSectionPos, Encode};
+use valence_protocol::{BlockPos, BlockState, ChunkPos, ChunkSectionPos, Encode, VarInt};
 use valence_registry::biome::BiomeId;
 use valence_registry::RegistryIdx;
 
@@ -20,9 +20,15 @@ use super::chunk::{
     bit_width, check_biome_oob, check_block_oob, check_section_oob, BiomeContainer,
     BlockStateContainer, Chunk, SECTION_BLOCK_COUNT,
 };
+use super::heightmap::Heightmaps;
+use super::light::ChunkLight;
 use super::paletted_container::PalettedContainer;
 use super::unloaded::{self, UnloadedChunk};
-use super::{ChunkLayerInfo, ChunkLayerMessages, LocalMsg};
+use super::{ChunkLayer, ChunkLayerInfo, ChunkLayerMessages, LocalMsg};
+
+/// The number of block updates in a single tick above which the whole chunk is
+/// sent to its viewers again instead of the individual updates.
+const CHUNK_RESEND_THRESHOLD: usize = SECTION_BLOCK_COUNT;
 
 #[derive(Debug)]
 pub struct LoadedChunk {
@@ -42,6 +48,14 @@ pub struct LoadedChunk {
     /// invalidated if empty. This should be cleared whenever the chunk is
     /// modified in an observable way, even if the chunk is not viewed.
     cached_init_packets: Mutex<Vec<u8>>,
+    /// If the blocks, biomes, or block entities of this chunk have changed since
+    /// [`Self::clear_modified`] was last called. Inserting new chunk data also
+    /// counts as a change.
+    modified: bool,
+    /// Sky and block light for the chunk.
+    pub(super) light: ChunkLight,
+    /// The heightmaps of the chunk, updated whenever a block state changes.
+    heightmaps: Heightmaps,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -91,6 +105,9 @@ impl LoadedChunk {
             changed_block_entities: BTreeSet::new(),
             changed_biomes: false,
             cached_init_packets: Mutex::new(vec![]),
+            modified: false,
+            light: ChunkLight::new(height as usize / 16),
+            heightmaps: Heightmaps::new(),
         }
     }
 
@@ -104,6 +121,8 @@ impl LoadedChunk {
     pub(crate) fn insert(&mut self, mut chunk: UnloadedChunk) -> UnloadedChunk {
         chunk.set_height(self.height());
 
+        let heightmaps = chunk.heightmaps.take();
+
         let old_sections = self
             .sections
             .iter_mut()
@@ -121,11 +140,17 @@ impl LoadedChunk {
         self.changed_block_entities.clear();
         self.changed_biomes = false;
         self.cached_init_packets.get_mut().clear();
+        self.modified = true;
+        self.light.invalidate();
         self.assert_no_changes();
 
+        let heightmaps = heightmaps.unwrap_or_else(|| Heightmaps::compute(self));
+        let old_heightmaps = mem::replace(&mut self.heightmaps, heightmaps);
+
         UnloadedChunk {
             sections: old_sections,
             block_entities: old_block_entities,
+            heightmaps: Some(old_heightmaps),
         }
     }
 
@@ -146,15 +171,88 @@ impl LoadedChunk {
         self.changed_block_entities.clear();
         self.changed_biomes = false;
         self.cached_init_packets.get_mut().clear();
+        self.light.invalidate();
+        let old_heightmaps = mem::take(&mut self.heightmaps);
 
         self.assert_no_changes();
 
         UnloadedChunk {
             sections: old_sections,
             block_entities: old_block_entities,
+            heightmaps: Some(old_heightmaps),
+        }
+    }
+
+    /// Returns the sky light level at the given offsets in this chunk. Chunks
+    /// that have not been lit yet return 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are outside the bounds of the chunk.
+    #[track_caller]
+    pub fn sky_light(&self, x: u32, y: u32, z: u32) -> u8 {
+        check_block_oob(self, x, y, z);
+
+        self.light.sky_light(x + z * 16 + y * 16 * 16)
+    }
+
+    /// Returns the block light level at the given offsets in this chunk.
+    /// Chunks that have not been lit yet return 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are outside the bounds of the chunk.
+    #[track_caller]
+    pub fn block_light(&self, x: u32, y: u32, z: u32) -> u8 {
+        check_block_oob(self, x, y, z);
+
+        self.light.block_light(x + z * 16 + y * 16 * 16)
+    }
+
+    /// Returns the block state filling the section at `sect_y` if the section
+    /// contains only one block state.
+    pub(super) fn uniform_section(&self, sect_y: u32) -> Option<BlockState> {
+        match self.sections[sect_y as usize].block_states {
+            PalettedContainer::Single(state) => Some(state),
+            _ => None,
         }
     }
 
+    /// Returns `true` if the blocks, biomes, or block entities of this chunk
+    /// have changed since the last call to [`Self::clear_modified`]. Inserting
+    /// new data into the chunk also counts as a change. Useful for knowing
+    /// which chunks need to be saved.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Resets the flag returned by [`Self::is_modified`].
+    pub fn clear_modified(&mut self) {
+        self.modified = false;
+    }
+
+    /// Returns a copy of the blocks, biomes, and block entities in this chunk.
+    pub fn to_unloaded(&self) -> UnloadedChunk {
+        UnloadedChunk {
+            sections: self
+                .sections
+                .iter()
+                .map(|sect| unloaded::Section {
+                    block_states: sect.block_states.clone(),
+                    biomes: sect.biomes.clone(),
+                })
+                .collect(),
+            block_entities: self.block_entities.clone(),
+            heightmaps: Some(self.heightmaps.clone()),
+        }
+    }
+
+    /// Returns the heightmaps of this chunk. Unlike light, heightmaps are
+    /// updated as soon as blocks change.
+    pub fn heightmaps(&self) -> &Heightmaps {
+        &self.heightmaps
+    }
+
     /// Returns the number of clients in view of this chunk.
     pub fn viewer_count(&self) -> u32 {
         self.viewer_count.load(Ordering::Relaxed)
@@ -186,15 +284,43 @@ impl LoadedChunk {
         info: &ChunkLayerInfo,
         messages: &mut ChunkLayerMessages,
     ) {
+        if self.light.has_changes() {
+            self.cached_init_packets.get_mut().clear();
+        }
+
         if *self.viewer_count.get_mut() == 0 {
-            // Nobody is viewing the chunk, so no need to send any update packets. There
-            // also shouldn't be any changes that need to be cleared.
+            // Nobody is viewing the chunk, so no need to send any update packets. Light
+            // is computed regardless of viewers, so its changes are discarded here. There
+            // shouldn't be any other changes that need to be cleared.
+            self.light.clear_changes();
             self.assert_no_changes();
 
             return;
         }
 
         // Block states
+        let update_count: usize = self.sections.iter().map(|sect| sect.updates.len()).sum();
+
+        if update_count > CHUNK_RESEND_THRESHOLD {
+            // Sending the whole chunk again is cheaper than this many block updates. It is
+            // queued in the viewers' chunk batches like any other chunk and contains all
+            // the other changes, so no update packets are sent for them.
+            messages.send_local_infallible(LocalMsg::ChangeChunkState { pos }, |b| {
+                b.push(ChunkLayer::RESEND)
+            });
+
+            for sect in &mut self.sections {
+                sect.updates.clear();
+            }
+
+            self.changed_block_entities.clear();
+            self.changed_biomes = false;
+            self.light.clear_changes();
+            self.assert_no_changes();
+
+            return;
+        }
+
         for (sect_y, sect) in self.sections.iter_mut().enumerate() {
             match sect.updates.as_slice() {
                 &[] => {}
@@ -204,7 +330,8 @@ impl LoadedChunk {
                     let global_z = pos.z * 16 + i32::from(entry.off_z());
 
                     messages.send_local_infallible(LocalMsg::PacketAt { pos }, |buf| {
-                        let mut writer = PacketWriter::new(buf, info.threshold);
+                        let mut writer =
+                            PacketWriter::with_options(buf, info.threshold, info.compression);
 
                         writer.write_packet(&BlockUpdateS2c {
                             position: BlockPos::new(global_x, global_y, global_z),
@@ -220,7 +347,8 @@ impl LoadedChunk {
                     };
 
                     messages.send_local_infallible(LocalMsg::PacketAt { pos }, |buf| {
-                        let mut writer = PacketWriter::new(buf, info.threshold);
+                        let mut writer =
+                            PacketWriter::with_options(buf, info.threshold, info.compression);
 
                         writer.write_packet(&SectionBlocksUpdateS2c {
                             chunk_sect_pos,
@@ -256,7 +384,7 @@ impl LoadedChunk {
             let global_z = pos.z * 16 + z as i32;
 
             messages.send_local_infallible(LocalMsg::PacketAt { pos }, |buf| {
-                let mut writer = PacketWriter::new(buf, info.threshold);
+                let mut writer = PacketWriter::with_options(buf, info.threshold, info.compression);
 
                 writer.write_packet(&BlockEntityDataS2c {
                     location: BlockPos::new(global_x, global_y, global_z),
@@ -287,89 +415,30 @@ impl LoadedChunk {
             });
         }
 
-        // All changes should be cleared.
-        self.assert_no_changes();
-    }
-
-    /// Generates the `MOTION_BLOCKING` heightmap for this chunk, which stores
-    /// the height of the highest non motion-blocking block in each column.
-    ///
-    /// The lowest value of the heightmap is 0, which means that there are no
-    /// motion-blocking blocks in the column. In this case, rain will fall
-    /// through the void and there will be no rain particles.
-    ///
-    /// A value of 1 means that rain particles will appear at the lowest
-    /// possible height given by [`DimensionType::min_y`]. Note that
-    /// blocks cannot be placed at `min_y - 1`.
-    ///
-    /// We take these two special cases into account by adding a value of 2 to
-    /// our heightmap if we find a motion-blocking block, since
-    /// `self.block_state(x, 0, z)` corresponds to the block at `(x, min_y, z)`
-    /// ingame.
-    ///
-    /// [`DimensionType::min_y`]: valence_registry::dimension_type::DimensionType::min_y
-    #[allow(clippy::needless_range_loop)]
-    fn motion_blocking(&self) -> Vec<Vec<u32>> {
-        let mut heightmap: Vec<Vec<u32>> = vec![vec![0; 16]; 16];
-
-        for z in 0..16 {
-            for x in 0..16 {
-                for y in (0..self.height()).rev() {
-                    let state = self.block_state(x as u32, y, z as u32);
-                    if state.blocks_motion()
-                        || state.is_liquid()
-                        || state.get(PropName::Waterlogged) == Some(PropValue::True)
-                    {
-                        heightmap[z][x] = y + 2;
-                        break;
-                    }
-                }
-            }
-        }
+        // Light
+        if self.light.has_changes() {
+            let light = self.light.changed_data();
 
-        heightmap
-    }
+            messages.send_local_infallible(LocalMsg::PacketAt { pos }, |buf| {
+                let mut writer = PacketWriter::with_options(buf, info.threshold, info.compression);
+
+                writer.write_packet(&LightUpdateS2c {
+                    chunk_x: VarInt(pos.x),
+                    chunk_z: VarInt(pos.z),
+                    sky_light_mask: Cow::Borrowed(&light.sky_mask),
+                    block_light_mask: Cow::Borrowed(&light.block_mask),
+                    empty_sky_light_mask: Cow::Borrowed(&light.empty_sky_mask),
+                    empty_block_light_mask: Cow::Borrowed(&light.empty_block_mask),
+                    sky_light_arrays: Cow::Borrowed(&light.sky_arrays),
+                    block_light_arrays: Cow::Borrowed(&light.block_arrays),
+                });
+            });
 
-    /// Encodes a given heightmap into the correct format of the
-    /// `ChunkDataS2c` packet.
-    ///
-    /// The heightmap values are stored in a long array. Each value is encoded
-    /// as a 9-bit unsigned integer, so every long with 64 bits can hold at
-    /// most seven values. The long is padded at the left side with a single
-    /// zero. Since there are 256 values for 256 columns in a chunk, there
-    /// will be 36 fully filled longs and one half-filled long with four
-    /// values. The remaining three values in the last long are left unused.
-    ///
-    /// For example, the `MOTION_BLOCKING` heightmap in an empty superflat
-    /// world is always 4. The first 36 long values will then be
-    ///
-    /// 0 000000100 000000100 000000100 000000100 000000100 000000100 000000100,
-    ///
-    /// and the last long will be
-    ///
-    /// 0 000000000 000000000 000000000 000000100 000000100 000000100 000000100.
-    fn encode_heightmap(heightmap: Vec<Vec<u32>>) -> Value {
-        const BITS_PER_ENTRY: u32 = 9;
-        const ENTRIES_PER_LONG: u32 = i64::BITS / BITS_PER_ENTRY;
-
-        // Unless `ENTRIES_PER_LONG` is a power of 2 and therefore evenly divides 16*16,
-        // we need to add one extra long to fit all values in the packet.
-        const LONGS_PER_PACKET: u32 =
-            16 * 16 / ENTRIES_PER_LONG + (16 * 16 % ENTRIES_PER_LONG != 0) as u32;
-
-        let mut encoded: Vec<i64> = vec![0; LONGS_PER_PACKET as usize];
-        let mut iter = heightmap.into_iter().flatten();
-
-        for long in &mut encoded {
-            for j in 0..ENTRIES_PER_LONG {
-                match iter.next() {
-                    None => break,
-                    Some(y) => *long += i64::from(y) << (BITS_PER_ENTRY * j),
-                }
-            }
+            self.light.clear_changes();
         }
 
-        Value::LongArray(encoded)
+        // All changes should be cleared.
+        self.assert_no_changes();
     }
 
     /// Writes the packet data needed to initialize this chunk.
@@ -382,11 +451,7 @@ impl LoadedChunk {
         let mut init_packets = self.cached_init_packets.lock();
 
         if init_packets.is_empty() {
-            let heightmaps = compound! {
-                "MOTION_BLOCKING" => LoadedChunk::encode_heightmap(self.motion_blocking()),
-                // TODO Implement `WORLD_SURFACE` (or explain why we don't need it)
-                // "WORLD_SURFACE" => self.encode_heightmap(self.world_surface()),
-            };
+            let heightmaps = self.heightmaps.to_client_nbt(self.height());
 
             let mut blocks_and_biomes: Vec<u8> = vec![];
 
@@ -438,20 +503,21 @@ impl LoadedChunk {
                 })
                 .collect();
 
-            PacketWriter::new(&mut init_packets, info.threshold).write_packet(
-                &LevelChunkWithLightS2c {
+            let light = self.light.init_data(info);
+
+            PacketWriter::with_options(&mut init_packets, info.threshold, info.compression)
+                .write_packet(&LevelChunkWithLightS2c {
                     pos,
                     heightmaps: Cow::Owned(heightmaps),
                     blocks_and_biomes: &blocks_and_biomes,
                     block_entities: Cow::Owned(block_entities),
-                    sky_light_mask: Cow::Borrowed(&[]),
-                    block_light_mask: Cow::Borrowed(&[]),
-                    empty_sky_light_mask: Cow::Borrowed(&[]),
-                    empty_block_light_mask: Cow::Borrowed(&[]),
-                    sky_light_arrays: Cow::Borrowed(&[]),
-                    block_light_arrays: Cow::Borrowed(&[]),
-                },
-            )
+                    sky_light_mask: Cow::Owned(light.sky_mask),
+                    block_light_mask: Cow::Owned(light.block_mask),
+                    empty_sky_light_mask: Cow::Owned(light.empty_sky_mask),
+                    empty_block_light_mask: Cow::Owned(light.empty_block_mask),
+                    sky_light_arrays: Cow::Owned(light.sky_arrays),
+                    block_light_arrays: Cow::Owned(light.block_arrays),
+                })
         }
 
         writer.write_packet_bytes(&init_packets);
@@ -464,6 +530,7 @@ impl LoadedChunk {
         {
             assert!(!self.changed_biomes);
             assert!(self.changed_block_entities.is_empty());
+            assert!(!self.light.has_changes());
 
             for sect in &self.sections {
                 assert!(sect.updates.is_empty());
@@ -497,6 +564,9 @@ impl Chunk for LoadedChunk {
 
         if block != old_block {
             self.cached_init_packets.get_mut().clear();
+            self.modified = true;
+            self.light
+                .block_state_changed(x + z * 16 + y * 16 * 16, old_block, block);
 
             if *self.viewer_count.get_mut() > 0 {
                 sect.updates.push(
@@ -507,6 +577,14 @@ impl Chunk for LoadedChunk {
                         .with_block_state(block.to_raw().into()),
                 );
             }
+
+            let sections = &self.sections;
+            self.heightmaps
+                .block_state_changed(x, y, z, block, |x, y, z| {
+                    sections[y as usize / 16]
+                        .block_states
+                        .get((x + z * 16 + y % 16 * 16 * 16) as usize)
+                });
         }
 
         old_block
@@ -520,6 +598,7 @@ impl Chunk for LoadedChunk {
         if let PalettedContainer::Single(b) = &sect.block_states {
             if *b != block {
                 self.cached_init_packets.get_mut().clear();
+                self.modified = true;
 
                 if *self.viewer_count.get_mut() > 0 {
                     // The whole section is being modified, so any previous modifications would
@@ -551,6 +630,7 @@ impl Chunk for LoadedChunk {
 
                         if block != sect.block_states.get(idx as usize) {
                             self.cached_init_packets.get_mut().clear();
+                            self.modified = true;
 
                             if *self.viewer_count.get_mut() > 0 {
                                 sect.updates.push(
@@ -567,7 +647,18 @@ impl Chunk for LoadedChunk {
             }
         }
 
+        if !matches!(sect.block_states, PalettedContainer::Single(b) if b == block) {
+            self.light.schedule_relight();
+        }
+
         sect.block_states.fill(block);
+
+        let sections = &self.sections;
+        self.heightmaps.section_filled(sect_y, block, |x, y, z| {
+            sections[y as usize / 16]
+                .block_states
+                .get((x + z * 16 + y % 16 * 16 * 16) as usize)
+        });
     }
 
     fn block_entity(&self, x: u32, y: u32, z: u32) -> Option<&Compound> {
@@ -587,6 +678,7 @@ impl Chunk for LoadedChunk {
                 self.changed_block_entities.insert(idx);
             }
             self.cached_init_packets.get_mut().clear();
+            self.modified = true;
 
             Some(be)
         } else {
@@ -611,6 +703,7 @@ impl Chunk for LoadedChunk {
                     self.changed_block_entities.insert(idx);
                 }
                 self.cached_init_packets.get_mut().clear();
+                self.modified = true;
 
                 self.block_entities.insert(idx, nbt)
             }
@@ -619,6 +712,7 @@ impl Chunk for LoadedChunk {
 
                 if res.is_some() {
                     self.cached_init_packets.get_mut().clear();
+                    self.modified = true;
                 }
 
                 res
@@ -632,6 +726,7 @@ impl Chunk for LoadedChunk {
         }
 
         self.cached_init_packets.get_mut().clear();
+        self.modified = true;
 
         if *self.viewer_count.get_mut() > 0 {
             self.changed_block_entities
@@ -658,6 +753,7 @@ impl Chunk for LoadedChunk {
 
         if biome != old_biome {
             self.cached_init_packets.get_mut().clear();
+            self.modified = true;
 
             if *self.viewer_count.get_mut() > 0 {
                 self.changed_biomes = true;
@@ -675,10 +771,12 @@ impl Chunk for LoadedChunk {
         if let PalettedContainer::Single(b) = &sect.biomes {
             if *b != biome {
                 self.cached_init_packets.get_mut().clear();
+                self.modified = true;
                 self.changed_biomes = *self.viewer_count.get_mut() > 0;
             }
         } else {
             self.cached_init_packets.get_mut().clear();
+            self.modified = true;
             self.changed_biomes = *self.viewer_count.get_mut() > 0;
         }
 
@@ -687,6 +785,7 @@ impl Chunk for LoadedChunk {
 
     fn shrink_to_fit(&mut self) {
         self.cached_init_packets.get_mut().shrink_to_fit();
+        self.light.shrink_to_fit();
 
         for sect in &mut self.sections {
             sect.block_states.shrink_to_fit();
@@ -698,10 +797,13 @@ impl Chunk for LoadedChunk {
 
 #[cfg(test)]
 mod tests {
+    use valence_nbt::compound;
+    use valence_protocol::compression::CompressionOptions;
     use valence_protocol::{ident, CompressionThreshold};
     use valence_registry::dimension_type::DimensionTypeId;
 
     use super::*;
+    use crate::ChunkView;
 
     #[test]
     fn loaded_chunk_unviewed_no_changes() {
@@ -728,8 +830,10 @@ mod tests {
                 dimension_type: DimensionTypeId::new(0),
                 height: 512,
                 min_y: -16,
+                has_skylight: true,
                 biome_registry_len: 200,
                 threshold: CompressionThreshold(-1),
+                compression: CompressionOptions::DEFAULT,
             };
 
             let mut buf = vec![];
@@ -775,4 +879,42 @@ mod tests {
 
         assert!(!chunk.cached_init_packets.get_mut().is_empty());
     }
+
+    #[test]
+    fn loaded_chunk_many_changes_resent() {
+        let info = ChunkLayerInfo {
+            dimension_type: DimensionTypeId::new(0),
+            height: 512,
+            min_y: -16,
+            has_skylight: true,
+            biome_registry_len: 200,
+            threshold: CompressionThreshold(-1),
+            compression: CompressionOptions::DEFAULT,
+        };
+
+        let pos = ChunkPos::new(3, 4);
+        let mut chunk = LoadedChunk::new(512);
+        chunk.inc_viewer_count();
+
+        for i in 0..=CHUNK_RESEND_THRESHOLD as u32 {
+            chunk.set_block_state(i % 16, i / 256, (i / 16) % 16, BlockState::STONE);
+        }
+        chunk.set_block_entity(3, 40, 5, Some(compound! {}));
+        chunk.set_biome(1, 2, 3, BiomeId::from_index(4));
+
+        let mut messages = ChunkLayerMessages::new();
+        chunk.update_pre_client(pos, &info, &mut messages);
+        messages.ready();
+
+        // Only the resend is sent, the other changes are part of the whole chunk.
+        let mut msgs = vec![];
+        messages.query_local(ChunkView::new(pos, 2), |msg, range| {
+            msgs.push((msg, messages.bytes()[range].to_vec()))
+        });
+
+        assert_eq!(
+            msgs,
+            [(LocalMsg::ChangeChunkState { pos }, vec![ChunkLayer::RESEND])]
+        );
+    }
 }
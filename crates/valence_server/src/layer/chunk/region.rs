@@ -0,0 +1,931 @@
+//! Editing boxes of blocks in a [`ChunkLayer`] at once.
+//!
+//! [`ChunkLayer::fill`], [`ChunkLayer::replace`] and [`ChunkLayer::paste`]
+//! look up every affected chunk only once and fill whole sections at a time
+//! where possible. Like any other block change, the changes are sent to
+//! clients at the end of the tick as one [`SectionBlocksUpdateS2c`] per
+//! modified section, or by sending the whole chunk again if that is cheaper.
+//!
+//! [`SectionBlocksUpdateS2c`]: valence_protocol::packets::play::SectionBlocksUpdateS2c
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use valence_generated::block::{PropName, PropValue};
+use valence_nbt::Compound;
+use valence_protocol::{BlockPos, BlockState, ChunkPos, Direction};
+
+use super::chunk::{Block, BlockRef, Chunk, IntoBlock};
+use super::ChunkLayer;
+
+/// An axis-aligned box of blocks. Both corners are part of the region.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BlockRegion {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl BlockRegion {
+    /// Creates the region between two opposite corners, in any order.
+    pub fn new<A: Into<BlockPos>, B: Into<BlockPos>>(a: A, b: B) -> Self {
+        let a = a.into();
+        let b = b.into();
+
+        Self {
+            min: BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Returns whether the position is inside the region.
+    pub fn contains<P: Into<BlockPos>>(&self, pos: P) -> bool {
+        let pos = pos.into();
+
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    /// Returns the number of blocks along the X, Y and Z axes.
+    pub fn size(&self) -> (u32, u32, u32) {
+        (
+            self.max.x.abs_diff(self.min.x).saturating_add(1),
+            self.max.y.abs_diff(self.min.y).saturating_add(1),
+            self.max.z.abs_diff(self.min.z).saturating_add(1),
+        )
+    }
+
+    /// Returns the part of the region inside each chunk of a chunk layer with
+    /// the given minimum Y and height. Offsets are relative to the chunk.
+    fn chunk_slices(self, min_y: i32, height: u32) -> impl Iterator<Item = ChunkSlice> {
+        let min_y = i64::from(min_y);
+        let y_start = (i64::from(self.min.y) - min_y).max(0);
+        let y_end = (i64::from(self.max.y) - min_y).min(i64::from(height) - 1);
+
+        let chunks_x = self.min.x.div_euclid(16)..=self.max.x.div_euclid(16);
+        let chunks_z = self.min.z.div_euclid(16)..=self.max.z.div_euclid(16);
+
+        let slices = chunks_z.flat_map(move |chunk_z| {
+            chunks_x.clone().map(move |chunk_x| ChunkSlice {
+                pos: ChunkPos::new(chunk_x, chunk_z),
+                x: offsets(chunk_x, self.min.x, self.max.x),
+                y: y_start as u32..=y_end as u32,
+                z: offsets(chunk_z, self.min.z, self.max.z),
+            })
+        });
+
+        // Nothing to do if the region is entirely above or below the world.
+        (y_start <= y_end).then_some(slices).into_iter().flatten()
+    }
+}
+
+/// Returns the offsets in the chunk at `chunk` covered by `min..=max`.
+fn offsets(chunk: i32, min: i32, max: i32) -> RangeInclusive<u32> {
+    let start = i64::from(chunk) * 16;
+
+    (i64::from(min) - start).max(0) as u32..=(i64::from(max) - start).min(15) as u32
+}
+
+/// The part of a [`BlockRegion`] inside a single chunk.
+struct ChunkSlice {
+    pos: ChunkPos,
+    x: RangeInclusive<u32>,
+    y: RangeInclusive<u32>,
+    z: RangeInclusive<u32>,
+}
+
+impl ChunkSlice {
+    fn for_each<F: FnMut(u32, u32, u32)>(&self, mut f: F) {
+        for y in self.y.clone() {
+            for z in self.z.clone() {
+                for x in self.x.clone() {
+                    f(x, y, z);
+                }
+            }
+        }
+    }
+
+    /// Returns the position of the block at the offsets in world space.
+    fn block_pos(&self, min_y: i32, x: u32, y: u32, z: u32) -> BlockPos {
+        BlockPos::new(
+            self.pos.x * 16 + x as i32,
+            min_y + y as i32,
+            self.pos.z * 16 + z as i32,
+        )
+    }
+}
+
+/// A rotation around the Y axis, clockwise when looking down.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    /// Rotates a direction. Up and down are unaffected.
+    pub const fn rotate(self, dir: Direction) -> Direction {
+        match self {
+            Rotation::None => dir,
+            Rotation::Clockwise90 => clockwise(dir),
+            Rotation::Clockwise180 => clockwise(clockwise(dir)),
+            Rotation::Counterclockwise90 => clockwise(clockwise(clockwise(dir))),
+        }
+    }
+
+    /// Rotates the properties of a block state that depend on its direction,
+    /// such as [`PropName::Facing`], [`PropName::Axis`] and
+    /// [`PropName::Shape`].
+    #[must_use]
+    pub fn rotate_state(self, state: BlockState) -> BlockState {
+        transform_state(state, Transform::Rotate(self))
+    }
+
+    const fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Counterclockwise90)
+    }
+}
+
+const fn clockwise(dir: Direction) -> Direction {
+    match dir {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North,
+        Direction::Down | Direction::Up => dir,
+    }
+}
+
+/// A reflection across a vertical plane.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Flips the X axis, swapping east and west.
+    X,
+    /// Flips the Z axis, swapping north and south.
+    Z,
+}
+
+impl Mirror {
+    /// Mirrors a direction. Up and down are unaffected.
+    pub const fn mirror(self, dir: Direction) -> Direction {
+        match (self, dir) {
+            (Mirror::X, Direction::East) => Direction::West,
+            (Mirror::X, Direction::West) => Direction::East,
+            (Mirror::Z, Direction::North) => Direction::South,
+            (Mirror::Z, Direction::South) => Direction::North,
+            _ => dir,
+        }
+    }
+
+    /// Mirrors the properties of a block state that depend on its direction,
+    /// such as [`PropName::Facing`], [`PropName::Shape`] and
+    /// [`PropName::Hinge`].
+    #[must_use]
+    pub fn mirror_state(self, state: BlockState) -> BlockState {
+        transform_state(state, Transform::Mirror(self))
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Transform {
+    Rotate(Rotation),
+    Mirror(Mirror),
+}
+
+impl Transform {
+    fn direction(self, dir: Direction) -> Direction {
+        match self {
+            Transform::Rotate(rotation) => rotation.rotate(dir),
+            Transform::Mirror(mirror) => mirror.mirror(dir),
+        }
+    }
+
+    fn is_mirror(self) -> bool {
+        matches!(self, Transform::Mirror(mirror) if mirror != Mirror::None)
+    }
+}
+
+/// The horizontal directions and the properties connecting a block to its
+/// neighbor in that direction, like those of fences and walls.
+const SIDES: [(Direction, PropName); 4] = [
+    (Direction::North, PropName::North),
+    (Direction::East, PropName::East),
+    (Direction::South, PropName::South),
+    (Direction::West, PropName::West),
+];
+
+fn transform_state(state: BlockState, transform: Transform) -> BlockState {
+    let mut new = state;
+
+    if let Some(dir) = state.get(PropName::Facing).and_then(prop_to_direction) {
+        new = new.set(
+            PropName::Facing,
+            direction_to_prop(transform.direction(dir)),
+        );
+    }
+
+    if let Transform::Rotate(rotation) = transform {
+        if rotation.swaps_axes() {
+            match state.get(PropName::Axis) {
+                Some(PropValue::X) => new = new.set(PropName::Axis, PropValue::Z),
+                Some(PropValue::Z) => new = new.set(PropName::Axis, PropValue::X),
+                _ => {}
+            }
+        }
+    }
+
+    // Signs, banners and skulls have 16 rotations, starting at south and going
+    // clockwise.
+    if let Some(value) = state.get(PropName::Rotation).and_then(PropValue::to_u16) {
+        let value = match transform {
+            Transform::Rotate(rotation) => value + rotation as u16 * 4,
+            Transform::Mirror(Mirror::None) => value,
+            Transform::Mirror(Mirror::X) => 16 - value,
+            Transform::Mirror(Mirror::Z) => 24 - value,
+        };
+
+        if let Some(value) = PropValue::from_u16(value % 16) {
+            new = new.set(PropName::Rotation, value);
+        }
+    }
+
+    for (dir, name) in SIDES {
+        if let Some(value) = state.get(name) {
+            new = new.set(side_prop(transform.direction(dir)), value);
+        }
+    }
+
+    if let Some(shape) = state.get(PropName::Shape) {
+        new = new.set(PropName::Shape, transform_shape(shape, transform));
+    }
+
+    // Jigsaw blocks and crafters face one direction with their top towards
+    // another, e.g. `north_up`.
+    if let Some((front, top)) = state
+        .get(PropName::Orientation)
+        .and_then(|value| value.to_str().split_once('_'))
+    {
+        let front = str_to_direction(front).map(|dir| transform.direction(dir));
+        let top = str_to_direction(top).map(|dir| transform.direction(dir));
+
+        if let (Some(front), Some(top)) = (front, top) {
+            let name = format!(
+                "{}_{}",
+                direction_to_prop(front).to_str(),
+                direction_to_prop(top).to_str()
+            );
+
+            if let Some(value) = PropValue::from_str(&name) {
+                new = new.set(PropName::Orientation, value);
+            }
+        }
+    }
+
+    if transform.is_mirror() {
+        match state.get(PropName::Hinge) {
+            Some(PropValue::Left) => new = new.set(PropName::Hinge, PropValue::Right),
+            Some(PropValue::Right) => new = new.set(PropName::Hinge, PropValue::Left),
+            _ => {}
+        }
+    }
+
+    new
+}
+
+/// Transforms the shape of stairs and rails.
+fn transform_shape(shape: PropValue, transform: Transform) -> PropValue {
+    match shape {
+        // Stairs. The corner shapes are relative to the facing direction, so
+        // only mirroring changes them.
+        PropValue::Straight => shape,
+        PropValue::InnerLeft
+        | PropValue::InnerRight
+        | PropValue::OuterLeft
+        | PropValue::OuterRight
+            if !transform.is_mirror() =>
+        {
+            shape
+        }
+        PropValue::InnerLeft => PropValue::InnerRight,
+        PropValue::InnerRight => PropValue::InnerLeft,
+        PropValue::OuterLeft => PropValue::OuterRight,
+        PropValue::OuterRight => PropValue::OuterLeft,
+        // Rails, e.g. `ascending_north`, `north_south` or `south_east`.
+        _ => {
+            let Some((a, b)) = shape.to_str().split_once('_') else {
+                return shape;
+            };
+
+            let name = if a == "ascending" {
+                let Some(dir) = str_to_direction(b) else {
+                    return shape;
+                };
+
+                format!(
+                    "ascending_{}",
+                    direction_to_prop(transform.direction(dir)).to_str()
+                )
+            } else {
+                let (Some(a), Some(b)) = (str_to_direction(a), str_to_direction(b)) else {
+                    return shape;
+                };
+
+                let mut dirs = [transform.direction(a), transform.direction(b)];
+
+                // North and south come first in the names of rail shapes.
+                if matches!(dirs[1], Direction::North | Direction::South) {
+                    dirs.swap(0, 1);
+                }
+
+                format!(
+                    "{}_{}",
+                    direction_to_prop(dirs[0]).to_str(),
+                    direction_to_prop(dirs[1]).to_str()
+                )
+            };
+
+            PropValue::from_str(&name).unwrap_or(shape)
+        }
+    }
+}
+
+const fn prop_to_direction(value: PropValue) -> Option<Direction> {
+    Some(match value {
+        PropValue::Down => Direction::Down,
+        PropValue::Up => Direction::Up,
+        PropValue::North => Direction::North,
+        PropValue::South => Direction::South,
+        PropValue::West => Direction::West,
+        PropValue::East => Direction::East,
+        _ => return None,
+    })
+}
+
+const fn direction_to_prop(dir: Direction) -> PropValue {
+    match dir {
+        Direction::Down => PropValue::Down,
+        Direction::Up => PropValue::Up,
+        Direction::North => PropValue::North,
+        Direction::South => PropValue::South,
+        Direction::West => PropValue::West,
+        Direction::East => PropValue::East,
+    }
+}
+
+fn str_to_direction(s: &str) -> Option<Direction> {
+    PropValue::from_str(s).and_then(prop_to_direction)
+}
+
+const fn side_prop(dir: Direction) -> PropName {
+    match dir {
+        Direction::North => PropName::North,
+        Direction::East => PropName::East,
+        Direction::South => PropName::South,
+        Direction::West => PropName::West,
+        Direction::Down => PropName::Down,
+        Direction::Up => PropName::Up,
+    }
+}
+
+/// A box of blocks copied from a [`ChunkLayer`] with [`ChunkLayer::copy`],
+/// which can be rotated, mirrored and pasted elsewhere.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Clipboard {
+    size: (u32, u32, u32),
+    /// Block states stored as `x + z * size_x + y * size_x * size_z`.
+    block_states: Vec<BlockState>,
+    /// Block entities with the same indices as the block states.
+    block_entities: BTreeMap<u32, Compound>,
+}
+
+impl Clipboard {
+    /// Creates a clipboard with the given size along the X, Y and Z axes that
+    /// is filled with air.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> Self {
+        let len = size_x as usize * size_y as usize * size_z as usize;
+
+        Self {
+            size: (size_x, size_y, size_z),
+            block_states: vec![BlockState::AIR; len],
+            block_entities: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of blocks along the X, Y and Z axes.
+    pub fn size(&self) -> (u32, u32, u32) {
+        self.size
+    }
+
+    /// Gets the block at the given offsets in the clipboard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are out of bounds.
+    #[track_caller]
+    pub fn block(&self, x: u32, y: u32, z: u32) -> BlockRef<'_> {
+        let idx = self.index(x, y, z);
+
+        BlockRef {
+            state: self.block_states[idx as usize],
+            nbt: self.block_entities.get(&idx),
+        }
+    }
+
+    /// Sets the block at the given offsets in the clipboard. The previous
+    /// block is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are out of bounds.
+    #[track_caller]
+    pub fn set_block<B: IntoBlock>(&mut self, x: u32, y: u32, z: u32, block: B) -> Block {
+        let idx = self.index(x, y, z);
+        let block = block.into_block();
+
+        let state = std::mem::replace(&mut self.block_states[idx as usize], block.state);
+        let nbt = match block.nbt {
+            Some(nbt) => self.block_entities.insert(idx, nbt),
+            None => self.block_entities.remove(&idx),
+        };
+
+        Block { state, nbt }
+    }
+
+    /// Returns a copy of the clipboard rotated around the Y axis. The block
+    /// states are rotated with [`Rotation::rotate_state`].
+    #[must_use]
+    pub fn rotated(&self, rotation: Rotation) -> Self {
+        let (size_x, size_y, size_z) = self.size;
+
+        let mut rotated = if rotation.swaps_axes() {
+            Self::new(size_z, size_y, size_x)
+        } else {
+            Self::new(size_x, size_y, size_z)
+        };
+
+        self.transform_into(
+            &mut rotated,
+            Transform::Rotate(rotation),
+            |x, z| match rotation {
+                Rotation::None => (x, z),
+                Rotation::Clockwise90 => (size_z - 1 - z, x),
+                Rotation::Clockwise180 => (size_x - 1 - x, size_z - 1 - z),
+                Rotation::Counterclockwise90 => (z, size_x - 1 - x),
+            },
+        );
+
+        rotated
+    }
+
+    /// Returns a mirrored copy of the clipboard. The block states are mirrored
+    /// with [`Mirror::mirror_state`].
+    #[must_use]
+    pub fn mirrored(&self, mirror: Mirror) -> Self {
+        let (size_x, size_y, size_z) = self.size;
+        let mut mirrored = Self::new(size_x, size_y, size_z);
+
+        self.transform_into(
+            &mut mirrored,
+            Transform::Mirror(mirror),
+            |x, z| match mirror {
+                Mirror::None => (x, z),
+                Mirror::X => (size_x - 1 - x, z),
+                Mirror::Z => (x, size_z - 1 - z),
+            },
+        );
+
+        mirrored
+    }
+
+    /// Copies every block to `dest`, moving the block at `(x, z)` to the
+    /// offsets returned by `move_xz`.
+    fn transform_into<F>(&self, dest: &mut Self, transform: Transform, move_xz: F)
+    where
+        F: Fn(u32, u32) -> (u32, u32),
+    {
+        let (size_x, size_y, size_z) = self.size;
+
+        for y in 0..size_y {
+            for z in 0..size_z {
+                for x in 0..size_x {
+                    let idx = self.index(x, y, z);
+                    let (new_x, new_z) = move_xz(x, z);
+                    let new_idx = dest.index(new_x, y, new_z);
+
+                    dest.block_states[new_idx as usize] =
+                        transform_state(self.block_states[idx as usize], transform);
+
+                    if let Some(nbt) = self.block_entities.get(&idx) {
+                        dest.block_entities.insert(new_idx, nbt.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    #[track_caller]
+    fn index(&self, x: u32, y: u32, z: u32) -> u32 {
+        let (size_x, size_y, size_z) = self.size;
+
+        assert!(
+            x < size_x && y < size_y && z < size_z,
+            "clipboard offsets of ({x}, {y}, {z}) are out of bounds"
+        );
+
+        x + z * size_x + y * size_x * size_z
+    }
+}
+
+impl ChunkLayer {
+    /// Sets every block in the region to `block`. Blocks outside of loaded
+    /// chunks or the height of the world are skipped.
+    pub fn fill<B: IntoBlock>(&mut self, region: BlockRegion, block: B) {
+        let block = block.into_block();
+        let min_y = self.info.min_y;
+
+        self.flush_journal();
+
+        for slice in region.chunk_slices(min_y, self.info.height) {
+            let Some(chunk) = self.chunks.get_mut(&slice.pos) else {
+                continue;
+            };
+
+            // Every replaced block has to be recorded in the journal.
+            let covers_columns =
+                slice.x == (0..=15) && slice.z == (0..=15) && self.journal.is_none();
+
+            for sect_y in slice.y.start() / 16..=slice.y.end() / 16 {
+                let bottom = sect_y * 16;
+                let y = *slice.y.start().max(&bottom)..=*slice.y.end().min(&(bottom + 15));
+
+                if covers_columns && y == (bottom..=bottom + 15) {
+                    chunk.fill_block_state_section(sect_y, block.state);
+
+                    for y in y {
+                        for z in 0..16 {
+                            for x in 0..16 {
+                                chunk.set_block_entity(x, y, z, block.nbt.clone());
+                            }
+                        }
+                    }
+                } else {
+                    for y in y {
+                        for z in slice.z.clone() {
+                            for x in slice.x.clone() {
+                                let old = chunk.set_block(x, y, z, block.clone());
+
+                                if let Some(journal) = &mut self.journal {
+                                    let pos = slice.block_pos(min_y, x, y, z);
+                                    journal.record(pos, old, block.clone(), self.tick);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets every block in the region whose state matches `predicate` to
+    /// `block`. Returns the number of blocks that were replaced.
+    pub fn replace<F, B>(&mut self, region: BlockRegion, mut predicate: F, block: B) -> u64
+    where
+        F: FnMut(BlockState) -> bool,
+        B: IntoBlock,
+    {
+        let block = block.into_block();
+        let min_y = self.info.min_y;
+        let mut count = 0;
+
+        self.flush_journal();
+
+        for slice in region.chunk_slices(min_y, self.info.height) {
+            let Some(chunk) = self.chunks.get_mut(&slice.pos) else {
+                continue;
+            };
+
+            slice.for_each(|x, y, z| {
+                if predicate(chunk.block_state(x, y, z)) {
+                    let old = chunk.set_block(x, y, z, block.clone());
+
+                    if let Some(journal) = &mut self.journal {
+                        let pos = slice.block_pos(min_y, x, y, z);
+                        journal.record(pos, old, block.clone(), self.tick);
+                    }
+
+                    count += 1;
+                }
+            });
+        }
+
+        count
+    }
+
+    /// Copies the blocks in the region to a new [`Clipboard`]. Blocks outside
+    /// of loaded chunks or the height of the world are copied as air.
+    pub fn copy(&self, region: BlockRegion) -> Clipboard {
+        let (size_x, size_y, size_z) = region.size();
+        let mut clipboard = Clipboard::new(size_x, size_y, size_z);
+
+        for slice in region.chunk_slices(self.info.min_y, self.info.height) {
+            let Some(chunk) = self.chunk(slice.pos) else {
+                continue;
+            };
+
+            slice.for_each(|x, y, z| {
+                let pos = slice.block_pos(self.info.min_y, x, y, z);
+
+                clipboard.set_block(
+                    pos.x.abs_diff(region.min.x),
+                    pos.y.abs_diff(region.min.y),
+                    pos.z.abs_diff(region.min.z),
+                    chunk.block(x, y, z),
+                );
+            });
+        }
+
+        clipboard
+    }
+
+    /// Sets the blocks starting at `origin` to the blocks in the clipboard
+    /// after rotating it with [`Clipboard::rotated`]. `origin` is where the
+    /// block at the offsets `(0, 0, 0)` of the rotated clipboard is placed.
+    /// Blocks outside of loaded chunks or the height of the world are skipped.
+    pub fn paste<P: Into<BlockPos>>(
+        &mut self,
+        clipboard: &Clipboard,
+        origin: P,
+        rotation: Rotation,
+    ) {
+        let rotated;
+        let clipboard = if rotation == Rotation::None {
+            clipboard
+        } else {
+            rotated = clipboard.rotated(rotation);
+            &rotated
+        };
+
+        let origin = origin.into();
+        let (size_x, size_y, size_z) = clipboard.size();
+
+        if size_x == 0 || size_y == 0 || size_z == 0 {
+            return;
+        }
+
+        let region = BlockRegion::new(
+            origin,
+            origin.offset(size_x as i32 - 1, size_y as i32 - 1, size_z as i32 - 1),
+        );
+
+        let min_y = self.info.min_y;
+
+        self.flush_journal();
+
+        for slice in region.chunk_slices(min_y, self.info.height) {
+            let Some(chunk) = self.chunks.get_mut(&slice.pos) else {
+                continue;
+            };
+
+            slice.for_each(|x, y, z| {
+                let pos = slice.block_pos(min_y, x, y, z);
+
+                let block = clipboard.block(
+                    pos.x.abs_diff(origin.x),
+                    pos.y.abs_diff(origin.y),
+                    pos.z.abs_diff(origin.z),
+                );
+
+                match &mut self.journal {
+                    Some(journal) => {
+                        let block = block.into_block();
+                        let old = chunk.set_block(x, y, z, block.clone());
+
+                        journal.record(pos, old, block, self.tick);
+                    }
+                    None => {
+                        chunk.set_block(x, y, z, block);
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn fill_and_replace_regions() {
+        let mut layer = ChunkLayer::with_test_chunks();
+        let min_y = layer.min_y();
+
+        // Spans several chunks and sections, and covers some sections fully.
+        let region = BlockRegion::new([-20, min_y + 40, -3], [15, min_y, 40]);
+        layer.fill(region, BlockState::STONE);
+
+        for slice in region.chunk_slices(min_y, layer.height()) {
+            let Some(chunk) = layer.chunk(slice.pos) else {
+                continue;
+            };
+
+            slice.for_each(|x, y, z| assert_eq!(chunk.block_state(x, y, z), BlockState::STONE));
+        }
+
+        assert_eq!(layer.block([-21, min_y, 0]).unwrap().state, BlockState::AIR);
+        assert_eq!(
+            layer.block([0, min_y + 41, 0]).unwrap().state,
+            BlockState::AIR
+        );
+
+        layer.set_block([0, min_y + 10, 0], BlockState::DIRT);
+
+        let replaced = layer.replace(
+            region,
+            |state| state == BlockState::STONE,
+            BlockState::CHEST,
+        );
+        let (size_x, size_y, _) = region.size();
+
+        // Chunks at z = 32 and above aren't loaded.
+        assert_eq!(replaced, u64::from(size_x * size_y * 35) - 1);
+        assert_eq!(
+            layer.block([0, min_y + 10, 0]).unwrap().state,
+            BlockState::DIRT
+        );
+        assert_eq!(
+            layer.block([-20, min_y, -3]).unwrap().nbt,
+            Some(&Compound::new())
+        );
+
+        // Filling a whole section removes the block entities inside it.
+        layer.fill(
+            BlockRegion::new([0, min_y, 0], [15, min_y + 15, 15]),
+            BlockState::AIR,
+        );
+        assert_eq!(layer.block([3, min_y + 3, 3]).unwrap().nbt, None);
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let mut layer = ChunkLayer::with_test_chunks();
+        let min_y = layer.min_y();
+
+        layer.set_block([-1, min_y, -1], BlockState::OAK_LOG);
+        layer.set_block(
+            [0, min_y + 1, -1],
+            Block::new(BlockState::CHEST, Some(compound! { "Lock" => "key" })),
+        );
+
+        let clipboard = layer.copy(BlockRegion::new([-1, min_y, -1], [0, min_y + 1, 0]));
+        assert_eq!(clipboard.size(), (2, 2, 2));
+        assert_eq!(clipboard.block(0, 0, 0).state, BlockState::OAK_LOG);
+
+        layer.paste(&clipboard, [10, min_y + 5, 12], Rotation::None);
+
+        assert_eq!(
+            layer.block([10, min_y + 5, 12]).unwrap().state,
+            BlockState::OAK_LOG
+        );
+        assert_eq!(
+            layer.block([11, min_y + 6, 12]).unwrap().nbt,
+            Some(&compound! { "Lock" => "key" })
+        );
+
+        // Blocks outside of the world are skipped.
+        layer.paste(&clipboard, [-1, min_y - 1, -1], Rotation::None);
+        assert_eq!(
+            layer.block([0, min_y, -1]).unwrap().state,
+            BlockState::CHEST
+        );
+        assert_eq!(layer.block([-1, min_y, -1]).unwrap().state, BlockState::AIR);
+
+        // Rotating moves the log at the offsets (0, 0, 0) to (1, 0, 0).
+        layer.paste(&clipboard, [20, min_y + 5, 12], Rotation::Clockwise90);
+        assert_eq!(
+            layer.block([21, min_y + 5, 12]).unwrap().state,
+            BlockState::OAK_LOG
+        );
+        assert_eq!(
+            layer.block([21, min_y + 6, 13]).unwrap().nbt,
+            Some(&compound! { "Lock" => "key" })
+        );
+    }
+
+    #[test]
+    fn rotate_and_mirror_clipboard() {
+        let mut clipboard = Clipboard::new(3, 1, 2);
+        let stairs = BlockState::OAK_STAIRS
+            .set(PropName::Facing, PropValue::North)
+            .set(PropName::Shape, PropValue::InnerLeft);
+
+        clipboard.set_block(0, 0, 0, stairs);
+        clipboard.set_block(
+            2,
+            0,
+            1,
+            BlockState::OAK_LOG.set(PropName::Axis, PropValue::X),
+        );
+
+        let rotated = clipboard.rotated(Rotation::Clockwise90);
+        assert_eq!(rotated.size(), (2, 1, 3));
+        assert_eq!(
+            rotated.block(1, 0, 0).state,
+            stairs.set(PropName::Facing, PropValue::East)
+        );
+        assert_eq!(
+            rotated.block(0, 0, 2).state,
+            BlockState::OAK_LOG.set(PropName::Axis, PropValue::Z)
+        );
+
+        // Rotating all the way around gives back the original.
+        let full = rotated
+            .rotated(Rotation::Clockwise180)
+            .rotated(Rotation::Clockwise90);
+        assert_eq!(full, clipboard);
+        assert_eq!(
+            clipboard
+                .rotated(Rotation::Clockwise90)
+                .rotated(Rotation::Counterclockwise90),
+            clipboard
+        );
+
+        let mirrored = clipboard.mirrored(Mirror::Z);
+        assert_eq!(
+            mirrored.block(0, 0, 1).state,
+            stairs
+                .set(PropName::Facing, PropValue::South)
+                .set(PropName::Shape, PropValue::InnerRight)
+        );
+        assert_eq!(mirrored.mirrored(Mirror::Z), clipboard);
+    }
+
+    #[test]
+    fn transform_block_states() {
+        let rail = BlockState::RAIL.set(PropName::Shape, PropValue::NorthEast);
+        assert_eq!(
+            Rotation::Clockwise90.rotate_state(rail),
+            rail.set(PropName::Shape, PropValue::SouthEast)
+        );
+        assert_eq!(
+            Mirror::X.mirror_state(rail),
+            rail.set(PropName::Shape, PropValue::NorthWest)
+        );
+
+        let ascending = BlockState::RAIL.set(PropName::Shape, PropValue::AscendingNorth);
+        assert_eq!(
+            Rotation::Counterclockwise90.rotate_state(ascending),
+            ascending.set(PropName::Shape, PropValue::AscendingWest)
+        );
+
+        let fence = BlockState::OAK_FENCE
+            .set(PropName::North, PropValue::True)
+            .set(PropName::East, PropValue::False);
+        assert_eq!(
+            Rotation::Clockwise90.rotate_state(fence),
+            fence
+                .set(PropName::North, PropValue::False)
+                .set(PropName::East, PropValue::True)
+        );
+
+        let sign = BlockState::OAK_SIGN.set(PropName::Rotation, PropValue::_1);
+        assert_eq!(
+            Rotation::Clockwise180.rotate_state(sign),
+            sign.set(PropName::Rotation, PropValue::_9)
+        );
+        assert_eq!(
+            Mirror::X.mirror_state(sign),
+            sign.set(PropName::Rotation, PropValue::_15)
+        );
+        assert_eq!(
+            Mirror::Z.mirror_state(sign),
+            sign.set(PropName::Rotation, PropValue::_7)
+        );
+
+        let door = BlockState::OAK_DOOR
+            .set(PropName::Facing, PropValue::East)
+            .set(PropName::Hinge, PropValue::Left);
+        assert_eq!(
+            Mirror::X.mirror_state(door),
+            door.set(PropName::Facing, PropValue::West)
+                .set(PropName::Hinge, PropValue::Right)
+        );
+        assert_eq!(
+            Mirror::Z.mirror_state(door),
+            door.set(PropName::Hinge, PropValue::Right)
+        );
+
+        let jigsaw = BlockState::JIGSAW.set(PropName::Orientation, PropValue::NorthUp);
+        assert_eq!(
+            Rotation::Clockwise90.rotate_state(jigsaw),
+            jigsaw.set(PropName::Orientation, PropValue::EastUp)
+        );
+    }
+}
@@ -9,11 +9,15 @@ use super::chunk::{
     check_biome_oob, check_block_oob, check_section_oob, BiomeContainer, BlockStateContainer,
     Chunk, MAX_HEIGHT, SECTION_BLOCK_COUNT,
 };
+use super::heightmap::Heightmaps;
 
 #[derive(Clone, Default, Debug)]
 pub struct UnloadedChunk {
     pub(super) sections: Vec<Section>,
     pub(super) block_entities: BTreeMap<u32, Compound>,
+    /// The heightmaps of the chunk, if they are known. They are discarded
+    /// whenever the blocks change.
+    pub(super) heightmaps: Option<Heightmaps>,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -31,9 +35,38 @@ impl UnloadedChunk {
         Self {
             sections: vec![Section::default(); height as usize / 16],
             block_entities: BTreeMap::new(),
+            heightmaps: None,
         }
     }
 
+    /// Returns an iterator over the block entities in this chunk and their
+    /// `(x, y, z)` offsets. The order of the block entities is unspecified.
+    pub fn block_entities(&self) -> impl Iterator<Item = ((u32, u32, u32), &Compound)> + '_ {
+        self.block_entities
+            .iter()
+            .map(|(&idx, nbt)| ((idx % 16, idx / 16 / 16, idx / 16 % 16), nbt))
+    }
+
+    /// Returns the heightmaps stored with this chunk. Chunks taken from a
+    /// [`LoadedChunk`] have their heightmaps, but they are discarded as soon as
+    /// any block state is changed. Use [`Heightmaps::compute`] in that case.
+    ///
+    /// [`LoadedChunk`]: super::LoadedChunk
+    pub fn heightmaps(&self) -> Option<&Heightmaps> {
+        self.heightmaps.as_ref()
+    }
+
+    /// Stores heightmaps with this chunk, which are used instead of computing
+    /// them when the chunk is inserted into a
+    /// [`ChunkLayer`](super::ChunkLayer). The heightmaps must match the block
+    /// states of the chunk.
+    ///
+    /// Heightmaps with values above the height of the chunk are discarded, so
+    /// they are computed on insertion instead.
+    pub fn set_heightmaps(&mut self, heightmaps: Option<Heightmaps>) {
+        self.heightmaps = heightmaps.filter(|heightmaps| heightmaps.fits_height(self.height()));
+    }
+
     /// Sets the height of this chunk in meters. The chunk is truncated or
     /// extended with [`BlockState::AIR`] and [`BiomeId::default()`] from the
     /// top.
@@ -45,6 +78,10 @@ impl UnloadedChunk {
         let new_count = height.min(MAX_HEIGHT) as usize / 16;
         let old_count = self.sections.len();
 
+        if new_count != old_count {
+            self.heightmaps = None;
+        }
+
         match new_count.cmp(&old_count) {
             Ordering::Less => {
                 self.sections.truncate(new_count);
@@ -81,14 +118,21 @@ impl Chunk for UnloadedChunk {
         check_block_oob(self, x, y, z);
 
         let idx = x + z * 16 + y % 16 * 16 * 16;
-        self.sections[y as usize / 16]
+        let old_block = self.sections[y as usize / 16]
             .block_states
-            .set(idx as usize, block)
+            .set(idx as usize, block);
+
+        if block != old_block {
+            self.heightmaps = None;
+        }
+
+        old_block
     }
 
     fn fill_block_state_section(&mut self, sect_y: u32, block: BlockState) {
         check_section_oob(self, sect_y);
 
+        self.heightmaps = None;
         self.sections[sect_y as usize].block_states.fill(block);
     }
 
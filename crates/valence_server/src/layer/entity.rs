@@ -6,6 +6,7 @@ use bevy_ecs::prelude::*;
 use rustc_hash::FxHashMap;
 use valence_entity::query::UpdateEntityQuery;
 use valence_entity::{EntityId, EntityLayerId, OldEntityLayerId, OldPosition, Position};
+use valence_protocol::compression::CompressionOptions;
 use valence_protocol::encode::{PacketWriter, WritePacket};
 use valence_protocol::{BlockPos, ChunkPos, CompressionThreshold, Encode, Packet};
 use valence_server_common::{Despawned, Server};
@@ -21,6 +22,7 @@ pub struct EntityLayer {
     messages: EntityLayerMessages,
     entities: FxHashMap<ChunkPos, BTreeSet<Entity>>,
     threshold: CompressionThreshold,
+    compression: CompressionOptions,
 }
 
 type EntityLayerMessages = Messages<GlobalMsg, LocalMsg>;
@@ -95,6 +97,7 @@ impl EntityLayer {
             messages: Messages::new(),
             entities: Default::default(),
             threshold: server.compression_threshold(),
+            compression: server.compression_options(),
         }
     }
 
@@ -185,7 +188,8 @@ impl WritePacket for EntityLayer {
         P: Packet + Encode,
     {
         self.messages.send_global(GlobalMsg::Packet, |b| {
-            PacketWriter::new(b, self.threshold).write_packet_fallible(packet)
+            PacketWriter::with_options(b, self.threshold, self.compression)
+                .write_packet_fallible(packet)
         })
     }
 
@@ -209,7 +213,10 @@ impl WritePacket for ExceptWriter<'_> {
             GlobalMsg::PacketExcept {
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(b, self.layer.threshold, self.layer.compression)
+                    .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -236,7 +243,8 @@ impl<'a> WritePacket for ViewWriter<'a> {
         self.layer
             .messages
             .send_local(LocalMsg::PacketAt { pos: self.pos }, |b| {
-                PacketWriter::new(b, self.layer.threshold).write_packet_fallible(packet)
+                PacketWriter::with_options(b, self.layer.threshold, self.layer.compression)
+                    .write_packet_fallible(packet)
             })
     }
 
@@ -265,7 +273,10 @@ impl WritePacket for ViewExceptWriter<'_> {
                 pos: self.pos,
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(b, self.layer.threshold, self.layer.compression)
+                    .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -296,7 +307,10 @@ impl WritePacket for RadiusWriter<'_> {
                 center: self.center,
                 radius_squared: self.radius_squared,
             },
-            |b| PacketWriter::new(b, self.layer.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(b, self.layer.threshold, self.layer.compression)
+                    .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -329,7 +343,10 @@ impl WritePacket for RadiusExceptWriter<'_> {
                 radius_squared: self.radius_squared,
                 except: self.except,
             },
-            |b| PacketWriter::new(b, self.layer.threshold).write_packet_fallible(packet),
+            |b| {
+                PacketWriter::with_options(b, self.layer.threshold, self.layer.compression)
+                    .write_packet_fallible(packet)
+            },
         )
     }
 
@@ -494,7 +511,11 @@ fn send_entity_update_messages(
                     };
 
                     layer.messages.send_local_infallible(msg, |b| {
-                        update.write_update_packets(PacketWriter::new(b, layer.threshold))
+                        update.write_update_packets(PacketWriter::with_options(
+                            b,
+                            layer.threshold,
+                            layer.compression,
+                        ))
                     });
                 } else {
                     panic!(
@@ -3,10 +3,12 @@
 pub mod abilities;
 pub mod action;
 pub mod brand;
+pub mod chunk_batch;
 mod chunk_view;
 pub mod client;
 pub mod client_command;
 pub mod client_settings;
+pub mod cookie;
 pub mod custom_payload;
 pub mod event_loop;
 pub mod hand_swing;
@@ -18,12 +20,14 @@ pub mod layer;
 pub mod message;
 pub mod movement;
 pub mod op_level;
+pub mod reconfigure;
 pub mod resource_pack;
 pub mod spawn;
 pub mod status;
 pub mod status_effect;
 pub mod teleport;
 pub mod title;
+mod transfer;
 
 pub use chunk_view::ChunkView;
 pub use event_loop::{EventLoopPostUpdate, EventLoopPreUpdate, EventLoopUpdate};
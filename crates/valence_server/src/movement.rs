@@ -1,13 +1,23 @@
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use valence_entity::{HeadYaw, Look, OnGround, Position};
-use valence_math::DVec3;
+use bevy_ecs::query::QueryData;
+use valence_entity::active_status_effects::ActiveStatusEffects;
+use valence_entity::entity::Flags;
+use valence_entity::{HeadYaw, Look, OnGround, Pose, Position, Velocity};
+use valence_generated::block::{BlockKind, PropName, PropValue};
+use valence_math::{Aabb, DVec3};
 use valence_protocol::packets::play::{
     MovePlayerPosC2s, MovePlayerPosRotC2s, MovePlayerRotC2s, MovePlayerStatusOnlyC2s,
     MoveVehicleC2s,
 };
+use valence_protocol::status_effects::StatusEffect;
+use valence_protocol::{BlockPos, BlockState, GameMode};
+use valence_server_common::Server;
 
+use crate::abilities::{FlyingSpeed, PlayerAbilitiesFlags};
+use crate::client::VisibleChunkLayer;
 use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
+use crate::layer::ChunkLayer;
 use crate::teleport::TeleportState;
 
 pub struct MovementPlugin;
@@ -16,13 +26,104 @@ impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MovementSettings>()
             .add_event::<MovementEvent>()
+            .add_event::<MovementViolationEvent>()
             .add_systems(EventLoopPreUpdate, handle_client_movement);
     }
 }
 
 /// Configuration resource for client movement checks.
-#[derive(Resource, Default)]
-pub struct MovementSettings; // TODO
+///
+/// All checks are disabled by default, in which case the server accepts any
+/// position the client sends. When a check fails, the client is teleported
+/// back to its last accepted position and a [`MovementViolationEvent`] is
+/// sent.
+///
+/// Distances are in blocks and speeds are in blocks per movement packet. The
+/// client sends at most one movement packet per tick.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct MovementSettings {
+    /// Whether to check that clients don't move faster than they are allowed
+    /// to.
+    pub speed_check: bool,
+    /// Whether to check that clients don't fly or hover when they are not
+    /// allowed to.
+    pub flight_check: bool,
+    /// Whether to check that clients don't move through blocks.
+    pub clip_check: bool,
+    /// The maximum horizontal distance a client may move in a single packet
+    /// while walking, sprinting, or jumping.
+    ///
+    /// [`Default`] value: `1.0`.
+    pub max_horizontal_speed: f64,
+    /// The maximum distance a client may rise in a single packet without
+    /// flying.
+    ///
+    /// [`Default`] value: `0.6`.
+    pub max_vertical_speed: f64,
+    /// The maximum distance a client may move in a single packet while
+    /// gliding with an elytra or riptiding.
+    ///
+    /// [`Default`] value: `4.0`.
+    pub max_elytra_speed: f64,
+    /// Multiplied with the client's [`FlyingSpeed`] to get the maximum
+    /// distance the client may move in a single packet while flying.
+    ///
+    /// [`Default`] value: `25.0`.
+    pub flying_speed_factor: f64,
+    /// Extra distance added to every speed limit to account for floating
+    /// point errors and small discrepancies with the client's physics.
+    ///
+    /// [`Default`] value: `0.1`.
+    pub speed_tolerance: f64,
+    /// The maximum height a client may gain above the ground it last stood
+    /// on.
+    ///
+    /// [`Default`] value: `1.5`.
+    pub max_jump_height: f64,
+    /// The maximum number of ticks a client may stay in the air without
+    /// descending.
+    ///
+    /// [`Default`] value: `20`.
+    pub max_hover_ticks: i64,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            speed_check: false,
+            flight_check: false,
+            clip_check: false,
+            max_horizontal_speed: 1.0,
+            max_vertical_speed: 0.6,
+            max_elytra_speed: 4.0,
+            flying_speed_factor: 25.0,
+            speed_tolerance: 0.1,
+            max_jump_height: 1.5,
+            max_hover_ticks: 20,
+        }
+    }
+}
+
+impl MovementSettings {
+    /// Returns settings with every check enabled.
+    pub fn all_checks() -> Self {
+        Self {
+            speed_check: true,
+            flight_check: true,
+            clip_check: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Per-client state used by the movement checks.
+#[derive(Component, Clone, Default, Debug)]
+pub struct MovementState {
+    /// The height of the ground the client last stood on.
+    ground_y: Option<f64>,
+    /// The tick the client started moving through the air without descending.
+    hover_start_tick: Option<i64>,
+}
 
 /// Event sent when a client successfully moves.
 #[derive(Event, Clone, Debug)]
@@ -36,173 +137,689 @@ pub struct MovementEvent {
     pub old_on_ground: bool,
 }
 
+/// Event sent when a client's movement is rejected by one of the checks in
+/// [`MovementSettings`]. The client has already been teleported back to
+/// `position` when this event is sent.
+#[derive(Event, Clone, Debug)]
+pub struct MovementViolationEvent {
+    pub client: Entity,
+    pub kind: MovementViolation,
+    /// The position the client tried to move to.
+    pub attempted_position: DVec3,
+    /// The position the client was sent back to.
+    pub position: DVec3,
+}
+
+/// The check that a [`MovementViolationEvent`] failed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MovementViolation {
+    /// The client moved faster than it is allowed to.
+    Speed,
+    /// The client flew or hovered without being allowed to.
+    Flight,
+    /// The client moved through a block.
+    Clip,
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+struct MovementQuery {
+    pos: &'static mut Position,
+    look: &'static mut Look,
+    head_yaw: &'static mut HeadYaw,
+    on_ground: &'static mut OnGround,
+    teleport_state: &'static mut TeleportState,
+    state: Option<&'static mut MovementState>,
+    velocity: Option<&'static Velocity>,
+    flags: Option<&'static Flags>,
+    pose: Option<&'static valence_entity::entity::Pose>,
+    effects: Option<&'static ActiveStatusEffects>,
+    game_mode: Option<&'static GameMode>,
+    abilities: Option<&'static PlayerAbilitiesFlags>,
+    flying_speed: Option<&'static FlyingSpeed>,
+    chunk_layer: Option<&'static VisibleChunkLayer>,
+}
+
 fn handle_client_movement(
     mut packets: EventReader<PacketEvent>,
-    mut clients: Query<(
-        &mut Position,
-        &mut Look,
-        &mut HeadYaw,
-        &mut OnGround,
-        &mut TeleportState,
-    )>,
+    mut clients: Query<MovementQuery>,
+    layers: Query<&ChunkLayer>,
+    settings: Res<MovementSettings>,
+    server: Res<Server>,
     mut movement_events: EventWriter<MovementEvent>,
+    mut violation_events: EventWriter<MovementViolationEvent>,
 ) {
+    let ctx = MovementContext {
+        settings: &settings,
+        layers: &layers,
+        tick: server.current_tick(),
+    };
+
     for packet in packets.read() {
         if let Some(pkt) = packet.decode::<MovePlayerPosC2s>() {
-            if let Ok((pos, look, head_yaw, on_ground, teleport_state)) =
-                clients.get_mut(packet.client)
-            {
+            if let Ok(client) = clients.get_mut(packet.client) {
                 let mov = MovementEvent {
                     client: packet.client,
                     position: pkt.position,
-                    old_position: pos.0,
-                    look: *look,
-                    old_look: *look,
+                    old_position: client.pos.0,
+                    look: *client.look,
+                    old_look: *client.look,
                     on_ground: pkt.flags.on_ground(),
-                    old_on_ground: on_ground.0,
+                    old_on_ground: client.on_ground.0,
                 };
 
                 handle(
                     mov,
-                    pos,
-                    look,
-                    head_yaw,
-                    on_ground,
-                    teleport_state,
+                    client,
+                    true,
+                    &ctx,
                     &mut movement_events,
+                    &mut violation_events,
                 );
             }
         } else if let Some(pkt) = packet.decode::<MovePlayerPosRotC2s>() {
-            if let Ok((pos, look, head_yaw, on_ground, teleport_state)) =
-                clients.get_mut(packet.client)
-            {
+            if let Ok(client) = clients.get_mut(packet.client) {
                 let mov = MovementEvent {
                     client: packet.client,
                     position: pkt.position,
-                    old_position: pos.0,
+                    old_position: client.pos.0,
                     look: Look {
                         yaw: pkt.yaw,
                         pitch: pkt.pitch,
                     },
-                    old_look: *look,
+                    old_look: *client.look,
                     on_ground: pkt.flags.on_ground(),
-                    old_on_ground: on_ground.0,
+                    old_on_ground: client.on_ground.0,
                 };
 
                 handle(
                     mov,
-                    pos,
-                    look,
-                    head_yaw,
-                    on_ground,
-                    teleport_state,
+                    client,
+                    true,
+                    &ctx,
                     &mut movement_events,
+                    &mut violation_events,
                 );
             }
         } else if let Some(pkt) = packet.decode::<MovePlayerRotC2s>() {
-            if let Ok((pos, look, head_yaw, on_ground, teleport_state)) =
-                clients.get_mut(packet.client)
-            {
+            if let Ok(client) = clients.get_mut(packet.client) {
                 let mov = MovementEvent {
                     client: packet.client,
-                    position: pos.0,
-                    old_position: pos.0,
+                    position: client.pos.0,
+                    old_position: client.pos.0,
                     look: Look {
                         yaw: pkt.yaw,
                         pitch: pkt.pitch,
                     },
-                    old_look: *look,
+                    old_look: *client.look,
                     on_ground: pkt.flags.on_ground(),
-                    old_on_ground: on_ground.0,
+                    old_on_ground: client.on_ground.0,
                 };
 
                 handle(
                     mov,
-                    pos,
-                    look,
-                    head_yaw,
-                    on_ground,
-                    teleport_state,
+                    client,
+                    true,
+                    &ctx,
                     &mut movement_events,
+                    &mut violation_events,
                 );
             }
         } else if let Some(pkt) = packet.decode::<MovePlayerStatusOnlyC2s>() {
-            if let Ok((pos, look, head_yaw, on_ground, teleport_state)) =
-                clients.get_mut(packet.client)
-            {
+            if let Ok(client) = clients.get_mut(packet.client) {
                 let mov = MovementEvent {
                     client: packet.client,
-                    position: pos.0,
-                    old_position: pos.0,
-                    look: *look,
-                    old_look: *look,
+                    position: client.pos.0,
+                    old_position: client.pos.0,
+                    look: *client.look,
+                    old_look: *client.look,
                     on_ground: pkt.flags.on_ground(),
-                    old_on_ground: on_ground.0,
+                    old_on_ground: client.on_ground.0,
                 };
 
                 handle(
                     mov,
-                    pos,
-                    look,
-                    head_yaw,
-                    on_ground,
-                    teleport_state,
+                    client,
+                    true,
+                    &ctx,
                     &mut movement_events,
+                    &mut violation_events,
                 );
             }
         } else if let Some(pkt) = packet.decode::<MoveVehicleC2s>() {
-            if let Ok((pos, look, head_yaw, on_ground, teleport_state)) =
-                clients.get_mut(packet.client)
-            {
+            if let Ok(client) = clients.get_mut(packet.client) {
                 let mov = MovementEvent {
                     client: packet.client,
                     position: pkt.position,
-                    old_position: pos.0,
+                    old_position: client.pos.0,
                     look: Look {
                         yaw: pkt.yaw,
                         pitch: pkt.pitch,
                     },
-                    old_look: *look,
-                    on_ground: on_ground.0,
-                    old_on_ground: on_ground.0,
+                    old_look: *client.look,
+                    on_ground: client.on_ground.0,
+                    old_on_ground: client.on_ground.0,
                 };
 
+                // Vehicles move by their own rules, so they aren't checked.
                 handle(
                     mov,
-                    pos,
-                    look,
-                    head_yaw,
-                    on_ground,
-                    teleport_state,
+                    client,
+                    false,
+                    &ctx,
                     &mut movement_events,
+                    &mut violation_events,
                 );
             }
         }
     }
 }
 
+struct MovementContext<'a, 'w, 's, 'l> {
+    settings: &'a MovementSettings,
+    layers: &'a Query<'w, 's, &'l ChunkLayer>,
+    tick: i64,
+}
+
 fn handle(
     mov: MovementEvent,
-    mut pos: Mut<Position>,
-    mut look: Mut<Look>,
-    mut head_yaw: Mut<HeadYaw>,
-    mut on_ground: Mut<OnGround>,
-    mut teleport_state: Mut<TeleportState>,
+    mut client: MovementQueryItem,
+    check: bool,
+    ctx: &MovementContext,
     movement_events: &mut EventWriter<MovementEvent>,
+    violation_events: &mut EventWriter<MovementViolationEvent>,
 ) {
-    if teleport_state.pending_teleports() != 0 {
+    if client.teleport_state.pending_teleports() != 0 {
+        // The client is about to be somewhere else entirely.
+        if let Some(state) = &mut client.state {
+            **state = MovementState::default();
+        }
+
         return;
     }
 
-    // TODO: check that the client isn't moving too fast / flying.
-    // TODO: check that the client isn't clipping through blocks.
+    if check {
+        if let Err(kind) = check_movement(&mov, &mut client, ctx) {
+            // Teleport the client back to where it was.
+            client.teleport_state.synced_pos = DVec3::NAN;
+            client.pos.set_changed();
 
-    pos.set_if_neq(Position(mov.position));
-    teleport_state.synced_pos = mov.position;
-    look.set_if_neq(mov.look);
-    teleport_state.synced_look = mov.look;
-    head_yaw.set_if_neq(HeadYaw(mov.look.yaw));
-    on_ground.set_if_neq(OnGround(mov.on_ground));
+            violation_events.send(MovementViolationEvent {
+                client: mov.client,
+                kind,
+                attempted_position: mov.position,
+                position: client.pos.0,
+            });
+
+            return;
+        }
+    }
+
+    client.pos.set_if_neq(Position(mov.position));
+    client.teleport_state.synced_pos = mov.position;
+    client.look.set_if_neq(mov.look);
+    client.teleport_state.synced_look = mov.look;
+    client.head_yaw.set_if_neq(HeadYaw(mov.look.yaw));
+    client.on_ground.set_if_neq(OnGround(mov.on_ground));
 
     movement_events.send(mov);
 }
+
+fn check_movement(
+    mov: &MovementEvent,
+    client: &mut MovementQueryItem,
+    ctx: &MovementContext,
+) -> Result<(), MovementViolation> {
+    let settings = ctx.settings;
+
+    if client.game_mode == Some(&GameMode::Spectator) {
+        return Ok(());
+    }
+
+    let layer = client
+        .chunk_layer
+        .and_then(|layer| ctx.layers.get(layer.0).ok());
+    let block_at = |pos: BlockPos| layer.and_then(|layer| layer.block(pos)).map(|b| b.state);
+
+    let abilities = client.abilities.copied().unwrap_or_default();
+    let gliding = client.flags.is_some_and(|flags| flags.fall_flying())
+        || client.pose.is_some_and(|pose| pose.0 == Pose::SpinAttack);
+    let effect_level = |effect| {
+        client
+            .effects
+            .and_then(|effects| effects.get_current_effect(effect))
+            .map_or(0.0, |effect| f64::from(effect.amplifier()) + 1.0)
+    };
+
+    if settings.speed_check {
+        let limits = SpeedLimits {
+            horizontal: settings.max_horizontal_speed,
+            vertical: settings.max_vertical_speed,
+            elytra: settings.max_elytra_speed,
+            flying: client
+                .flying_speed
+                .map_or(FlyingSpeed::default().0, |speed| speed.0),
+            flying_speed_factor: settings.flying_speed_factor,
+            tolerance: settings.speed_tolerance,
+        };
+
+        let allowance = MovementAllowance {
+            velocity: client.velocity.map_or(DVec3::ZERO, |v| v.0.as_dvec3()),
+            speed_level: effect_level(StatusEffect::Speed),
+            jump_boost_level: effect_level(StatusEffect::JumpBoost),
+            levitation_level: effect_level(StatusEffect::Levitation),
+            flying: abilities.flying() && abilities.allow_flying(),
+            gliding,
+        };
+
+        if !limits.allows(mov.position - mov.old_position, &allowance) {
+            return Err(MovementViolation::Speed);
+        }
+    }
+
+    let size = player_size(client.pose.map_or(Pose::Standing, |pose| pose.0));
+
+    if settings.clip_check && clips_through_blocks(mov.old_position, mov.position, size, block_at) {
+        return Err(MovementViolation::Clip);
+    }
+
+    if settings.flight_check {
+        let Some(state) = &mut client.state else {
+            return Ok(());
+        };
+
+        let bounds = player_bounds(mov.position, size);
+
+        let exempt = abilities.allow_flying()
+            || gliding
+            || effect_level(StatusEffect::Levitation) > 0.0
+            || client.velocity.is_some_and(|v| v.0.y > 0.0)
+            || touches_climbable(bounds, block_at);
+
+        // The client decides whether it's on the ground, so make sure there is
+        // actually something below it.
+        let on_ground = mov.on_ground && is_supported(bounds, layer.is_some(), block_at);
+
+        if exempt || on_ground {
+            state.ground_y = Some(mov.position.y);
+            state.hover_start_tick = None;
+        } else {
+            let ground_y = *state.ground_y.get_or_insert(mov.old_position.y);
+            let max_height = settings.max_jump_height + effect_level(StatusEffect::JumpBoost) * 0.5;
+
+            if mov.position.y - ground_y > max_height {
+                return Err(MovementViolation::Flight);
+            }
+
+            if mov.position.y >= mov.old_position.y {
+                let start = *state.hover_start_tick.get_or_insert(ctx.tick);
+
+                if ctx.tick - start > settings.max_hover_ticks {
+                    return Err(MovementViolation::Flight);
+                }
+            } else {
+                state.hover_start_tick = None;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The speed limits for a single movement packet.
+#[derive(Copy, Clone, Debug)]
+struct SpeedLimits {
+    horizontal: f64,
+    vertical: f64,
+    elytra: f64,
+    flying: f32,
+    flying_speed_factor: f64,
+    tolerance: f64,
+}
+
+/// The circumstances that let a client move faster than usual.
+#[derive(Copy, Clone, Default, Debug)]
+struct MovementAllowance {
+    /// Velocity in m/s given to the client by the server.
+    velocity: DVec3,
+    speed_level: f64,
+    jump_boost_level: f64,
+    levitation_level: f64,
+    flying: bool,
+    gliding: bool,
+}
+
+impl SpeedLimits {
+    fn allows(&self, delta: DVec3, allowance: &MovementAllowance) -> bool {
+        // Velocity is in m/s, but the limits are per tick.
+        let velocity = allowance.velocity / 20.0;
+
+        if allowance.gliding {
+            return delta.length() <= self.elytra + velocity.length() + self.tolerance;
+        }
+
+        let mut horizontal = self.horizontal * (1.0 + 0.2 * allowance.speed_level);
+        let mut vertical =
+            self.vertical + 0.1 * allowance.jump_boost_level + 0.05 * allowance.levitation_level;
+
+        if allowance.flying {
+            let flying = f64::from(self.flying) * self.flying_speed_factor;
+
+            horizontal = horizontal.max(flying);
+            vertical = vertical.max(flying);
+        }
+
+        horizontal += velocity.x.hypot(velocity.z) + self.tolerance;
+        vertical += velocity.y.max(0.0) + self.tolerance;
+
+        // Falling is never limited.
+        delta.x.hypot(delta.z) <= horizontal && delta.y <= vertical
+    }
+}
+
+/// The width and height of a player's hitbox in the given pose.
+fn player_size(pose: Pose) -> (f64, f64) {
+    match pose {
+        Pose::Sneaking => (0.6, 1.5),
+        Pose::FallFlying | Pose::Swimming | Pose::SpinAttack => (0.6, 0.6),
+        Pose::Sleeping | Pose::Dying => (0.2, 0.2),
+        _ => (0.6, 1.8),
+    }
+}
+
+/// The hitbox of a player standing at `pos`, shrunk slightly so that touching
+/// a block doesn't count as colliding with it.
+fn player_bounds(pos: DVec3, (width, height): (f64, f64)) -> Aabb {
+    const EPSILON: f64 = 1e-3;
+
+    Aabb::new(
+        DVec3::new(pos.x - width / 2.0, pos.y, pos.z - width / 2.0) + EPSILON,
+        DVec3::new(pos.x + width / 2.0, pos.y + height, pos.z + width / 2.0) - EPSILON,
+    )
+}
+
+/// Calls `f` with the state of every block whose cell overlaps `bounds`.
+/// Blocks can have collision shapes taller than a block (e.g. fences), so the
+/// layer of blocks below `bounds` is included too.
+fn blocks_in<F, G>(bounds: Aabb, block_at: F, mut f: G) -> bool
+where
+    F: Fn(BlockPos) -> Option<BlockState>,
+    G: FnMut(BlockPos, BlockState) -> bool,
+{
+    let min = bounds.min().floor().as_ivec3();
+    let max = bounds.max().floor().as_ivec3();
+
+    for y in min.y - 1..=max.y {
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let pos = BlockPos::new(x, y, z);
+
+                if let Some(state) = block_at(pos) {
+                    if f(pos, state) {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn collides<F>(bounds: Aabb, block_at: F) -> bool
+where
+    F: Fn(BlockPos) -> Option<BlockState>,
+{
+    blocks_in(bounds, block_at, |pos, state| {
+        let offset = DVec3::new(pos.x.into(), pos.y.into(), pos.z.into());
+
+        state
+            .collision_shapes()
+            .any(|shape| (shape + offset).intersects(bounds))
+    })
+}
+
+/// The longest move [`clips_through_blocks`] checks. No client moves this far
+/// in a single packet without cheating.
+const MAX_CLIP_CHECK_DISTANCE: f64 = 100.0;
+
+/// Returns whether a player moving in a straight line from `from` to `to`
+/// passes through a block. Moves longer than [`MAX_CLIP_CHECK_DISTANCE`]
+/// always count as clipping. Players that are stuck inside of a block may only
+/// move out of it in a single short step.
+fn clips_through_blocks<F>(from: DVec3, to: DVec3, size: (f64, f64), block_at: F) -> bool
+where
+    F: Fn(BlockPos) -> Option<BlockState> + Copy,
+{
+    /// The largest distance between two tested points on the path.
+    const STEP: f64 = 0.25;
+
+    let distance = (to - from).length();
+
+    // Bounds the work done for a single packet.
+    if distance.is_nan() || distance > MAX_CLIP_CHECK_DISTANCE {
+        return true;
+    }
+
+    if collides(player_bounds(from, size), block_at) {
+        return distance > STEP || collides(player_bounds(to, size), block_at);
+    }
+
+    let steps = (distance / STEP).ceil().max(1.0) as u32;
+
+    (1..=steps).any(|i| {
+        let pos = from.lerp(to, f64::from(i) / f64::from(steps));
+        collides(player_bounds(pos, size), block_at)
+    })
+}
+
+/// Returns whether there is a block right below `bounds` that can carry a
+/// player. If the chunk layer is unknown, the client is trusted.
+fn is_supported<F>(bounds: Aabb, has_layer: bool, block_at: F) -> bool
+where
+    F: Fn(BlockPos) -> Option<BlockState>,
+{
+    if !has_layer {
+        return true;
+    }
+
+    let below = Aabb::new(
+        bounds.min() - DVec3::new(0.0, 0.1, 0.0),
+        DVec3::new(bounds.max().x, bounds.min().y, bounds.max().z),
+    );
+
+    collides(below, block_at)
+}
+
+/// Returns whether `bounds` touches a fluid or any block that lets players
+/// climb or float.
+fn touches_climbable<F>(bounds: Aabb, block_at: F) -> bool
+where
+    F: Fn(BlockPos) -> Option<BlockState>,
+{
+    blocks_in(bounds, block_at, |pos, state| {
+        // Skip the extra layer below the bounds.
+        if f64::from(pos.y) + 1.0 < bounds.min().y {
+            return false;
+        }
+
+        state.is_liquid()
+            || state.get(PropName::Waterlogged) == Some(PropValue::True)
+            || matches!(
+                state.to_kind(),
+                BlockKind::Ladder
+                    | BlockKind::Vine
+                    | BlockKind::Scaffolding
+                    | BlockKind::TwistingVines
+                    | BlockKind::TwistingVinesPlant
+                    | BlockKind::WeepingVines
+                    | BlockKind::WeepingVinesPlant
+                    | BlockKind::CaveVines
+                    | BlockKind::CaveVinesPlant
+                    | BlockKind::Cobweb
+                    | BlockKind::PowderSnow
+                    | BlockKind::BubbleColumn
+                    | BlockKind::SlimeBlock
+                    | BlockKind::HoneyBlock
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor(pos: BlockPos) -> Option<BlockState> {
+        Some(if pos.y < 0 {
+            BlockState::STONE
+        } else {
+            BlockState::AIR
+        })
+    }
+
+    fn wall(pos: BlockPos) -> Option<BlockState> {
+        Some(if pos.y < 0 || pos.x == 2 {
+            BlockState::STONE
+        } else {
+            BlockState::AIR
+        })
+    }
+
+    fn limits() -> SpeedLimits {
+        let settings = MovementSettings::default();
+
+        SpeedLimits {
+            horizontal: settings.max_horizontal_speed,
+            vertical: settings.max_vertical_speed,
+            elytra: settings.max_elytra_speed,
+            flying: FlyingSpeed::default().0,
+            flying_speed_factor: settings.flying_speed_factor,
+            tolerance: settings.speed_tolerance,
+        }
+    }
+
+    #[test]
+    fn speed_limits() {
+        let limits = limits();
+        let walking = MovementAllowance::default();
+
+        assert!(limits.allows(DVec3::new(0.3, 0.42, 0.0), &walking));
+        assert!(limits.allows(DVec3::new(0.0, -3.0, 0.0), &walking));
+        assert!(!limits.allows(DVec3::new(3.0, 0.0, 0.0), &walking));
+        assert!(!limits.allows(DVec3::new(0.0, 1.0, 0.0), &walking));
+
+        let speed = MovementAllowance {
+            speed_level: 3.0,
+            ..walking
+        };
+        assert!(limits.allows(DVec3::new(1.5, 0.0, 0.0), &speed));
+
+        let knockback = MovementAllowance {
+            velocity: DVec3::new(40.0, 20.0, 0.0),
+            ..walking
+        };
+        assert!(limits.allows(DVec3::new(2.5, 1.5, 0.0), &knockback));
+
+        let flying = MovementAllowance {
+            flying: true,
+            ..walking
+        };
+        assert!(limits.allows(DVec3::new(1.2, 1.0, 0.0), &flying));
+        assert!(!limits.allows(DVec3::new(5.0, 0.0, 0.0), &flying));
+
+        let gliding = MovementAllowance {
+            gliding: true,
+            ..walking
+        };
+        assert!(limits.allows(DVec3::new(2.0, 2.0, 2.0), &gliding));
+        assert!(!limits.allows(DVec3::new(10.0, 0.0, 0.0), &gliding));
+    }
+
+    #[test]
+    fn block_clipping() {
+        let size = player_size(Pose::Standing);
+        let start = DVec3::new(0.5, 0.0, 0.5);
+
+        assert!(!clips_through_blocks(
+            start,
+            DVec3::new(1.5, 0.0, 0.5),
+            size,
+            wall
+        ));
+        assert!(clips_through_blocks(
+            start,
+            DVec3::new(2.5, 0.0, 0.5),
+            size,
+            wall
+        ));
+        // Skipping over the wall in a single packet is caught too.
+        assert!(clips_through_blocks(
+            start,
+            DVec3::new(4.5, 0.0, 0.5),
+            size,
+            wall
+        ));
+        // Sinking into the floor.
+        assert!(clips_through_blocks(
+            start,
+            DVec3::new(0.5, -0.5, 0.5),
+            size,
+            floor
+        ));
+        // Players stuck in a block can move out in a short step, but can't
+        // move through the block.
+        assert!(!clips_through_blocks(
+            DVec3::new(1.75, 0.0, 0.5),
+            DVec3::new(1.6, 0.0, 0.5),
+            size,
+            wall
+        ));
+        assert!(clips_through_blocks(
+            DVec3::new(2.5, 0.0, 0.5),
+            DVec3::new(1.5, 0.0, 0.5),
+            size,
+            wall
+        ));
+        assert!(clips_through_blocks(
+            DVec3::new(2.5, 0.0, 0.5),
+            DVec3::new(40.5, 0.0, 0.5),
+            size,
+            wall
+        ));
+        // Moves that are too long to check are rejected.
+        assert!(clips_through_blocks(
+            start,
+            DVec3::new(1e12, 0.0, 0.5),
+            size,
+            floor
+        ));
+        assert!(clips_through_blocks(
+            start,
+            DVec3::new(f64::NAN, 0.0, 0.5),
+            size,
+            floor
+        ));
+    }
+
+    #[test]
+    fn ground_support() {
+        let size = player_size(Pose::Standing);
+
+        let standing = player_bounds(DVec3::new(0.5, 0.0, 0.5), size);
+        assert!(is_supported(standing, true, floor));
+
+        let hovering = player_bounds(DVec3::new(0.5, 2.0, 0.5), size);
+        assert!(!is_supported(hovering, true, floor));
+        assert!(is_supported(hovering, false, floor));
+
+        let water = |pos: BlockPos| {
+            Some(if pos.y == 2 {
+                BlockState::WATER
+            } else {
+                BlockState::AIR
+            })
+        };
+        assert!(touches_climbable(hovering, water));
+        assert!(!touches_climbable(hovering, floor));
+    }
+}
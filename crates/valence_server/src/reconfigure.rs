@@ -0,0 +1,342 @@
+//! Moving clients in the play state back into the configuration state.
+//!
+//! Registries and tags are only sent while a client is being configured, so
+//! this is needed to change them for clients that already joined. Inserting
+//! the [`Reconfigure`] component on a client starts the process:
+//!
+//! 1. The client is sent to the configuration state.
+//! 2. The client's [`ClientFeatures`], the current registries and tags are
+//!    sent, followed by a [`ConfigurationStartEvent`]. Any configuration packets like resource
+//!    packs can be written to the [`Reconfigure`] component from now on.
+//! 3. Once [`Reconfigure::hold`] is `false`, the client is sent back to the
+//!    play state and a [`ConfigurationFinishEvent`] is sent.
+//! 4. The client is marked [`Rejoined`] for the rest of the tick, so
+//!    everything it can see is sent to it again as if it had just connected.
+//!
+//! Play packets written to the client while it is being configured are
+//! dropped.
+
+use std::borrow::Cow;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_ecs::world::EntityWorldMut;
+use tracing::warn;
+use valence_entity::living::Health;
+use valence_entity::Position;
+use valence_protocol::compression::CompressionOptions;
+use valence_protocol::encode::{PacketWriter, WritePacket};
+use valence_protocol::packets::configuration::select_known_packs_s2c::KnownPack;
+use valence_protocol::packets::configuration::{
+    FinishConfigurationC2s, FinishConfigurationS2c, SelectKnownPacksC2s, SelectKnownPacksS2c,
+    UpdateEnabledFeaturesS2c, UpdateTagsS2c,
+};
+use valence_protocol::packets::play::{ConfigurationAcknowledgedC2s, StartConfigurationS2c};
+use valence_protocol::{
+    ident, CompressionThreshold, Encode, Ident, Packet, PacketState, MINECRAFT_VERSION,
+};
+use valence_registry::tags::TagsRegistry;
+use valence_registry::RegistryCodec;
+use valence_server_common::Server;
+
+use crate::abilities::PlayerAbilitiesFlags;
+use crate::client::{
+    flush_packets, Client, FlushPacketsSet, OldVisibleEntityLayers, VisibleChunkLayer,
+    VisibleEntityLayers,
+};
+use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
+use crate::op_level::OpLevel;
+use crate::spawn::RespawnPosition;
+use crate::teleport::TeleportState;
+
+pub struct ReconfigurePlugin;
+
+impl Plugin for ReconfigurePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConfigurationStartEvent>()
+            .add_event::<ConfigurationFinishEvent>()
+            .add_systems(
+                PostUpdate,
+                flush_reconfiguring_clients
+                    .in_set(FlushPacketsSet)
+                    .before(flush_packets),
+            )
+            .add_systems(EventLoopPreUpdate, handle_configuration_packets)
+            .add_systems(Last, remove_rejoined_markers);
+    }
+}
+
+/// A marker component on clients that returned to the play state after being
+/// [`Reconfigure`]d. It is present until the end of the tick.
+///
+/// The client forgot everything it was sent before, so systems that send the
+/// initial state to clients with `Added<Client>` should also send it to
+/// clients with `Added<Rejoined>`. Unlike a new client, a rejoined client keeps
+/// all of its components.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Rejoined;
+
+/// The feature flags and data packs a client was configured with when it
+/// joined. They are sent to the client again when it is [`Reconfigure`]d.
+#[derive(Component, Clone, Debug)]
+pub struct ClientFeatures {
+    /// The enabled feature flags, like `minecraft:vanilla` or experimental
+    /// ones like `minecraft:bundle`.
+    pub features: Vec<Ident<String>>,
+    /// The data packs the server expects the client to have.
+    pub known_packs: Vec<KnownPack<'static>>,
+}
+
+impl Default for ClientFeatures {
+    fn default() -> Self {
+        Self {
+            features: vec![ident!("minecraft:vanilla").into()],
+            known_packs: vec![core_pack()],
+        }
+    }
+}
+
+/// A component that moves a client back into the configuration state while it
+/// is present. It is removed once the client has returned to the play state.
+///
+/// Configuration packets are written to this component instead of the
+/// [`Client`].
+#[derive(Component, Debug)]
+pub struct Reconfigure {
+    /// While `true`, the client stays in the configuration state after the
+    /// registries are sent, for example until it has loaded a resource pack.
+    pub hold: bool,
+    phase: Phase,
+    buf: Vec<u8>,
+    threshold: CompressionThreshold,
+    compression: CompressionOptions,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Phase {
+    /// [`StartConfigurationS2c`] hasn't been sent yet.
+    Requested,
+    /// Waiting for the client to acknowledge [`StartConfigurationS2c`].
+    Started,
+    /// Waiting for the client to select the known packs.
+    SelectingPacks,
+    /// The registries were sent.
+    Configuring,
+    /// Waiting for the client to acknowledge [`FinishConfigurationS2c`].
+    Finishing,
+}
+
+impl Reconfigure {
+    pub fn new(server: &Server) -> Self {
+        Self {
+            hold: false,
+            phase: Phase::Requested,
+            buf: vec![],
+            threshold: server.compression_threshold(),
+            compression: server.compression_options(),
+        }
+    }
+
+    /// Whether the client is in the configuration state and has been sent the
+    /// registries.
+    pub fn is_configuring(&self) -> bool {
+        matches!(self.phase, Phase::Configuring | Phase::Finishing)
+    }
+
+    /// Whether packets from the client will be in the configuration state.
+    pub(crate) fn incoming_state(&self) -> PacketState {
+        match self.phase {
+            Phase::Requested | Phase::Started => PacketState::Play,
+            Phase::SelectingPacks | Phase::Configuring | Phase::Finishing => {
+                PacketState::Configuration
+            }
+        }
+    }
+
+    /// Whether play packets are no longer sent to the client.
+    pub(crate) fn drops_play_packets(&self) -> bool {
+        self.phase != Phase::Requested
+    }
+}
+
+impl WritePacket for Reconfigure {
+    fn write_packet_fallible<P>(&mut self, packet: &P) -> anyhow::Result<()>
+    where
+        P: Packet + Encode,
+    {
+        PacketWriter::with_options(&mut self.buf, self.threshold, self.compression)
+            .write_packet_fallible(packet)
+    }
+
+    fn write_packet_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes)
+    }
+}
+
+/// Sent when a [`Reconfigure`]d client is in the configuration state and has
+/// been sent the registries.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ConfigurationStartEvent {
+    pub client: Entity,
+}
+
+/// Sent when a [`Reconfigure`]d client returned to the play state.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ConfigurationFinishEvent {
+    pub client: Entity,
+}
+
+fn core_pack() -> KnownPack<'static> {
+    KnownPack {
+        namespace: "minecraft".into(),
+        id: "core".into(),
+        version: MINECRAFT_VERSION.into(),
+    }
+}
+
+fn flush_reconfiguring_clients(mut clients: Query<(&mut Client, &mut Reconfigure)>) {
+    for (mut client, mut reconfigure) in &mut clients {
+        match reconfigure.phase {
+            Phase::Requested => {
+                // Play packets written before this one are still sent.
+                client.write_packet(&StartConfigurationS2c {});
+                reconfigure.phase = Phase::Started;
+                // Configuration packets are held back until the client has
+                // switched states.
+                continue;
+            }
+            Phase::Started => {
+                client.enc.clear();
+                continue;
+            }
+            Phase::Configuring if !reconfigure.hold => {
+                reconfigure.write_packet(&FinishConfigurationS2c);
+                reconfigure.phase = Phase::Finishing;
+            }
+            _ => {}
+        }
+
+        client.enc.clear();
+
+        if !reconfigure.buf.is_empty() {
+            client.write_packet_bytes(&reconfigure.buf);
+            reconfigure.buf.clear();
+        }
+    }
+}
+
+fn handle_configuration_packets(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&mut Reconfigure, &ClientFeatures)>,
+    codec: Res<RegistryCodec>,
+    tags: Res<TagsRegistry>,
+    mut start_events: EventWriter<ConfigurationStartEvent>,
+    mut finish_events: EventWriter<ConfigurationFinishEvent>,
+    mut commands: Commands,
+) {
+    for packet in packets.read() {
+        let Ok((mut reconfigure, features)) = clients.get_mut(packet.client) else {
+            continue;
+        };
+
+        if packet.decode::<ConfigurationAcknowledgedC2s>().is_some() {
+            if reconfigure.phase != Phase::Started {
+                warn!(
+                    "unexpected configuration acknowledgement from client {:?}",
+                    packet.client
+                );
+                commands.entity(packet.client).remove::<Client>();
+                continue;
+            }
+
+            reconfigure.phase = Phase::SelectingPacks;
+
+            reconfigure.write_packet(&UpdateEnabledFeaturesS2c {
+                features: features
+                    .features
+                    .iter()
+                    .map(|f| f.as_str_ident().into())
+                    .collect(),
+            });
+
+            reconfigure.write_packet(&SelectKnownPacksS2c {
+                packs: features.known_packs.clone(),
+            });
+        } else if let Some(pkt) = packet.decode::<SelectKnownPacksC2s>() {
+            if reconfigure.phase != Phase::SelectingPacks {
+                continue;
+            }
+
+            let core_pack = core_pack();
+            let has_core_pack = pkt.packs.iter().any(|pack| {
+                pack.namespace == core_pack.namespace
+                    && pack.id == core_pack.id
+                    && pack.version == core_pack.version
+            });
+
+            for pkt in codec.registry_data_packets(has_core_pack) {
+                reconfigure.write_packet(&pkt);
+            }
+
+            reconfigure.write_packet(&UpdateTagsS2c {
+                groups: Cow::Borrowed(&tags.registries),
+            });
+
+            reconfigure.phase = Phase::Configuring;
+
+            start_events.send(ConfigurationStartEvent {
+                client: packet.client,
+            });
+        } else if packet.decode::<FinishConfigurationC2s>().is_some() {
+            if reconfigure.phase != Phase::Finishing {
+                continue;
+            }
+
+            commands
+                .entity(packet.client)
+                .remove::<Reconfigure>()
+                .add(rejoin);
+
+            finish_events.send(ConfigurationFinishEvent {
+                client: packet.client,
+            });
+        }
+    }
+}
+
+/// Marks the client as [`Rejoined`], so that the login packet, chunks,
+/// entities and everything else is sent to it again.
+fn rejoin(mut entity: EntityWorldMut) {
+    let Some(mut client) = entity.get_mut::<Client>() else {
+        return;
+    };
+
+    // These were written while the client was still in the configuration state.
+    client.enc.clear();
+
+    entity.insert((Rejoined, TeleportState::new()));
+
+    if let Some(mut old_visible) = entity.get_mut::<OldVisibleEntityLayers>() {
+        old_visible.0.clear();
+    }
+
+    set_changed::<VisibleChunkLayer>(&mut entity);
+    set_changed::<VisibleEntityLayers>(&mut entity);
+    set_changed::<Position>(&mut entity);
+    set_changed::<RespawnPosition>(&mut entity);
+    set_changed::<PlayerAbilitiesFlags>(&mut entity);
+    set_changed::<OpLevel>(&mut entity);
+    set_changed::<Health>(&mut entity);
+}
+
+fn set_changed<C: Component>(entity: &mut EntityWorldMut) {
+    if let Some(mut component) = entity.get_mut::<C>() {
+        component.set_changed();
+    }
+}
+
+fn remove_rejoined_markers(clients: Query<Entity, With<Rejoined>>, mut commands: Commands) {
+    for entity in &clients {
+        commands.entity(entity).remove::<Rejoined>();
+    }
+}
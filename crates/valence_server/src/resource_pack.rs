@@ -1,6 +1,7 @@
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use uuid::Uuid;
+use valence_protocol::packets::configuration;
 use valence_protocol::packets::play::{ResourcePackC2s, ResourcePackPushS2c};
 use valence_protocol::text::Text;
 use valence_protocol::WritePacket;
@@ -96,6 +97,15 @@ fn handle_resource_pack_status(
                 client: packet.client,
                 status: pkt,
             });
+        } else if let Some(pkt) = packet.decode::<configuration::ResourcePackC2s>() {
+            // Sent by clients that are being reconfigured.
+            events.send(ResourcePackStatusEvent {
+                client: packet.client,
+                status: ResourcePackC2s {
+                    uuid: pkt.uuid,
+                    result: pkt.result,
+                },
+            });
         }
     }
 }
@@ -16,8 +16,9 @@ use valence_protocol::{BlockPos, GameMode, GlobalPos, Ident, VarInt, WritePacket
 use valence_registry::tags::TagsRegistry;
 use valence_registry::{DimensionTypeRegistry, RegistryCodec};
 
-use crate::client::{Client, ViewDistance, VisibleChunkLayer};
+use crate::client::{Client, SimulationDistance, ViewDistance, VisibleChunkLayer};
 use crate::layer::ChunkLayer;
+use crate::reconfigure::Rejoined;
 
 // Components for the join game and respawn packet.
 
@@ -79,6 +80,7 @@ pub struct ClientSpawnQuery {
     pub prev_game_mode: &'static mut PrevGameMode,
     pub hashed_seed: &'static mut HashedSeed,
     pub view_distance: &'static mut ViewDistance,
+    pub simulation_distance: &'static mut SimulationDistance,
     pub reduced_debug_info: &'static mut ReducedDebugInfo,
     pub has_respawn_screen: &'static mut HasRespawnScreen,
     pub is_debug: &'static mut IsDebug,
@@ -90,7 +92,10 @@ pub struct ClientSpawnQuery {
 pub(super) fn initial_join(
     codec: Res<RegistryCodec>,
     tags: Res<TagsRegistry>,
-    mut clients: Query<(&mut Client, &VisibleChunkLayer, ClientSpawnQueryReadOnly), Added<Client>>,
+    mut clients: Query<
+        (&mut Client, &VisibleChunkLayer, ClientSpawnQueryReadOnly),
+        Or<(Added<Client>, Added<Rejoined>)>,
+    >,
     chunk_layers: Query<&ChunkLayer>,
 ) {
     for (mut client, visible_chunk_layer, spawn) in &mut clients {
@@ -123,7 +128,7 @@ pub(super) fn initial_join(
             hashed_seed: spawn.hashed_seed.0 as i64,
             max_players: VarInt(0), // Ignored by clients.
             view_distance: VarInt(i32::from(spawn.view_distance.get())),
-            simulation_distance: VarInt(16), // TODO.
+            simulation_distance: VarInt(i32::from(spawn.simulation_distance.get())),
             reduced_debug_info: spawn.reduced_debug_info.0,
             enable_respawn_screen: spawn.has_respawn_screen.0,
             is_debug: spawn.is_debug.0,
@@ -164,15 +169,25 @@ pub(super) fn respawn(
             &PrevGameMode,
             &IsDebug,
             &IsFlat,
+            Has<Rejoined>,
         ),
         Changed<VisibleChunkLayer>,
     >,
     chunk_layers: Query<&ChunkLayer>,
 ) {
-    for (mut client, loc, death_loc, hashed_seed, game_mode, prev_game_mode, is_debug, is_flat) in
-        &mut clients
+    for (
+        mut client,
+        loc,
+        death_loc,
+        hashed_seed,
+        game_mode,
+        prev_game_mode,
+        is_debug,
+        is_flat,
+        rejoined,
+    ) in &mut clients
     {
-        if client.is_added() {
+        if client.is_added() || rejoined {
             // No need to respawn since we are sending the game join packet this tick.
             continue;
         }
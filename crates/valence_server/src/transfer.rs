@@ -0,0 +1,17 @@
+use valence_protocol::packets::play::TransferS2c;
+use valence_protocol::{VarInt, WritePacket};
+
+use crate::client::Client;
+
+impl Client {
+    /// Sends the client to the server at `host` and `port`, which can be
+    /// another Valence server or any server that accepts transfers. The client
+    /// disconnects from this server and keeps its cookies, see
+    /// [`Client::store_cookie`].
+    pub fn transfer(&mut self, host: &str, port: u16) {
+        self.write_packet(&TransferS2c {
+            host: host.into(),
+            port: VarInt(port.into()),
+        });
+    }
+}
@@ -10,6 +10,7 @@ use bevy_app::prelude::*;
 use bevy_app::ScheduleRunnerPlugin;
 use bevy_ecs::prelude::*;
 pub use despawn::*;
+use valence_protocol::compression::CompressionOptions;
 use valence_protocol::CompressionThreshold;
 
 pub use crate::uuid::*;
@@ -49,6 +50,14 @@ pub struct ServerSettings {
     /// Compression is enabled with an unspecified value. This value may
     /// change in future versions.
     pub compression_threshold: CompressionThreshold,
+    /// The backend and level used to compress packets over the compression
+    /// threshold. Lower levels use less CPU time per packet at the cost of
+    /// bandwidth, which matters most for servers that send a lot of chunks.
+    ///
+    /// # Default Value
+    ///
+    /// [`CompressionOptions::DEFAULT`]
+    pub compression_options: CompressionOptions,
 }
 
 impl Default for ServerSettings {
@@ -56,6 +65,7 @@ impl Default for ServerSettings {
         Self {
             tick_rate: DEFAULT_TPS,
             compression_threshold: CompressionThreshold(256),
+            compression_options: CompressionOptions::DEFAULT,
         }
     }
 }
@@ -72,6 +82,7 @@ impl Plugin for ServerPlugin {
         app.insert_resource(Server {
             current_tick: 0,
             threshold: settings.compression_threshold,
+            compression: settings.compression_options,
             tick_rate: settings.tick_rate,
         });
 
@@ -94,6 +105,7 @@ pub struct Server {
     /// Incremented on every tick.
     current_tick: i64,
     threshold: CompressionThreshold,
+    compression: CompressionOptions,
     tick_rate: NonZeroU32,
 }
 
@@ -109,6 +121,12 @@ impl Server {
         self.threshold
     }
 
+    /// Returns the server's [compression
+    /// options](ServerSettings::compression_options).
+    pub fn compression_options(&self) -> CompressionOptions {
+        self.compression
+    }
+
     // Returns the server's [tick rate](ServerPlugin::tick_rate).
     pub fn tick_rate(&self) -> NonZeroU32 {
         self.tick_rate
@@ -121,7 +121,7 @@ fn handle_chunk_loads(
 
     for event in events.read() {
         match &event.status {
-            ChunkLoadStatus::Success { .. } => {
+            ChunkLoadStatus::Success { .. } | ChunkLoadStatus::Unsaved => {
                 // The chunk was inserted into the world. Nothing for us to do.
             }
             ChunkLoadStatus::Empty => {
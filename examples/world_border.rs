@@ -1,9 +1,10 @@
 #![allow(clippy::type_complexity)]
 
 use bevy_app::App;
+use valence::chat::ChatMessageEvent;
 use valence::client::despawn_disconnected_clients;
 use valence::inventory::HeldItem;
-use valence::message::{ChatMessageEvent, SendMessage};
+use valence::message::SendMessage;
 use valence::prelude::*;
 use valence::world_border::*;
 
@@ -43,6 +43,8 @@ pub use valence_advancement as advancement;
 pub use valence_anvil as anvil;
 #[cfg(feature = "boss_bar")]
 pub use valence_boss_bar as boss_bar;
+#[cfg(feature = "chat")]
+pub use valence_chat as chat;
 #[cfg(feature = "command")]
 pub use valence_command as command;
 #[cfg(feature = "command")]
@@ -50,10 +52,14 @@ pub use valence_command_macros as command_macros;
 #[cfg(feature = "inventory")]
 pub use valence_inventory as inventory;
 pub use valence_lang as lang;
+#[cfg(feature = "map")]
+pub use valence_map as map;
 #[cfg(feature = "network")]
 pub use valence_network as network;
 #[cfg(feature = "player_list")]
 pub use valence_player_list as player_list;
+#[cfg(feature = "rcon")]
+pub use valence_rcon as rcon;
 use valence_registry::RegistryPlugin;
 #[cfg(feature = "scoreboard")]
 pub use valence_scoreboard as scoreboard;
@@ -62,6 +68,7 @@ use valence_server::action::ActionPlugin;
 use valence_server::client::ClientPlugin;
 use valence_server::client_command::ClientCommandPlugin;
 use valence_server::client_settings::ClientSettingsPlugin;
+use valence_server::cookie::CookiePlugin;
 use valence_server::custom_payload::CustomPayloadPlugin;
 use valence_server::entity::hitbox::HitboxPlugin;
 use valence_server::entity::EntityPlugin;
@@ -72,10 +79,10 @@ use valence_server::interact_entity::InteractEntityPlugin;
 use valence_server::interact_item::InteractItemPlugin;
 use valence_server::keepalive::KeepalivePlugin;
 use valence_server::layer::LayerPlugin;
-use valence_server::message::MessagePlugin;
 use valence_server::movement::MovementPlugin;
 use valence_server::op_level::OpLevelPlugin;
 pub use valence_server::protocol::status_effects;
+use valence_server::reconfigure::ReconfigurePlugin;
 use valence_server::resource_pack::ResourcePackPlugin;
 use valence_server::status::StatusPlugin;
 use valence_server::status_effect::StatusEffectPlugin;
@@ -124,8 +131,9 @@ pub mod prelude {
     pub use valence_server::action::{DiggingEvent, DiggingState};
     pub use valence_server::block::{BlockKind, BlockState, PropName, PropValue};
     pub use valence_server::client::{
-        despawn_disconnected_clients, Client, Ip, OldView, OldViewDistance, Properties, Username,
-        View, ViewDistance, VisibleChunkLayer, VisibleEntityLayers,
+        despawn_disconnected_clients, Client, Ip, OldView, OldViewDistance, Properties,
+        SimulationDistance, SimulationView, Username, View, ViewDistance, VisibleChunkLayer,
+        VisibleEntityLayers,
     };
     pub use valence_server::client_command::{
         JumpWithHorseEvent, JumpWithHorseState, LeaveBedEvent, PlayerCommand, SneakEvent,
@@ -142,7 +150,7 @@ pub mod prelude {
     pub use valence_server::ident::Ident;
     pub use valence_server::interact_entity::{EntityInteraction, InteractEntityEvent};
     pub use valence_server::layer::chunk::{
-        Block, BlockRef, Chunk, ChunkLayer, LoadedChunk, UnloadedChunk,
+        Block, BlockRef, Chunk, ChunkLayer, HeightmapKind, LoadedChunk, UnloadedChunk,
     };
     pub use valence_server::layer::{EntityLayer, LayerBundle};
     pub use valence_server::math::{DVec2, DVec3, Vec2, Vec3};
@@ -188,13 +196,14 @@ impl PluginGroup for DefaultPlugins {
             .add(ClientSettingsPlugin)
             .add(ActionPlugin)
             .add(TeleportPlugin)
-            .add(MessagePlugin)
             .add(CustomPayloadPlugin)
             .add(HandSwingPlugin)
             .add(InteractBlockPlugin)
             .add(InteractItemPlugin)
             .add(OpLevelPlugin)
             .add(ResourcePackPlugin)
+            .add(CookiePlugin)
+            .add(ReconfigurePlugin)
             .add(StatusPlugin)
             .add(StatusEffectPlugin)
             .add(AbilitiesPlugin);
@@ -244,11 +253,26 @@ impl PluginGroup for DefaultPlugins {
             group = group.add(valence_boss_bar::BossBarPlugin)
         }
 
+        #[cfg(feature = "map")]
+        {
+            group = group.add(valence_map::MapPlugin)
+        }
+
+        #[cfg(feature = "chat")]
+        {
+            group = group.add(valence_chat::ChatPlugin)
+        }
+
         #[cfg(feature = "command")]
         {
             group = group.add(valence_command::manager::CommandPlugin)
         }
 
+        #[cfg(feature = "rcon")]
+        {
+            group = group.add(valence_rcon::RconPlugin)
+        }
+
         #[cfg(feature = "scoreboard")]
         {
             group = group.add(valence_scoreboard::ScoreboardPlugin)
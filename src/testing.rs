@@ -14,7 +14,9 @@ use valence_registry::{BiomeRegistry, DimensionTypeRegistry};
 use valence_server::client::{ClientBundle, ClientBundleArgs, ClientConnection, ReceivedPacket};
 use valence_server::keepalive::KeepaliveSettings;
 use valence_server::protocol::decode::PacketFrame;
-use valence_server::protocol::packets::play::{AcceptTeleportationC2s, PlayerPositionS2c};
+use valence_server::protocol::packets::play::{
+    AcceptTeleportationC2s, ChunkBatchFinishedS2c, ChunkBatchReceivedC2s, PlayerPositionS2c,
+};
 use valence_server::protocol::{Decode, Encode, Packet, PacketDecoder, PacketEncoder, VarInt};
 use valence_server::{ChunkLayer, EntityLayer, Server, ServerSettings};
 
@@ -93,6 +95,7 @@ pub fn create_mock_client<N: Into<String>>(name: N) -> (ClientBundle, MockClient
         properties: Default::default(),
         conn: Box::new(conn.clone()),
         enc: PacketEncoder::new(),
+        features: Default::default(),
     });
 
     let helper = MockClientHelper::new(conn);
@@ -144,10 +147,6 @@ impl MockClientConnection {
     fn take_received(&self) -> BytesMut {
         self.inner.lock().unwrap().send_buf.split()
     }
-
-    fn clear_received(&self) {
-        self.inner.lock().unwrap().send_buf.clear();
-    }
 }
 
 impl ClientConnection for MockClientConnection {
@@ -203,6 +202,9 @@ impl MockClientHelper {
     }
 
     /// Collect all packets that have been received by the client.
+    ///
+    /// Like a vanilla client, every chunk batch that was received is
+    /// acknowledged so the server keeps sending chunks.
     #[track_caller]
     pub fn collect_received(&mut self) -> PacketFrames {
         self.dec.queue_bytes(self.conn.take_received());
@@ -214,6 +216,12 @@ impl MockClientHelper {
             .try_next_packet()
             .expect("failed to decode packet frame")
         {
+            if frame.id == ChunkBatchFinishedS2c::ID {
+                self.send(&ChunkBatchReceivedC2s {
+                    chunks_per_tick: 64.0,
+                });
+            }
+
             res.push(frame);
         }
 
@@ -221,7 +229,8 @@ impl MockClientHelper {
     }
 
     pub fn clear_received(&mut self) {
-        self.conn.clear_received();
+        // Still acknowledge the chunk batches.
+        self.collect_received();
     }
 
     pub fn confirm_initial_pending_teleports(&mut self) {
@@ -6,6 +6,7 @@ mod inventory;
 mod layer;
 mod player_list;
 mod potions;
+mod reconfigure;
 mod scoreboard;
 mod weather;
 mod world_border;
@@ -1,9 +1,11 @@
 use crate::abilities::PlayerAbilitiesFlags;
+use crate::client::SimulationDistance;
 use crate::layer::chunk::UnloadedChunk;
 use crate::layer::ChunkLayer;
 use crate::math::DVec3;
 use crate::protocol::packets::play::{
-    AcceptTeleportationC2s, MoveEntityPosS2c, MovePlayerPosRotC2s, PlayerPositionS2c,
+    AcceptTeleportationC2s, LoginS2c, MoveEntityPosS2c, MovePlayerPosRotC2s, PlayerPositionS2c,
+    SetSimulationDistanceS2c,
 };
 use crate::testing::{create_mock_client, ScenarioSingleClient};
 use crate::{ChunkPos, GameMode};
@@ -101,3 +103,46 @@ fn client_gamemode_changed_ability() {
     assert!(!abilities.instant_break());
     assert!(!abilities.invulnerable());
 }
+
+#[test]
+fn client_simulation_distance() {
+    let ScenarioSingleClient {
+        mut app,
+        client,
+        mut helper,
+        ..
+    } = ScenarioSingleClient::new();
+
+    app.world_mut()
+        .get_mut::<SimulationDistance>(client)
+        .unwrap()
+        .set(6);
+
+    app.update();
+
+    // The simulation distance is sent with the game join packet.
+    {
+        let recvd = helper.collect_received();
+
+        assert_eq!(recvd.first::<LoginS2c>().simulation_distance.0, 6);
+        recvd.assert_count::<SetSimulationDistanceS2c>(0);
+    }
+
+    app.world_mut()
+        .get_mut::<SimulationDistance>(client)
+        .unwrap()
+        .set(4);
+
+    app.update();
+
+    let recvd = helper.collect_received();
+
+    recvd.assert_count::<SetSimulationDistanceS2c>(1);
+    assert_eq!(
+        recvd
+            .first::<SetSimulationDistanceS2c>()
+            .simulation_distance
+            .0,
+        4
+    );
+}
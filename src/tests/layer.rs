@@ -1,18 +1,20 @@
 use std::collections::BTreeSet;
 
+use bevy_app::App;
 use bevy_ecs::world::EntityWorldMut;
 
 use crate::client::{ViewDistance, VisibleEntityLayers};
 use crate::entity::cow::CowEntityBundle;
 use crate::entity::{EntityLayerId, Position};
-use crate::layer::chunk::UnloadedChunk;
+use crate::layer::chunk::{BlockRegion, UnloadedChunk};
 use crate::layer::{ChunkLayer, EntityLayer};
+use crate::protocol::decode::PacketFrame;
 use crate::protocol::packets::play::{
-    AddEntityS2c, BlockEntityDataS2c, ForgetLevelChunkS2c, LevelChunkWithLightS2c,
-    MoveEntityPosS2c, RemoveEntitiesS2c, SectionBlocksUpdateS2c,
+    AddEntityS2c, BlockEntityDataS2c, ChunkBatchFinishedS2c, ForgetLevelChunkS2c,
+    LevelChunkWithLightS2c, MoveEntityPosS2c, RemoveEntitiesS2c, SectionBlocksUpdateS2c,
 };
 use crate::protocol::Packet;
-use crate::testing::ScenarioSingleClient;
+use crate::testing::{MockClientHelper, ScenarioSingleClient};
 use crate::{BlockState, ChunkView, Despawned, Server};
 
 #[test]
@@ -64,6 +66,154 @@ fn block_create_destroy() {
     }
 }
 
+#[test]
+fn region_fill_batches_updates() {
+    let ScenarioSingleClient {
+        mut app,
+        mut helper,
+        layer: layer_ent,
+        ..
+    } = ScenarioSingleClient::new();
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+
+    layer.insert_chunk([0, 0], UnloadedChunk::new());
+
+    update_until_chunks_sent(&mut app, &mut helper);
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+    let min_y = layer.min_y();
+
+    // Spans two sections.
+    layer.fill(
+        BlockRegion::new([0, min_y + 10, 0], [3, min_y + 20, 3]),
+        BlockState::STONE,
+    );
+
+    app.update();
+
+    {
+        let recvd = helper.collect_received();
+
+        recvd.assert_count::<SectionBlocksUpdateS2c>(2);
+        recvd.assert_count::<LevelChunkWithLightS2c>(0);
+    }
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+
+    // Too many blocks change, so the chunk is sent again instead.
+    layer.fill(
+        BlockRegion::new([0, min_y, 0], [15, min_y + 31, 15]),
+        BlockState::DIRT,
+    );
+
+    app.update();
+
+    {
+        let recvd = helper.collect_received();
+
+        recvd.assert_count::<SectionBlocksUpdateS2c>(0);
+        recvd.assert_count::<LevelChunkWithLightS2c>(1);
+    }
+}
+
+#[test]
+fn region_fill_resend_leaving_view() {
+    let ScenarioSingleClient {
+        mut app,
+        client: client_ent,
+        mut helper,
+        layer: layer_ent,
+    } = ScenarioSingleClient::new();
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+
+    layer.insert_chunk([0, 0], UnloadedChunk::new());
+
+    update_until_chunks_sent(&mut app, &mut helper);
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+    let min_y = layer.min_y();
+
+    // Enough blocks change for the chunk to be sent again, but the client leaves
+    // its view on the same tick.
+    layer.fill(
+        BlockRegion::new([0, min_y, 0], [15, min_y + 31, 15]),
+        BlockState::DIRT,
+    );
+
+    app.world_mut()
+        .get_mut::<Position>(client_ent)
+        .unwrap()
+        .set([1000.0, 0.0, 1000.0]);
+
+    app.update();
+
+    {
+        let recvd = helper.collect_received();
+
+        recvd.assert_count::<ForgetLevelChunkS2c>(1);
+        recvd.assert_count::<LevelChunkWithLightS2c>(0);
+        recvd.assert_count::<SectionBlocksUpdateS2c>(0);
+    }
+}
+
+/// Ticks until no more chunk batches are sent, and returns all the packets
+/// received in the meantime.
+fn update_until_chunks_sent(app: &mut App, helper: &mut MockClientHelper) -> Vec<PacketFrame> {
+    let mut frames = vec![];
+
+    loop {
+        app.update();
+
+        let recvd = helper.collect_received().0;
+        let done = !recvd.iter().any(|f| f.id == ChunkBatchFinishedS2c::ID);
+
+        frames.extend(recvd);
+
+        if done {
+            return frames;
+        }
+    }
+}
+
+#[test]
+fn resent_chunk_leaving_view_is_unloaded() {
+    let ScenarioSingleClient {
+        mut app,
+        client: client_ent,
+        mut helper,
+        layer: layer_ent,
+    } = ScenarioSingleClient::new();
+
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+
+    layer.insert_chunk([0, 0], UnloadedChunk::new());
+
+    update_until_chunks_sent(&mut app, &mut helper);
+
+    // Overwriting the chunk queues it to be sent again, but the client leaves
+    // its view before the batch is sent.
+    let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
+
+    layer.insert_chunk([0, 0], UnloadedChunk::new());
+
+    app.world_mut()
+        .get_mut::<Position>(client_ent)
+        .unwrap()
+        .set([1000.0, 0.0, 1000.0]);
+
+    app.update();
+
+    {
+        let recvd = helper.collect_received();
+
+        // The client still has the old chunk.
+        recvd.assert_count::<ForgetLevelChunkS2c>(1);
+        recvd.assert_count::<LevelChunkWithLightS2c>(0);
+    }
+}
+
 #[test]
 fn layer_chunk_view_change() {
     fn view(client: &EntityWorldMut) -> ChunkView {
@@ -93,14 +243,13 @@ fn layer_chunk_view_change() {
     client.get_mut::<Position>().unwrap().set([8.0, 0.0, 8.0]);
     client.get_mut::<ViewDistance>().unwrap().set(6);
 
-    // Tick
-    app.update();
+    // Collect all chunks received on join. They are sent over several ticks.
+    let frames = update_until_chunks_sent(&mut app, &mut helper);
     let mut client = app.world_mut().entity_mut(client_ent);
 
     let mut loaded_chunks = BTreeSet::new();
 
-    // Collect all chunks received on join.
-    for f in helper.collect_received().0 {
+    for f in frames {
         if f.id == LevelChunkWithLightS2c::ID {
             let LevelChunkWithLightS2c { pos, .. } = f.decode::<LevelChunkWithLightS2c>().unwrap();
             // Newly received chunk was not previously loaded.
@@ -118,12 +267,11 @@ fn layer_chunk_view_change() {
     // Move the client to the adjacent chunk.
     client.get_mut::<Position>().unwrap().set([24.0, 0.0, 24.0]);
 
-    // Tick
-    app.update();
+    let frames = update_until_chunks_sent(&mut app, &mut helper);
     let client = app.world_mut().entity_mut(client_ent);
 
-    // For all chunks received this tick...
-    for f in helper.collect_received().0 {
+    // For all chunks received since the client moved...
+    for f in frames {
         match f.id {
             LevelChunkWithLightS2c::ID => {
                 let LevelChunkWithLightS2c { pos, .. } = f.decode().unwrap();
@@ -186,8 +334,10 @@ fn chunk_viewer_count() {
     {
         let recvd = helper.collect_received();
 
-        recvd.assert_count::<LevelChunkWithLightS2c>(1);
-        recvd.assert_count::<ForgetLevelChunkS2c>(2)
+        // The new chunk left the view before it was sent, so the client only
+        // forgets the first one.
+        recvd.assert_count::<LevelChunkWithLightS2c>(0);
+        recvd.assert_count::<ForgetLevelChunkS2c>(1)
     };
 
     let mut layer = app.world_mut().get_mut::<ChunkLayer>(layer_ent).unwrap();
@@ -0,0 +1,116 @@
+use crate::protocol::packets::configuration::select_known_packs_s2c::KnownPack;
+use crate::protocol::packets::configuration::{
+    FinishConfigurationC2s, FinishConfigurationS2c, SelectKnownPacksC2s, SelectKnownPacksS2c,
+    UpdateEnabledFeaturesS2c, UpdateTagsS2c,
+};
+use crate::protocol::packets::play::{
+    ConfigurationAcknowledgedC2s, LoginS2c, StartConfigurationS2c,
+};
+use crate::reconfigure::{ClientFeatures, Reconfigure};
+use crate::testing::ScenarioSingleClient;
+use crate::{ident, Despawned, Server};
+
+#[test]
+fn reconfigure_client() {
+    let ScenarioSingleClient {
+        mut app,
+        client,
+        mut helper,
+        ..
+    } = ScenarioSingleClient::new();
+
+    app.update();
+    helper.clear_received();
+
+    let reconfigure = Reconfigure::new(app.world().resource::<Server>());
+    app.world_mut().entity_mut(client).insert(reconfigure);
+
+    app.update();
+
+    helper
+        .collect_received()
+        .assert_count::<StartConfigurationS2c>(1);
+
+    helper.send(&ConfigurationAcknowledgedC2s {});
+
+    app.update();
+
+    helper
+        .collect_received()
+        .assert_count::<SelectKnownPacksS2c>(1);
+
+    helper.send(&SelectKnownPacksC2s { packs: vec![] });
+
+    app.update();
+
+    {
+        let recvd = helper.collect_received();
+        recvd.assert_count::<UpdateTagsS2c>(1);
+        recvd.assert_count::<FinishConfigurationS2c>(1);
+    }
+
+    helper.send(&FinishConfigurationC2s);
+
+    app.update();
+
+    assert!(app.world().get::<Reconfigure>(client).is_none());
+    // The client stays connected while it rejoins.
+    assert!(app.world().get::<Despawned>(client).is_none());
+
+    // The client joins the game again.
+    helper.collect_received().assert_count::<LoginS2c>(1);
+}
+
+#[test]
+fn reconfigure_keeps_client_features() {
+    let ScenarioSingleClient {
+        mut app,
+        client,
+        mut helper,
+        ..
+    } = ScenarioSingleClient::new();
+
+    // The client joined with experimental features and a custom pack.
+    app.world_mut().entity_mut(client).insert(ClientFeatures {
+        features: vec![
+            ident!("minecraft:vanilla").into(),
+            ident!("minecraft:bundle").into(),
+            ident!("minecraft:trade_rebalance").into(),
+        ],
+        known_packs: vec![KnownPack {
+            namespace: "example".into(),
+            id: "arena".into(),
+            version: "1".into(),
+        }],
+    });
+
+    app.update();
+    helper.clear_received();
+
+    let reconfigure = Reconfigure::new(app.world().resource::<Server>());
+    app.world_mut().entity_mut(client).insert(reconfigure);
+
+    app.update();
+    helper.clear_received();
+
+    helper.send(&ConfigurationAcknowledgedC2s {});
+
+    app.update();
+
+    let recvd = helper.collect_received();
+
+    let features = recvd.first::<UpdateEnabledFeaturesS2c>().features;
+    assert_eq!(
+        features.iter().map(|f| f.as_str()).collect::<Vec<_>>(),
+        [
+            "minecraft:vanilla",
+            "minecraft:bundle",
+            "minecraft:trade_rebalance"
+        ]
+    );
+
+    let packs = recvd.first::<SelectKnownPacksS2c>().packs;
+    assert_eq!(packs.len(), 1);
+    assert_eq!(packs[0].namespace, "example");
+    assert_eq!(packs[0].id, "arena");
+}
@@ -3,7 +3,9 @@ use valence_scoreboard::*;
 use crate::client::VisibleEntityLayers;
 use crate::entity::EntityLayerId;
 use crate::layer::EntityLayer;
-use crate::protocol::packets::play::{SetDisplayObjectiveS2c, SetObjectiveS2c, SetScoreS2c};
+use crate::protocol::packets::play::{
+    SetDisplayObjectiveS2c, SetObjectiveS2c, SetPlayerTeamS2c, SetScoreS2c,
+};
 use crate::testing::ScenarioSingleClient;
 use crate::text::IntoText;
 use crate::Server;
@@ -189,3 +191,91 @@ fn should_only_update_score_diff() {
         recvd.assert_count::<SetScoreS2c>(1);
     }
 }
+
+#[test]
+fn show_team_when_client_join() {
+    let ScenarioSingleClient {
+        mut app,
+        client,
+        mut helper,
+        ..
+    } = ScenarioSingleClient::new();
+
+    // Add a new entity layer for the team.
+    let server = app.world().get_resource::<Server>().unwrap().clone();
+    let team_layer = app.world_mut().spawn(EntityLayer::new(&server)).id();
+    app.world_mut()
+        .entity_mut(client)
+        .get_mut::<VisibleEntityLayers>()
+        .unwrap()
+        .0
+        .insert(team_layer);
+
+    // Spawn the team.
+    app.world_mut().spawn(TeamBundle {
+        name: Team::new("red"),
+        display: TeamDisplay("Red".into_text()),
+        members: ["foo"].into_iter().collect(),
+        layer: EntityLayerId(team_layer),
+        ..Default::default()
+    });
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    // Check that the team was sent to the client.
+    {
+        let recvd = helper.collect_received();
+
+        recvd.assert_count::<SetPlayerTeamS2c>(1);
+    }
+}
+
+#[test]
+fn should_only_update_team_member_diff() {
+    let ScenarioSingleClient {
+        mut app,
+        client,
+        mut helper,
+        ..
+    } = ScenarioSingleClient::new();
+
+    // Add a new entity layer for the team.
+    let server = app.world().get_resource::<Server>().unwrap().clone();
+    let team_layer = app.world_mut().spawn(EntityLayer::new(&server)).id();
+    app.world_mut()
+        .entity_mut(client)
+        .get_mut::<VisibleEntityLayers>()
+        .unwrap()
+        .0
+        .insert(team_layer);
+
+    // Spawn the team.
+    let team = app
+        .world_mut()
+        .spawn(TeamBundle {
+            name: Team::new("red"),
+            display: TeamDisplay("Red".into_text()),
+            members: ["foo", "bar"].into_iter().collect(),
+            layer: EntityLayerId(team_layer),
+            ..Default::default()
+        })
+        .id();
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    helper.clear_received();
+
+    let mut members = app.world_mut().get_mut::<TeamMembers>(team).unwrap();
+    members.insert("baz");
+    members.insert("foo");
+
+    app.update();
+
+    // Check that only the new member was sent to the client.
+    {
+        let recvd = helper.collect_received();
+
+        recvd.assert_count::<SetPlayerTeamS2c>(1);
+    }
+}
@@ -206,7 +206,9 @@ impl Proxy {
                     if let Some(handshake) = extrapolate_packet::<IntentionC2s>(&packet) {
                         *state_lock.write().await = match handshake.next_state {
                             HandshakeNextState::Status => PacketState::Status,
-                            HandshakeNextState::Login => PacketState::Login,
+                            HandshakeNextState::Login | HandshakeNextState::Transfer => {
+                                PacketState::Login
+                            }
                         };
                     }
                 }